@@ -0,0 +1,251 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use chrono::{Local, NaiveDate};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+
+use crate::{get_pref_label, stats::load_history, AppState};
+
+/// Used if the user hasn't set `api_port` in their preferences
+const DEFAULT_PORT: u16 = 7370;
+
+/// Requests that change anything have to carry this header. Web pages can't
+/// add it to a request to another site without the server agreeing to it
+/// first, which this one never does, so they can't start or stop tracking.
+const CLIENT_HEADER: &str = "X-Ydnc-Time";
+
+/// A small HTTP server on localhost that lets scripts, Stream Deck buttons,
+/// browser extensions and the like control tracking without the TUI:
+///
+/// - `GET /today`: today's entries
+/// - `POST /start/{n}`: start tracking task n
+/// - `POST /stop`: stop tracking
+/// - `GET /stats?from=YYYY-MM-DD&to=YYYY-MM-DD`: per-task totals, where both
+///   bounds are optional and inclusive
+///
+/// POSTs need an `X-Ydnc-Time` header (any value). Requests from web pages,
+/// which have an `Origin`, and for any host but localhost, as a page that's
+/// rebound its own domain to 127.0.0.1 would send, are refused.
+pub struct ApiServer {
+    server: Arc<Server>,
+    thread: JoinHandle<()>,
+}
+
+impl ApiServer {
+    /// Starts serving on a background thread. Returns None if the port can't
+    /// be bound, since the app works fine without the API.
+    pub fn start(app: AppState) -> Option<Self> {
+        let port = app
+            .lock()
+            .unwrap()
+            .preferences
+            .api_port
+            .unwrap_or(DEFAULT_PORT);
+        // Only ever listen on localhost, there's no authentication beyond the
+        // checks in `allowed`!
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let server = match Server::http(addr) {
+            Ok(server) => Arc::new(server),
+            Err(err) => {
+                warn!("Unable to start HTTP API on {}: {}", addr, err);
+                return None;
+            }
+        };
+        info!("Serving HTTP API on {}", addr);
+
+        let thread_server = Arc::clone(&server);
+        let thread = thread::spawn(move || {
+            for request in thread_server.incoming_requests() {
+                handle(&app, request);
+            }
+        });
+
+        Some(Self { server, thread })
+    }
+
+    pub fn stop(self) {
+        info!("Stopping HTTP API");
+        self.server.unblock();
+        if self.thread.join().is_err() {
+            warn!("HTTP API thread panicked");
+        }
+    }
+}
+
+fn json_response(status: u16, body: Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+fn error_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(status, json!({ "error": message }))
+}
+
+/// Parses an optional date query parameter like `from=2023-06-01`
+fn date_param(query: &str, name: &str) -> Result<Option<NaiveDate>, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| {
+            value
+                .parse::<NaiveDate>()
+                .map_err(|_| format!("{} must be a date like 2023-06-01", name))
+        })
+        .transpose()
+}
+
+fn today(app: &AppState) -> Value {
+    json!(app.lock().unwrap().today)
+}
+
+fn stats(app: &AppState, query: &str) -> Result<Value, String> {
+    let from = date_param(query, "from")?;
+    let to = date_param(query, "to")?;
+    let (dirs, rounding, profile) = {
+        let app = app.lock().unwrap();
+        (
+            app.dirs.clone(),
+            app.preferences.billing_rounding,
+            app.preferences.profile.clone(),
+        )
+    };
+    let history =
+        load_history(&dirs, from, to, rounding, profile.as_deref()).map_err(|e| e.to_string())?;
+
+    let app = app.lock().unwrap();
+    let labels = app.preferences.labels.as_ref();
+    let tasks: Vec<Value> = history
+        .tasks
+        .iter()
+        .map(|ts| {
+            json!({
+                "number": ts.task_number,
+                "label": get_pref_label(ts.task_number, labels),
+                "count": ts.count,
+                "total_secs": ts.total.num_seconds(),
+                "mean_secs": ts.mean.num_seconds(),
+                "billed_secs": ts.billed.num_seconds(),
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "from": history.min_date,
+        "to": to.unwrap_or_else(|| Local::now().date_naive()),
+        "tasks": tasks,
+    }))
+}
+
+/// Whether a request may be handled at all, given its method and headers, or
+/// else why not
+fn allowed(method: &Method, headers: &[Header]) -> Result<(), &'static str> {
+    let header = |name: &'static str| {
+        headers
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str())
+    };
+    let host = header("Host").unwrap_or_default();
+    let hostname = host.rsplit_once(':').map_or(host, |(name, _)| name);
+    if !matches!(hostname, "localhost" | "127.0.0.1") {
+        return Err("Only requests for localhost are served");
+    }
+    if header("Origin").is_some() {
+        return Err("Requests from web pages aren't served");
+    }
+    if *method != Method::Get && header(CLIENT_HEADER).is_none() {
+        return Err("Requests that change anything need an X-Ydnc-Time header");
+    }
+    Ok(())
+}
+
+fn handle(app: &AppState, request: Request) {
+    if let Err(message) = allowed(request.method(), request.headers()) {
+        warn!(
+            "Refused HTTP API request for {}: {}",
+            request.url(),
+            message
+        );
+        if let Err(err) = request.respond(error_response(403, message)) {
+            warn!("Unable to respond to HTTP API request: {}", err);
+        }
+        return;
+    }
+    let (path, query) = request
+        .url()
+        .split_once('?')
+        .map_or((request.url(), ""), |(p, q)| (p, q));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let response = match (request.method(), segments.as_slice()) {
+        (Method::Get, ["today"]) => json_response(200, today(app)),
+        (Method::Post, ["start", n]) => match n.parse::<u8>() {
+            Ok(number @ 1..=8) => {
+                let mut locked = app.lock().unwrap();
+                locked.start_entry(number);
+                locked.notify(format!("Started task {} via API", number));
+                drop(locked);
+                json_response(200, today(app))
+            }
+            _ => error_response(400, "Task number must be 1-8"),
+        },
+        (Method::Post, ["stop"]) => {
+            let mut locked = app.lock().unwrap();
+            locked.close_entry_if_open(Local::now());
+            locked.notify("Stopped tracking via API");
+            drop(locked);
+            json_response(200, today(app))
+        }
+        (Method::Get, ["stats"]) => match stats(app, query) {
+            Ok(body) => json_response(200, body),
+            Err(message) => error_response(400, &message),
+        },
+        _ => error_response(404, "Not found"),
+    };
+
+    if let Err(err) = request.respond(response) {
+        warn!("Unable to respond to HTTP API request: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_requests() {
+        let header = |field: &str, value: &str| Header::from_bytes(field, value).unwrap();
+        let local = header("Host", "localhost:7370");
+        let client = header(CLIENT_HEADER, "1");
+
+        assert_eq!(allowed(&Method::Get, std::slice::from_ref(&local)), Ok(()));
+        assert_eq!(
+            allowed(
+                &Method::Post,
+                &[header("Host", "127.0.0.1"), client.clone()]
+            ),
+            Ok(())
+        );
+        // Forms and fetches from web pages
+        assert!(allowed(&Method::Post, std::slice::from_ref(&local)).is_err());
+        assert!(allowed(
+            &Method::Post,
+            &[
+                local,
+                client.clone(),
+                header("Origin", "https://example.com")
+            ]
+        )
+        .is_err());
+        // DNS rebinding
+        assert!(allowed(&Method::Get, &[header("Host", "evil.example:7370")]).is_err());
+        assert!(allowed(&Method::Post, &[client]).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+// Bundles old daily logs into one compressed file per month, like
+// "2023-06.ron.zst", so that years of tracking don't leave thousands of files
+// in the save directory. Archived days are still read by history and stats.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, NaiveDate};
+use tracing::{info, warn};
+
+use crate::{
+    crypto, error, load_log_file, migrate,
+    stats::{self, DatedLogs},
+    storage::Dirs,
+    TimeLog,
+};
+
+const ARCHIVE_SUFFIX: &str = ".ron.zst";
+
+/// One month's daily logs, by date
+type MonthArchive = BTreeMap<NaiveDate, Vec<TimeLog>>;
+
+fn archive_path(dir: &Path, date: NaiveDate) -> PathBuf {
+    dir.join(format!("{}{}", date.format("%Y-%m"), ARCHIVE_SUFFIX))
+}
+
+/// The first day of the month an archive file is for, if it's an archive
+fn archive_month(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    let month = name.strip_suffix(ARCHIVE_SUFFIX)?;
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%F").ok()
+}
+
+fn load_archive(path: &Path) -> error::Result<MonthArchive> {
+    info!("Loading archive from {}", path.display());
+    let compressed = crypto::read(path)?;
+    let contents = zstd::decode_all(compressed.as_slice())?;
+    migrate::from_ron(&contents).map_err(|e| e.in_file(path))
+}
+
+fn save_archive(path: &Path, archive: &MonthArchive) -> io::Result<()> {
+    info!("Saving archive to {}", path.display());
+    let contents = migrate::to_ron(archive, false)?;
+    let compressed = zstd::encode_all(contents.as_bytes(), 0)?;
+    // The daily files get deleted once they're archived, so make sure a
+    // half-written archive can never replace a good one
+    let tmp = path.with_extension("zst.tmp");
+    crypto::write(&tmp, compressed)?;
+    fs::rename(tmp, path)
+}
+
+/// Moves the daily logs in `dirs` dated before `before` into their monthly
+/// archives, merging with any archive that already exists. A daily file wins
+/// over an archived copy of the same day, since it can only have been written
+/// since that day was archived. Returns how many daily files were archived.
+pub fn archive_before(dirs: &Dirs, before: NaiveDate) -> io::Result<usize> {
+    let dir = dirs.data();
+    let mut months: BTreeMap<NaiveDate, Vec<(NaiveDate, PathBuf)>> = BTreeMap::new();
+    for (date, path) in stats::daily_files(dir, None, before.pred_opt())? {
+        months
+            .entry(date.with_day(1).expect("every month has a first day"))
+            .or_default()
+            .push((date, path));
+    }
+
+    let mut archived = 0;
+    for (month, days) in months {
+        let path = archive_path(dir, month);
+        let mut archive = match load_archive(&path) {
+            Ok(archive) => archive,
+            Err(e) if e.is_not_found() => MonthArchive::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Files that can't be read are left where they are for the user to
+        // sort out
+        let days: Vec<(NaiveDate, PathBuf)> = days
+            .into_iter()
+            .filter(|(date, day_path)| match load_log_file(dirs, day_path) {
+                Ok(logs) => {
+                    archive.insert(*date, logs);
+                    true
+                }
+                Err(e) => {
+                    warn!("Not archiving {}: {}", day_path.display(), e);
+                    false
+                }
+            })
+            .collect();
+        if days.is_empty() {
+            continue;
+        }
+
+        save_archive(&path, &archive)?;
+        for (_, day_path) in &days {
+            fs::remove_file(day_path)?;
+            // Tidy away the month's and year's subdirectories once they're
+            // empty, which removing a non-empty directory won't do
+            let mut parent = day_path.parent();
+            while let Some(sub) = parent.filter(|p| *p != dir) {
+                if fs::remove_dir(sub).is_err() {
+                    break;
+                }
+                parent = sub.parent();
+            }
+        }
+        archived += days.len();
+    }
+
+    Ok(archived)
+}
+
+/// Loads the archived days within the given (inclusive) dates, leaving out
+/// any in `skip`. Archives that can't be read are skipped with a warning.
+pub fn load_archived(
+    dir: &Path,
+    min_date: Option<NaiveDate>,
+    max_date: Option<NaiveDate>,
+    skip: &HashSet<NaiveDate>,
+) -> io::Result<DatedLogs> {
+    let in_range = |date: NaiveDate| {
+        min_date.is_none_or(|min| date >= min) && max_date.is_none_or(|max| date <= max)
+    };
+
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(month) = archive_month(&path) else {
+            continue;
+        };
+        // Skip whole months outside our date range
+        let month_end = month
+            .checked_add_months(chrono::Months::new(1))
+            .and_then(|d| d.pred_opt())
+            .unwrap_or(NaiveDate::MAX);
+        if min_date.is_some_and(|min| month_end < min) || max_date.is_some_and(|max| month > max) {
+            continue;
+        }
+
+        match load_archive(&path) {
+            Ok(archive) => logs.extend(
+                archive
+                    .into_iter()
+                    .filter(|(date, _)| in_range(*date) && !skip.contains(date)),
+            ),
+            Err(e) => warn!("Unable to load history from {}: {}", path.display(), e),
+        }
+    }
+    Ok(logs)
+}
+
+/// Loads one archived day's logs. NotFound if the day isn't archived.
+pub fn load_archived_day(dir: &Path, date: NaiveDate) -> error::Result<Vec<TimeLog>> {
+    load_archive(&archive_path(dir, date))?
+        .remove(&date)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Day is not in its archive").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+    use crate::save_log_for;
+
+    #[test]
+    fn archive_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ydnc-archive-{}", std::process::id()));
+        let dirs = Dirs::in_dir(dir.clone());
+        let date = |m, d| NaiveDate::from_ymd_opt(2023, m, d).unwrap();
+        let day = |m, d, number| {
+            let start = Local.with_ymd_and_hms(2023, m, d, 9, 0, 0).unwrap();
+            vec![TimeLog::new(number, start, Some(start + chrono::Duration::hours(1))).unwrap()]
+        };
+        save_log_for(&dirs, date(5, 31), &day(5, 31, 1)).unwrap();
+        save_log_for(&dirs, date(6, 1), &day(6, 1, 2)).unwrap();
+        save_log_for(&dirs, date(6, 2), &day(6, 2, 3)).unwrap();
+        save_log_for(&dirs, date(7, 1), &day(7, 1, 4)).unwrap();
+
+        assert_eq!(archive_before(&dirs, date(7, 1)).unwrap(), 3);
+        assert!(dir.join("2023-05.ron.zst").exists());
+        assert!(dir.join("2023-06.ron.zst").exists());
+        // The archived months' directories are tidied away, the rest kept
+        assert!(!dir.join("2023").join("06").exists());
+        assert!(dir.join("2023").join("07").exists());
+
+        assert_eq!(load_archived_day(&dir, date(6, 2)).unwrap(), day(6, 2, 3));
+        assert!(load_archived_day(&dir, date(6, 3))
+            .unwrap_err()
+            .is_not_found());
+        let skip = HashSet::from([date(6, 2)]);
+        let archived = load_archived(&dir, Some(date(6, 1)), None, &skip).unwrap();
+        assert_eq!(archived, vec![(date(6, 1), day(6, 1, 2))]);
+
+        // A day saved again since it was archived wins over the archived copy
+        save_log_for(&dirs, date(6, 1), &day(6, 1, 5)).unwrap();
+        assert_eq!(archive_before(&dirs, date(7, 1)).unwrap(), 1);
+        assert_eq!(load_archived_day(&dir, date(6, 1)).unwrap(), day(6, 1, 5));
+        assert_eq!(load_archived_day(&dir, date(6, 2)).unwrap(), day(6, 2, 3));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,135 @@
+// Starts tracking a project's task when the shell moves into its directory,
+// e.g. task 3 anywhere under ~/src/clientA, by asking the running app to
+// start it over its control socket. The `auto` subcommand does one check:
+//
+//     ydnc-time auto --map ~/src/clientA=3,~/src/oss=6 [--dir DIR] [--socket PATH]
+//
+// and `auto --hook --map ...` prints a snippet for ~/.bashrc or ~/.zshrc (so
+// new tmux panes too) that runs that check after every cd:
+//
+//     eval "$(ydnc-time auto --hook --map ~/src/clientA=3,~/src/oss=6)"
+//
+// Directories outside every mapped one leave tracking alone, as does being in
+// a project whose task is already being tracked.
+
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{control, storage::Dirs};
+
+/// Which task each project directory (and everything under it) is tracked as
+#[derive(Debug, PartialEq)]
+pub struct DirMap(Vec<(PathBuf, u8)>);
+
+impl DirMap {
+    /// Parses a map like "~/src/clientA=3,~/src/oss=6", expanding the ~
+    pub fn parse(map: &str) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        map.split(',')
+            .filter(|pair| !pair.trim().is_empty())
+            .map(|pair| {
+                let (dir, task) = pair
+                    .rsplit_once('=')
+                    .ok_or_else(|| invalid(format!("Expected DIR=TASK, got {:?}", pair)))?;
+                match task.trim().parse() {
+                    Ok(n @ 1..=8) => Ok((expand_home(dir.trim()), n)),
+                    _ => Err(invalid(format!("Invalid task {:?}, expected 1-8", task))),
+                }
+            })
+            .collect::<io::Result<_>>()
+            .map(Self)
+    }
+
+    /// The task of the innermost mapped directory containing `dir`, if any
+    pub fn task_for(&self, dir: &Path) -> Option<u8> {
+        self.0
+            .iter()
+            .filter(|(mapped, _)| dir.starts_with(mapped))
+            .max_by_key(|(mapped, _)| mapped.components().count())
+            .map(|(_, task)| *task)
+    }
+}
+
+fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(dir),
+    }
+}
+
+/// The shell snippet that runs `auto` after every cd, with the same options
+fn hook(map: &str, socket: &Path) -> String {
+    let socket = socket.display();
+    format!(
+        r#"_ydnc_time_auto() {{ (ydnc-time auto --map '{map}' --socket '{socket}' >/dev/null 2>&1 &) }}
+if [ -n "$ZSH_VERSION" ]; then
+    autoload -Uz add-zsh-hook && add-zsh-hook chpwd _ydnc_time_auto
+else
+    cd() {{ builtin cd "$@" && _ydnc_time_auto; }}
+fi
+_ydnc_time_auto
+"#
+    )
+}
+
+/// Runs the `auto` subcommand with its command line options, talking to the
+/// app keeping its files in `dirs`. Returns what to print, if anything.
+pub fn run(dirs: &Dirs, args: impl IntoIterator<Item = String>) -> io::Result<Option<String>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    let mut map = None;
+    let mut dir = None;
+    let mut socket = control::socket_path(dirs);
+    let mut print_hook = false;
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        if flag == "--hook" {
+            print_hook = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| invalid(format!("{} needs a value", flag)))?;
+        match flag.as_str() {
+            "--map" => map = Some(value),
+            "--dir" => dir = Some(PathBuf::from(value)),
+            "--socket" => socket = PathBuf::from(value),
+            _ => return Err(invalid(format!("Unknown option {}", flag))),
+        }
+    }
+    let map = map.ok_or_else(|| invalid("Expected --map DIR=TASK,...".to_string()))?;
+    let mapped = DirMap::parse(&map)?;
+    if print_hook {
+        return Ok(Some(hook(&map, &socket)));
+    }
+
+    let dir = match dir {
+        Some(dir) => dir,
+        None => env::current_dir()?,
+    };
+    let Some(number) = mapped.task_for(&dir) else {
+        return Ok(None);
+    };
+
+    // The app leaves a task that's already being tracked alone
+    control::request(&socket, &format!("start {}", number))?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_mapping() {
+        let dirs = DirMap::parse("/src/client=3, /src/client/oss=6,").unwrap();
+        assert_eq!(dirs.task_for(Path::new("/src/client/api")), Some(3));
+        assert_eq!(dirs.task_for(Path::new("/src/client/oss/lib")), Some(6));
+        assert_eq!(dirs.task_for(Path::new("/src/clientele")), None);
+
+        assert!(DirMap::parse("/src/client").is_err());
+        assert!(DirMap::parse("/src/client=9").is_err());
+    }
+}
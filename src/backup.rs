@@ -0,0 +1,73 @@
+// Copies each daily save file to a second directory (a NAS mount, a USB stick)
+// whenever it's saved, as a simple safeguard for people who don't sync their
+// save files anywhere. Copies happen on a background thread so that a slow or
+// missing drive never holds up the UI.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use tracing::{info, warn};
+
+use crate::Notifier;
+
+/// The thread that backs up save files, and how to send it files, if backups
+/// are on
+static WORKER: Mutex<Option<(mpsc::Sender<PathBuf>, thread::JoinHandle<()>)>> = Mutex::new(None);
+
+/// Turns backups to `dir` on, or off if None. Failures are sent to `notifier`.
+pub fn set_dir(dir: Option<PathBuf>, notifier: Notifier) {
+    let worker = dir.map(|dir| {
+        info!("Backing up save files to {}", dir.display());
+        let (tx, rx) = mpsc::channel::<PathBuf>();
+        // Dropping the sender (by turning backups off or changing the
+        // directory) ends the thread
+        let handle = thread::spawn(move || {
+            for file in rx {
+                if let Err(err) = copy_to(&file, &dir) {
+                    warn!("Unable to back up {}: {}", file.display(), err);
+                    let _ = notifier.send(format!(
+                        "Could not back up {} to {}: {}",
+                        file.file_name().unwrap_or_default().to_string_lossy(),
+                        dir.display(),
+                        err
+                    ));
+                }
+            }
+        });
+        (tx, handle)
+    });
+    *WORKER.lock().unwrap() = worker;
+}
+
+/// Waits for any backups still underway, e.g. before exiting
+pub fn finish() {
+    if let Some((tx, handle)) = WORKER.lock().unwrap().take() {
+        drop(tx);
+        let _ = handle.join();
+    }
+}
+
+/// Queues a just-saved file to be copied to the backup directory, if there is
+/// one
+pub fn back_up(file: &Path) {
+    if let Some((tx, _)) = WORKER.lock().unwrap().as_ref() {
+        let _ = tx.send(file.to_path_buf());
+    }
+}
+
+fn copy_to(file: &Path, dir: &Path) -> io::Result<()> {
+    let name = file
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Save file has no name"))?;
+    fs::create_dir_all(dir)?;
+    // Copy next to the old backup and then swap it in, so that failing
+    // halfway (e.g. the drive gets unplugged) doesn't ruin the old backup
+    let target = dir.join(name);
+    let tmp = target.with_extension("ron.tmp");
+    fs::copy(file, &tmp)?;
+    fs::rename(tmp, target)
+}
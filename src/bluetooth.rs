@@ -1,3 +1,5 @@
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -6,7 +8,6 @@ use btleplug::api::bleuuid::BleUuid;
 use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
 use btleplug::api::{Characteristic, PeripheralProperties};
 use btleplug::platform::{Manager, Peripheral, PeripheralId};
-use chrono::Local;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::time;
@@ -14,10 +15,91 @@ use tokio_stream::StreamExt;
 use tracing::{debug, info, trace, warn};
 use uuid::{uuid, Uuid};
 
-use crate::{lock_and_set_connected, AppState};
+use crate::{
+    error::YdncError,
+    input::{EventSender, InputEvent, InputSource},
+    lock_and_message, AppState,
+};
 
-// const TRACKER_SERVICE: Uuid = uuid!("c7e70010-c847-11e6-8175-8c89a55d403c");
-const TRACKER_SIDE_CH: Uuid = uuid!("c7e70012-c847-11e6-8175-8c89a55d403c");
+/// Where one firmware variant of the tracker reports which side is up
+#[derive(Debug)]
+struct TrackerProfile {
+    name: &'static str,
+    service: Uuid,
+    side_ch: Uuid,
+}
+
+/// The tracker firmware variants we know how to talk to, tried in order. Only
+/// the legacy Timeular orientation characteristic is supported for now; for a
+/// tracker that reports its side anywhere else, the user can set a custom side
+/// characteristic, and the characteristics each unrecognized tracker offers are
+/// logged to help with finding it.
+const TRACKER_PROFILES: &[TrackerProfile] = &[TrackerProfile {
+    name: "Timeular orientation service",
+    service: uuid!("c7e70010-c847-11e6-8175-8c89a55d403c"),
+    side_ch: uuid!("c7e70012-c847-11e6-8175-8c89a55d403c"),
+}];
+
+/// Finds the characteristic that reports the tracker's side, trying the
+/// user's own characteristic if they've set one and then each known firmware
+/// profile in turn
+fn probe_side_characteristic(
+    tracker: &Peripheral,
+    custom_side_ch: Option<Uuid>,
+) -> Option<Characteristic> {
+    let chars = tracker.characteristics();
+    if let Some(uuid) = custom_side_ch {
+        if let Some(c) = chars.iter().find(|c| c.uuid == uuid) {
+            info!(
+                "Tracker matched the configured side characteristic {}",
+                uuid
+            );
+            return Some(c.clone());
+        }
+    }
+    let found = TRACKER_PROFILES.iter().find_map(|profile| {
+        chars
+            .iter()
+            .find(|c| c.uuid == profile.side_ch && c.service_uuid == profile.service)
+            .map(|c| (profile, c.clone()))
+    });
+    match found {
+        Some((profile, side_ch)) => {
+            info!("Tracker matched the {} profile", profile.name);
+            Some(side_ch)
+        }
+        None => {
+            for c in &chars {
+                debug!(
+                    "Unrecognized tracker offers characteristic {} in service {} ({:?})",
+                    c.uuid, c.service_uuid, c.properties
+                );
+            }
+            None
+        }
+    }
+}
+
+/// The task the user wants tracker side `side` (1-8) to start, if any
+fn side_to_task(app_state: &AppState, side: u8) -> Option<u8> {
+    let app = app_state.lock().unwrap();
+    match app.preferences.tracker_sides {
+        Some(sides) => sides
+            .get((side as usize).wrapping_sub(1))
+            .copied()
+            .flatten()
+            .filter(|n| (1..=8).contains(n)),
+        None => Some(side),
+    }
+}
+
+/// The window for the double-flip break gesture, if the user has turned it on
+fn double_flip_window(app_state: &AppState) -> Option<Duration> {
+    let app = app_state.lock().unwrap();
+    app.preferences
+        .tracker_double_flip_secs
+        .map(|secs| Duration::from_secs(secs.into()))
+}
 
 /// This macro adds a timeout, awaits it, unnests the Result, and returns an
 /// anyhow Result. The Error type will be either `tokio::time::error::Elapsed`
@@ -55,6 +137,7 @@ enum State {
 async fn create_conn_mgr(
     app_state: &AppState,
     state_tx: &mpsc::UnboundedSender<State>,
+    events: &EventSender,
 ) -> btleplug::Result<()> {
     let manager = Manager::new().await?;
     let adapters = manager.adapters().await?;
@@ -66,15 +149,26 @@ async fn create_conn_mgr(
     }
     let central = central.unwrap();
 
+    let (name_filter, custom_side_ch) = {
+        let app = app_state.lock().unwrap();
+        (
+            app.preferences
+                .tracker_name
+                .clone()
+                .unwrap_or_else(|| String::from("Timeular")),
+            app.preferences.tracker_side_uuid,
+        )
+    };
+
     let _ = state_tx.send(State::Connecting);
 
-    let mut events = central.events().await?;
+    let mut central_events = central.events().await?;
 
     central.start_scan(ScanFilter::default()).await?;
     let mut scanning = true;
 
     let mut tracker_id: Option<PeripheralId> = None;
-    while let Some(event) = events.next().await {
+    while let Some(event) = central_events.next().await {
         match event {
             CentralEvent::DeviceDiscovered(id) => {
                 debug!(
@@ -102,7 +196,7 @@ async fn create_conn_mgr(
                 let props = props.unwrap();
 
                 if let Some(PeripheralProperties { local_name, .. }) = props {
-                    if local_name.map_or(false, |name| name.contains("Timeular")) {
+                    if local_name.is_some_and(|name| name.contains(&name_filter)) {
                         info!("Found tracker");
 
                         if let Err(e) = await_timeout!(10, p.connect()) {
@@ -115,17 +209,16 @@ async fn create_conn_mgr(
                             continue;
                         }
 
-                        let chars = p.characteristics();
-                        let cmd_char = chars.into_iter().find(|c| c.uuid == TRACKER_SIDE_CH);
-                        if cmd_char.is_none() {
-                            info!("Found a device named like a tracker but lacking the correct service");
+                        let Some(cmd_char) = probe_side_characteristic(&p, custom_side_ch) else {
+                            info!(
+                                "Found a device named like a tracker but lacking any known service"
+                            );
                             continue;
-                        }
-                        let cmd_char = cmd_char.unwrap();
+                        };
 
                         tracker_id = Some(id);
                         let _ = state_tx.send(State::Connected(p, cmd_char));
-                        lock_and_set_connected(app_state, true);
+                        let _ = events.send(InputEvent::Connected(true));
                         // this one is okay to kill the task if it fails b/c it'd mean our BTLE
                         // Central has died which I'm assuming is unrecoverable
                         central.stop_scan().await?;
@@ -145,7 +238,7 @@ async fn create_conn_mgr(
                     if tid == &id {
                         tracker_id = None;
                         let _ = state_tx.send(State::Connecting);
-                        lock_and_set_connected(app_state, false);
+                        let _ = events.send(InputEvent::Connected(false));
                         if !scanning {
                             central.start_scan(ScanFilter::default()).await?;
                             scanning = true;
@@ -180,7 +273,11 @@ async fn create_conn_mgr(
     Ok(())
 }
 
-async fn start_conn_mgr(app_state: AppState, state_tx: mpsc::UnboundedSender<State>) {
+async fn start_conn_mgr(
+    app_state: AppState,
+    state_tx: mpsc::UnboundedSender<State>,
+    events: EventSender,
+) {
     let mut i = 5;
     while i > 0 {
         i -= 1;
@@ -189,8 +286,11 @@ async fn start_conn_mgr(app_state: AppState, state_tx: mpsc::UnboundedSender<Sta
         } else {
             "giving up on bluetooth"
         };
-        if let Err(e) = create_conn_mgr(&app_state, &state_tx).await {
+        if let Err(e) = create_conn_mgr(&app_state, &state_tx, &events).await {
             warn!("Received BTLE error, {}: {}", msg, e);
+            if i == 0 {
+                lock_and_message(&app_state, YdncError::from(e).to_string());
+            }
         } else {
             warn!("BTLE Central is/became unavailable, {}", msg,);
         }
@@ -220,6 +320,7 @@ async fn subscribe(
     tracker: &Peripheral,
     cmd_char: &Characteristic,
     app_state: &AppState,
+    events: &EventSender,
 ) -> anyhow::Result<()> {
     info!("Starting subscription handler");
     if !ensure_connection(tracker).await? {
@@ -232,17 +333,19 @@ async fn subscribe(
     await_timeout!(3, tracker.subscribe(cmd_char))?;
     let mut notifs = await_timeout!(3, tracker.notifications())?;
 
+    // The side the tracker is on, and the side it was on when it last went
+    // onto an edge and when, for spotting the double-flip gesture
+    let mut current_side = current_value.first().copied();
+    let mut left_side: Option<(u8, time::Instant)> = None;
+
     if let Some(&side_num) = current_value.first() {
         info!("...got {:?}", side_num);
         // If the tracker is not on a side (sides are 1-8, other numbers are
         // edges), don't do anything
         if (1..=8).contains(&side_num) {
             info!("Setting initial state to side {}", side_num);
-            let mut app = app_state.lock().unwrap();
-            // Only do something if there is NOT an already open entry with the
-            // same number
-            if app.open_entry_number().map_or(true, |n| n != side_num) {
-                app.start_entry(side_num);
+            if let Some(number) = side_to_task(app_state, side_num) {
+                let _ = events.send(InputEvent::Start(number));
             }
         }
     }
@@ -262,19 +365,33 @@ async fn subscribe(
             }
             Ok(Some(notif)) => {
                 if let Some(&side_num) = notif.value.first() {
-                    let mut app = app_state.lock().unwrap();
                     match side_num {
                         1..=8 => {
                             info!("Tracker switched to side {:?}", side_num);
-                            // Only do something if there is NOT an already open
-                            // entry with the same number
-                            if app.open_entry_number().map_or(true, |n| n != side_num) {
-                                app.start_entry(side_num);
+                            current_side = Some(side_num);
+                            let Some(number) = side_to_task(app_state, side_num) else {
+                                info!("Side {} is set to do nothing", side_num);
+                                continue;
+                            };
+                            let double_flipped = left_side.take().is_some_and(|(side, left_at)| {
+                                side == side_num
+                                    && double_flip_window(app_state)
+                                        .is_some_and(|window| left_at.elapsed() <= window)
+                            });
+                            if double_flipped {
+                                info!("Tracker double-flipped on side {:?}", side_num);
+                                let _ = events.send(InputEvent::ToggleBreak(number));
+                            } else {
+                                let _ = events.send(InputEvent::Switch(number));
                             }
                         }
                         _ => {
                             info!("Tracker switched to edge {:?}", side_num);
-                            app.close_entry_if_open(Local::now());
+                            if let Some(side) = current_side.take().filter(|s| (1..=8).contains(s))
+                            {
+                                left_side = Some((side, time::Instant::now()));
+                            }
+                            let _ = events.send(InputEvent::Stop);
                         }
                     }
                 }
@@ -285,7 +402,12 @@ async fn subscribe(
     Ok(())
 }
 
-fn spawn_sub_task(tracker: Peripheral, chr: Characteristic, app_state: AppState) -> JoinHandle<()> {
+fn spawn_sub_task(
+    tracker: Peripheral,
+    chr: Characteristic,
+    app_state: AppState,
+    events: EventSender,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         let mut i = 5;
         while i > 0 {
@@ -295,7 +417,7 @@ fn spawn_sub_task(tracker: Peripheral, chr: Characteristic, app_state: AppState)
             } else {
                 "giving up"
             };
-            if let Err(e) = subscribe(&tracker, &chr, &app_state).await {
+            if let Err(e) = subscribe(&tracker, &chr, &app_state, &events).await {
                 warn!(
                     "Error subscribing to notifications from tracker, {}: {}",
                     msg, e
@@ -308,7 +430,11 @@ fn spawn_sub_task(tracker: Peripheral, chr: Characteristic, app_state: AppState)
     })
 }
 
-async fn start_subscriber(app_state: &AppState, mut state_rx: mpsc::UnboundedReceiver<State>) {
+async fn start_subscriber(
+    app_state: &AppState,
+    mut state_rx: mpsc::UnboundedReceiver<State>,
+    events: EventSender,
+) {
     let mut handler: Option<(JoinHandle<()>, Peripheral)> = None;
 
     // Initialization is different; we can take some shortcuts during this phase
@@ -320,7 +446,10 @@ async fn start_subscriber(app_state: &AppState, mut state_rx: mpsc::UnboundedRec
             }
             State::Connected(t, c) => {
                 info!("State::Connected > Subscriber initialization complete");
-                handler = Some((spawn_sub_task(t.clone(), c, Arc::clone(app_state)), t));
+                handler = Some((
+                    spawn_sub_task(t.clone(), c, Arc::clone(app_state), events.clone()),
+                    t,
+                ));
                 break;
             }
             s => debug!(
@@ -345,8 +474,10 @@ async fn start_subscriber(app_state: &AppState, mut state_rx: mpsc::UnboundedRec
             }
             State::Connected(t, c) => {
                 info!("State::Connected > Starting new handler");
-                let prev_handler =
-                    handler.replace((spawn_sub_task(t.clone(), c, Arc::clone(app_state)), t));
+                let prev_handler = handler.replace((
+                    spawn_sub_task(t.clone(), c, Arc::clone(app_state), events.clone()),
+                    t,
+                ));
 
                 if let Some((task, _)) = prev_handler {
                     task.abort();
@@ -371,20 +502,21 @@ pub struct BluetoothTask {
 }
 
 impl BluetoothTask {
-    pub fn start(app: AppState) -> Self {
+    pub fn start(app: AppState, events: EventSender) -> Self {
         let (state_tx, state_rx) = mpsc::unbounded_channel();
         state_tx.send(State::Starting).unwrap();
 
         let cmgr_app = Arc::clone(&app);
         let cmgr_tx = state_tx.clone();
+        let cmgr_events = events.clone();
         info!("Starting BTLE connection manager");
         let conn_mgr = tokio::spawn(async move {
-            start_conn_mgr(cmgr_app, cmgr_tx).await;
+            start_conn_mgr(cmgr_app, cmgr_tx, cmgr_events).await;
         });
 
         info!("Starting BTLE subscriber");
         let subscriber = tokio::spawn(async move {
-            start_subscriber(&app, state_rx).await;
+            start_subscriber(&app, state_rx, events).await;
         });
 
         Self {
@@ -422,3 +554,27 @@ impl BluetoothTask {
         }
     }
 }
+
+/// The tracker, as an input source
+#[derive(Default)]
+pub struct BluetoothInput {
+    task: Option<BluetoothTask>,
+}
+
+impl InputSource for BluetoothInput {
+    fn name(&self) -> &str {
+        "Bluetooth tracker"
+    }
+
+    fn start(&mut self, app: &AppState, events: EventSender) {
+        self.task = Some(BluetoothTask::start(Arc::clone(app), events));
+    }
+
+    fn stop(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async {
+            if let Some(task) = self.task.take() {
+                task.stop().await;
+            }
+        })
+    }
+}
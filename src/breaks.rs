@@ -0,0 +1,178 @@
+// Breaks taken from a task, with b on Home or by double-flipping the tracker,
+// are recorded as their own intervals rather than just leaving a gap in the
+// log, so that stats can tell a break apart from time that simply wasn't
+// tracked. Like plans, each day's breaks live in their own file in a "breaks"
+// subdirectory of the save file directory.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    crypto,
+    error::{self, YdncError},
+    storage::Dirs,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Break {
+    pub start: DateTime<Local>,
+    /// None while the break is still going
+    pub end: Option<DateTime<Local>>,
+    /// The task the break was taken from, which resumes once it's over
+    pub number: u8,
+}
+
+impl Break {
+    /// How long the break lasted, running until `now` if it's still going
+    pub fn duration(&self, now: Option<DateTime<Local>>) -> chrono::Duration {
+        match self.end.or(now) {
+            Some(end) => (end - self.start).max(chrono::Duration::zero()),
+            None => chrono::Duration::zero(),
+        }
+    }
+}
+
+/// The sum of the breaks' durations
+pub fn total(breaks: &[Break], now: Option<DateTime<Local>>) -> chrono::Duration {
+    breaks
+        .iter()
+        .fold(chrono::Duration::zero(), |acc, b| acc + b.duration(now))
+}
+
+fn get_breaks_dir(dirs: &Dirs) -> PathBuf {
+    dirs.data().join("breaks")
+}
+
+fn breaks_file(dirs: &Dirs, date: NaiveDate) -> PathBuf {
+    get_breaks_dir(dirs).join(format!("{}.ron", date.format("%F")))
+}
+
+pub fn save_breaks(dirs: &Dirs, date: NaiveDate, breaks: &[Break]) -> error::Result<()> {
+    dirs.instance().check_writable()?;
+    let filename = breaks_file(dirs, date);
+
+    info!("Saving breaks to {}", filename.display());
+    let contents = ron::ser::to_string_pretty(breaks, ron::ser::PrettyConfig::default())?;
+    Ok(crypto::write(&filename, contents.into_bytes())?)
+}
+
+/// Loads the breaks taken on the given date. A day without breaks has none
+/// rather than an error.
+pub fn load_breaks(dirs: &Dirs, date: NaiveDate) -> error::Result<Vec<Break>> {
+    let filename = breaks_file(dirs, date);
+    if !filename.exists() {
+        return Ok(Vec::new());
+    }
+
+    info!("Loading breaks from {}", filename.display());
+    read_breaks(&filename)
+}
+
+fn read_breaks(path: &Path) -> error::Result<Vec<Break>> {
+    let contents = crypto::read(path)?;
+    ron::de::from_bytes(&contents).map_err(|e| YdncError::from(e).in_file(path))
+}
+
+/// How long was spent on breaks within the given (inclusive) dates. Breaks
+/// still going count for nothing, like open entries in history. Files that
+/// can't be read are skipped with a warning.
+pub fn total_between(
+    dirs: &Dirs,
+    min_date: Option<NaiveDate>,
+    max_date: Option<NaiveDate>,
+) -> io::Result<chrono::Duration> {
+    let mut total_time = chrono::Duration::zero();
+    let listed = match fs::read_dir(get_breaks_dir(dirs)) {
+        Ok(listed) => listed,
+        // No breaks taken yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(total_time),
+        Err(e) => return Err(e),
+    };
+    for entry in listed {
+        let path = entry?.path();
+        let Some(date) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| NaiveDate::parse_from_str(s, "%F").ok())
+        else {
+            continue;
+        };
+        if min_date.is_some_and(|min| date < min) || max_date.is_some_and(|max| date > max) {
+            continue;
+        }
+
+        match read_breaks(&path) {
+            Ok(breaks) => total_time = total_time + total(&breaks, None),
+            Err(e) => warn!("Unable to load breaks from {}: {}", path.display(), e),
+        }
+    }
+    Ok(total_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn durations() {
+        let start = Local.with_ymd_and_hms(2023, 6, 5, 12, 0, 0).unwrap();
+        let breaks = [
+            Break {
+                start,
+                end: Some(start + Duration::minutes(15)),
+                number: 1,
+            },
+            Break {
+                start: start + Duration::hours(3),
+                end: None,
+                number: 2,
+            },
+        ];
+        assert_eq!(breaks[1].duration(None), Duration::zero());
+        assert_eq!(total(&breaks, None), Duration::minutes(15));
+        assert_eq!(
+            total(&breaks, Some(start + Duration::hours(4))),
+            Duration::minutes(75)
+        );
+    }
+
+    #[test]
+    fn breaks_between() {
+        let dir = std::env::temp_dir().join(format!("ydnc-breaks-{}", std::process::id()));
+        let dirs = Dirs::in_dir(dir.clone());
+        let date = |d| NaiveDate::from_ymd_opt(2023, 6, d).unwrap();
+        let taken = |d, minutes| {
+            let start = Local.with_ymd_and_hms(2023, 6, d, 12, 0, 0).unwrap();
+            vec![Break {
+                start,
+                end: Some(start + Duration::minutes(minutes)),
+                number: 1,
+            }]
+        };
+        assert!(load_breaks(&dirs, date(5)).unwrap().is_empty());
+        assert_eq!(total_between(&dirs, None, None).unwrap(), Duration::zero());
+
+        save_breaks(&dirs, date(5), &taken(5, 10)).unwrap();
+        save_breaks(&dirs, date(6), &taken(6, 20)).unwrap();
+        save_breaks(&dirs, date(7), &taken(7, 30)).unwrap();
+        assert_eq!(load_breaks(&dirs, date(6)).unwrap(), taken(6, 20));
+        assert_eq!(
+            total_between(&dirs, Some(date(6)), Some(date(7))).unwrap(),
+            Duration::minutes(50)
+        );
+        assert_eq!(
+            total_between(&dirs, None, Some(date(5))).unwrap(),
+            Duration::minutes(10)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
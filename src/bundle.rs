@@ -0,0 +1,151 @@
+// Theme bundles package up how the app looks (its theme, task colors and fill
+// patterns) as a file that can be shared, like "colorblind-safe.ron". Bundles
+// are kept in a "bundles" folder next to the settings file: exporting from
+// Settings writes the current look there, and any bundle file dropped in there
+// is offered alongside the built-in ones.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{error, migrate, storage::Dirs, ui::Theme, Preferences};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThemeBundle {
+    /// Taken from the file name, rather than saved in the file, so that
+    /// renaming a shared bundle renames it in the app too
+    #[serde(skip)]
+    pub name: String,
+    theme: Option<Theme>,
+    colors: Option<[Color; 8]>,
+    patterns: Option<bool>,
+}
+
+impl migrate::Migrate for ThemeBundle {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        migrate::parse_data(version, contents)
+    }
+}
+
+impl ThemeBundle {
+    /// The look the user currently has
+    pub fn from_prefs(name: impl Into<String>, prefs: &Preferences) -> Self {
+        Self {
+            name: name.into(),
+            theme: prefs.theme,
+            colors: prefs.colors,
+            patterns: prefs.patterns,
+        }
+    }
+
+    /// Switches the user's look to this bundle's. Doesn't save the preferences.
+    pub fn apply_to(&self, prefs: &mut Preferences) {
+        prefs.theme = self.theme;
+        prefs.colors = self.colors;
+        prefs.patterns = self.patterns;
+    }
+}
+
+fn builtin_bundles() -> Vec<ThemeBundle> {
+    let builtin = |name: &str, theme, patterns| ThemeBundle {
+        name: name.to_string(),
+        theme: Some(theme),
+        colors: None,
+        patterns: Some(patterns),
+    };
+    vec![
+        builtin("default", Theme::Default, false),
+        builtin("colorblind-safe", Theme::HighContrast, true),
+        builtin("monochrome", Theme::Monochrome, true),
+    ]
+}
+
+fn bundles_dir(dirs: &Dirs) -> PathBuf {
+    dirs.config().join("bundles")
+}
+
+fn load_bundle(path: &Path) -> io::Result<ThemeBundle> {
+    let contents = fs::read(path)?;
+    let mut bundle: ThemeBundle = migrate::from_ron(&contents).map_err(|e| e.in_file(path))?;
+    bundle.name = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    Ok(bundle)
+}
+
+/// The built-in bundles followed by the user's own, sorted by name. Bundle
+/// files that can't be read are skipped with a warning.
+pub fn load_bundles(dirs: &Dirs) -> Vec<ThemeBundle> {
+    let mut bundles = builtin_bundles();
+    let entries = match fs::read_dir(bundles_dir(dirs)) {
+        Ok(entries) => entries,
+        // None of the user's own yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return bundles,
+        Err(e) => {
+            warn!("Unable to look for theme bundles: {}", e);
+            return bundles;
+        }
+    };
+
+    let mut own: Vec<ThemeBundle> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .filter_map(|path| match load_bundle(&path) {
+            Ok(bundle) => Some(bundle),
+            Err(e) => {
+                warn!("Unable to load theme bundle {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    own.sort_by(|a, b| a.name.cmp(&b.name));
+    bundles.extend(own);
+    bundles
+}
+
+/// Saves the user's current look as a new bundle, named for when it was
+/// exported, and returns where it was written. Bundles aren't encrypted, since
+/// they're meant for sharing.
+pub fn export(dirs: &Dirs, prefs: &Preferences) -> io::Result<PathBuf> {
+    let name = format!("exported-{}", Local::now().format("%Y%m%d-%H%M%S"));
+    let dir = bundles_dir(dirs);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.ron", name));
+    info!("Exporting theme bundle to {}", path.display());
+    let contents = migrate::to_ron(&ThemeBundle::from_prefs(name, prefs), true)?;
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_round_trip() {
+        let mut prefs = Preferences {
+            theme: Some(Theme::Monochrome),
+            colors: Some([Color::Red; 8]),
+            ..Default::default()
+        };
+        let bundle = ThemeBundle::from_prefs("mine", &prefs);
+        let contents = migrate::to_ron(&bundle, true).unwrap();
+        let mut loaded: ThemeBundle = migrate::from_ron(contents.as_bytes()).unwrap();
+        loaded.name = bundle.name.clone();
+        assert_eq!(loaded, bundle);
+
+        builtin_bundles()[1].apply_to(&mut prefs);
+        assert_eq!(prefs.theme, Some(Theme::HighContrast));
+        assert_eq!(prefs.colors, None);
+        loaded.apply_to(&mut prefs);
+        assert_eq!(ThemeBundle::from_prefs("mine", &prefs), bundle);
+    }
+}
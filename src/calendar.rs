@@ -0,0 +1,317 @@
+// Events from the user's calendar, which start the task whose label is in
+// their title as they begin. They're read from an iCalendar (.ics) file, and
+// with the "caldav" feature from a CalDAV calendar too. Like Slack's token, the
+// CalDAV username and password live in their own "caldav-secrets.ron" next to
+// the settings file, like `(username: "me", password: "...")`, and requests are
+// made with curl on a background thread.
+
+use std::{fs, io, path::Path};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use tracing::{info, warn};
+
+#[cfg(feature = "caldav")]
+pub use caldav::{start_fetching, take_fetched};
+
+/// One event from the user's calendar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start: DateTime<Local>,
+}
+
+/// Parses an iCalendar date-time like "20230605T090000Z" (UTC) or
+/// "20230605T090000" (local). Time zone IDs aren't supported, so times given
+/// in a named zone are assumed to be local; all-day events have no time and
+/// are skipped.
+fn parse_ics_datetime(value: &str) -> Option<DateTime<Local>> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        Some(Utc.from_utc_datetime(&naive).with_timezone(&Local))
+    } else {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        naive.and_local_timezone(Local).earliest()
+    }
+}
+
+/// Pulls the events with a start time out of an iCalendar (.ics) file.
+/// Recurring events only count their first occurrence.
+pub fn parse_ics(contents: &str) -> Vec<CalendarEvent> {
+    // Long lines are folded onto following lines that start with whitespace
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(continued) if !lines.is_empty() => lines.last_mut().unwrap().push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut summary: Option<String> = None;
+    let mut start: Option<DateTime<Local>> = None;
+    let mut in_event = false;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop parameters like ";TZID=Europe/Paris"
+        let name = name.split(';').next().unwrap_or_default();
+        match (name, value) {
+            ("BEGIN", "VEVENT") => {
+                in_event = true;
+                summary = None;
+                start = None;
+            }
+            ("END", "VEVENT") => {
+                in_event = false;
+                if let (Some(summary), Some(start)) = (summary.take(), start.take()) {
+                    events.push(CalendarEvent { summary, start });
+                }
+            }
+            ("SUMMARY", _) if in_event => {
+                summary = Some(value.replace("\\,", ",").replace("\\;", ";"));
+            }
+            ("DTSTART", _) if in_event => start = parse_ics_datetime(value),
+            _ => {}
+        }
+    }
+
+    events.sort_by_key(|e| e.start);
+    events
+}
+
+pub fn load_events(path: &Path) -> io::Result<Vec<CalendarEvent>> {
+    info!("Loading calendar from {}", path.display());
+    let contents = fs::read_to_string(path)?;
+    let events = parse_ics(&contents);
+    if events.is_empty() {
+        warn!("No timed events found in calendar {}", path.display());
+    }
+    Ok(events)
+}
+
+/// Finds the task whose label appears in the event's title, ignoring case
+pub fn matching_task(event: &CalendarEvent, labels: Option<&[String; 8]>) -> Option<u8> {
+    let summary = event.summary.to_lowercase();
+    labels?
+        .iter()
+        .position(|label| !label.is_empty() && summary.contains(&label.to_lowercase()))
+        .map(|i| i as u8 + 1)
+}
+
+#[cfg(feature = "caldav")]
+mod caldav {
+    use std::{
+        fs, io,
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Mutex,
+        },
+        thread,
+    };
+
+    use chrono::{Duration, Utc};
+    use serde::Deserialize;
+    use tracing::info;
+
+    use super::{parse_ics, CalendarEvent};
+    use crate::{
+        error::{self, YdncError},
+        storage::Dirs,
+    };
+
+    #[derive(Deserialize)]
+    struct Secrets {
+        username: String,
+        password: String,
+    }
+
+    /// Whether the calendar is being fetched in the background
+    static FETCHING: AtomicBool = AtomicBool::new(false);
+    /// What the last fetch found, until the app takes it in
+    static FETCHED: Mutex<Option<io::Result<Vec<CalendarEvent>>>> = Mutex::new(None);
+
+    fn secrets_file_path(dirs: &Dirs) -> PathBuf {
+        dirs.config().join("caldav-secrets.ron")
+    }
+
+    fn load_secrets(dirs: &Dirs) -> error::Result<Option<Secrets>> {
+        let path = secrets_file_path(dirs);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            // Not every calendar needs logging into
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        ron::de::from_bytes(&contents).map_err(|e| YdncError::from(e).in_file(&path))
+    }
+
+    /// Fetches the events at the CalDAV calendar `url` in the background, for
+    /// `take_fetched` to hand over. Does nothing if a fetch is going already.
+    pub fn start_fetching(dirs: &Dirs, url: String) {
+        let secrets = match load_secrets(dirs) {
+            Ok(secrets) => secrets,
+            Err(e) => {
+                *FETCHED.lock().unwrap() = Some(Err(io::Error::other(format!(
+                    "Could not load the CalDAV login: {}",
+                    e
+                ))));
+                return;
+            }
+        };
+        if FETCHING.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        thread::spawn(move || {
+            let fetched = fetch(&url, secrets.as_ref());
+            *FETCHED.lock().unwrap() = Some(fetched);
+            FETCHING.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// What the last fetch of the calendar found, once it's done
+    pub fn take_fetched() -> Option<io::Result<Vec<CalendarEvent>>> {
+        FETCHED.lock().unwrap().take()
+    }
+
+    /// Asks the server for the events from yesterday to the day after
+    /// tomorrow, with recurring ones expanded into each time they happen
+    fn fetch(url: &str, secrets: Option<&Secrets>) -> io::Result<Vec<CalendarEvent>> {
+        info!("Fetching calendar from {}", url);
+        let now = Utc::now();
+        let format = |dt: chrono::DateTime<Utc>| dt.format("%Y%m%dT%H%M%SZ").to_string();
+        let (start, end) = (
+            format(now - Duration::days(1)),
+            format(now + Duration::days(2)),
+        );
+        let query = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <C:calendar-query xmlns:D=\"DAV:\" xmlns:C=\"urn:ietf:params:xml:ns:caldav\">\
+             <D:prop><C:calendar-data><C:expand start=\"{0}\" end=\"{1}\"/></C:calendar-data></D:prop>\
+             <C:filter><C:comp-filter name=\"VCALENDAR\"><C:comp-filter name=\"VEVENT\">\
+             <C:time-range start=\"{0}\" end=\"{1}\"/>\
+             </C:comp-filter></C:comp-filter></C:filter>\
+             </C:calendar-query>",
+            start, end
+        );
+
+        // The login goes in through curl's config on stdin rather than its
+        // arguments, which other users can see
+        let mut curl = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--config", "-"])
+            .args(["--request", "REPORT", "--header", "Depth: 1"])
+            .args(["--header", "Content-Type: application/xml; charset=utf-8"])
+            .args(["--data-raw", &query])
+            .arg(url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = curl.stdin.take() {
+            if let Some(secrets) = secrets {
+                let user = format!("{}:{}", secrets.username, secrets.password);
+                writeln!(
+                    stdin,
+                    "user = \"{}\"",
+                    user.replace('\\', "\\\\").replace('"', "\\\"")
+                )?;
+            }
+        }
+        let output = curl.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(parse_multistatus(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Pulls the events out of the calendars in a CalDAV REPORT response, where
+    /// each one is XML-escaped inside a calendar-data element
+    pub(super) fn parse_multistatus(body: &str) -> Vec<CalendarEvent> {
+        let mut events = Vec::new();
+        let mut rest = body;
+        while let Some(start) = rest.find("BEGIN:VCALENDAR") {
+            let Some(len) = rest[start..].find("END:VCALENDAR") else {
+                break;
+            };
+            let end = start + len + "END:VCALENDAR".len();
+            let calendar = rest[start..end]
+                .replace("&#13;", "")
+                .replace("&#xD;", "")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&");
+            events.extend(parse_ics(&calendar));
+            rest = &rest[end..];
+        }
+        events.sort_by_key(|e| e.start);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_events() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Weekly team\r\n  meeting\r\n\
+                   DTSTART;TZID=America/Chicago:20230605T090000\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Holiday\r\n\
+                   DTSTART;VALUE=DATE:20230606\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let events = parse_ics(ics);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "Weekly team meeting");
+        assert_eq!(
+            events[0].start.naive_local(),
+            NaiveDateTime::parse_from_str("20230605T090000", "%Y%m%dT%H%M%S").unwrap()
+        );
+
+        let labels = ["Coding", "Meeting", "", "", "", "", "", ""].map(String::from);
+        assert_eq!(matching_task(&events[0], Some(&labels)), Some(2));
+    }
+
+    #[cfg(feature = "caldav")]
+    #[test]
+    fn parse_caldav_response() {
+        let body = "<?xml version=\"1.0\"?>\n\
+                    <d:multistatus xmlns:d=\"DAV:\" xmlns:cal=\"urn:ietf:params:xml:ns:caldav\">\n\
+                    <d:response><d:propstat><d:prop><cal:calendar-data>BEGIN:VCALENDAR&#13;\n\
+                    BEGIN:VEVENT&#13;\n\
+                    SUMMARY:Design review &amp; planning&#13;\n\
+                    DTSTART:20230605T140000Z&#13;\n\
+                    END:VEVENT&#13;\n\
+                    END:VCALENDAR</cal:calendar-data></d:prop></d:propstat></d:response>\n\
+                    <d:response><d:propstat><d:prop><cal:calendar-data>BEGIN:VCALENDAR\n\
+                    BEGIN:VEVENT\n\
+                    SUMMARY:Standup\n\
+                    DTSTART:20230605T090000Z\n\
+                    END:VEVENT\n\
+                    END:VCALENDAR</cal:calendar-data></d:prop></d:propstat></d:response>\n\
+                    </d:multistatus>";
+        let events = caldav::parse_multistatus(body);
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| e.summary.as_str())
+                .collect::<Vec<_>>(),
+            ["Standup", "Design review & planning"]
+        );
+        assert_eq!(
+            events[0].start,
+            Utc.with_ymd_and_hms(2023, 6, 5, 9, 0, 0).unwrap()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+// A unix socket that scripts and shell hooks can control tracking through,
+// when built with the "control-socket" feature. It lives in the cache
+// directory as "control.sock", only the user can connect to it, and it takes
+// one command per line, answering each with a line of its own:
+//
+// - `start N`: start tracking task N (1-8), unless it's being tracked already
+// - `stop`: stop tracking
+// - `current`: the number of the task being tracked, or 0 if nothing is
+//
+// Answers are "ok", the number for `current`, or "error: " and why not. The
+// `auto` subcommand uses it to start tasks by directory.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chrono::Local;
+use tracing::{info, warn};
+
+use crate::{storage::Dirs, App, AppState};
+
+/// Where the running app listens
+pub fn socket_path(dirs: &Dirs) -> PathBuf {
+    dirs.cache().join("control.sock")
+}
+
+/// Makes one request of the app listening on `path`, returning its answer
+pub fn request(path: &Path, command: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{}", command)?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    let answer = answer.trim_end();
+    match answer.strip_prefix("error: ") {
+        Some(e) => Err(io::Error::other(e.to_string())),
+        None => Ok(answer.to_string()),
+    }
+}
+
+/// Carries out one command, returning the answer to it
+fn respond(app: &mut App, command: &str) -> String {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("start"), Some(n), None) => match n.parse::<u8>() {
+            Ok(number @ 1..=8) => {
+                if app.open_entry_for(number).is_none() {
+                    app.start_entry(number);
+                    app.notify(format!("Started task {} via the control socket", number));
+                }
+                String::from("ok")
+            }
+            _ => String::from("error: Task number must be 1-8"),
+        },
+        (Some("stop"), None, None) => {
+            app.close_entry_if_open(Local::now());
+            app.notify("Stopped tracking via the control socket");
+            String::from("ok")
+        }
+        (Some("current"), None, None) => app.open_entry_number().unwrap_or(0).to_string(),
+        _ => format!("error: Unknown command {:?}", command),
+    }
+}
+
+/// How long a connection can sit idle before it's dropped, so that one left
+/// open never keeps the others waiting
+const IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn handle(app: &AppState, stream: UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let answer = respond(&mut app.lock().unwrap(), line.trim());
+        writeln!(writer, "{}", answer)?;
+        line.clear();
+    }
+    Ok(())
+}
+
+pub struct ControlSocket {
+    path: PathBuf,
+    stopping: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl ControlSocket {
+    /// Starts listening on a background thread. Returns None if the socket
+    /// can't be made, e.g. another instance is listening already, since the
+    /// app works fine without it.
+    pub fn start(app: AppState) -> Option<Self> {
+        let path = socket_path(&app.lock().unwrap().dirs);
+        if UnixStream::connect(&path).is_ok() {
+            warn!("Another instance is listening on {}", path.display());
+            return None;
+        }
+        // Left behind by an instance that didn't get to clean up
+        let _ = fs::remove_file(&path);
+        let listener = match bind(&path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("Unable to listen on {}: {}", path.display(), err);
+                return None;
+            }
+        };
+        info!("Listening for commands on {}", path.display());
+
+        let stopping = Arc::new(AtomicBool::new(false));
+        let thread_stopping = Arc::clone(&stopping);
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stopping.load(Ordering::SeqCst) {
+                    break;
+                }
+                let result = stream.and_then(|stream| handle(&app, stream));
+                if let Err(err) = result {
+                    warn!("Unable to answer on the control socket: {}", err);
+                }
+            }
+        });
+
+        Some(Self {
+            path,
+            stopping,
+            thread,
+        })
+    }
+
+    pub fn stop(self) {
+        info!("Stopping the control socket");
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wakes the thread up to see that it's stopping
+        let _ = UnixStream::connect(&self.path);
+        if self.thread.join().is_err() {
+            warn!("Control socket thread panicked");
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Listens on `path`, which only the user can connect to
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let mut app = App::default();
+        assert_eq!(respond(&mut app, "current"), "0");
+        assert_eq!(respond(&mut app, "start 3"), "ok");
+        assert_eq!(respond(&mut app, "current"), "3");
+        // Starting it again carries on with the same entry
+        assert_eq!(respond(&mut app, "start 3"), "ok");
+        assert_eq!(app.today.len(), 1);
+
+        assert!(respond(&mut app, "start 9").starts_with("error: "));
+        assert!(respond(&mut app, "start").starts_with("error: "));
+        assert!(respond(&mut app, "dance").starts_with("error: "));
+
+        assert_eq!(respond(&mut app, "stop"), "ok");
+        assert_eq!(respond(&mut app, "current"), "0");
+    }
+
+    #[test]
+    fn over_the_socket() {
+        let dir = std::env::temp_dir().join(format!("ydnc-control-{}", std::process::id()));
+        let app = AppState::new(std::sync::Mutex::new(App {
+            dirs: Dirs::in_dir(dir.clone()),
+            ..Default::default()
+        }));
+        let socket = ControlSocket::start(AppState::clone(&app)).unwrap();
+        let path = socket_path(&app.lock().unwrap().dirs);
+
+        assert_eq!(request(&path, "start 2").unwrap(), "ok");
+        assert_eq!(request(&path, "current").unwrap(), "2");
+        assert!(request(&path, "start 0").is_err());
+        // Only one instance listens at a time
+        assert!(ControlSocket::start(AppState::clone(&app)).is_none());
+
+        socket.stop();
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
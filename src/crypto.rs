@@ -0,0 +1,216 @@
+// Reading and writing of save files, with optional at-rest encryption when
+// built with the "encryption" feature. Without the feature these are plain
+// reads and writes.
+
+use std::{fs, io, path::Path};
+
+#[cfg(feature = "encryption")]
+mod imp {
+    use std::{collections::HashMap, io, sync::Mutex};
+
+    use argon2::Argon2;
+    use chacha20poly1305::{
+        aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+        ChaCha20Poly1305, Key, Nonce,
+    };
+
+    /// Encrypted files start with this so that we can tell them apart from
+    /// plain ones, which are still read as-is. That way turning encryption on
+    /// doesn't lock you out of your old files; they're encrypted the next time
+    /// they're saved.
+    const MAGIC: &[u8] = b"YDNCENC1";
+    const SALT_LEN: usize = 16;
+    const NONCE_LEN: usize = 12;
+
+    struct Unlocked {
+        passphrase: String,
+        /// The salt this session's writes use
+        salt: [u8; SALT_LEN],
+        /// Deriving a key is deliberately slow, so we hold on to every key
+        /// we've derived, by salt. Each session's files share a salt, so this
+        /// stays small even after loading a long history.
+        keys: HashMap<[u8; SALT_LEN], Key>,
+    }
+
+    impl Unlocked {
+        fn key_for(&mut self, salt: [u8; SALT_LEN]) -> io::Result<Key> {
+            if let Some(key) = self.keys.get(&salt) {
+                return Ok(*key);
+            }
+            let key = derive_key(&self.passphrase, &salt)?;
+            self.keys.insert(salt, key);
+            Ok(key)
+        }
+    }
+
+    static UNLOCKED: Mutex<Option<Unlocked>> = Mutex::new(None);
+
+    fn derive_key(passphrase: &str, salt: &[u8]) -> io::Result<Key> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(key)
+    }
+
+    pub fn set_passphrase(passphrase: Option<String>) -> io::Result<()> {
+        *UNLOCKED.lock().unwrap() = match passphrase {
+            Some(passphrase) => {
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let mut unlocked = Unlocked {
+                    passphrase,
+                    salt,
+                    keys: HashMap::new(),
+                };
+                unlocked.key_for(salt)?;
+                Some(unlocked)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub fn encrypt(contents: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut guard = UNLOCKED.lock().unwrap();
+        let Some(unlocked) = guard.as_mut() else {
+            return Ok(contents);
+        };
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&unlocked.key_for(unlocked.salt)?)
+            .encrypt(&nonce, contents.as_slice())
+            .map_err(|_| io::Error::other("Could not encrypt save file"))?;
+
+        let mut out = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&unlocked.salt);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    pub fn is_encrypted(contents: &[u8]) -> bool {
+        contents.starts_with(MAGIC)
+    }
+
+    pub fn decrypt(contents: Vec<u8>) -> io::Result<Vec<u8>> {
+        let Some(rest) = contents.strip_prefix(MAGIC) else {
+            return Ok(contents);
+        };
+        if rest.len() < SALT_LEN + NONCE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Encrypted save file is truncated",
+            ));
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let mut guard = UNLOCKED.lock().unwrap();
+        let unlocked = guard.as_mut().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Save file is encrypted but no passphrase was given",
+            )
+        })?;
+        let key = unlocked.key_for(salt.try_into().unwrap())?;
+
+        ChaCha20Poly1305::new(&key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Wrong passphrase or corrupted save file",
+                )
+            })
+    }
+}
+
+/// Sets the passphrase used to encrypt everything we save from now on, and
+/// to decrypt encrypted files. `None` turns encryption off again, leaving
+/// encrypted files unreadable.
+#[cfg(feature = "encryption")]
+pub fn set_passphrase(passphrase: Option<String>) -> io::Result<()> {
+    imp::set_passphrase(passphrase)
+}
+
+/// Whether the file at `path` is encrypted, and so can't be read without the
+/// passphrase. Never, without the "encryption" feature.
+pub fn is_encrypted(path: &Path) -> bool {
+    #[cfg(feature = "encryption")]
+    return fs::read(path).is_ok_and(|contents| imp::is_encrypted(&contents));
+    #[cfg(not(feature = "encryption"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Encodes one line of a file that's appended to rather than rewritten,
+/// encrypting it if a passphrase has been set. Encrypted lines are written as
+/// "#" and then hex, so that they stay one line.
+pub fn encode_line(line: String) -> io::Result<String> {
+    #[cfg(feature = "encryption")]
+    {
+        let sealed = imp::encrypt(line.into_bytes())?;
+        if imp::is_encrypted(&sealed) {
+            let hex: String = sealed.iter().map(|b| format!("{:02x}", b)).collect();
+            return Ok(format!("#{}", hex));
+        }
+        String::from_utf8(sealed).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    #[cfg(not(feature = "encryption"))]
+    Ok(line)
+}
+
+/// Decodes a line written by `encode_line`
+pub fn decode_line(line: &str) -> io::Result<Vec<u8>> {
+    let Some(hex) = line.strip_prefix('#') else {
+        return Ok(line.as_bytes().to_vec());
+    };
+    #[cfg(feature = "encryption")]
+    {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid encrypted line");
+        let sealed = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(invalid)
+            })
+            .collect::<io::Result<Vec<u8>>>()?;
+        imp::decrypt(sealed)
+    }
+    #[cfg(not(feature = "encryption"))]
+    {
+        let _ = hex;
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "Line is encrypted but this build can't decrypt",
+        ))
+    }
+}
+
+/// Reads a save file, decrypting it if it's encrypted
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let contents = fs::read(path)?;
+    #[cfg(feature = "encryption")]
+    let contents = imp::decrypt(contents)?;
+    Ok(contents)
+}
+
+/// Writes a save file, encrypting it if a passphrase has been set, and
+/// creating its directory if need be. It's written next to where it goes and
+/// then swapped in, so that anything reading it meanwhile, like our own watcher
+/// or a sync tool, never sees half a file.
+pub fn write(path: &Path, contents: Vec<u8>) -> io::Result<()> {
+    #[cfg(feature = "encryption")]
+    let contents = imp::encrypt(contents)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("ron.tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use zbus::{dbus_interface, fdo, ConnectionBuilder, SignalContext};
+
+use crate::{App, AppState};
+
+const NAME: &str = "org.ydnc.Time";
+const PATH: &str = "/org/ydnc/Time";
+
+/// Publishes the `org.ydnc.Time` service on the session bus, so that desktop
+/// widgets like GNOME extensions and KDE plasmoids can show and control what's
+/// being tracked:
+///
+/// - `StartEntry(y number)`: start tracking task number (1-8)
+/// - `StopEntry()`: stop tracking
+/// - `CurrentEntry() -> (y number, s label, s start)`: what's being tracked,
+///   with the start in RFC 3339, or number 0 if nothing is
+/// - `EntryChanged(y number, s label, s start)`: sent whenever that changes,
+///   with the same arguments as `CurrentEntry` returns
+pub struct DbusService {
+    handle: JoinHandle<()>,
+}
+
+impl DbusService {
+    pub fn start(app: AppState) -> Self {
+        info!("Starting D-Bus service");
+        let handle = tokio::spawn(async move {
+            if let Err(err) = serve(app).await {
+                warn!("Unable to serve {} on D-Bus: {}", NAME, err);
+            }
+        });
+        Self { handle }
+    }
+
+    pub fn stop(self) {
+        info!("Stopping D-Bus service");
+        self.handle.abort();
+    }
+}
+
+struct Tracking {
+    app: AppState,
+}
+
+/// What's being tracked, as sent over D-Bus
+fn current(app: &App) -> (u8, String, String) {
+    match app.open_entry() {
+        Some(tl) => (tl.number, tl.label(app), tl.start.to_rfc3339()),
+        None => (0, String::new(), String::new()),
+    }
+}
+
+#[dbus_interface(name = "org.ydnc.Time")]
+impl Tracking {
+    fn start_entry(&self, number: u8) -> fdo::Result<()> {
+        if !(1..=8).contains(&number) {
+            return Err(fdo::Error::InvalidArgs(String::from(
+                "Task number must be 1-8",
+            )));
+        }
+        let mut app = self.app.lock().unwrap();
+        // Starting what's already being tracked would only split its entry
+        if app.open_entry_for(number).is_some() {
+            return Ok(());
+        }
+        app.start_entry(number);
+        app.notify(format!("Started task {} via D-Bus", number));
+        Ok(())
+    }
+
+    fn stop_entry(&self) {
+        let mut app = self.app.lock().unwrap();
+        app.close_entry_if_open(Local::now());
+        app.notify("Stopped tracking via D-Bus");
+    }
+
+    fn current_entry(&self) -> (u8, String, String) {
+        current(&self.app.lock().unwrap())
+    }
+
+    #[dbus_interface(signal)]
+    async fn entry_changed(
+        ctxt: &SignalContext<'_>,
+        number: u8,
+        label: &str,
+        start: &str,
+    ) -> zbus::Result<()>;
+}
+
+async fn serve(app: AppState) -> zbus::Result<()> {
+    let conn = ConnectionBuilder::session()?
+        .name(NAME)?
+        .serve_at(
+            PATH,
+            Tracking {
+                app: AppState::clone(&app),
+            },
+        )?
+        .build()
+        .await?;
+    let ctxt = SignalContext::new(&conn, PATH)?;
+
+    // Tracking changes from all over (the keyboard, the tracker, the API...),
+    // so rather than hooking into each of them, check for changes every second
+    let mut last: Option<(u8, DateTime<Local>)> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        // One look at the app for both, so what's sent is what was compared
+        let (open, (number, label, start)) = {
+            let app = app.lock().unwrap();
+            (
+                app.open_entry().map(|tl| (tl.number, tl.start)),
+                current(&app),
+            )
+        };
+        if open == last {
+            continue;
+        }
+        last = open;
+        Tracking::entry_changed(&ctxt, number, &label, &start).await?;
+    }
+}
@@ -0,0 +1,221 @@
+// The duration arithmetic behind every number the app shows, exposed so that
+// scripts using this crate as a library come up with exactly the same numbers.
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{utils::datetime_with_zeroed_time, TimeLog};
+
+/// How long an entry ran for. Open entries run until `now`, or count for
+/// nothing if `now` is None, which is how history and stats treat them.
+pub fn elapsed(entry: &TimeLog, now: Option<DateTime<Local>>) -> chrono::Duration {
+    match entry.end.or(now) {
+        Some(end) => (end - entry.start).max(chrono::Duration::zero()),
+        None => chrono::Duration::zero(),
+    }
+}
+
+/// The sum of `elapsed` over all the given entries
+pub fn total<'a>(
+    entries: impl IntoIterator<Item = &'a TimeLog>,
+    now: Option<DateTime<Local>>,
+) -> chrono::Duration {
+    entries
+        .into_iter()
+        .fold(chrono::Duration::zero(), |acc, tl| acc + elapsed(tl, now))
+}
+
+/// How much of the clock the given entries cover, counting time when several
+/// ran at once (parallel tasks, like being on call during a meeting) only
+/// once. Open entries run until `now` as with `elapsed`.
+pub fn covered<'a>(
+    entries: impl IntoIterator<Item = &'a TimeLog>,
+    now: Option<DateTime<Local>>,
+) -> chrono::Duration {
+    let mut spans: Vec<(DateTime<Local>, DateTime<Local>)> = entries
+        .into_iter()
+        .filter_map(|tl| Some((tl.start, tl.end.or(now)?)))
+        .filter(|(start, end)| end > start)
+        .collect();
+    spans.sort();
+
+    let mut covered = chrono::Duration::zero();
+    let mut reached: Option<DateTime<Local>> = None;
+    for (start, end) in spans {
+        let start = reached.map_or(start, |r| start.max(r));
+        if end > start {
+            covered = covered + (end - start);
+            reached = Some(end);
+        }
+    }
+    covered
+}
+
+/// How much of the `total` of the given entries overlaps other entries, i.e.
+/// how much more it is than the time they `covered`
+pub fn overlap<'a>(
+    entries: impl IntoIterator<Item = &'a TimeLog> + Clone,
+    now: Option<DateTime<Local>>,
+) -> chrono::Duration {
+    total(entries.clone(), now) - covered(entries, now)
+}
+
+/// The latest representable time on the same calendar day as `dt`. Entries
+/// that are still open at midnight are split here.
+pub fn end_of_day(dt: &DateTime<Local>) -> DateTime<Local> {
+    datetime_with_zeroed_time(&(*dt + chrono::Days::new(1))) - chrono::Duration::nanoseconds(1)
+}
+
+/// Like `elapsed`, but only counting the part of the entry that falls on the
+/// given calendar day
+pub fn elapsed_on_day(
+    entry: &TimeLog,
+    date: NaiveDate,
+    now: Option<DateTime<Local>>,
+) -> chrono::Duration {
+    let Some(end) = entry.end.or(now) else {
+        return chrono::Duration::zero();
+    };
+    let Some(day_start) = date
+        .and_hms_opt(0, 0, 0)
+        .and_then(|dt| dt.and_local_timezone(Local).earliest())
+    else {
+        return chrono::Duration::zero();
+    };
+
+    let start = entry.start.max(day_start);
+    let end = end.min(end_of_day(&day_start));
+    (end - start).max(chrono::Duration::zero())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    Down,
+    /// Halves round up
+    Nearest,
+    Up,
+}
+
+/// Rounds a duration to a whole multiple of `to`, e.g. to the nearest
+/// quarter hour for billing. Durations are rounded as whole seconds.
+pub fn round(d: chrono::Duration, to: chrono::Duration, rounding: Rounding) -> chrono::Duration {
+    let step = to.num_seconds();
+    if step <= 0 {
+        return d;
+    }
+    let secs = d.num_seconds();
+    let steps = match rounding {
+        Rounding::Down => secs.div_euclid(step),
+        Rounding::Nearest => (secs + step / 2).div_euclid(step),
+        Rounding::Up => (secs + step - 1).div_euclid(step),
+    };
+    chrono::Duration::seconds(steps * step)
+}
+
+/// How each entry's time is rounded when billing for it, e.g. up to the next
+/// 6 minutes. The logs themselves always keep the exact times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BillingRounding {
+    pub minutes: u32,
+    pub rounding: Rounding,
+}
+
+impl BillingRounding {
+    pub fn apply(self, d: chrono::Duration) -> chrono::Duration {
+        round(
+            d,
+            chrono::Duration::minutes(self.minutes.into()),
+            self.rounding,
+        )
+    }
+
+    /// Describes the rule like "nearest 15m"
+    pub fn describe(self) -> String {
+        let direction = match self.rounding {
+            Rounding::Down => "down to",
+            Rounding::Nearest => "nearest",
+            Rounding::Up => "up to",
+        };
+        format!("{} {}m", direction, self.minutes)
+    }
+}
+
+/// How long an entry counts for on an invoice: its `elapsed` time, rounded
+/// per the user's billing rule if they have one
+pub fn billed(
+    entry: &TimeLog,
+    now: Option<DateTime<Local>>,
+    rounding: Option<BillingRounding>,
+) -> chrono::Duration {
+    let d = elapsed(entry, now);
+    rounding.map_or(d, |r| r.apply(d))
+}
+
+/// Formats like "07:30:00", with hours going past 24 if need be
+pub fn format_hms(d: chrono::Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Formats as hours to one decimal place, like "7.5h"
+pub fn format_decimal_hours(d: chrono::Duration) -> String {
+    format!("{:.1}h", d.num_seconds() as f64 / 3600.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounding() {
+        let quarter = chrono::Duration::minutes(15);
+        let d = chrono::Duration::minutes(22) + chrono::Duration::seconds(30);
+        assert_eq!(round(d, quarter, Rounding::Down), quarter);
+        assert_eq!(round(d, quarter, Rounding::Nearest), quarter * 2);
+        assert_eq!(round(d, quarter, Rounding::Up), quarter * 2);
+        assert_eq!(
+            round(chrono::Duration::minutes(7), quarter, Rounding::Nearest),
+            chrono::Duration::zero()
+        );
+        assert_eq!(round(quarter, quarter, Rounding::Up), quarter);
+
+        let six_up = BillingRounding {
+            minutes: 6,
+            rounding: Rounding::Up,
+        };
+        assert_eq!(
+            six_up.apply(chrono::Duration::minutes(13)),
+            chrono::Duration::minutes(18)
+        );
+        assert_eq!(six_up.describe(), "up to 6m");
+    }
+
+    #[test]
+    fn overlapping() {
+        let now = Local::now();
+        let entry = |from: i64, to: Option<i64>| TimeLog {
+            start: now - chrono::Duration::minutes(from),
+            end: to.map(|to| now - chrono::Duration::minutes(to)),
+            number: 1,
+            ..Default::default()
+        };
+        // A meeting from 90 to 30 minutes ago, on call for the last hour and
+        // something else 20 minutes inside the meeting
+        let entries = [entry(90, Some(30)), entry(60, None), entry(80, Some(60))];
+        assert_eq!(covered(&entries, Some(now)), chrono::Duration::minutes(90));
+        assert_eq!(overlap(&entries, Some(now)), chrono::Duration::minutes(50));
+        assert_eq!(overlap(&entries[..1], Some(now)), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format_hms(chrono::Duration::seconds(3723)), "01:02:03");
+        assert_eq!(format_hms(chrono::Duration::hours(25)), "25:00:00");
+        assert_eq!(format_decimal_hours(chrono::Duration::minutes(90)), "1.5h");
+    }
+}
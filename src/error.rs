@@ -0,0 +1,154 @@
+// The ways loading and saving can go wrong, so that each one can be told to
+// the user with what to do about it rather than as a bare I/O error. Functions
+// that only ever fail reading or writing keep returning `io::Result`; both
+// convert into each other, so `?` works across the two.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum YdncError {
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// A save file is there but doesn't parse
+    Corrupt {
+        path: Option<PathBuf>,
+        source: ron::error::SpannedError,
+    },
+    /// A save file was written in a format version newer than this one
+    TooNew(u32),
+    /// Something couldn't be turned into RON to be saved
+    Serialize(ron::Error),
+    /// Talking to the tracker over Bluetooth failed
+    Bluetooth(btleplug::Error),
+}
+
+pub type Result<T> = std::result::Result<T, YdncError>;
+
+impl YdncError {
+    /// Whether this is just the file not being there yet, which callers
+    /// usually treat as empty
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, YdncError::Io(e) if e.kind() == io::ErrorKind::NotFound)
+    }
+
+    /// Notes which file failed to parse, for the message to point at it
+    pub fn in_file(self, file: &Path) -> Self {
+        match self {
+            YdncError::Corrupt { path: None, source } => YdncError::Corrupt {
+                path: Some(file.to_path_buf()),
+                source,
+            },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for YdncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YdncError::Io(e) => match e.kind() {
+                io::ErrorKind::PermissionDenied => {
+                    write!(f, "{} (check who owns the app's files)", e)
+                }
+                io::ErrorKind::StorageFull => write!(f, "{} (free up some disk space)", e),
+                _ => write!(f, "{}", e),
+            },
+            YdncError::Corrupt { path, source } => {
+                let file = path
+                    .as_ref()
+                    .map_or_else(|| String::from("A save file"), |p| p.display().to_string());
+                write!(
+                    f,
+                    "{} is corrupt at line {}, column {}: {} (fix it, or move it aside to start that file over)",
+                    file, source.position.line, source.position.col, source.code
+                )
+            }
+            YdncError::TooNew(version) => write!(
+                f,
+                "Saved in format version {}, which is newer than this version of the app understands (update the app)",
+                version
+            ),
+            YdncError::Serialize(e) => write!(f, "Could not write out the save data: {}", e),
+            YdncError::Bluetooth(e) => write!(
+                f,
+                "Bluetooth failed: {} (check that Bluetooth is on and the tracker is nearby)",
+                e
+            ),
+        }
+    }
+}
+
+impl std::error::Error for YdncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            YdncError::Io(e) => Some(e),
+            YdncError::Corrupt { source, .. } => Some(source),
+            YdncError::Serialize(e) => Some(e),
+            YdncError::Bluetooth(e) => Some(e),
+            YdncError::TooNew(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for YdncError {
+    fn from(e: io::Error) -> Self {
+        YdncError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for YdncError {
+    fn from(source: ron::error::SpannedError) -> Self {
+        YdncError::Corrupt { path: None, source }
+    }
+}
+
+impl From<ron::Error> for YdncError {
+    fn from(e: ron::Error) -> Self {
+        YdncError::Serialize(e)
+    }
+}
+
+impl From<btleplug::Error> for YdncError {
+    fn from(e: btleplug::Error) -> Self {
+        YdncError::Bluetooth(e)
+    }
+}
+
+/// Keeps the kind of I/O errors, so that e.g. a missing file is still
+/// NotFound, and the message of the rest
+impl From<YdncError> for io::Error {
+    fn from(e: YdncError) -> Self {
+        let kind = match &e {
+            YdncError::Io(e) => e.kind(),
+            YdncError::Corrupt { .. } | YdncError::TooNew(_) => io::ErrorKind::InvalidData,
+            YdncError::Serialize(_) | YdncError::Bluetooth(_) => io::ErrorKind::Other,
+        };
+        match e {
+            YdncError::Io(e) => e,
+            e => io::Error::new(kind, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_messages() {
+        let missing = YdncError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert!(missing.is_not_found());
+        assert_eq!(io::Error::from(missing).kind(), io::ErrorKind::NotFound);
+
+        let source = ron::de::from_str::<Vec<u8>>("[1, 2").unwrap_err();
+        let corrupt = YdncError::from(source).in_file(Path::new("2023-06-05.ron"));
+        assert!(!corrupt.is_not_found());
+        assert!(corrupt
+            .to_string()
+            .starts_with("2023-06-05.ron is corrupt at line 1, column 6"));
+        assert_eq!(io::Error::from(corrupt).kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,563 @@
+use std::{fmt::Write, fs, io, path::PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    durations, get_pref_label, profiles,
+    stats::{earnings, format_earnings, group_by_client, DatedLogs, TagStats, TimeStats},
+    storage::Dirs,
+    ui::patterned_label,
+    Preferences, TimeLog,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+/// One line of an exported breakdown, holding the same columns as the Stats
+/// page table
+struct ExportRow {
+    percent: u8,
+    label: String,
+    count: u16,
+    mean: Option<chrono::Duration>,
+    total: chrono::Duration,
+    billed: chrono::Duration,
+    earnings: Option<f64>,
+}
+
+/// Gets the directory exports should be written to: the user's preference if
+/// they've set one, else an "exports" folder in the app data directory.
+fn get_exports_dir(dirs: &Dirs, prefs: &Preferences) -> io::Result<PathBuf> {
+    let dir = prefs
+        .exports_dir
+        .clone()
+        .unwrap_or_else(|| dirs.data().join("exports"));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn percent_of(part: chrono::Duration, total_ms: i64) -> u8 {
+    if total_ms == 0 {
+        0
+    } else {
+        (100.0 * part.num_milliseconds() as f64 / total_ms as f64).round() as u8
+    }
+}
+
+fn format_duration(d: chrono::Duration) -> String {
+    humantime::format_duration(d.to_std().unwrap_or_default()).to_string()
+}
+
+/// Quotes a CSV field if it contains anything that would otherwise break the
+/// row apart
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Builds the rows of a breakdown in the same order as the Stats page: tasks
+/// by descending total, each followed by its tags, then client subtotals.
+fn breakdown_rows(
+    stats: &[TimeStats; 8],
+    tags: &[TagStats],
+    prefs: &Preferences,
+) -> Vec<ExportRow> {
+    let mut stats = *stats;
+    stats.sort_by_key(|ts| ts.total);
+    stats.reverse();
+
+    let total_ms: i64 = stats.iter().map(|ts| ts.total.num_milliseconds()).sum();
+    let labels = prefs.labels.as_ref();
+    let rates = prefs.hourly_rates.as_ref();
+
+    let mut rows = Vec::new();
+    for ts in &stats {
+        rows.push(ExportRow {
+            percent: percent_of(ts.total, total_ms),
+            label: patterned_label(
+                prefs,
+                ts.task_number,
+                get_pref_label(ts.task_number, labels)
+                    .unwrap_or_else(|| ts.task_number.to_string()),
+            ),
+            count: ts.count,
+            mean: Some(ts.mean),
+            total: ts.total,
+            billed: ts.billed,
+            earnings: earnings(ts.task_number, ts.billed, rates),
+        });
+        for tag in tags.iter().filter(|t| t.task_number == ts.task_number) {
+            rows.push(ExportRow {
+                percent: percent_of(tag.total, total_ms),
+                label: format!("#{}", tag.tag),
+                count: tag.count,
+                mean: None,
+                total: tag.total,
+                billed: tag.billed,
+                earnings: earnings(tag.task_number, tag.billed, rates),
+            });
+        }
+    }
+
+    if let Some(clients) = prefs.clients.as_ref() {
+        for cs in group_by_client(&stats, tags, clients, rates) {
+            rows.push(ExportRow {
+                percent: percent_of(cs.total, total_ms),
+                label: cs.name,
+                count: cs.count,
+                mean: None,
+                total: cs.total,
+                billed: cs.billed,
+                earnings: cs.earnings,
+            });
+        }
+    }
+
+    rows
+}
+
+/// Writes the given breakdown to a new file in the exports directory, returning
+/// the path of the file it wrote
+pub fn export_stats(
+    dirs: &Dirs,
+    format: ExportFormat,
+    stats: &[TimeStats; 8],
+    tags: &[TagStats],
+    prefs: &Preferences,
+    min_date: Option<NaiveDate>,
+    max_date: NaiveDate,
+) -> io::Result<PathBuf> {
+    let out = stats_to_string(format, stats, tags, prefs, min_date, max_date);
+    let filename = get_exports_dir(dirs, prefs)?.join(format!(
+        "stats-{}-{}.{}",
+        min_date.map_or_else(|| "all".to_string(), |d| d.format("%F").to_string()),
+        max_date.format("%F"),
+        format.extension()
+    ));
+    info!("Exporting stats to {}", filename.display());
+    fs::write(&filename, out)?;
+
+    Ok(filename)
+}
+
+/// The given breakdown in `format`. If the user has a billing rule, there's
+/// an extra column with the billed totals, and likewise for earnings if they
+/// have hourly rates.
+pub fn stats_to_string(
+    format: ExportFormat,
+    stats: &[TimeStats; 8],
+    tags: &[TagStats],
+    prefs: &Preferences,
+    min_date: Option<NaiveDate>,
+    max_date: NaiveDate,
+) -> String {
+    let range = match min_date {
+        Some(min) => format!("{} to {}", min.format("%F"), max_date.format("%F")),
+        None => format!("all time to {}", max_date.format("%F")),
+    };
+    let rows = breakdown_rows(stats, tags, prefs);
+    let billing = prefs.billing_rounding.is_some();
+    let earning = prefs.hourly_rates.is_some();
+
+    // Writing to a String can't fail, hence all the unwraps
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => {
+            write!(out, "date range,percent,label,count,avg,total").unwrap();
+            if billing {
+                write!(out, ",billed").unwrap();
+            }
+            if earning {
+                write!(out, ",earned").unwrap();
+            }
+            writeln!(out).unwrap();
+            for row in rows {
+                write!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    csv_field(&range),
+                    row.percent,
+                    csv_field(&row.label),
+                    row.count,
+                    row.mean.map_or_else(String::new, format_duration),
+                    format_duration(row.total),
+                )
+                .unwrap();
+                if billing {
+                    write!(out, ",{}", format_duration(row.billed)).unwrap();
+                }
+                if earning {
+                    write!(
+                        out,
+                        ",{}",
+                        row.earnings.map_or_else(String::new, format_earnings)
+                    )
+                    .unwrap();
+                }
+                writeln!(out).unwrap();
+            }
+        }
+        ExportFormat::Markdown => {
+            writeln!(out, "## Time breakdown: {}\n", range).unwrap();
+            let mut header = "| % | task | # | avg | total |".to_string();
+            let mut divider = "|--:|------|--:|-----|-------|".to_string();
+            if billing {
+                header.push_str(" billed |");
+                divider.push_str("--------|");
+            }
+            if earning {
+                header.push_str(" earned |");
+                divider.push_str("-------:|");
+            }
+            writeln!(out, "{}\n{}", header, divider).unwrap();
+            for row in rows {
+                write!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    row.percent,
+                    row.label.replace('|', "\\|"),
+                    row.count,
+                    row.mean.map_or_else(String::new, format_duration),
+                    format_duration(row.total),
+                )
+                .unwrap();
+                if billing {
+                    write!(out, " {} |", format_duration(row.billed)).unwrap();
+                }
+                if earning {
+                    write!(
+                        out,
+                        " {} |",
+                        row.earnings.map_or_else(String::new, format_earnings)
+                    )
+                    .unwrap();
+                }
+                writeln!(out).unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntriesFormat {
+    Csv,
+    Json,
+    Ics,
+}
+
+impl EntriesFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            EntriesFormat::Csv => "csv",
+            EntriesFormat::Json => "json",
+            EntriesFormat::Ics => "ics",
+        }
+    }
+}
+
+/// Which entries an entries export includes. Each part of the filter that's
+/// set has to match; a list matches if the entry has any of its items.
+#[derive(Debug, Default, PartialEq)]
+pub struct EntryFilter {
+    pub tasks: Vec<u8>,
+    pub tags: Vec<String>,
+    pub min_duration: Option<chrono::Duration>,
+}
+
+impl EntryFilter {
+    pub fn matches(&self, entry: &TimeLog) -> bool {
+        (self.tasks.is_empty() || self.tasks.contains(&entry.number))
+            && (self.tags.is_empty() || entry.tags.iter().any(|t| self.tags.contains(t)))
+            && self
+                .min_duration
+                .is_none_or(|min| durations::elapsed(entry, None) >= min)
+    }
+}
+
+/// What to export, from the command line
+#[derive(Debug, PartialEq)]
+pub struct EntriesExport {
+    pub format: EntriesFormat,
+    pub filter: EntryFilter,
+    pub min_date: Option<NaiveDate>,
+    pub max_date: NaiveDate,
+}
+
+impl EntriesExport {
+    /// Parses options like `--task 3 --tag clientA --min-duration 10m`, plus
+    /// `--from`/`--to` dates and `--format csv|json|ics`. By default every
+    /// entry up to `today` is exported as CSV. --task and --tag can be given
+    /// more than once.
+    pub fn parse(args: impl IntoIterator<Item = String>, today: NaiveDate) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        let parse_date = |s: &str| {
+            NaiveDate::parse_from_str(s, "%F")
+                .map_err(|e| invalid(format!("Invalid date {:?}, expected YYYY-MM-DD: {}", s, e)))
+        };
+
+        let mut export = Self {
+            format: EntriesFormat::Csv,
+            filter: EntryFilter::default(),
+            min_date: None,
+            max_date: today,
+        };
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| invalid(format!("{} needs a value", flag)))?;
+            match flag.as_str() {
+                "--task" => match value.parse() {
+                    Ok(n @ 1..=8) => export.filter.tasks.push(n),
+                    _ => return Err(invalid(format!("Invalid task {:?}, expected 1-8", value))),
+                },
+                "--tag" => export
+                    .filter
+                    .tags
+                    .push(value.trim_start_matches('#').to_string()),
+                "--min-duration" => {
+                    let d = humantime::parse_duration(&value)
+                        .map_err(|e| invalid(format!("Invalid duration {:?}: {}", value, e)))?;
+                    export.filter.min_duration =
+                        Some(chrono::Duration::from_std(d).map_err(|e| {
+                            invalid(format!("Duration {:?} is too long: {}", value, e))
+                        })?);
+                }
+                "--from" => export.min_date = Some(parse_date(&value)?),
+                "--to" => export.max_date = parse_date(&value)?,
+                "--format" => {
+                    export.format = match value.to_ascii_lowercase().as_str() {
+                        "csv" => EntriesFormat::Csv,
+                        "json" => EntriesFormat::Json,
+                        "ics" => EntriesFormat::Ics,
+                        _ => {
+                            return Err(invalid(format!(
+                                "Unknown format {:?}, expected csv, json or ics",
+                                value
+                            )))
+                        }
+                    }
+                }
+                _ => return Err(invalid(format!("Unknown option {}", flag))),
+            }
+        }
+        Ok(export)
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn ics_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn ics_time(dt: DateTime<Local>) -> String {
+    dt.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Writes the entries matching the filter to a new file in the exports
+/// directory, one row (or event) per entry, returning the path of the file it
+/// wrote. Entries still open have no end, and are left out of calendars.
+pub fn export_entries(
+    dirs: &Dirs,
+    export: &EntriesExport,
+    logs: &DatedLogs,
+    prefs: &Preferences,
+) -> io::Result<PathBuf> {
+    let entries = matching_entries(&export.filter, logs, prefs);
+    let out = format_entries(export.format, &entries, prefs);
+    let filename = get_exports_dir(dirs, prefs)?.join(format!(
+        "entries-{}-{}.{}",
+        export
+            .min_date
+            .map_or_else(|| "all".to_string(), |d| d.format("%F").to_string()),
+        export.max_date.format("%F"),
+        export.format.extension()
+    ));
+    info!(
+        "Exporting {} entries to {}",
+        entries.len(),
+        filename.display()
+    );
+    fs::write(&filename, out)?;
+
+    Ok(filename)
+}
+
+/// The entries of `logs` in the current profile that match `filter`, in
+/// `format`
+pub fn entries_to_string(
+    format: EntriesFormat,
+    filter: &EntryFilter,
+    logs: &DatedLogs,
+    prefs: &Preferences,
+) -> String {
+    format_entries(format, &matching_entries(filter, logs, prefs), prefs)
+}
+
+/// The entries of `logs` in the current profile that match `filter`, with the
+/// day each one's from
+fn matching_entries<'a>(
+    filter: &EntryFilter,
+    logs: &'a DatedLogs,
+    prefs: &Preferences,
+) -> Vec<(NaiveDate, &'a TimeLog)> {
+    logs.iter()
+        .flat_map(|(date, day)| day.iter().map(move |tl| (*date, tl)))
+        .filter(|(_, tl)| profiles::in_current(tl, prefs.profile.as_deref()) && filter.matches(tl))
+        .collect()
+}
+
+fn format_entries(
+    format: EntriesFormat,
+    entries: &[(NaiveDate, &TimeLog)],
+    prefs: &Preferences,
+) -> String {
+    let label = |tl: &TimeLog| tl.resolve_label(prefs.labels.as_ref());
+    let tags = |tl: &TimeLog| {
+        tl.tags
+            .iter()
+            .map(|t| format!("#{}", t))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    // Writing to a String can't fail, hence all the unwraps
+    let mut out = String::new();
+    match format {
+        EntriesFormat::Csv => {
+            writeln!(out, "date,start,end,duration,task,label,tags").unwrap();
+            for (date, tl) in entries {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{}",
+                    date.format("%F"),
+                    tl.start.to_rfc3339(),
+                    tl.end.map_or_else(String::new, |e| e.to_rfc3339()),
+                    format_duration(durations::elapsed(tl, None)),
+                    tl.number,
+                    csv_field(&label(tl)),
+                    csv_field(&tags(tl)),
+                )
+                .unwrap();
+            }
+        }
+        EntriesFormat::Json => {
+            writeln!(out, "[").unwrap();
+            for (i, (date, tl)) in entries.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  {{\"date\": \"{}\", \"start\": \"{}\", \"end\": {}, \"duration_secs\": {}, \"task\": {}, \"label\": {}, \"tags\": [{}]}}{}",
+                    date.format("%F"),
+                    tl.start.to_rfc3339(),
+                    tl.end.map_or_else(|| "null".to_string(), |e| format!("\"{}\"", e.to_rfc3339())),
+                    durations::elapsed(tl, None).num_seconds(),
+                    tl.number,
+                    json_string(&label(tl)),
+                    tl.tags.iter().map(|t| json_string(t)).collect::<Vec<_>>().join(", "),
+                    if i + 1 < entries.len() { "," } else { "" },
+                )
+                .unwrap();
+            }
+            writeln!(out, "]").unwrap();
+        }
+        EntriesFormat::Ics => {
+            // Calendar apps want CRLF line endings
+            write!(
+                out,
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//ydnc//time//EN\r\n"
+            )
+            .unwrap();
+            let stamp = ics_time(Local::now());
+            for (_, tl) in entries {
+                let Some(end) = tl.end else {
+                    continue;
+                };
+                let summary = match tags(tl) {
+                    t if t.is_empty() => label(tl),
+                    t => format!("{} {}", label(tl), t),
+                };
+                write!(
+                    out,
+                    "BEGIN:VEVENT\r\nUID:{}-{}@ydnc-time\r\nDTSTAMP:{}\r\nDTSTART:{}\r\nDTEND:{}\r\nSUMMARY:{}\r\nEND:VEVENT\r\n",
+                    tl.start.timestamp(),
+                    tl.number,
+                    stamp,
+                    ics_time(tl.start),
+                    ics_time(end),
+                    ics_text(&summary),
+                )
+                .unwrap();
+            }
+            write!(out, "END:VCALENDAR\r\n").unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_filters() {
+        let today = NaiveDate::from_ymd_opt(2023, 6, 8).unwrap();
+        let args = "--task 3 --tag #clientA --min-duration 10m --format ics"
+            .split(' ')
+            .map(String::from);
+        let export = EntriesExport::parse(args, today).unwrap();
+        assert_eq!(export.format, EntriesFormat::Ics);
+        assert_eq!(export.max_date, today);
+
+        let start = Local::now() - chrono::Duration::hours(1);
+        let entry = |number, tag: &str, mins| TimeLog {
+            start,
+            end: Some(start + chrono::Duration::minutes(mins)),
+            number,
+            tags: vec![tag.to_string()],
+            ..Default::default()
+        };
+        assert!(export.filter.matches(&entry(3, "clientA", 10)));
+        assert!(!export.filter.matches(&entry(3, "clientA", 9)));
+        assert!(!export.filter.matches(&entry(2, "clientA", 30)));
+        assert!(!export.filter.matches(&entry(3, "clientB", 30)));
+
+        assert!(EntriesExport::parse(["--task".to_string(), "9".to_string()], today).is_err());
+        assert!(EntriesExport::parse(["--tag".to_string()], today).is_err());
+    }
+}
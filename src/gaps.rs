@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Days, DurationRound, Local, NaiveDate, NaiveTime, Timelike, Weekday};
+
+use crate::{plan::PlannedBlock, Preferences, TimeLog};
+
+/// Gaps shorter than this aren't worth bothering the user about
+const MIN_GAP_MINS: i64 = 15;
+
+/// How many weeks of history are looked at to find what the user usually does
+/// at a given time
+pub const HISTORY_WEEKS: u64 = 8;
+
+/// Why a gap's suggested task was suggested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// A planned block overlaps the gap
+    Planned,
+    /// The entries on either side of the gap are the same task, so it was
+    /// probably one long stretch with a forgotten restart
+    SameTaskAround,
+    /// It's what the user has most often tracked at this time on this day of
+    /// the week
+    Usual,
+    /// The gap comes right after an entry, which may have carried on
+    Previous,
+}
+
+impl Reason {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Reason::Planned => "planned",
+            Reason::SameTaskAround => "same task before & after",
+            Reason::Usual => "usual for this time",
+            Reason::Previous => "continues previous entry",
+        }
+    }
+}
+
+/// A stretch of work hours on some day when nothing was being tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub date: NaiveDate,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// The task the user most likely forgot to track, if we can guess
+    pub suggestion: Option<(u8, Reason)>,
+}
+
+impl Gap {
+    pub fn duration(&self) -> chrono::Duration {
+        self.end - self.start
+    }
+
+    /// Makes the entry that fills this gap with the given task
+    pub fn to_entry(self, number: u8) -> Option<TimeLog> {
+        let start = self.date.and_time(self.start).and_local_timezone(Local);
+        let end = self.date.and_time(self.end).and_local_timezone(Local);
+        Some(TimeLog {
+            start: start.single()?,
+            end: Some(end.single()?),
+            number,
+            ..Default::default()
+        })
+    }
+}
+
+/// The user's work hours, defaulting to 9 to 5
+pub fn work_hours(prefs: &Preferences) -> (NaiveTime, NaiveTime) {
+    (
+        prefs
+            .work_day_start
+            .unwrap_or_else(|| NaiveTime::from_hms_opt(9, 0, 0).unwrap()),
+        prefs
+            .work_day_end
+            .unwrap_or_else(|| NaiveTime::from_hms_opt(17, 0, 0).unwrap()),
+    )
+}
+
+/// Whether the user works on the given date's day of the week, by default
+/// Monday to Friday
+pub fn is_work_day(prefs: &Preferences, date: NaiveDate) -> bool {
+    match prefs.work_days.as_ref() {
+        Some(days) => days.contains(&date.weekday()),
+        None => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+    }
+}
+
+/// The work days of the 7 days ending with `today`, which are the days a
+/// weekly report covers
+pub fn report_days(prefs: &Preferences, today: NaiveDate) -> Vec<NaiveDate> {
+    (0..7)
+        .rev()
+        .filter_map(|n| today.checked_sub_days(Days::new(n)))
+        .filter(|d| is_work_day(prefs, *d))
+        .collect()
+}
+
+/// How many seconds of each task were tracked during each hour of each day of
+/// the week, for guessing what the user was doing during a gap
+#[derive(Debug, Default)]
+pub struct HourHistogram {
+    secs: HashMap<(Weekday, u32), [i64; 8]>,
+}
+
+impl HourHistogram {
+    /// Adds up the finished entries of the given days
+    pub fn from_logs<'a>(logs: impl IntoIterator<Item = &'a TimeLog>) -> Self {
+        let mut histogram = Self::default();
+        for tl in logs {
+            let (Some(end), Some(idx)) = (tl.end, (tl.number as usize).checked_sub(1)) else {
+                continue;
+            };
+            if idx >= 8 {
+                continue;
+            }
+            // Walk through the entry an hour at a time, crediting each hour of
+            // the day with the part of the entry that fell within it
+            let mut cursor = tl.start;
+            while cursor < end {
+                let Ok(hour_start) = cursor.duration_trunc(chrono::Duration::hours(1)) else {
+                    break;
+                };
+                let next = (hour_start + chrono::Duration::hours(1)).min(end);
+                histogram
+                    .secs
+                    .entry((cursor.weekday(), cursor.hour()))
+                    .or_default()[idx] += (next - cursor).num_seconds();
+                cursor = next;
+            }
+        }
+        histogram
+    }
+
+    /// The task most tracked during the hours from `start` to `end` on the
+    /// given day of the week, if anything was
+    pub fn likeliest(&self, weekday: Weekday, start: NaiveTime, end: NaiveTime) -> Option<u8> {
+        let last_hour = (end - chrono::Duration::seconds(1))
+            .hour()
+            .max(start.hour());
+        let mut totals = [0i64; 8];
+        for hour in start.hour()..=last_hour {
+            if let Some(secs) = self.secs.get(&(weekday, hour)) {
+                for (total, s) in totals.iter_mut().zip(secs) {
+                    *total += s;
+                }
+            }
+        }
+        let (idx, &most) = totals
+            .iter()
+            .enumerate()
+            .max_by_key(|&(i, secs)| (*secs, std::cmp::Reverse(i)))?;
+        (most > 0).then_some(idx as u8 + 1)
+    }
+}
+
+fn suggest(
+    plan: &[PlannedBlock],
+    history: Option<u8>,
+    before: Option<&TimeLog>,
+    after: Option<&TimeLog>,
+    start: NaiveTime,
+    end: NaiveTime,
+) -> Option<(u8, Reason)> {
+    let planned = plan
+        .iter()
+        .map(|b| {
+            (
+                b.number,
+                (b.end.min(end) - b.start.max(start)).num_seconds(),
+            )
+        })
+        .filter(|&(_, overlap)| overlap > 0)
+        .max_by_key(|&(_, overlap)| overlap);
+    if let Some((number, _)) = planned {
+        return Some((number, Reason::Planned));
+    }
+
+    match (before, after, history) {
+        (Some(b), Some(a), _) if b.number == a.number => Some((b.number, Reason::SameTaskAround)),
+        (_, _, Some(number)) => Some((number, Reason::Usual)),
+        (Some(b), _, _) => Some((b.number, Reason::Previous)),
+        _ => None,
+    }
+}
+
+/// Finds the untracked stretches of one day's work hours, up to `now` if the
+/// day is today. Assumes `logs` is sorted by start time, which loading does.
+pub fn find_gaps(
+    date: NaiveDate,
+    logs: &[TimeLog],
+    plan: &[PlannedBlock],
+    history: &HourHistogram,
+    (work_start, work_end): (NaiveTime, NaiveTime),
+    now: chrono::DateTime<Local>,
+) -> Vec<Gap> {
+    let work_end = if date == now.date_naive() {
+        work_end.min(now.time())
+    } else {
+        work_end
+    };
+
+    let mut gaps = Vec::new();
+    let mut cursor = work_start;
+    let mut before: Option<&TimeLog> = None;
+    for tl in logs {
+        let start = tl.start.time();
+        let end = tl.end.unwrap_or(now).time();
+        if start > cursor && cursor < work_end {
+            let gap_end = start.min(work_end);
+            gaps.push((cursor, gap_end, before, Some(tl)));
+        }
+        if end > cursor {
+            cursor = end;
+            before = Some(tl);
+        }
+    }
+    if cursor < work_end {
+        gaps.push((cursor, work_end, before, None));
+    }
+
+    gaps.into_iter()
+        .filter(|(start, end, _, _)| (*end - *start).num_minutes() >= MIN_GAP_MINS)
+        .map(|(start, end, before, after)| Gap {
+            date,
+            start,
+            end,
+            suggestion: suggest(
+                plan,
+                history.likeliest(date.weekday(), start, end),
+                before,
+                after,
+                start,
+                end,
+            ),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn entry(date: NaiveDate, number: u8, start: NaiveTime, end: NaiveTime) -> TimeLog {
+        Gap {
+            date,
+            start,
+            end,
+            suggestion: None,
+        }
+        .to_entry(number)
+        .unwrap()
+    }
+
+    #[test]
+    fn gaps_between_entries() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        // Well after `date`, so that the whole work day counts
+        let now = Local::now();
+        let logs = vec![
+            entry(date, 1, hm(9, 0), hm(10, 0)),
+            // The 10 minute gap before this one is too short to count
+            entry(date, 1, hm(10, 10), hm(12, 0)),
+            entry(date, 2, hm(13, 0), hm(16, 0)),
+        ];
+
+        let gaps = find_gaps(
+            date,
+            &logs,
+            &[],
+            &HourHistogram::default(),
+            (hm(9, 0), hm(17, 0)),
+            now,
+        );
+        assert_eq!(
+            gaps.iter().map(|g| (g.start, g.end)).collect::<Vec<_>>(),
+            vec![(hm(12, 0), hm(13, 0)), (hm(16, 0), hm(17, 0))]
+        );
+        assert_eq!(gaps[0].suggestion, Some((1, Reason::Previous)));
+
+        let plan = [PlannedBlock {
+            number: 4,
+            start: hm(16, 30),
+            end: hm(18, 0),
+        }];
+        let gaps = find_gaps(
+            date,
+            &logs,
+            &plan,
+            &HourHistogram::default(),
+            (hm(9, 0), hm(17, 0)),
+            now,
+        );
+        assert_eq!(gaps[1].suggestion, Some((4, Reason::Planned)));
+    }
+
+    #[test]
+    fn untracked_day() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let hours = (hm(9, 0), hm(17, 0));
+        let gaps = find_gaps(
+            date,
+            &[],
+            &[],
+            &HourHistogram::default(),
+            hours,
+            Local::now(),
+        );
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].duration(), chrono::Duration::hours(8));
+        assert_eq!(gaps[0].suggestion, None);
+    }
+
+    #[test]
+    fn usual_task() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let hours = (hm(9, 0), hm(17, 0));
+
+        // Mondays a week and two weeks earlier, mostly spent on task 3
+        let history = HourHistogram::from_logs(&[
+            entry(date - Days::new(7), 3, hm(9, 0), hm(13, 0)),
+            entry(date - Days::new(7), 5, hm(13, 0), hm(14, 0)),
+            entry(date - Days::new(14), 5, hm(9, 30), hm(10, 0)),
+        ]);
+        assert_eq!(
+            history.likeliest(Weekday::Mon, hm(9, 0), hm(17, 0)),
+            Some(3)
+        );
+        assert_eq!(
+            history.likeliest(Weekday::Mon, hm(13, 0), hm(14, 0)),
+            Some(5)
+        );
+        assert_eq!(history.likeliest(Weekday::Tue, hm(9, 0), hm(17, 0)), None);
+        let gaps = find_gaps(date, &[], &[], &history, hours, Local::now());
+        assert_eq!(gaps[0].suggestion, Some((3, Reason::Usual)));
+    }
+}
@@ -0,0 +1,112 @@
+// Runs the user's own scripts when tracking changes, as a way to hook anything
+// up to the app without compiling it in. Scripts go in a "hooks" folder next
+// to the settings file, named for when they run:
+//
+// - on-start: an entry was started
+// - on-stop: an entry was stopped
+// - on-day-rollover: the app carried on into a new day
+//
+// and learn about the entry from YDNC_* environment variables: YDNC_TASK (its
+// number), YDNC_LABEL, YDNC_TAGS (comma-separated), YDNC_START and YDNC_END
+// (RFC 3339, with YDNC_END empty while it's open). on-day-rollover gets
+// YDNC_DATE, the day that just ended, instead. Scripts run in the background,
+// so a slow one never holds up the UI, and their output is only logged.
+
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+};
+
+use chrono::NaiveDate;
+use tracing::{info, warn};
+
+use crate::{storage::Dirs, TimeLog};
+
+#[derive(Debug, Clone, Copy)]
+enum Hook {
+    Start,
+    Stop,
+    DayRollover,
+}
+
+impl Hook {
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Start => "on-start",
+            Self::Stop => "on-stop",
+            Self::DayRollover => "on-day-rollover",
+        }
+    }
+}
+
+fn hook_path(dirs: &Dirs, hook: Hook) -> Option<PathBuf> {
+    Some(dirs.config().join("hooks").join(hook.file_name())).filter(|path| path.is_file())
+}
+
+/// Runs the user's script for `hook`, if they have one, without waiting for it
+fn spawn(dirs: &Dirs, hook: Hook, env: Vec<(&'static str, String)>) {
+    let Some(path) = hook_path(dirs, hook) else {
+        return;
+    };
+    info!("Running hook {}", path.display());
+    let child = Command::new(&path)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Unable to run hook {}: {}", path.display(), e);
+            return;
+        }
+    };
+    // Wait on a thread of its own, so that the script doesn't linger as a
+    // zombie once it's done
+    thread::spawn(move || match child.wait_with_output() {
+        Ok(output) if output.status.success() => {
+            info!("Hook {} finished", path.display());
+        }
+        Ok(output) => warn!(
+            "Hook {} exited with {}: {}",
+            path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => warn!("Unable to wait for hook {}: {}", path.display(), e),
+    });
+}
+
+fn entry_env(entry: &TimeLog, label: String) -> Vec<(&'static str, String)> {
+    vec![
+        ("YDNC_TASK", entry.number.to_string()),
+        ("YDNC_LABEL", label),
+        ("YDNC_TAGS", entry.tags.join(",")),
+        ("YDNC_START", entry.start.to_rfc3339()),
+        (
+            "YDNC_END",
+            entry.end.map(|end| end.to_rfc3339()).unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Runs the on-start script for an entry that was just started
+pub fn started(dirs: &Dirs, entry: &TimeLog, label: String) {
+    spawn(dirs, Hook::Start, entry_env(entry, label));
+}
+
+/// Runs the on-stop script for an entry that was just stopped
+pub fn stopped(dirs: &Dirs, entry: &TimeLog, label: String) {
+    spawn(dirs, Hook::Stop, entry_env(entry, label));
+}
+
+/// Runs the on-day-rollover script for the day that just ended
+pub fn day_rolled_over(dirs: &Dirs, date: NaiveDate) {
+    spawn(
+        dirs,
+        Hook::DayRollover,
+        vec![("YDNC_DATE", date.format("%F").to_string())],
+    );
+}
@@ -0,0 +1,236 @@
+// Input sources are what can start and stop tracking live, besides the
+// keyboard: the Bluetooth tracker, and any commands the user has configured.
+// Each one runs in the background, sending what happens to an event channel
+// rather than changing the app itself, and `Inputs` applies the events to the
+// app in the order they arrive. Adding a new kind of input, like an MPRIS
+// watcher that tracks while music plays, is then a matter of implementing
+// `InputSource` and adding it to `configured`, without touching `run()`.
+//
+// Commands are long-running programs whose output reads as events, one per
+// line:
+//
+//     start 3     track task 3, unless it's being tracked already
+//     switch 3    as if the tracker was flipped onto task 3's side
+//     break 3     take a break as task 3, or end the break if on one
+//     stop        stop tracking
+//
+// and anything else is shown to the user as a message. So e.g. a script
+// watching a video call app goes in the preferences like:
+//
+//     input_commands: Some([(name: "calls", command: "~/bin/watch-calls")])
+
+use std::{future::Future, pin::Pin, process::Stdio};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    sync::mpsc,
+    task::JoinHandle,
+};
+use tracing::{info, warn};
+
+#[cfg(feature = "window-tracking")]
+use crate::window::WindowInput;
+use crate::{bluetooth::BluetoothInput, AppState, Preferences};
+
+/// Something an input source saw happen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    /// Track this task, unless it's being tracked already
+    Start(u8),
+    /// Switch to this task the way flipping the tracker does, which stops it
+    /// instead if it's already going and the user has toggling turned on
+    Switch(u8),
+    /// Take a break as this task, or end the break if on one
+    ToggleBreak(u8),
+    /// Track this task because of the window rule `rule`, unless the user
+    /// is tracking something themselves
+    AutoStart { number: u8, rule: String },
+    /// Stop tracking whatever's being tracked
+    Stop,
+    /// The system went to sleep at this time
+    Suspend(DateTime<Local>),
+    /// The system woke up again
+    Resume,
+    /// The tracker connected or disconnected
+    Connected(bool),
+    /// Something to tell the user
+    Message(String),
+}
+
+pub type EventSender = mpsc::UnboundedSender<InputEvent>;
+
+/// A way of starting and stopping tracking that runs alongside the TUI
+pub trait InputSource: Send {
+    /// What to call the source in the log
+    fn name(&self) -> &str;
+
+    /// Starts listening in the background, sending what happens to `events`.
+    /// `app` is only for reading settings; changes go through the events.
+    fn start(&mut self, app: &AppState, events: EventSender);
+
+    /// Stops listening, cleaning up as needed
+    fn stop(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+}
+
+/// A command whose output is read as events, while it runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandInput {
+    name: String,
+    command: String,
+}
+
+/// A `CommandInput` and its task, once started
+struct CommandSource {
+    input: CommandInput,
+    task: Option<JoinHandle<()>>,
+}
+
+impl InputSource for CommandSource {
+    fn name(&self) -> &str {
+        &self.input.name
+    }
+
+    fn start(&mut self, _app: &AppState, events: EventSender) {
+        let CommandInput { name, command } = self.input.clone();
+        self.task = Some(tokio::spawn(async move {
+            let child = Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    warn!("Unable to run input command {}: {}", command, e);
+                    let _ = events.send(InputEvent::Message(format!(
+                        "Couldn't start the {} input: {}",
+                        name, e
+                    )));
+                    return;
+                }
+            };
+            if let Some(stdout) = child.stdout.take() {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(event) = parse_event(&line) {
+                        if events.send(event).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            warn!("Input command {} exited: {:?}", command, child.wait().await);
+            let _ = events.send(InputEvent::Message(format!("The {} input stopped", name)));
+        }));
+    }
+
+    fn stop(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        // Aborting drops the child, which kills it
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        Box::pin(async {})
+    }
+}
+
+/// Parses a line of an input command's output. Blank lines are nothing, and
+/// any other line that isn't an event is a message.
+pub fn parse_event(line: &str) -> Option<InputEvent> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let task = |word: &str| word.parse::<u8>().ok().filter(|n| (1..=8).contains(n));
+    Some(match words[..] {
+        ["stop"] => InputEvent::Stop,
+        [command, n] => match (command, task(n)) {
+            ("start", Some(n)) => InputEvent::Start(n),
+            ("switch", Some(n)) => InputEvent::Switch(n),
+            ("break", Some(n)) => InputEvent::ToggleBreak(n),
+            _ => InputEvent::Message(line.to_string()),
+        },
+        _ => InputEvent::Message(line.to_string()),
+    })
+}
+
+/// The input sources the user has: the tracker, the suspend listener and window
+/// tracking if built with them (and the user's set the latter up), then their
+/// commands
+pub fn configured(prefs: &Preferences) -> Vec<Box<dyn InputSource>> {
+    let mut sources: Vec<Box<dyn InputSource>> = vec![Box::<BluetoothInput>::default()];
+    #[cfg(all(feature = "suspend", target_os = "linux"))]
+    sources.push(Box::<crate::sleep::SleepInput>::default());
+    #[cfg(feature = "window-tracking")]
+    if let Some(tracking) = &prefs.window_tracking {
+        sources.push(Box::new(WindowInput::new(tracking.clone())));
+    }
+    for command in prefs.input_commands.iter().flatten() {
+        sources.push(Box::new(CommandSource {
+            input: command.clone(),
+            task: None,
+        }));
+    }
+    sources
+}
+
+/// The running input sources, and the task applying their events to the app
+pub struct Inputs {
+    sources: Vec<Box<dyn InputSource>>,
+    dispatcher: JoinHandle<()>,
+}
+
+impl Inputs {
+    pub fn start(app: AppState, mut sources: Vec<Box<dyn InputSource>>) -> Self {
+        let (events, mut events_rx) = mpsc::unbounded_channel();
+        for source in &mut sources {
+            info!("Starting the {} input", source.name());
+            source.start(&app, events.clone());
+        }
+        let dispatcher = tokio::spawn(async move {
+            while let Some(event) = events_rx.recv().await {
+                app.lock().unwrap().handle_input(event);
+            }
+        });
+        Self {
+            sources,
+            dispatcher,
+        }
+    }
+
+    /// Stops every source, in turn
+    pub async fn stop(mut self) {
+        for source in &mut self.sources {
+            info!("Stopping the {} input", source.name());
+            source.stop().await;
+        }
+        self.dispatcher.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_events() {
+        assert_eq!(parse_event("start 3"), Some(InputEvent::Start(3)));
+        assert_eq!(parse_event("  switch 8\n"), Some(InputEvent::Switch(8)));
+        assert_eq!(parse_event("break 1"), Some(InputEvent::ToggleBreak(1)));
+        assert_eq!(parse_event("stop"), Some(InputEvent::Stop));
+        assert_eq!(parse_event(""), None);
+        assert_eq!(
+            parse_event("start 9"),
+            Some(InputEvent::Message(String::from("start 9")))
+        );
+        assert_eq!(
+            parse_event("stop it"),
+            Some(InputEvent::Message(String::from("stop it")))
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Keeps two copies of the app from writing the same logs at once. The first
+// one to start takes an advisory lock on a ".lock" file in the data directory
+// and holds it until it quits. Any copy started meanwhile becomes a read-only
+// view: it changes and saves nothing, and follows along as the first one
+// changes today's log (see the watch module, which merges in changes made on
+// disk), until the first one quits and it can take the lock over.
+//
+// Where a copy of the app stands is kept on the `Dirs` for the data directory,
+// which every clone of it shares, and each save function checks the `Dirs`
+// it's given before writing.
+
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use tracing::{info, warn};
+
+use crate::storage::Dirs;
+
+/// Held for as long as the app runs. Dropping it lets another copy write.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Whether this copy of the app gets to write to a data directory, and the
+/// lock that lets it
+#[derive(Debug, Default)]
+pub struct Instance {
+    /// Read-only for some other reason than another copy having the lock,
+    /// which there's no getting out of
+    read_only: AtomicBool,
+    /// Read-only because another copy of the app had the lock
+    locked_out: AtomicBool,
+    /// Held while this is the copy writing the logs, if locking works where
+    /// they're kept
+    lock: Mutex<Option<InstanceLock>>,
+}
+
+impl Instance {
+    /// Stops this copy of the app writing anything, e.g. because today's log
+    /// couldn't be read and would otherwise be saved over
+    pub fn make_read_only(&self) {
+        self.read_only.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether this copy of the app mustn't change or save anything, usually
+    /// because another copy is the one writing the logs
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed) || self.locked_out.load(Ordering::Relaxed)
+    }
+
+    /// Whether this copy holds the lock
+    pub fn is_locked(&self) -> bool {
+        self.lock.lock().unwrap().is_some()
+    }
+
+    /// Fails if this copy of the app mustn't write, for the save functions to
+    /// check first
+    pub fn check_writable(&self) -> io::Result<()> {
+        if self.is_read_only() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "another instance of the app has the logs open",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Takes the lock on the data directory, or turns on read-only mode if
+/// another copy of the app already has it, and returns whether it took it.
+/// Where locking isn't possible, e.g. on some network drives, carries on
+/// without one.
+pub fn acquire(dirs: &Dirs) -> bool {
+    let dir = dirs.data();
+    let instance = dirs.instance();
+    match fs::create_dir_all(dir).and_then(|()| lock(dir)) {
+        Ok(lock) => {
+            *instance.lock.lock().unwrap() = Some(lock);
+            true
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+            info!(
+                "Another instance holds the lock on {}, starting read-only",
+                dir.display()
+            );
+            instance.locked_out.store(true, Ordering::Relaxed);
+            false
+        }
+        Err(e) => {
+            warn!("Unable to lock {}: {}", dir.display(), e);
+            false
+        }
+    }
+}
+
+/// Takes the lock over once the copy of the app that had it has quit, for a
+/// read-only copy to keep trying, and returns whether it did. Never does if
+/// this copy wasn't locked out to begin with.
+pub fn retry(dirs: &Dirs) -> bool {
+    let instance = dirs.instance();
+    if !instance.locked_out.load(Ordering::Relaxed) {
+        return false;
+    }
+    let Ok(lock) = lock(dirs.data()) else {
+        return false;
+    };
+    info!("Took over the lock on the data directory");
+    *instance.lock.lock().unwrap() = Some(lock);
+    instance.locked_out.store(false, Ordering::Relaxed);
+    true
+}
+
+/// Takes the lock on `dir`, which fails with `WouldBlock` while another copy
+/// of the app, or anything else writing the logs, has it
+fn lock(dir: &Path) -> io::Result<InstanceLock> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(".lock"))?;
+    match file.try_lock() {
+        Ok(()) => Ok(InstanceLock { _file: file }),
+        Err(TryLockError::WouldBlock) => Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "the app is running and has the logs open",
+        )),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// The lock on `dirs` for a single write from outside the app's own saving,
+/// like a `Storage`'s: None when `dirs` holds it already. Fails if this copy
+/// is read-only or another has the lock.
+pub(crate) fn lock_for_write(dirs: &Dirs) -> io::Result<Option<InstanceLock>> {
+    let instance = dirs.instance();
+    instance.check_writable()?;
+    if instance.is_locked() {
+        return Ok(None);
+    }
+    fs::create_dir_all(dirs.data())?;
+    lock(dirs.data()).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_out() {
+        let dir = std::env::temp_dir().join(format!("ydnc-instance-{}", std::process::id()));
+        let first = Dirs::in_dir(dir.clone());
+        let second = Dirs::in_dir(dir.clone());
+        assert!(acquire(&first));
+        assert!(!acquire(&second));
+        // Each copy has its own say on whether it writes
+        assert!(first.instance().check_writable().is_ok());
+        assert!(second.instance().is_read_only());
+        assert!(!retry(&second));
+
+        // Once the first one lets go, the second can take over
+        *first.instance().lock.lock().unwrap() = None;
+        assert!(retry(&second));
+        assert!(second.instance().is_locked());
+        assert!(!second.instance().is_read_only());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
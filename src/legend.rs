@@ -1,9 +1,12 @@
+use chrono::Timelike;
 use lazy_static::lazy_static;
 use ratatui::{
     layout::Constraint,
     widgets::{Row, Table},
 };
 
+use crate::timeline::Window;
+
 const LEGEND_WIDTHS: [Constraint; 24] = [Constraint::Ratio(1, 24); 24];
 const TRUNC_LEGEND_WIDTHS: [Constraint; 24] = [
     Constraint::Ratio(1, 24),
@@ -49,3 +52,22 @@ lazy_static! {
         .column_spacing(0)
         .widths(&TRUNC_LEGEND_WIDTHS);
 }
+
+/// Labels for each hour of a zoomed in timeline, like the full day's legend:
+/// the first hour and noon and midnight say whether they're am or pm
+pub fn hour_labels(window: Window) -> Vec<String> {
+    (0..window.hours)
+        .map(|i| {
+            let hour = (window.start + chrono::Duration::hours(i as i64)).hour();
+            let twelve_hour = match hour % 12 {
+                0 => 12,
+                h => h,
+            };
+            if i == 0 || twelve_hour == 12 {
+                format!("{}{}", twelve_hour, if hour < 12 { "am" } else { "pm" })
+            } else {
+                twelve_hour.to_string()
+            }
+        })
+        .collect()
+}
@@ -13,30 +13,89 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use chrono::{DateTime, Days, Local, Weekday};
-use crossterm::event::{self, Event, KeyCode};
-use directories::ProjectDirs;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, Weekday};
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+};
+use error::YdncError;
 use ratatui::{
     backend::Backend,
+    style::{Color, Style},
     text::{Line, Span},
     widgets::{Cell, Row},
     Terminal,
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashMap},
     fs, io,
-    path::PathBuf,
+    io::Write,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     time::Duration,
 };
-use tracing::info;
-use utils::{adjust_datetime_digit, datetime_with_zeroed_time};
+use tracing::{info, warn};
+use utils::{adjust_datetime_digit, datetime_with_zeroed_time, parse_quick_start};
 
+#[cfg(feature = "http-api")]
+pub mod api;
+mod archive;
+#[cfg(all(feature = "control-socket", unix))]
+mod auto;
+mod backup;
 pub mod bluetooth;
+mod breaks;
+mod bundle;
+mod calendar;
+#[cfg(all(feature = "control-socket", unix))]
+pub mod control;
+mod crypto;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+pub mod dbus;
+pub mod durations;
+mod error;
+mod export;
+mod gaps;
+mod hooks;
+pub mod input;
+mod instance;
 mod legend;
+mod locale;
+#[cfg(all(feature = "lock-screen", target_os = "linux"))]
+pub mod lock;
+mod migrate;
+mod plan;
+mod profiles;
+mod recovery;
+mod report;
+mod saver;
+#[cfg(feature = "slack")]
+mod slack;
+#[cfg(all(feature = "suspend", target_os = "linux"))]
+mod sleep;
+mod sources;
 mod stats;
+mod storage;
+pub mod store;
+mod sync;
+mod targets;
+mod templates;
+pub mod timeline;
+mod trash;
 mod ui;
 mod utils;
+mod watch;
+mod window;
+
+pub use export::{entries_to_string, stats_to_string, EntriesFormat, EntryFilter, ExportFormat};
+pub use stats::{compute_stats, compute_tag_stats, DatedLogs, TagStats, TimeStats};
+pub use storage::Dirs;
+pub use store::Storage;
 
 fn get_pref_label(number: u8, labels: Option<&[String; 8]>) -> Option<String> {
     labels
@@ -50,11 +109,33 @@ fn get_pref_label(number: u8, labels: Option<&[String; 8]>) -> Option<String> {
         })
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct TimeLog {
     start: DateTime<Local>,
     end: Option<DateTime<Local>>,
     number: u8,
+    /// How long the user expected this entry to take, in minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimate_mins: Option<u32>,
+    /// Free-form tags breaking a task number down further, without the
+    /// leading '#'
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// Overrides the task's color for just this entry, e.g. to make an
+    /// incident stand out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<Color>,
+    /// The profile the entry was tracked in, None for the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    /// The device the entry was synced from, None if it was tracked on this
+    /// one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    device: Option<String>,
+    /// The window rule that started the entry, if it was tracked
+    /// automatically
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auto: Option<String>,
 }
 
 impl Default for TimeLog {
@@ -63,11 +144,81 @@ impl Default for TimeLog {
             start: Local::now(),
             end: Default::default(),
             number: 1,
+            estimate_mins: None,
+            tags: Vec::new(),
+            color: None,
+            profile: None,
+            device: None,
+            auto: None,
         }
     }
 }
 
 impl TimeLog {
+    /// An entry for task `number`, from 1 to 8, from `start` until `end`, or
+    /// still going if that's None. Fails if `number` isn't one.
+    pub fn new(
+        number: u8,
+        start: DateTime<Local>,
+        end: Option<DateTime<Local>>,
+    ) -> io::Result<Self> {
+        if !(1..=8).contains(&number) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("There's no task {}, tasks go from 1 to 8", number),
+            ));
+        }
+        Ok(Self {
+            start,
+            end,
+            number,
+            ..Default::default()
+        })
+    }
+
+    /// This entry with `tags`, given without the leading '#'
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn start(&self) -> DateTime<Local> {
+        self.start
+    }
+
+    /// None while the entry is still going
+    pub fn end(&self) -> Option<DateTime<Local>> {
+        self.end
+    }
+
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// How long the user expected the entry to take, in minutes
+    pub fn estimate_mins(&self) -> Option<u32> {
+        self.estimate_mins
+    }
+
+    /// The profile the entry was tracked in, None for the default one
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// The device the entry was synced from, None if it wasn't synced
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// The window rule that started the entry, None if the user did
+    pub fn auto_rule(&self) -> Option<&str> {
+        self.auto.as_deref()
+    }
+
     fn is_open(&self) -> bool {
         self.end.is_none()
     }
@@ -82,7 +233,7 @@ impl TimeLog {
         self.resolve_label(app.preferences.labels.as_ref())
     }
 
-    fn _to_row(self: &TimeLog, labels: Option<&[String; 8]>, styled: bool) -> Row {
+    fn _to_row(self: &TimeLog, prefs: &Preferences, styled: bool) -> Row {
         let start_hm = self.start.format("%R").to_string();
         let start_s = self.start.format(":%S").to_string();
         let end_hm = self
@@ -97,31 +248,92 @@ impl TimeLog {
         let maybe_bold = if styled { ui::utils::bold } else { Span::raw };
         let maybe_dim = if styled { ui::utils::dim } else { Span::raw };
 
+        let mut spans = vec![
+            Span::raw("from "),
+            maybe_bold(start_hm),
+            maybe_dim(start_s),
+            Span::raw(if self.end.is_some() { " to " } else { " - " }),
+            maybe_bold(end_hm),
+            maybe_dim(end_s),
+        ];
+        for tag in &self.tags {
+            spans.push(maybe_dim(format!(" #{}", tag)));
+        }
+        if let Some(mins) = self.estimate_mins {
+            spans.push(maybe_dim(format!(
+                " (est. {})",
+                ui::utils::format_mins(mins)
+            )));
+        }
+        if self.auto.is_some() {
+            spans.push(maybe_dim(String::from(" (auto)")));
+        }
+
         Row::new(vec![
-            Cell::from(format!("[{}]", self.resolve_label(labels))),
-            Cell::from(Line::from(vec![
-                Span::raw("from "),
-                maybe_bold(start_hm),
-                maybe_dim(start_s),
-                Span::raw(if self.end.is_some() { " to " } else { " - " }),
-                maybe_bold(end_hm),
-                maybe_dim(end_s),
-            ])),
+            Cell::from(format!(
+                "[{}]",
+                ui::patterned_label(
+                    prefs,
+                    self.number,
+                    self.resolve_label(prefs.labels.as_ref())
+                )
+            ))
+            .style(Style::default().fg(ui::entry_color(prefs, self))),
+            Cell::from(Line::from(spans)),
         ])
     }
 
-    fn to_row(self: &TimeLog, labels: Option<&[String; 8]>) -> Row {
-        self._to_row(labels, true)
+    /// The entry as a line of plain text, like "[Coding] from 09:00:00 to
+    /// 10:30:00 #standup"
+    fn describe(&self, prefs: &Preferences) -> String {
+        let mut text = format!(
+            "[{}] from {}",
+            self.resolve_label(prefs.labels.as_ref()),
+            self.start.format("%T")
+        );
+        match self.end {
+            Some(end) => text.push_str(&format!(" to {}", end.format("%T"))),
+            None => text.push_str(", ongoing"),
+        }
+        for tag in &self.tags {
+            text.push_str(&format!(" #{}", tag));
+        }
+        if let Some(mins) = self.estimate_mins {
+            text.push_str(&format!(" (est. {})", ui::utils::format_mins(mins)));
+        }
+        if self.auto.is_some() {
+            text.push_str(" (auto)");
+        }
+        text
+    }
+
+    fn to_row(self: &TimeLog, prefs: &Preferences) -> Row {
+        self._to_row(prefs, true)
     }
 
-    fn to_row_unstyled(self: &TimeLog, labels: Option<&[String; 8]>) -> Row {
-        self._to_row(labels, false)
+    fn to_row_unstyled(self: &TimeLog, prefs: &Preferences) -> Row {
+        self._to_row(prefs, false)
     }
 }
 
 #[derive(Debug)]
 pub struct Message(String, DateTime<Local>);
 
+/// How many messages can be on screen at once
+const MAX_MESSAGES: usize = 3;
+/// How long each message stays on screen
+const MESSAGE_SECS: i64 = 10;
+/// How long today's log has to go unchanged before it's saved
+const SAVE_DEBOUNCE_SECS: i64 = 2;
+/// The longest to wait between attempts when saves keep failing
+const SAVE_RETRY_MAX_SECS: i64 = 300;
+/// How far the time since boot, or else the wall clock, has to get ahead of the
+/// monotonic clock between two times round the run loop for the system to have
+/// been asleep
+const SLEEP_GAP_SECS: u64 = 120;
+/// What a read-only copy of the app says when asked to change something
+const READ_ONLY_NOTICE: &str = "Read-only while another instance is running, so nothing can change";
+
 impl Default for Message {
     fn default() -> Self {
         Self(Default::default(), Local::now())
@@ -137,216 +349,3237 @@ where
     }
 }
 
+/// A named group of task numbers and tags, e.g. all the tasks you do for one
+/// client and the time tagged for them on shared tasks. Reports break their
+/// totals down by these groups in addition to by task.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ClientGroup {
+    name: String,
+    tasks: Vec<u8>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// A named kind of work, e.g. "Billable" or "Internal", that task numbers are
+/// sorted into for the category rollup on Stats. A task listed in more than
+/// one only counts toward the first.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct Category {
+    name: String,
+    tasks: Vec<u8>,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct Preferences {
     labels: Option<[String; 8]>,
+    /// The profile in use, the default one if unset. Its labels, colors and
+    /// targets are the ones above and below, with the other profiles' put
+    /// away in `profiles`.
+    profile: Option<String>,
+    profiles: Option<Vec<profiles::Profile>>,
     week_start_day: Option<Weekday>,
+    clients: Option<Vec<ClientGroup>>,
+    categories: Option<Vec<Category>>,
+    colors: Option<[Color; 8]>,
+    /// Where Stats exports are written. Defaults to an "exports" folder in the
+    /// app data directory.
+    exports_dir: Option<PathBuf>,
+    /// When a planned block begins, offer to start its task
+    prompt_planned_starts: Option<bool>,
+    theme: Option<ui::Theme>,
+    /// Draw each task with its own fill pattern as well as its color, which
+    /// the accessible themes do regardless of this setting
+    patterns: Option<bool>,
+    /// Which localhost port the HTTP API listens on, when built with the
+    /// "http-api" feature
+    #[cfg_attr(not(feature = "http-api"), allow(dead_code))]
+    api_port: Option<u16>,
+    /// The hours of a workday, by default 9 to 5, which the untracked gaps
+    /// report looks at and which Stats expects tracked on work days without a
+    /// target of their own
+    work_day_start: Option<NaiveTime>,
+    work_day_end: Option<NaiveTime>,
+    /// The days of the week the user works, by default Monday to Friday
+    work_days: Option<Vec<Weekday>>,
+    /// If set, time spent with the screen locked is tracked as this task (e.g.
+    /// a break) instead of going untracked
+    lock_break_task: Option<u8>,
+    /// An iCalendar (.ics) file to watch. When one of its events starts and
+    /// its title contains a task's label, that task is started automatically.
+    calendar_file: Option<PathBuf>,
+    /// A CalDAV calendar's URL, whose events start tasks like the calendar
+    /// file's do, when built with the "caldav" feature
+    #[cfg_attr(not(feature = "caldav"), allow(dead_code))]
+    calendar_url: Option<String>,
+    /// Choosing the task that's already being tracked, by number key or by
+    /// tracker side, stops it instead of carrying on
+    toggle_open_task: Option<bool>,
+    /// Tasks tracked alongside whatever else is, like being on call, rather
+    /// than stopping it. Each keeps running until it's chosen again (or the
+    /// app quits), so several entries can be open at once.
+    parallel_tasks: Option<Vec<u8>>,
+    /// Sum up the previous day when first starting the app on a new day, and
+    /// the day so far when quitting. On by default.
+    daily_summary: Option<bool>,
+    /// Remind the user once nothing has been tracked for this many minutes
+    /// during work hours on a work day, and again every time as long after.
+    /// Off unless set.
+    nag_mins: Option<u32>,
+    /// Shift+1-8 on Home starts an entry this many minutes ago, for noticing a
+    /// switch late. Unset starts it when the last entry ended.
+    backdate_mins: Option<u32>,
+    /// Ring the terminal bell with each reminder
+    nag_bell: Option<bool>,
+    /// Also send each reminder as a desktop notification, with notify-send
+    nag_desktop: Option<bool>,
+    /// Show what's being tracked, and for how long, in the terminal's title bar
+    title_timer: Option<bool>,
+    /// How to round each entry for the billing view on Stats and in exports
+    billing_rounding: Option<durations::BillingRounding>,
+    /// What each task earns per hour of billed time, with 0 for unpaid tasks
+    hourly_rates: Option<[f64; 8]>,
+    /// The most time the user wants to spend on each task in a day, in
+    /// minutes, with 0 for no limit. Going over flashes a warning.
+    daily_limit_mins: Option<[u32; 8]>,
+    /// Also send the warning as a desktop notification, with notify-send
+    limit_desktop: Option<bool>,
+    /// Daily logs older than this many days get bundled into monthly archives
+    /// at startup. Off unless set.
+    archive_after_days: Option<u32>,
+    /// How many hours the user expects to track on each day of the week, e.g.
+    /// `{Mon: 8, Fri: 4}`. Days left out expect nothing.
+    target_hours: Option<HashMap<Weekday, f32>>,
+    /// A second directory, e.g. on a NAS or USB drive, that each day's save
+    /// file is copied to whenever it's saved
+    backup_dir: Option<PathBuf>,
+    /// A folder shared between the user's devices (with Syncthing, Dropbox...)
+    /// to keep their logs in step through. Off unless set.
+    sync_dir: Option<PathBuf>,
+    /// What this device is called in the sync folder, its hostname by default
+    device_name: Option<String>,
+    /// When to send out the end-of-day report each work day. Off unless set.
+    report_time: Option<NaiveTime>,
+    /// Whether reports are plain text or HTML, text by default
+    report_format: Option<report::ReportFormat>,
+    /// Where reports are written, a "reports" folder in the app data directory
+    /// by default
+    reports_dir: Option<PathBuf>,
+    /// A shell command to pipe each report to instead of writing it to a file,
+    /// e.g. to mail it with sendmail
+    report_command: Option<String>,
+    /// How often today's log is saved regardless of changes, which is also
+    /// when the app notices a new day has begun. 5 minutes by default.
+    autosave_mins: Option<u32>,
+    /// A locale like "fr_FR" for month and weekday names and date formats.
+    /// Defaults to the one the environment (LC_ALL, LC_TIME or LANG) says.
+    locale: Option<String>,
+    /// Only Bluetooth devices whose name contains this are connected to as a
+    /// tracker, "Timeular" by default
+    tracker_name: Option<String>,
+    /// The characteristic a DIY tracker (e.g. an ESP32 cube) reports its side
+    /// on, as a byte from 1 to 8 with anything else meaning an edge. Tried
+    /// before the known Timeular firmware profiles.
+    tracker_side_uuid: Option<uuid::Uuid>,
+    /// Which task each side of the tracker starts, with None for sides that
+    /// should do nothing. Side 1 starts task 1 and so on by default.
+    tracker_sides: Option<[Option<u8>; 8]>,
+    /// Flipping the tracker onto an edge and back to the same side within this
+    /// many seconds takes a break from that side's task, or ends the break.
+    /// Off unless set.
+    tracker_double_flip_secs: Option<u32>,
+    /// Stop timeboxed entries (started like "2 for 30m") once their time is
+    /// up, instead of only saying so
+    timebox_auto_close: Option<bool>,
+    /// Commands reporting blocks of time to log automatically, like commutes;
+    /// see the sources module for the format
+    hook_sources: Option<Vec<sources::HookSource>>,
+    /// Commands whose output starts and stops tracking while they run; see the
+    /// input module for the format
+    input_commands: Option<Vec<input::CommandInput>>,
+    /// Rules for starting tasks by the focused window, when built with the
+    /// "window-tracking" feature; see the window module
+    #[cfg_attr(not(feature = "window-tracking"), allow(dead_code))]
+    window_tracking: Option<window::WindowTracking>,
+    /// The emoji shown with each task's label as the user's Slack status, like
+    /// ":computer:", when built with the "slack" feature. ":stopwatch:" for
+    /// tasks left out.
+    #[cfg_attr(not(feature = "slack"), allow(dead_code))]
+    slack_emojis: Option<[String; 8]>,
+}
+
+impl Preferences {
+    /// The user's label for task `number`, or the number if they haven't set
+    /// one
+    pub fn label(&self, number: u8) -> String {
+        get_pref_label(number, self.labels.as_ref()).unwrap_or_else(|| number.to_string())
+    }
+
+    /// Whether `number` is one of the user's parallel tasks
+    pub fn is_parallel(&self, number: u8) -> bool {
+        self.parallel_tasks
+            .as_ref()
+            .is_some_and(|tasks| tasks.contains(&number))
+    }
+
+    /// The entry being tracked in `entries`, not counting parallel tasks
+    /// running alongside it unless there's nothing else
+    pub fn open_entry<'a>(&self, entries: &'a [TimeLog]) -> Option<&'a TimeLog> {
+        let mut open = entries.iter().rev().filter(|tl| tl.is_open());
+        open.clone()
+            .find(|tl| !self.is_parallel(tl.number))
+            .or_else(|| open.next())
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct App {
+    /// Where the logs, settings and everything else are kept
+    pub dirs: Dirs,
+    /// Today's log and the settings as this copy last loaded or saved them
+    pub known: watch::KnownFiles,
     pub today: Vec<TimeLog>,
-    pub message: Option<Message>,
+    /// The day that `today` and its breaks, trash and plan are for, which
+    /// rolls over once it's no longer the current date, even if nothing was
+    /// tracked on it
+    pub day: Option<NaiveDate>,
+    /// Today's entries tracked in the profiles not in use, kept out of `today`
+    /// so that only the profile in use's are shown, and saved along with them
+    pub other_profiles: Vec<TimeLog>,
+    /// Recent messages, oldest first, each shown until it expires
+    pub messages: Vec<Message>,
     pub tracker_connected: bool,
     pub selected_page: ui::Page,
     pub preferences: Preferences,
+    /// What the user planned to do today, shown alongside what they actually
+    /// did on the Home page
+    pub today_plan: Vec<plan::PlannedBlock>,
+    /// The last planned block whose task the user was offered to start (with
+    /// Enter on Home), so that each block only prompts once
+    pub prompted_planned: Option<plan::PlannedBlock>,
+    /// The task that was being tracked when the screen locked or the user took
+    /// a break with the tracker, which they're offered to resume (with Enter on
+    /// Home)
+    pub paused: Option<u8>,
+    /// The tasks r on Home goes back through, most recent first, which one
+    /// of them the entry it started is for, and when that entry started
+    pub resume_cycle: Option<(Vec<u8>, usize, DateTime<Local>)>,
+    /// Events from the user's calendar file, if they have one
+    pub calendar_events: Vec<calendar::CalendarEvent>,
+    /// Events from the user's CalDAV calendar, as of the last time it was
+    /// fetched
+    pub remote_calendar_events: Vec<calendar::CalendarEvent>,
+    /// When calendar events were last checked for having started, so that each
+    /// event only starts its task once
+    pub calendar_checked_at: Option<DateTime<Local>>,
+    /// The last day the user was told they'd met their target for, so that
+    /// they're only told once a day
+    pub target_met_on: Option<NaiveDate>,
+    /// The tasks the user has been warned went over their daily limits, and on
+    /// which day, so that each one only warns once a day
+    pub limits_warned: Vec<(NaiveDate, u8)>,
+    /// The last day the end-of-day report was sent for, so that it's only
+    /// sent once a day
+    pub reported_on: Option<NaiveDate>,
+    /// When the last entry the user was told had used up its timebox started,
+    /// so that each entry only alerts once
+    pub timebox_alerted: Option<DateTime<Local>>,
+    /// When today's log was last changed without being saved, if it has been
+    pub dirty_since: Option<DateTime<Local>>,
+    /// Entries deleted from today's log, which can be restored until the day
+    /// is over
+    pub trash: trash::Trash,
+    /// Another day's entries, drawn dimly under today's on the Home timeline
+    /// to compare the two, if the user asked for that
+    pub comparison: Option<(NaiveDate, Vec<TimeLog>)>,
+    /// How much of the day the Home timeline shows
+    pub zoom: timeline::Zoom,
+    /// Past days whose logs couldn't be saved, e.g. because the save directory
+    /// was on a drive that got ejected, kept until they can be
+    pub unsaved_days: BTreeMap<NaiveDate, Vec<TimeLog>>,
+    /// After a failed save, when to try again and how long that wait was, which
+    /// doubles with each failure
+    pub save_retry: Option<(DateTime<Local>, i64)>,
+    /// Saves logs in the background once the UI is running, before which
+    /// they're saved right away
+    pub saver: Option<saver::Saver>,
+    /// The breaks taken today, the last of which may still be going
+    pub today_breaks: Vec<breaks::Break>,
+    /// When the user was last reminded that nothing was being tracked
+    pub nagged_at: Option<DateTime<Local>>,
+    /// The message bar flashes until then, to catch the user's eye
+    pub flash_until: Option<DateTime<Local>>,
+    /// What can be clicked on the page as it was last drawn
+    pub mouse_targets: ui::MouseTargets,
+    /// The terminal title last set, so that it's only set again once it
+    /// changes
+    pub shown_title: Option<String>,
+    /// Draws Home and Stats as plain lines of text, and everything without
+    /// colors, for screen readers and dumb terminals
+    pub plain: bool,
+    /// A failure the user has to see before carrying on, shown over the page
+    /// until then
+    pub error_popup: Option<ui::ErrorPopup>,
+    /// Messages from the background workers, shown in between frames
+    pub notices: Notices,
+    /// Syncing with the user's other devices, if they've set a sync folder
+    pub sync: sync::SyncFolder,
 }
 
 impl App {
-    pub fn load_or_default() -> Self {
+    /// Shows a message to the user, pushing out the oldest one if there are
+    /// already too many. Repeating the newest message just keeps it up longer.
+    pub fn notify<T: Into<Message>>(&mut self, msg: T) {
+        let msg = msg.into();
+        if self.messages.last().is_some_and(|m| m.0 == msg.0) {
+            self.messages.pop();
+        }
+        self.messages.push(msg);
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    /// Stops to show `e` in a popup until the user dismisses it, for failures
+    /// that mustn't just scroll by in the message bar
+    pub fn show_error(&mut self, e: YdncError) {
+        warn!("{}", e);
+        self.error_popup = Some(ui::ErrorPopup {
+            message: e.to_string(),
+            keys: "Enter/Esc: OK",
+            edit_today: false,
+            edit_file: None,
+        });
+    }
+
+    /// Tells the user about a day whose log was corrupt, in a popup offering to
+    /// check what was kept of it in the editor: the app's own for today, and
+    /// $EDITOR for older days, unless the log is encrypted. Anything recovered
+    /// while a popup is up, like the rest of the days the Stats page comes
+    /// across at once, only gets a message.
+    fn show_recovered(&mut self, recovered: recovery::Recovery) {
+        let today = recovered.date == Local::now().date_naive();
+        let message = format!(
+            "The log for {} was corrupt, so {} of its entries were kept and the original was moved to {}",
+            locale::format_date(&self.preferences, recovered.date, "%A %b %-d"),
+            recovered.kept,
+            recovered.moved_to.display()
+        );
+        if self.error_popup.is_some() {
+            self.notify(message);
+        } else if today {
+            self.error_popup = Some(ui::ErrorPopup {
+                message,
+                keys: "e: check them in the editor | Enter/Esc: OK",
+                edit_today: true,
+                edit_file: None,
+            });
+        } else if !crypto::is_encrypted(&recovered.path) {
+            self.error_popup = Some(ui::ErrorPopup {
+                message,
+                keys: "e: check them in $EDITOR | Enter/Esc: OK",
+                edit_today: false,
+                edit_file: Some(recovered.path),
+            });
+        } else {
+            self.notify(message);
+        }
+    }
+
+    /// Saves the days that couldn't be saved earlier, then today's log, on the
+    /// way out. A read-only copy of the app leaves them to the copy that
+    /// writes.
+    fn save_before_quitting(&mut self) -> error::Result<()> {
+        if self.is_read_only() {
+            warn!(
+                "Read-only, quitting without saving {} unsaved day(s)",
+                self.unsaved_days.len()
+            );
+            return Ok(());
+        }
+        while let Some((&date, logs)) = self.unsaved_days.iter().next() {
+            save_log_for(&self.dirs, date, logs)?;
+            self.sync.record(date, logs);
+            self.unsaved_days.remove(&date);
+        }
+        let whole = self.whole_day();
+        save_log(&self.dirs, &whole)?;
+        self.sync.record(Local::now().date_naive(), &whole);
+        Ok(())
+    }
+
+    /// Shows the messages the background workers have sent since last time
+    pub fn take_notices(&mut self) {
+        while let Ok(msg) = self.notices.rx.try_recv() {
+            self.notify(msg);
+        }
+    }
+
+    fn expire_messages(&mut self, now: DateTime<Local>) {
+        self.messages
+            .retain(|m| now.signed_duration_since(m.1) <= chrono::Duration::seconds(MESSAGE_SECS));
+    }
+
+    /// Whether this copy of the app mustn't change or save anything (see the
+    /// instance module)
+    pub fn is_read_only(&self) -> bool {
+        self.dirs.instance().is_read_only()
+    }
+
+    /// Notes that today's log has changed, so that it gets saved once it
+    /// settles down. Never in a read-only copy of the app, which keeps
+    /// today's log from changing to begin with (see `refuse_changes`).
+    pub fn mark_dirty(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
+        self.dirty_since = Some(Local::now());
+    }
+
+    /// In a read-only copy of the app, undoes whatever a key or click just
+    /// changed of today's log (`before`), before it's ever drawn, and keeps
+    /// out of the editor, so that nothing the user does is thrown away later
+    fn refuse_changes(&mut self, before: Vec<TimeLog>) {
+        let editing = matches!(
+            self.selected_page,
+            ui::Page::Home(ui::home::State::Editing { .. })
+        );
+        if !editing && self.today == before {
+            return;
+        }
+        self.today = before;
+        if editing {
+            self.selected_page = ui::Page::Home(Default::default());
+        }
+        self.notify(READ_ONLY_NOTICE);
+    }
+
+    /// Takes over once the copy of the app that had the logs has quit, picking
+    /// up today's log where it left it
+    fn take_over_lock(&mut self) {
+        if !instance::retry(&self.dirs) || self.is_read_only() {
+            return;
+        }
+        self.dirs.recoveries().enable();
+        match load_log(&self.dirs, &mut self.known) {
+            Ok(today) => self.set_whole_day(today),
+            Err(e) if e.is_not_found() => {}
+            Err(e) => warn!("Unable to reload today's log: {}", e),
+        }
+        self.notify("The other instance has quit, so this one can make changes now");
+        self.archive_old_logs();
+        self.check_sources();
+        self.pull_sync();
+    }
+
+    /// Saves today's log if it has changed and then gone unchanged for a
+    /// little while, so that a burst of changes only saves once. After a
+    /// failure, saves wait longer and longer before trying again, and any past
+    /// days that couldn't be saved are caught up on first.
+    pub fn save_if_settled(&mut self, now: DateTime<Local>) {
+        // Whatever's being saved has to finish first, or the same changes
+        // would be sent again each time round. Nor is anything saved between
+        // midnight and the UI loop rolling the day over, as yesterday's
+        // entries would go in today's file.
+        if self.save_retry.is_some_and(|(at, _)| now < at)
+            || self.is_saving()
+            || self.day_to_roll_over(now).is_some()
+        {
+            return;
+        }
+        if let Some((&date, logs)) = self.unsaved_days.first_key_value() {
+            let snapshot = saver::Snapshot {
+                date,
+                logs: logs.clone(),
+                past: true,
+                dirty_since: None,
+                announce: false,
+            };
+            self.send_save(snapshot, now);
+            return;
+        }
+
+        let settled = self
+            .dirty_since
+            .is_some_and(|t| now - t >= chrono::Duration::seconds(SAVE_DEBOUNCE_SECS));
+        if settled {
+            self.save_today_log(false, now);
+        }
+    }
+
+    /// Sends today's log to be saved as it is now, announcing how it went if
+    /// the user asked for it
+    pub fn save_today_log(&mut self, announce: bool, now: DateTime<Local>) {
+        let snapshot = saver::Snapshot {
+            date: now.date_naive(),
+            logs: self.whole_day(),
+            past: false,
+            dirty_since: self.dirty_since,
+            announce,
+        };
+        self.send_save(snapshot, now);
+    }
+
+    /// Saves in the background if the saver's running, or else right away
+    fn send_save(&mut self, snapshot: saver::Snapshot, now: DateTime<Local>) {
+        match self.saver.as_mut() {
+            Some(saver) => saver.send(snapshot),
+            None => self.on_saved(saver::save(&self.dirs, &self.sync, snapshot), now),
+        }
+    }
+
+    /// Whether a save is still going in the background
+    pub fn is_saving(&self) -> bool {
+        self.saver.as_ref().is_some_and(saver::Saver::is_saving)
+    }
+
+    /// Saves something other than today's log in the background if the
+    /// saver's running, or else right away
+    fn send_write(&mut self, write: saver::Write) {
+        match self.saver.as_mut() {
+            Some(saver) => saver.write(write),
+            None => self.on_written(saver::write(&self.dirs, &self.sync, write)),
+        }
+    }
+
+    /// Takes in how the background saves that have finished went
+    pub fn take_saved(&mut self, now: DateTime<Local>) {
+        while let Some(done) = self.saver.as_mut().and_then(saver::Saver::try_recv) {
+            self.on_done(done, now);
+        }
+    }
+
+    fn on_done(&mut self, done: saver::Done, now: DateTime<Local>) {
+        match done {
+            saver::Done::Log(saved) => self.on_saved(saved, now),
+            saver::Done::Write(written) => self.on_written(written),
+        }
+    }
+
+    fn on_written(&mut self, written: saver::Written) {
+        match written.result {
+            Ok(()) => {
+                if written.settings_mtime.is_some() {
+                    self.known.record_settings(written.settings_mtime);
+                }
+                if let Some(message) = written.message {
+                    self.notify(message);
+                }
+            }
+            Err(e) => {
+                warn!("Unable to save {}: {}", written.what, e);
+                self.notify(format!("Could not save {}: {}", written.what, e));
+            }
+        }
+    }
+
+    fn on_saved(&mut self, saved: saver::Saved, now: DateTime<Local>) {
+        match saved.result {
+            Ok(()) => {
+                if saved.date == now.date_naive() {
+                    self.known.record(saved.mtime, &saved.logs);
+                }
+                if saved.past {
+                    self.unsaved_days.remove(&saved.date);
+                } else if self.dirty_since == saved.dirty_since {
+                    // Nothing's changed since the snapshot
+                    self.dirty_since = None;
+                }
+                if saved.announce {
+                    self.notify("Saved today's log");
+                }
+                let all_saved = self.dirty_since.is_none() && self.unsaved_days.is_empty();
+                if all_saved && !self.is_saving() && self.save_retry.take().is_some() {
+                    info!("Saving works again");
+                    self.notify("Save directory is back, everything is saved");
+                }
+            }
+            // Saving when asked to is a one-off, which is only reported
+            Err(e) if saved.announce => {
+                self.notify(format!("Could not save today's log: {}", e));
+            }
+            Err(e) => {
+                if !saved.past {
+                    self.dirty_since.get_or_insert(now);
+                }
+                self.save_failed(now, e);
+            }
+        }
+    }
+
+    /// Saves today's log right away. If the day it's for is over, it's saved
+    /// as that day's instead, and kept until it can be if that fails, since
+    /// it's about to be cleared for the new day.
+    pub fn save_today(&mut self, now: DateTime<Local>) {
+        if self.is_read_only() {
+            return;
+        }
+        let logs = self.whole_day();
+        let date = logs
+            .first()
+            .map_or(now.date_naive(), |tl| tl.start.date_naive());
+        if date != now.date_naive() {
+            // Kept as unsaved until the save's gone through
+            self.unsaved_days.insert(date, logs.clone());
+            let snapshot = saver::Snapshot {
+                date,
+                logs,
+                past: true,
+                dirty_since: None,
+                announce: false,
+            };
+            self.send_save(snapshot, now);
+            return;
+        }
+        self.save_today_log(false, now);
+    }
+
+    fn save_failed(&mut self, now: DateTime<Local>, e: YdncError) {
+        let wait = self.save_retry.map_or(SAVE_DEBOUNCE_SECS, |(_, wait)| {
+            (wait * 2).min(SAVE_RETRY_MAX_SECS)
+        });
+        self.save_retry = Some((now + chrono::Duration::seconds(wait), wait));
+        warn!("Unable to save, retrying in {}s: {}", wait, e);
+
+        let days = self.unsaved_days.len() + usize::from(self.dirty_since.is_some());
+        self.notify(format!(
+            "Could not save ({}), keeping {} unsaved day{} in memory, retrying in {}s",
+            e,
+            days,
+            if days == 1 { "" } else { "s" },
+            wait
+        ));
+    }
+
+    pub fn load_or_default(dirs: Dirs) -> Self {
+        // Settle which copy of the app writes before anything gets written
+        if instance::acquire(&dirs) {
+            dirs.recoveries().enable();
+        }
         // Load from save files if possible
-        let preferences = load_prefs().unwrap_or_default();
-        match load_log() {
+        let mut known = watch::KnownFiles::default();
+        let preferences = load_prefs(&dirs).unwrap_or_default();
+        known.record_settings(watch::mtime(&get_settings_file_path(&dirs)));
+        let today_plan = plan::load_plan(&dirs, Local::now().date_naive()).unwrap_or_default();
+        let notices = Notices::default();
+        backup::set_dir(preferences.backup_dir.clone(), notices.sender());
+        let sync = sync::SyncFolder::default();
+        sync.set_dir(
+            preferences.sync_dir.clone(),
+            sync::device_name(&dirs, &preferences),
+            notices.sender(),
+        );
+        #[cfg(feature = "slack")]
+        slack::start(&dirs, notices.sender());
+        let loaded = load_log(&dirs, &mut known);
+        let first_launch_today = matches!(&loaded, Err(e) if e.is_not_found());
+        // Today's log is there but can't be read, e.g. it's encrypted with a
+        // passphrase other than the one given. Starting empty and saving
+        // would lose it, so nothing gets saved this session.
+        let unreadable = matches!(&loaded, Err(e) if !e.is_not_found());
+        if unreadable {
+            dirs.instance().make_read_only();
+        }
+        let mut app = match loaded {
             Ok(today) => Self {
+                dirs,
+                known,
                 today,
                 preferences,
-                message: Some("Loaded today's time log from save file".into()),
+                today_plan,
+                messages: vec!["Loaded today's time log from save file".into()],
+                notices,
+                sync,
                 ..Default::default()
             },
             Err(err) => Self {
+                dirs,
+                known,
                 preferences,
-                message: Some(
-                    format!("Could not load today's log from save: {}", err.kind()).into(),
-                ),
+                today_plan,
+                messages: vec![format!("Could not load today's log from save: {}", err).into()],
+                notices,
+                sync,
                 ..Default::default()
             },
+        };
+        if first_launch_today && app.preferences.daily_summary.unwrap_or(true) {
+            app.summarize_previous_day();
         }
+        let today = std::mem::take(&mut app.today);
+        app.set_whole_day(today);
+        app.day = Some(Local::now().date_naive());
+        app.trash = trash::Trash::load(&app.dirs, Local::now().date_naive());
+        app.today_breaks =
+            breaks::load_breaks(&app.dirs, Local::now().date_naive()).unwrap_or_default();
+        // A break left going last time can still be resumed
+        app.paused = app.on_break().map(|b| b.number);
+        app.reload_calendar();
+        if unreadable {
+            app.notify("Today's log couldn't be read, so nothing will be saved this session");
+        } else if app.is_read_only() {
+            app.notify("Another instance is running, so this one is read-only");
+        } else {
+            app.archive_old_logs();
+            app.check_sources();
+            app.pull_sync();
+        }
+        app.reported_on = report::last_sent(&app.dirs);
+        app
     }
 
-    pub fn has_open_entry(&self) -> bool {
-        self.today.last().map_or(false, |tl| tl.is_open())
+    /// Shows a summary of the last day tracked within the past week, if any
+    fn summarize_previous_day(&mut self) {
+        let today = Local::now().date_naive();
+        let previous = (1..=7)
+            .filter_map(|n| today.checked_sub_days(Days::new(n)))
+            .find_map(|date| {
+                load_log_for(&self.dirs, date)
+                    .ok()
+                    .filter(|logs| !logs.is_empty())
+                    .map(|logs| (date, logs))
+            });
+        if let Some((date, logs)) = previous {
+            // It's over, so anything left open there ran until midnight
+            let end_of_day = durations::end_of_day(&logs[0].start);
+            let summary = stats::DaySummary::new(date, &logs, &self.preferences, end_of_day);
+            self.selected_page = ui::Page::Home(ui::home::State::Summary(Box::new(summary)));
+        }
     }
 
-    pub fn close_entry_if_open(&mut self, now: DateTime<Local>) {
-        // If we have an open entry, close it
-        if self.has_open_entry() {
-            self.today.last_mut().unwrap().end = Some(now);
-        };
+    /// What the user's Slack status should say: the task being tracked, if any
+    #[cfg(feature = "slack")]
+    fn slack_status(&self) -> slack::Status {
+        let tl = self.open_entry()?;
+        let emoji = self
+            .preferences
+            .slack_emojis
+            .as_ref()
+            .and_then(|emojis| emojis.get((tl.number as usize).wrapping_sub(1)))
+            .filter(|e| !e.is_empty())
+            .cloned()
+            .unwrap_or_else(|| String::from(":stopwatch:"));
+        Some((emoji, tl.label(self)))
     }
 
-    pub fn open_entry_number(&self) -> Option<u8> {
-        if let Some(&tl) = self.today.last() {
-            if tl.is_open() {
-                return Some(tl.number);
+    /// Starts checking the user's sources in the background, for
+    /// `take_fetched` to log what they report
+    pub fn check_sources(&mut self) {
+        sources::start_fetching(self.preferences.hook_sources.clone().unwrap_or_default());
+    }
+
+    /// Logs the new blocks of time reported by the user's sources, once
+    /// they've been checked, i.e. the ones that don't overlap anything
+    /// already logged that day
+    fn take_fetched(&mut self) {
+        let Some(fetched) = sources::take_fetched() else {
+            return;
+        };
+        for (name, entries) in fetched {
+            let entries = match entries {
+                Ok(entries) => entries,
+                Err(e) => {
+                    warn!("Unable to check source {}: {}", name, e);
+                    self.notify(format!("Could not check {}: {}", name, e));
+                    continue;
+                }
+            };
+
+            // Past days' blocks are checked against their logs by the saver
+            let mut logged = 0;
+            let mut past: BTreeMap<NaiveDate, Vec<TimeLog>> = BTreeMap::new();
+            for mut entry in entries {
+                let date = entry.start.date_naive();
+                if date != Local::now().date_naive() {
+                    entry.profile = self.preferences.profile.clone();
+                    past.entry(date).or_default().push(entry);
+                } else if !sources::overlaps_any(&entry, &self.whole_day(), Local::now()) {
+                    self.insert_entry(entry);
+                    logged += 1;
+                }
+            }
+            if logged > 0 {
+                info!("Logged {} blocks from {}", logged, name);
+                self.notify(format!("Logged {} blocks from {}", logged, name));
+            }
+            for (date, entries) in past {
+                self.send_write(saver::Write::Entries {
+                    date,
+                    entries,
+                    source: Some(name.clone()),
+                });
             }
         }
-        None
     }
 
-    pub fn start_entry(&mut self, number: u8) {
-        let now = Local::now();
-        // Heckyea DateTime is Copy
-        self.close_entry_if_open(now);
-        self.today.push(TimeLog {
-            start: now,
-            end: None,
-            number,
-        });
+    /// Starts merging in what the user's other devices tracked, from their
+    /// journals in the sync folder, in the background. `take_pulled` takes
+    /// in what it found.
+    pub fn pull_sync(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
+        self.sync.start_pull(&self.dirs, Local::now().date_naive());
+    }
 
-        if let ui::Page::Settings(ref mut state) = self.selected_page {
-            if !state.editing {
-                state.list_state.select(Some((number - 1).into()));
+    /// Takes in what the last pull from the sync folder found, merging it
+    /// into today's log. Today's log is left alone while it's being edited,
+    /// until the next time.
+    fn take_pulled(&mut self) {
+        let Some(pulled) = self.sync.take_pulled() else {
+            return;
+        };
+        if let Some(e) = pulled.error {
+            self.notify(format!("Could not sync: {}", e));
+            return;
+        }
+        let editing = matches!(
+            self.selected_page,
+            ui::Page::Home(ui::home::State::Editing { .. })
+        );
+        let mut merged_days = pulled.merged_days;
+        if let Some(updates) = pulled.today {
+            let whole = self.whole_day();
+            let merged = sync::merge_day(&whole, &updates);
+            if !editing && merged != whole {
+                self.set_whole_day(merged);
+                self.mark_dirty();
+                merged_days += 1;
             }
         }
+        if merged_days > 0 {
+            info!("Merged synced entries into {} days", merged_days);
+            self.notify(format!(
+                "Synced entries from other devices into {} day{}",
+                merged_days,
+                if merged_days == 1 { "" } else { "s" }
+            ));
+        }
     }
-}
-
-pub type AppState = Arc<Mutex<App>>;
 
-pub fn lock_and_message<T>(app_state: &AppState, msg: T)
-where
-    T: Into<Message>,
-{
-    let mut app = app_state.lock().unwrap();
-    app.message = Some(msg.into());
-}
+    fn archive_old_logs(&mut self) {
+        let Some(days) = self.preferences.archive_after_days else {
+            return;
+        };
+        let before = Local::now().date_naive() - Days::new(days.into());
+        match archive::archive_before(&self.dirs, before) {
+            Ok(0) => {}
+            Ok(n) => self.notify(format!("Archived {} old daily logs", n)),
+            Err(e) => {
+                warn!("Unable to archive old logs: {}", e);
+                self.notify(format!("Could not archive old logs: {}", e));
+            }
+        }
+    }
 
-pub fn lock_and_set_connected(app_state: &AppState, connected: bool) {
-    let mut app = app_state.lock().unwrap();
-    app.tracker_connected = connected;
-    app.message = Some(
-        if connected {
-            "Successfully connected to tracker"
-        } else {
-            "Connection to tracker lost"
+    /// Re-reads the user's calendar file, if they have one, so that changes to
+    /// it are picked up while the app is running. A CalDAV calendar is fetched
+    /// again in the background, for `take_fetched_calendar` to take in.
+    pub fn reload_calendar(&mut self) {
+        self.calendar_events = match self.preferences.calendar_file.as_ref() {
+            Some(path) => calendar::load_events(path).unwrap_or_else(|err| {
+                warn!("Unable to load calendar: {}", err);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        #[cfg(feature = "caldav")]
+        match self.preferences.calendar_url.clone() {
+            Some(url) => calendar::start_fetching(&self.dirs, url),
+            None => self.remote_calendar_events.clear(),
         }
-        .into(),
-    );
-}
+    }
 
-/// Gets the path to the save file directory we should use at this time. It will
-/// be the OS-appropriate "user data" directory, and the expected directories
-/// will be created if they don't exist (assuming we have permission to do so).
-/// Only returns None if we were not able to determine a suitable directory on
-/// this OS.
-fn get_save_file_dir() -> Option<PathBuf> {
-    let dirs = ProjectDirs::from_path(PathBuf::from("ydnc/time"));
-    dirs.and_then(|d| {
-        let dir = d.data_dir();
-        if fs::create_dir_all(dir).is_err() {
-            return None;
+    /// Takes in the CalDAV calendar's events once they've been fetched,
+    /// keeping the ones from before if fetching them failed
+    #[cfg(feature = "caldav")]
+    fn take_fetched_calendar(&mut self) {
+        match calendar::take_fetched() {
+            Some(Ok(events)) => self.remote_calendar_events = events,
+            Some(Err(e)) => {
+                warn!("Unable to fetch calendar: {}", e);
+                self.notify(format!("Could not fetch the calendar: {}", e));
+            }
+            None => {}
         }
-        Some(dir.to_path_buf())
-    })
-}
+    }
 
-/// Gets the path to the save file we should use at this time (save files
-/// include the current date, so the result of this function may change on
-/// subsequent calls). Only returns None if we were not able to determine a
-/// suitable directory on this OS.
-fn get_save_file_path() -> Option<PathBuf> {
-    get_save_file_dir().map(|dir| dir.join(format!("{}.ron", Local::now().format("%F"))))
-}
-
-/// Like `get_save_file_path` but for the user's preferences. Goes in the OS
-/// preferences/config directory.
-fn get_settings_file_path() -> Option<PathBuf> {
-    let dirs = ProjectDirs::from_path(PathBuf::from("ydnc/time"));
-    dirs.and_then(|d| {
-        let dir = d.preference_dir();
-        if fs::create_dir_all(dir).is_err() {
-            return None;
-        }
-        Some(dir.join("settings.ron"))
-    })
-}
+    /// Starts the task matching any calendar event that has begun since the
+    /// last check. The first check only sets the starting point, so events
+    /// from before the app started are left alone.
+    pub fn check_calendar_starts(&mut self, now: DateTime<Local>) {
+        let since = self.calendar_checked_at.replace(now);
+        let Some(since) = since else {
+            return;
+        };
 
-fn save_log(today: &Vec<TimeLog>) -> io::Result<()> {
-    let filename = get_save_file_path().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::PermissionDenied,
-            "Can't find or create app data directory",
-        )
-    })?;
+        let started = self
+            .calendar_events
+            .iter()
+            .chain(&self.remote_calendar_events)
+            .filter(|e| since < e.start && e.start <= now)
+            .filter_map(|e| {
+                calendar::matching_task(e, self.preferences.labels.as_ref()).map(|n| (e, n))
+            })
+            // The most recently started event wins if several started at once
+            .max_by_key(|(e, _)| e.start)
+            .map(|(e, n)| (n, e.summary.clone()));
+        if let Some((number, summary)) = started {
+            if self.open_entry_number() != Some(number) {
+                self.start_entry(number);
+                self.notify(format!("Started tracking for \"{}\"", summary));
+            }
+        }
+    }
 
-    info!("Saving log to {}", filename.display());
-    let file = fs::File::create(filename)?;
-    ron::ser::to_writer_pretty(file, today, ron::ser::PrettyConfig::default())
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    pub fn has_open_entry(&self) -> bool {
+        self.today.iter().any(|tl| tl.is_open())
+    }
 
-    Ok(())
-}
+    /// Whether `number` is one of the user's parallel tasks
+    pub fn is_parallel(&self, number: u8) -> bool {
+        self.preferences.is_parallel(number)
+    }
 
-fn load_log_file(filename: &PathBuf) -> io::Result<Vec<TimeLog>> {
-    info!("Loading log from {}", filename.display());
-    let file = fs::File::open(filename)?;
-    let mut tl_vec: Vec<TimeLog> =
-        ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    /// The entry being tracked, not counting parallel tasks running alongside
+    /// it unless there's nothing else
+    pub fn open_entry(&self) -> Option<&TimeLog> {
+        self.preferences.open_entry(&self.today)
+    }
 
-    tl_vec.sort_unstable_by_key(|tl| tl.start);
+    /// What's being tracked and for how long so far, like "Coding 00:42:13"
+    pub fn open_entry_elapsed(&self, now: DateTime<Local>) -> Option<String> {
+        self.open_entry().map(|tl| {
+            format!(
+                "{} {}",
+                tl.label(self),
+                durations::format_hms(now - tl.start)
+            )
+        })
+    }
 
-    Ok(tl_vec)
-}
+    /// Shows the open entry's elapsed time in the terminal's title bar, if the
+    /// user wants it there
+    pub fn update_title(&mut self, now: DateTime<Local>) {
+        if !self.preferences.title_timer.unwrap_or(false) {
+            return;
+        }
+        let title = match self.open_entry_elapsed(now) {
+            Some(elapsed) => format!("{} - ydnc-time", elapsed),
+            None => "ydnc-time".to_string(),
+        };
+        if self.shown_title.as_ref() == Some(&title) {
+            return;
+        }
+        if let Err(e) = execute!(io::stdout(), SetTitle(&title)) {
+            warn!("Unable to set the terminal title: {}", e);
+        }
+        self.shown_title = Some(title);
+    }
 
-fn load_log() -> io::Result<Vec<TimeLog>> {
-    let filename = get_save_file_path().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::PermissionDenied,
-            "Can't find or create app data directory",
-        )
-    })?;
+    /// Starts comparing today with the same weekday last week on the Home
+    /// timeline, or stops comparing if already doing so
+    pub fn toggle_comparison(&mut self) {
+        if self.comparison.take().is_none() {
+            let date = Local::now().date_naive() - Days::new(7);
+            self.compare_with(date);
+        }
+    }
 
-    load_log_file(&filename)
-}
+    /// Compares today with the day `days` after (or before, if negative) the
+    /// one being compared with, if any
+    pub fn shift_comparison(&mut self, days: i64) {
+        if let Some(date) = self
+            .comparison
+            .as_ref()
+            .and_then(|(date, _)| date.checked_add_signed(chrono::Duration::days(days)))
+        {
+            self.compare_with(date);
+        }
+    }
 
-fn save_prefs(prefs: &Preferences) -> io::Result<()> {
-    let filename = get_settings_file_path().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::PermissionDenied,
-            "Can't find or create config directory",
-        )
-    })?;
+    /// Copies yesterday's entries into today at the same times of day, for
+    /// regular days the user forgot to track, and opens today in the editor to
+    /// adjust them
+    pub fn duplicate_yesterday(&mut self) {
+        let today = Local::now().date_naive();
+        let Some(yesterday) = today.pred_opt() else {
+            return;
+        };
+        let logs = match load_log_for(&self.dirs, yesterday) {
+            Ok(logs) => logs,
+            Err(e) if e.is_not_found() => Vec::new(),
+            Err(e) => {
+                self.notify(format!("Could not load yesterday: {}", e));
+                return;
+            }
+        };
+        let copies: Vec<TimeLog> = logs
+            .iter()
+            .filter(|tl| profiles::in_current(tl, self.preferences.profile.as_deref()))
+            .filter_map(|tl| {
+                let start = utils::on_date(&tl.start, today)?;
+                Some(TimeLog {
+                    start,
+                    end: tl.end.map(|end| start + (end - tl.start)),
+                    device: None,
+                    ..tl.clone()
+                })
+            })
+            .collect();
+        self.fill_today(copies, "yesterday");
+    }
 
-    info!("Saving prefs to {}", filename.display());
-    let file = fs::File::create(filename)?;
-    ron::ser::to_writer_pretty(file, prefs, ron::ser::PrettyConfig::default())
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    /// Fills today's entries out from `template`, opening today in the editor
+    /// to adjust them like `duplicate_yesterday`
+    pub fn apply_template(&mut self, template: &templates::Template) {
+        let copies = template.entries_on(Local::now().date_naive());
+        self.fill_today(copies, &template.name);
+    }
 
-    Ok(())
-}
+    /// Adds `copies`, entries copied from `source`, to today's log and opens
+    /// it in the editor with the first of them selected. Copies that haven't
+    /// started yet or that would overlap what's already tracked today are
+    /// left out, and one that would still be going stops now.
+    fn fill_today(&mut self, copies: Vec<TimeLog>, source: &str) {
+        let now = Local::now();
+        let copies: Vec<TimeLog> = copies
+            .into_iter()
+            .filter(|copy| copy.start < now)
+            .map(|copy| TimeLog {
+                end: Some(copy.end.map_or(now, |end| end.min(now))),
+                profile: self.preferences.profile.clone(),
+                ..copy
+            })
+            .filter(|copy| !sources::overlaps_any(copy, &self.today, now))
+            .collect();
+        let Some(first) = copies.iter().map(|tl| tl.start).min() else {
+            self.notify(format!("Nothing from {} to copy", source));
+            return;
+        };
 
-fn load_prefs() -> io::Result<Preferences> {
-    let filename = get_settings_file_path().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::PermissionDenied,
-            "Can't find or create app data directory",
-        )
-    })?;
+        let count = copies.len();
+        self.today.extend(copies);
+        self.today.sort_by_key(|tl| tl.start);
+        self.mark_dirty();
+        let selected = self.today.iter().position(|tl| tl.start == first);
+        self.selected_page =
+            ui::Page::Home(ui::home::State::editable(self.today.clone(), selected));
+        self.notify(format!(
+            "Copied {} {} from {}",
+            count,
+            if count == 1 { "entry" } else { "entries" },
+            source
+        ));
+    }
 
-    info!("Loading prefs from {}", filename.display());
-    let file = fs::File::open(filename)?;
-    let prefs = ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fn compare_with(&mut self, date: NaiveDate) {
+        let day = locale::format_date(&self.preferences, date, "%a %b %-d");
+        match load_log_for(&self.dirs, date) {
+            Ok(logs) => self.comparison = Some((date, logs)),
+            Err(e) if e.is_not_found() => {
+                self.notify(format!("Nothing was tracked on {}", day));
+                self.comparison = Some((date, Vec::new()));
+            }
+            Err(e) => self.notify(format!("Could not load {}: {}", day, e)),
+        }
+    }
 
-    Ok(prefs)
+    /// Closes whatever's being tracked, except parallel tasks, which carry on
+    pub fn close_entry_if_open(&mut self, now: DateTime<Local>) {
+        let parallel = self.preferences.parallel_tasks.clone().unwrap_or_default();
+        self.close_open_entries(now, |tl| !parallel.contains(&tl.number));
+    }
+
+    /// Closes every open entry, parallel tasks included
+    pub fn close_all_entries(&mut self, now: DateTime<Local>) {
+        self.close_open_entries(now, |_| true);
+    }
+
+    /// Closes the open entry for `number`, if there is one. Returns whether
+    /// there was.
+    pub fn close_entry_for(&mut self, number: u8, now: DateTime<Local>) -> bool {
+        self.close_open_entries(now, |tl| tl.number == number)
+    }
+
+    fn close_open_entries(
+        &mut self,
+        now: DateTime<Local>,
+        which: impl Fn(&TimeLog) -> bool,
+    ) -> bool {
+        let mut closed = Vec::new();
+        for tl in self.today.iter_mut().filter(|tl| tl.is_open() && which(tl)) {
+            tl.end = Some(now);
+            closed.push(tl.clone());
+        }
+        if closed.is_empty() {
+            return false;
+        }
+        self.mark_dirty();
+        for tl in &closed {
+            hooks::stopped(&self.dirs, tl, tl.label(self));
+        }
+        true
+    }
+
+    /// Today's whole log as it's saved, the other profiles' entries included
+    fn whole_day(&self) -> Vec<TimeLog> {
+        let mut logs = self.today.clone();
+        logs.extend(self.other_profiles.iter().cloned());
+        logs.sort_by_key(|tl| tl.start);
+        logs
+    }
+
+    /// Takes in today's whole log, keeping the entries of the profiles not in
+    /// use apart from the rest
+    fn set_whole_day(&mut self, logs: Vec<TimeLog>) {
+        let profile = self.preferences.profile.as_deref();
+        (self.today, self.other_profiles) = logs
+            .into_iter()
+            .partition(|tl| profiles::in_current(tl, profile));
+    }
+
+    /// The open entry for `number`, which may be a parallel task running
+    /// alongside the open entry
+    fn open_entry_for(&mut self, number: u8) -> Option<&mut TimeLog> {
+        self.today
+            .iter_mut()
+            .rev()
+            .find(|tl| tl.is_open() && tl.number == number)
+    }
+
+    pub fn open_entry_number(&self) -> Option<u8> {
+        self.open_entry().map(|tl| tl.number)
+    }
+
+    /// If the user wants to be prompted about planned blocks, checks whether
+    /// one has just begun and offers to start it. Blocks whose task is already
+    /// being tracked don't need a prompt.
+    pub fn check_planned_starts(&mut self, now: DateTime<Local>) {
+        if !self.preferences.prompt_planned_starts.unwrap_or(false) {
+            return;
+        }
+
+        let current = plan::current_block(&self.today_plan, now.time()).copied();
+        if current == self.prompted_planned {
+            return;
+        }
+        self.prompted_planned = current;
+
+        if let Some(block) = current {
+            if self.open_entry_number() != Some(block.number) {
+                self.notify(format!(
+                    "Planned: {} from {}. Press Enter to start it",
+                    get_pref_label(block.number, self.preferences.labels.as_ref())
+                        .unwrap_or_else(|| block.number.to_string()),
+                    block.start.format("%R")
+                ));
+            }
+        }
+    }
+
+    /// Lets the user know once the open entry has run as long as its estimate,
+    /// which is how long it was timeboxed to, stopping it there if they want
+    pub fn check_timebox(&mut self, now: DateTime<Local>) {
+        let Some(entry) = self.open_entry().cloned() else {
+            return;
+        };
+        let Some(mins) = entry.estimate_mins else {
+            return;
+        };
+        let ends_at = entry.start + chrono::Duration::minutes(mins.into());
+        if now < ends_at || self.timebox_alerted == Some(entry.start) {
+            return;
+        }
+        self.timebox_alerted = Some(entry.start);
+
+        let label = entry.label(self);
+        let limit = ui::utils::format_mins(mins);
+        if self.preferences.timebox_auto_close.unwrap_or(false) {
+            self.close_entry_for(entry.number, ends_at);
+            self.notify(format!("Time's up! Stopped {} after {}", label, limit));
+        } else {
+            self.notify(format!("Time's up! {} has run for {}", label, limit));
+        }
+    }
+
+    /// Warns the user once a task has run past the daily limit they set for
+    /// it, flashing the message bar to catch their eye
+    pub fn check_limits(&mut self, now: DateTime<Local>) {
+        let today = now.date_naive();
+        self.limits_warned.retain(|(date, _)| *date == today);
+        for (number, mins) in targets::limits_reached(&self.preferences, &self.today, today, now) {
+            if self.limits_warned.contains(&(today, number)) {
+                continue;
+            }
+            self.limits_warned.push((today, number));
+            self.flash_until = Some(now + chrono::Duration::seconds(MESSAGE_SECS));
+            let msg = format!(
+                "{} has gone over its daily limit of {}",
+                get_pref_label(number, self.preferences.labels.as_ref())
+                    .unwrap_or_else(|| number.to_string()),
+                ui::utils::format_mins(mins)
+            );
+            if self.preferences.limit_desktop.unwrap_or(false) {
+                utils::desktop_notification("ydnc-time", &msg);
+            }
+            self.notify(msg);
+        }
+    }
+
+    /// Reminds the user to start tracking if they want reminding and nothing
+    /// has been tracked for a while during work hours. A break or a pause
+    /// (e.g. from the screen locking) doesn't count as forgetting.
+    pub fn check_nag(&mut self, now: DateTime<Local>) {
+        let Some(mins) = self.preferences.nag_mins.filter(|&m| m > 0) else {
+            return;
+        };
+        if self.has_open_entry() || self.paused.is_some() {
+            return;
+        }
+        let (work_start, work_end) = gaps::work_hours(&self.preferences);
+        if !gaps::is_work_day(&self.preferences, now.date_naive())
+            || now.time() < work_start
+            || now.time() >= work_end
+        {
+            return;
+        }
+
+        let Some(work_started_at) = now
+            .date_naive()
+            .and_time(work_start)
+            .and_local_timezone(Local)
+            .earliest()
+        else {
+            return;
+        };
+        let idle_since = self
+            .today
+            .iter()
+            .filter_map(|tl| tl.end)
+            .chain([work_started_at])
+            .chain(self.nagged_at)
+            .max()
+            .unwrap_or(work_started_at);
+        let idle = now - idle_since;
+        if idle < chrono::Duration::minutes(mins.into()) {
+            return;
+        }
+
+        self.nagged_at = Some(now);
+        self.flash_until = Some(now + chrono::Duration::seconds(MESSAGE_SECS));
+        let msg = format!(
+            "Nothing's been tracked for {}. Press 1-8 to start a task",
+            ui::utils::format_mins(mins)
+        );
+        if self.preferences.nag_bell.unwrap_or(false) {
+            let mut stdout = io::stdout();
+            if let Err(e) = stdout.write_all(b"\x07").and_then(|_| stdout.flush()) {
+                warn!("Unable to ring the bell: {}", e);
+            }
+        }
+        if self.preferences.nag_desktop.unwrap_or(false) {
+            utils::desktop_notification("ydnc-time", &msg);
+        }
+        self.notify(msg);
+    }
+
+    /// Lets the user know once they've tracked as much as they meant to today
+    pub fn check_target_met(&mut self, now: DateTime<Local>) {
+        let today = now.date_naive();
+        if self.target_met_on == Some(today) {
+            return;
+        }
+        let Some(target) = targets::target_for(&self.preferences, today) else {
+            return;
+        };
+        if target > chrono::Duration::zero() && durations::covered(&self.today, Some(now)) >= target
+        {
+            self.target_met_on = Some(today);
+            self.notify(format!(
+                "You've tracked today's {} target!",
+                ui::utils::format_mins(target.num_minutes() as u32)
+            ));
+        }
+    }
+
+    /// Sends out the end-of-day report once it's time to, if the user wants
+    /// one and today is a work day
+    pub fn check_report(&mut self, now: DateTime<Local>) {
+        let Some(time) = self.preferences.report_time else {
+            return;
+        };
+        let today = now.date_naive();
+        if self.reported_on == Some(today)
+            || now.time() < time
+            || !gaps::is_work_day(&self.preferences, today)
+            || self.is_read_only()
+        {
+            return;
+        }
+        // Only try once a day, rather than every tick while it keeps failing
+        self.reported_on = Some(today);
+        let notifier = self.notices.sender();
+        match report::send(&self.dirs, &self.today, &self.preferences, notifier) {
+            Ok(()) => self.notify("Sent today's report"),
+            Err(e) => {
+                warn!("Unable to send today's report: {}", e);
+                self.notify(format!("Could not send today's report: {}", e));
+            }
+        }
+    }
+
+    /// Starts the task of the planned block the user was last prompted about,
+    /// as long as that block is still underway and isn't already being tracked
+    pub fn accept_planned_start(&mut self) {
+        let current = plan::current_block(&self.today_plan, Local::now().time()).copied();
+        if let Some(block) = self.prompted_planned.filter(|&b| Some(b) == current) {
+            if self.open_entry_number() != Some(block.number) {
+                self.start_entry(block.number);
+            }
+        }
+    }
+
+    /// If the user prefers choosing the open task to stop it, and `number` is
+    /// the open task, stops it. Parallel tasks are always stopped by choosing
+    /// them again. Returns whether it did.
+    pub fn stop_if_toggled(&mut self, number: u8) -> bool {
+        let toggled = if self.is_parallel(number) {
+            self.today
+                .iter()
+                .any(|tl| tl.is_open() && tl.number == number)
+        } else {
+            self.preferences.toggle_open_task.unwrap_or(false)
+                && self.open_entry_number() == Some(number)
+        };
+        if toggled {
+            self.close_entry_for(number, Local::now());
+        }
+        toggled
+    }
+
+    /// Starts the task of the most recently closed entry. Pressed again while
+    /// that entry is open, switches it to the task before that, and so on
+    /// through today's last few distinct tasks.
+    pub fn resume_recent(&mut self) {
+        const CYCLE_LEN: usize = 5;
+
+        let open_start = self.open_entry().map(|tl| tl.start);
+        if let Some((tasks, i, started)) = self.resume_cycle.as_mut() {
+            if open_start == Some(*started) && tasks.len() > 1 {
+                *i = (*i + 1) % tasks.len();
+                let number = tasks[*i];
+                let started = *started;
+                if let Some(tl) = self
+                    .today
+                    .iter_mut()
+                    .find(|tl| tl.is_open() && tl.start == started)
+                {
+                    tl.number = number;
+                }
+                self.mark_dirty();
+                return;
+            }
+        }
+
+        let open = self.open_entry_number();
+        let mut tasks: Vec<u8> = Vec::new();
+        for tl in self.today.iter().rev().filter(|tl| !tl.is_open()) {
+            if Some(tl.number) != open && !tasks.contains(&tl.number) {
+                tasks.push(tl.number);
+            }
+            if tasks.len() == CYCLE_LEN {
+                break;
+            }
+        }
+        let Some(&number) = tasks.first() else {
+            self.resume_cycle = None;
+            self.notify("Nothing to go back to yet today");
+            return;
+        };
+        self.start_entry(number);
+        self.resume_cycle = self
+            .open_entry()
+            .filter(|tl| tl.number == number)
+            .map(|tl| (tasks, 0, tl.start));
+    }
+
+    /// Starts `number` as of a little while ago, for when the user noticed
+    /// late that they'd switched: as many minutes ago as they've set, or when
+    /// the last entry ended if they haven't. Whatever was open stops then, and
+    /// the entry never starts before it or before the end of the entry before.
+    pub fn start_backdated(&mut self, number: u8) {
+        let now = Local::now();
+        if self.open_entry_for(number).is_some() {
+            self.notify("Already tracking that");
+            return;
+        }
+        let parallel = self.is_parallel(number);
+        let earliest = self
+            .today
+            .iter()
+            .filter(|tl| !self.is_parallel(tl.number))
+            .map(|tl| tl.end.unwrap_or(tl.start))
+            .max();
+        let start = match (self.preferences.backdate_mins, earliest) {
+            (Some(mins), earliest) => {
+                let start = now - chrono::Duration::minutes(mins.into());
+                earliest.map_or(start, |e| start.max(e))
+            }
+            (None, Some(earliest)) => earliest,
+            (None, None) => now,
+        }
+        .min(now);
+
+        if !parallel {
+            self.close_entry_if_open(start);
+        }
+        self.start_entry(number);
+        if let Some(tl) = self.open_entry_for(number) {
+            tl.start = start;
+        }
+        self.notify(format!(
+            "Started {} as of {}",
+            get_pref_label(number, self.preferences.labels.as_ref())
+                .unwrap_or_else(|| number.to_string()),
+            start.format("%R")
+        ));
+    }
+
+    /// Starts `number` as chosen by the user on Home, or stops it if that's
+    /// what choosing the open task does
+    fn start_chosen(&mut self, number: u8) {
+        if self.stop_if_toggled(number) {
+            return;
+        }
+        self.start_entry(number);
+        // Offer to name unlabeled tasks right away instead of making new users
+        // find Settings
+        if get_pref_label(number, self.preferences.labels.as_ref()).is_none() {
+            self.selected_page = ui::Page::Home(ui::home::State::NamingTask {
+                number,
+                input: String::new(),
+            });
+        }
+    }
+
+    pub fn start_entry(&mut self, number: u8) {
+        self.start_tagged_entry(number, Vec::new(), None);
+    }
+
+    /// Stops tracking when the screen locks, remembering what was being
+    /// tracked so it can be resumed on unlock
+    pub fn on_screen_lock(&mut self, now: DateTime<Local>) {
+        let was_open = self.open_entry_number();
+        self.close_entry_if_open(now);
+        let break_task = self.preferences.lock_break_task;
+        if let Some(number) = break_task {
+            self.start_entry(number);
+        }
+        self.paused = was_open.filter(|&n| Some(n) != break_task);
+    }
+
+    /// Ends the locked interval and offers to resume whatever the lock stopped
+    pub fn on_screen_unlock(&mut self, now: DateTime<Local>) {
+        if self.preferences.lock_break_task.is_some()
+            && self.open_entry_number() == self.preferences.lock_break_task
+        {
+            self.close_entry_if_open(now);
+        }
+        self.offer_resume();
+    }
+
+    /// Stops tracking everything at `at`, when the system went to sleep,
+    /// remembering what was being tracked so it can be resumed on waking up.
+    /// Suspending while the screen is locked keeps what the lock stopped.
+    pub fn on_suspend(&mut self, at: DateTime<Local>) {
+        let break_task = self.preferences.lock_break_task;
+        if let Some(number) = self.open_entry_number().filter(|&n| Some(n) != break_task) {
+            self.paused = Some(number);
+        }
+        self.close_all_entries(at);
+    }
+
+    /// Offers to resume what was stopped, if anything was
+    fn offer_resume(&mut self) {
+        if let Some(number) = self.paused {
+            self.notify(format!(
+                "Welcome back! Press Enter to resume {}",
+                get_pref_label(number, self.preferences.labels.as_ref())
+                    .unwrap_or_else(|| number.to_string())
+            ));
+        }
+    }
+
+    /// The break being taken, if there is one
+    pub fn on_break(&self) -> Option<&breaks::Break> {
+        self.today_breaks.last().filter(|b| b.end.is_none())
+    }
+
+    /// Applies what an input source saw happen
+    pub fn handle_input(&mut self, event: input::InputEvent) {
+        use input::InputEvent;
+
+        let changes = !matches!(
+            event,
+            InputEvent::Resume | InputEvent::Connected(_) | InputEvent::Message(_)
+        );
+        if changes && self.is_read_only() {
+            self.notify(READ_ONLY_NOTICE);
+            return;
+        }
+        match event {
+            InputEvent::Start(number) => {
+                if self.open_entry_number() != Some(number) {
+                    self.start_entry(number);
+                }
+            }
+            // Only do something if there is NOT an already open entry with
+            // the same number, unless the user wants that to stop tracking
+            InputEvent::Switch(number) => {
+                if !self.stop_if_toggled(number) && self.open_entry_number() != Some(number) {
+                    self.start_entry(number);
+                }
+            }
+            // Never take over from something the user started themselves
+            InputEvent::AutoStart { number, rule } => {
+                let open = self.open_entry();
+                if open.is_some_and(|tl| tl.auto.is_none() || tl.number == number)
+                    || self.open_entry_for(number).is_some()
+                {
+                    return;
+                }
+                self.start_entry(number);
+                if let Some(tl) = self.open_entry_for(number) {
+                    tl.auto = Some(rule);
+                }
+            }
+            InputEvent::ToggleBreak(number) => self.toggle_break(number),
+            InputEvent::Stop => self.close_entry_if_open(Local::now()),
+            InputEvent::Suspend(at) => self.on_suspend(at),
+            InputEvent::Resume => self.offer_resume(),
+            InputEvent::Connected(connected) => {
+                self.tracker_connected = connected;
+                self.notify(if connected {
+                    "Successfully connected to tracker"
+                } else {
+                    "Connection to tracker lost"
+                });
+            }
+            InputEvent::Message(msg) => self.notify(msg),
+        }
+    }
+
+    /// Pauses `number` for a break, or resumes it if it's the task on a break.
+    /// When it comes from the tracker, whatever was being tracked has already
+    /// been stopped by the tracker going onto an edge.
+    pub fn toggle_break(&mut self, number: u8) {
+        if self.on_break().is_some_and(|b| b.number == number) {
+            self.resume_from_break();
+        } else {
+            self.start_break(number);
+        }
+    }
+
+    /// Takes a break from whatever's being tracked, or ends the break if on one
+    pub fn toggle_break_from_open(&mut self) {
+        if self.on_break().is_some() {
+            self.resume_from_break();
+        } else if let Some(number) = self.open_entry_number() {
+            self.start_break(number);
+        } else {
+            self.notify("Start a task first to take a break from it");
+        }
+    }
+
+    fn start_break(&mut self, number: u8) {
+        let now = Local::now();
+        self.close_entry_if_open(now);
+        self.end_break(now);
+        self.paused = Some(number);
+        self.today_breaks.push(breaks::Break {
+            start: now,
+            end: None,
+            number,
+        });
+        self.save_breaks();
+        self.notify(format!(
+            "On a break from {}. Press b (or double-flip the tracker) to resume",
+            get_pref_label(number, self.preferences.labels.as_ref())
+                .unwrap_or_else(|| number.to_string())
+        ));
+    }
+
+    /// Ends the break and starts its task again as a new entry
+    fn resume_from_break(&mut self) {
+        let Some(b) = self.on_break().cloned() else {
+            return;
+        };
+        self.end_break(Local::now());
+        self.start_entry(b.number);
+        self.notify(format!(
+            "Resumed {} after a {} break",
+            get_pref_label(b.number, self.preferences.labels.as_ref())
+                .unwrap_or_else(|| b.number.to_string()),
+            ui::utils::format_mins(b.duration(Some(Local::now())).num_minutes() as u32)
+        ));
+    }
+
+    /// Ends the break being taken, if any, without starting anything
+    fn end_break(&mut self, now: DateTime<Local>) {
+        if let Some(b) = self.today_breaks.last_mut().filter(|b| b.end.is_none()) {
+            b.end = Some(now);
+            self.save_breaks();
+        }
+    }
+
+    /// Saves the breaks as the day they were taken on's, which is only ever
+    /// not today just before a new day's rollover
+    fn save_breaks(&mut self) {
+        let date = self
+            .today_breaks
+            .first()
+            .map_or(Local::now().date_naive(), |b| b.start.date_naive());
+        let breaks = self.today_breaks.clone();
+        self.send_write(saver::Write::Breaks { date, breaks });
+    }
+
+    /// The day that today's entries, or `day` if there are none, are for, if
+    /// `now` is past it
+    fn day_to_roll_over(&self, now: DateTime<Local>) -> Option<NaiveDate> {
+        self.today
+            .iter()
+            .chain(&self.other_profiles)
+            .map(|tl| tl.start)
+            .min()
+            .map(|start| start.date_naive())
+            .or(self.day)
+            .filter(|date| *date != now.date_naive())
+    }
+
+    /// Once `now` is past the day of today's entries, or of `day` if there are
+    /// none, closes whatever's open at the end of that day, saves it as that
+    /// day's log and starts the new day with the open entries carrying on from
+    /// midnight. Returns whether it did.
+    pub fn roll_over_day(&mut self, now: DateTime<Local>) -> bool {
+        // Check if we have advanced into a new day
+        let Some(yesterday) = self.day_to_roll_over(now) else {
+            return false;
+        };
+        self.day = Some(now.date_naive());
+        // The other profiles' entries are yesterday's too
+        self.today = self.whole_day();
+        self.other_profiles.clear();
+        info!("Rolling over from {} to {}", yesterday, now.date_naive());
+        // The editor's copy of the entries and its indices into them are all
+        // for yesterday, so it can't carry on with today's
+        if matches!(
+            self.selected_page,
+            ui::Page::Home(ui::home::State::Editing { .. })
+        ) {
+            self.selected_page = ui::Page::Home(Default::default());
+            self.notify("The day is over, so editing yesterday's entries has stopped");
+        }
+
+        // If so and we have open entries (more than one if parallel tasks are
+        // running):
+        let mut open_entries: Vec<TimeLog> = Vec::new();
+        for entry_ref in self.today.iter_mut().filter(|tl| tl.is_open()) {
+            // Clone it so it can carry on into the new day
+            open_entries.push(entry_ref.clone());
+            // Close it inside `self.today`, setting its end date to the end of
+            // yesterday
+            entry_ref.end = Some(durations::end_of_day(&entry_ref.start));
+        }
+
+        // Save yesterday to its file, or keep it to retry if that fails. A day
+        // with nothing tracked has nothing to save.
+        if !self.today.is_empty() {
+            self.save_today(now);
+        }
+        hooks::day_rolled_over(&self.dirs, yesterday);
+
+        // Wipe self.today, and yesterday's trash with it
+        self.today.clear();
+        self.roll_over_breaks(now);
+        self.trash.empty_for(now.date_naive());
+        self.save_trash();
+        self.today_plan = plan::load_plan(&self.dirs, now.date_naive()).unwrap_or_default();
+        self.reload_calendar();
+
+        // For each previously open entry we cloned:
+        for mut entry in open_entries {
+            // Set its start date to the beginning of today
+            entry.start = datetime_with_zeroed_time(&now);
+            // Leave its `end` open and push it to the clean self.today
+            self.today.push(entry);
+        }
+        let today = std::mem::take(&mut self.today);
+        self.set_whole_day(today);
+        // The new day's file doesn't have them yet
+        if !self.today.is_empty() {
+            self.mark_dirty();
+        }
+        true
+    }
+
+    /// Splits a break still going at the start of a new day, like entries
+    /// are, and starts recording the new day's breaks
+    fn roll_over_breaks(&mut self, now: DateTime<Local>) {
+        let open = self.on_break().cloned();
+        if let Some(b) = self.today_breaks.last_mut().filter(|b| b.end.is_none()) {
+            b.end = Some(durations::end_of_day(&b.start));
+        }
+        if !self.today_breaks.is_empty() {
+            self.save_breaks();
+        }
+        self.today_breaks.clear();
+        if let Some(mut b) = open {
+            b.start = datetime_with_zeroed_time(&now);
+            self.today_breaks.push(b);
+            self.save_breaks();
+        }
+    }
+
+    /// Handles a click or a scroll of the mouse wheel. On Home, clicking an
+    /// entry selects it and clicking it again edits it, and scrolling moves the
+    /// selection. On Stats, clicking a date range option switches to it.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) -> io::Result<()> {
+        let clicked = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+        let scrolled_up = match mouse.kind {
+            MouseEventKind::ScrollUp => Some(true),
+            MouseEventKind::ScrollDown => Some(false),
+            _ => None,
+        };
+        let entry = self
+            .mouse_targets
+            .entry_at(mouse.column, mouse.row)
+            .filter(|_| clicked);
+
+        match self.selected_page {
+            ui::Page::Home(ui::home::State::Viewing { ref mut list_state }) => {
+                if let Some(up) = scrolled_up {
+                    ui::home::move_selection(list_state, self.today.len(), up);
+                } else if let Some(i) = entry.filter(|&i| i < self.today.len()) {
+                    if list_state.selected() == Some(i) {
+                        self.selected_page =
+                            ui::Page::Home(ui::home::State::editing_entry(self.today.clone(), i));
+                    } else {
+                        list_state.select(Some(i));
+                    }
+                }
+            }
+            ui::Page::Home(ui::home::State::Editing {
+                ref mut state,
+                ref mut delete_pending,
+                ..
+            }) if !state.editing && !state.options.is_empty() => {
+                if let Some(up) = scrolled_up {
+                    *delete_pending = false;
+                    if up {
+                        state.select_prev();
+                    } else {
+                        state.select_next();
+                    }
+                } else if let Some(i) = entry.filter(|&i| i < state.options.len()) {
+                    *delete_pending = false;
+                    if state.list_state.selected() == Some(i) {
+                        state.start_editing(None);
+                    } else {
+                        state.list_state.select(Some(i));
+                    }
+                }
+            }
+            ui::Page::Stats(ref mut state) if clicked => {
+                if let Some(dr) = self.mouse_targets.date_range_at(mouse.column, mouse.row) {
+                    state.select_date_range(&self.dirs, &self.preferences, dr);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Accepts whichever offer the user was last shown a message about:
+    /// resuming the paused task, else starting a planned block
+    pub fn accept_prompt(&mut self) {
+        match self.paused.take() {
+            Some(number) => self.start_entry(number),
+            None => self.accept_planned_start(),
+        }
+    }
+
+    /// Starts tracking `number` with the given tags, timeboxed to
+    /// `timebox_mins` if given. The timebox is kept as the entry's estimate.
+    pub fn start_tagged_entry(&mut self, number: u8, tags: Vec<String>, timebox_mins: Option<u32>) {
+        let now = Local::now();
+        if self.is_parallel(number) {
+            // A parallel task is only ever open once, and leaves everything
+            // else running
+            if self
+                .today
+                .iter()
+                .any(|tl| tl.is_open() && tl.number == number)
+            {
+                return;
+            }
+        } else {
+            // Once something else has been started there's nothing to resume
+            self.paused = None;
+            self.end_break(now);
+            // Heckyea DateTime is Copy
+            self.close_entry_if_open(now);
+        }
+        self.today.push(TimeLog {
+            start: now,
+            end: None,
+            number,
+            tags,
+            estimate_mins: timebox_mins,
+            profile: self.preferences.profile.clone(),
+            ..Default::default()
+        });
+        self.mark_dirty();
+        if let Some(tl) = self.today.last() {
+            hooks::started(&self.dirs, tl, tl.label(self));
+        }
+
+        if let ui::Page::Settings(ref mut state) = self.selected_page {
+            if !state.labels.editing && state.color_picker.is_none() {
+                state.labels.list_state.select(Some((number - 1).into()));
+            }
+        }
+    }
+
+    /// Switches to the profile called `name`, creating it if it's new, and
+    /// saves the preferences
+    pub fn switch_profile(&mut self, name: &str) {
+        let mut prefs = self.preferences.clone();
+        profiles::switch(&mut prefs, name);
+        self.apply_prefs(prefs);
+        self.save_prefs();
+        self.notify(format!("Switched to the {} profile", name));
+    }
+
+    /// Switches to preferences edited in Settings and saves them
+    fn save_changed_prefs(&mut self, prefs: Preferences) {
+        self.apply_prefs(prefs);
+        self.save_prefs();
+    }
+
+    /// Sends the preferences to be saved
+    fn save_prefs(&mut self) {
+        let prefs = Box::new(self.preferences.clone());
+        self.send_write(saver::Write::Prefs(prefs));
+    }
+
+    /// Sends the trash to be saved
+    fn save_trash(&mut self) {
+        let trash = self.trash.clone();
+        self.send_write(saver::Write::Trash(trash));
+    }
+
+    /// Sends `date`'s plan to be saved, and puts it to use if it's today's
+    fn save_plan(&mut self, date: NaiveDate, blocks: Vec<plan::PlannedBlock>) {
+        if date == Local::now().date_naive() {
+            self.today_plan = blocks.clone();
+        }
+        self.send_write(saver::Write::Plan { date, blocks });
+    }
+
+    /// Switches to `prefs`, e.g. ones reloaded after being edited outside the
+    /// app, without saving them. The preferences that are only read once when
+    /// they're set are put to use again here.
+    pub fn apply_prefs(&mut self, prefs: Preferences) {
+        if prefs.backup_dir != self.preferences.backup_dir {
+            backup::set_dir(prefs.backup_dir.clone(), self.notices.sender());
+        }
+        if prefs.sync_dir != self.preferences.sync_dir
+            || prefs.device_name != self.preferences.device_name
+        {
+            self.sync.set_dir(
+                prefs.sync_dir.clone(),
+                sync::device_name(&self.dirs, &prefs),
+                self.notices.sender(),
+            );
+        }
+        if prefs.labels != self.preferences.labels {
+            if let ui::Page::Settings(ref mut state) = self.selected_page {
+                state.reload_labels(prefs.labels.clone().unwrap_or_default().to_vec());
+            }
+        }
+        let calendar_changed = prefs.calendar_file != self.preferences.calendar_file
+            || prefs.calendar_url != self.preferences.calendar_url;
+        if prefs.profile != self.preferences.profile {
+            // What's being tracked belongs to the profile being left, where it
+            // would carry on out of sight
+            self.close_all_entries(Local::now());
+            let whole = self.whole_day();
+            self.preferences = prefs;
+            self.set_whole_day(whole);
+        } else {
+            self.preferences = prefs;
+        }
+        if calendar_changed {
+            self.reload_calendar();
+        }
+    }
+
+    /// Adds a finished entry to the log of the day it's on, for filling in
+    /// time that wasn't tracked as it happened. A past day's log is added to
+    /// by the saver.
+    pub fn insert_entry(&mut self, mut entry: TimeLog) {
+        entry.profile = self.preferences.profile.clone();
+        let date = entry.start.date_naive();
+        if date == Local::now().date_naive() {
+            self.today.push(entry);
+            self.today.sort_by_key(|tl| tl.start);
+            self.mark_dirty();
+        } else {
+            self.send_write(saver::Write::Entries {
+                date,
+                entries: vec![entry],
+                source: None,
+            });
+        }
+    }
+}
+
+pub type AppState = Arc<Mutex<App>>;
+
+/// Where background workers send messages for the user (see `Notices`)
+pub type Notifier = std::sync::mpsc::Sender<String>;
+
+/// Messages for the user from background workers. They're sent down a channel
+/// rather than the workers locking the app, so that a worker never waits on
+/// the UI loop while the UI loop waits on it, e.g. for it to finish on the
+/// way out.
+#[derive(Debug)]
+pub struct Notices {
+    tx: Notifier,
+    rx: std::sync::mpsc::Receiver<String>,
+}
+
+impl Default for Notices {
+    fn default() -> Self {
+        let (tx, rx) = std::sync::mpsc::channel();
+        Self { tx, rx }
+    }
+}
+
+impl Notices {
+    /// Somewhere for a worker to send its messages
+    pub fn sender(&self) -> Notifier {
+        self.tx.clone()
+    }
+}
+
+pub fn lock_and_message<T>(app_state: &AppState, msg: T)
+where
+    T: Into<Message>,
+{
+    let mut app = app_state.lock().unwrap();
+    app.notify(msg);
+}
+
+/// Gets the path to the save file we should use at this time (save files
+/// include the current date, so the result of this function may change on
+/// subsequent calls)
+fn get_save_file_path(dirs: &Dirs) -> PathBuf {
+    get_save_file_path_for(dirs, Local::now().date_naive())
+}
+
+/// Like `get_save_file_path` but for an arbitrary date. Each day's file goes
+/// in a subdirectory for its year and month, like "2023/06/2023-06-08.ron",
+/// which `save_day` creates the first time it's needed.
+fn get_save_file_path_for(dirs: &Dirs, date: NaiveDate) -> PathBuf {
+    day_file_in(dirs.data(), date)
+}
+
+/// Where a day's file goes in the save file directory `dir`
+fn day_file_in(dir: &Path, date: NaiveDate) -> PathBuf {
+    dir.join(date.format("%Y/%m").to_string())
+        .join(format!("{}.ron", date.format("%F")))
+}
+
+/// Where older versions of the app saved a day's file, right in the save file
+/// directory. Files there are still loaded, and get moved into place the next
+/// time they're saved.
+fn get_legacy_save_file_path_for(dirs: &Dirs, date: NaiveDate) -> PathBuf {
+    legacy_day_file_in(dirs.data(), date)
+}
+
+fn legacy_day_file_in(dir: &Path, date: NaiveDate) -> PathBuf {
+    dir.join(format!("{}.ron", date.format("%F")))
+}
+
+/// Like `get_save_file_path` but for the user's preferences. Goes in the
+/// config directory.
+fn get_settings_file_path(dirs: &Dirs) -> PathBuf {
+    dirs.config().join("settings.ron")
+}
+
+fn save_log(dirs: &Dirs, today: &[TimeLog]) -> error::Result<PathBuf> {
+    save_log_for(dirs, Local::now().date_naive(), today)
+}
+
+/// Like `save_log` but for an arbitrary date. Returns where it went.
+fn save_log_for(dirs: &Dirs, date: NaiveDate, logs: &[TimeLog]) -> error::Result<PathBuf> {
+    let filename = save_day(dirs, date, logs)?;
+    backup::back_up(&filename);
+
+    Ok(filename)
+}
+
+/// Saves a day's log where it belongs, removing any copy of it left in the old
+/// flat layout, and returns where it went
+fn save_day(dirs: &Dirs, date: NaiveDate, logs: &[TimeLog]) -> error::Result<PathBuf> {
+    dirs.instance().check_writable()?;
+    let filename = get_save_file_path_for(dirs, date);
+    if let Some(month_dir) = filename.parent() {
+        fs::create_dir_all(month_dir)?;
+    }
+
+    save_log_file(&filename, logs)?;
+    let legacy = get_legacy_save_file_path_for(dirs, date);
+    match fs::remove_file(&legacy) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            warn!("Unable to remove {}: {}", legacy.display(), e);
+        }
+        _ => {}
+    }
+    Ok(filename)
+}
+
+/// Loads a day's log from wherever it is, in its month's subdirectory or in
+/// the old flat layout, and returns where it came from
+fn load_day(dirs: &Dirs, date: NaiveDate) -> error::Result<(PathBuf, Vec<TimeLog>)> {
+    let filename = get_save_file_path_for(dirs, date);
+    match load_log_file(dirs, &filename) {
+        Err(e) if e.is_not_found() => {
+            let legacy = get_legacy_save_file_path_for(dirs, date);
+            load_log_file(dirs, &legacy).map(|logs| (legacy, logs))
+        }
+        r => r.map(|logs| (filename, logs)),
+    }
+}
+
+fn save_log_file(filename: &Path, logs: &[TimeLog]) -> error::Result<()> {
+    info!("Saving log to {}", filename.display());
+    let contents = migrate::to_ron(logs, true)?;
+    Ok(crypto::write(filename, contents.into_bytes())?)
+}
+
+/// Reads the log in `filename` as it is, sorted by start time
+fn read_log_file(filename: &Path) -> error::Result<Vec<TimeLog>> {
+    info!("Loading log from {}", filename.display());
+    let contents = crypto::read(filename)?;
+    let mut tl_vec: Vec<TimeLog> = migrate::from_ron(&contents).map_err(|e| e.in_file(filename))?;
+
+    tl_vec.sort_unstable_by_key(|tl| tl.start);
+
+    Ok(tl_vec)
+}
+
+/// Like `read_log_file`, but salvages what it can of a corrupt log (see the
+/// recovery module) rather than leave the day unreadable, or to be saved over,
+/// once the app has turned recovery on for `dirs`
+fn load_log_file(dirs: &Dirs, filename: &Path) -> error::Result<Vec<TimeLog>> {
+    let date = filename
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| NaiveDate::parse_from_str(s, "%F").ok());
+    match (read_log_file(filename), date) {
+        (Err(e @ YdncError::Corrupt { .. }), Some(date)) if recovery::is_enabled(dirs) => {
+            warn!("{}", e);
+            Ok(recovery::recover(dirs, filename, date)?)
+        }
+        (r, _) => r,
+    }
+}
+
+/// Loads today's log, noting it in `known` as what's on disk
+fn load_log(dirs: &Dirs, known: &mut watch::KnownFiles) -> error::Result<Vec<TimeLog>> {
+    let (filename, today) = load_day(dirs, Local::now().date_naive())?;
+    known.record(watch::mtime(&filename), &today);
+    Ok(today)
+}
+
+/// Like `load_log` but for an arbitrary date, which may have been archived
+fn load_log_for(dirs: &Dirs, date: NaiveDate) -> error::Result<Vec<TimeLog>> {
+    match load_day(dirs, date) {
+        Err(e) if e.is_not_found() => archive::load_archived_day(dirs.data(), date),
+        r => r.map(|(_, logs)| logs),
+    }
+}
+
+fn save_prefs(dirs: &Dirs, prefs: &Preferences) -> error::Result<()> {
+    dirs.instance().check_writable()?;
+    let filename = get_settings_file_path(dirs);
+
+    info!("Saving prefs to {}", filename.display());
+    let contents = migrate::to_ron(prefs, true)?;
+    crypto::write(&filename, contents.into_bytes())?;
+
+    Ok(())
+}
+
+fn load_prefs(dirs: &Dirs) -> error::Result<Preferences> {
+    let filename = get_settings_file_path(dirs);
+
+    info!("Loading prefs from {}", filename.display());
+    let contents = crypto::read(&filename)?;
+    let mut prefs: Preferences = migrate::from_ron(&contents).map_err(|e| e.in_file(&filename))?;
+
+    // Locking the screen would start a task that doesn't exist
+    if let Some(n) = prefs.lock_break_task.filter(|n| !(1..=8).contains(n)) {
+        warn!("Ignoring lock_break_task {}, tasks go from 1 to 8", n);
+        prefs.lock_break_task = None;
+    }
+
+    Ok(prefs)
+}
+
+/// Sets the passphrase that save files are encrypted with, then checks it
+/// against the files we already have so that a typo doesn't leave us unable to
+/// read (and later overwriting) them. On failure, encryption is turned back off.
+#[cfg(feature = "encryption")]
+pub fn unlock(dirs: &Dirs, passphrase: String) -> io::Result<()> {
+    crypto::set_passphrase(Some(passphrase))?;
+    let today = Local::now().date_naive();
+    for filename in [
+        get_settings_file_path(dirs),
+        get_save_file_path(dirs),
+        get_legacy_save_file_path_for(dirs, today),
+    ]
+    .into_iter()
+    .filter(|f| f.exists())
+    {
+        if let Err(err) = crypto::read(&filename) {
+            crypto::set_passphrase(None)?;
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the settings or today's log are encrypted, in which case starting
+/// without a passphrase would have them saved over unencrypted
+#[cfg(feature = "encryption")]
+pub fn needs_passphrase(dirs: &Dirs) -> bool {
+    let today = Local::now().date_naive();
+    [
+        get_settings_file_path(dirs),
+        get_save_file_path(dirs),
+        get_legacy_save_file_path_for(dirs, today),
+    ]
+    .into_iter()
+    .any(|f| crypto::is_encrypted(&f))
+}
+
+/// Exports the entries picked out by command line options like `--task 3
+/// --tag clientA --min-duration 10m` (see `export::EntriesExport::parse`) to
+/// the exports directory, returning the path of the file it wrote
+pub fn export_entries(dirs: &Dirs, args: impl IntoIterator<Item = String>) -> io::Result<PathBuf> {
+    let export = export::EntriesExport::parse(args, Local::now().date_naive())?;
+    let prefs = match load_prefs(dirs) {
+        Ok(prefs) => prefs,
+        Err(e) if e.is_not_found() => Preferences::default(),
+        Err(e) => return Err(e.into()),
+    };
+    let logs = stats::load_dated_logs(dirs, export.min_date, Some(export.max_date))?;
+    export::export_entries(dirs, &export, &logs, &prefs)
+}
+
+/// Starts the task mapped to the current directory, per command line options
+/// like `--map ~/src/clientA=3`, in the running app (see the auto module).
+/// Returns a shell hook to print instead if asked for one with `--hook`.
+#[cfg(all(feature = "control-socket", unix))]
+pub fn auto_start(
+    dirs: &Dirs,
+    args: impl IntoIterator<Item = String>,
+) -> io::Result<Option<String>> {
+    auto::run(dirs, args)
+}
+
+/// Adds the task being tracked and how long it's been going to a commit
+/// message, for git's prepare-commit-msg hook, which passes the message file
+/// first. Merges and squashes are left alone, as are all commits while nothing
+/// is being tracked.
+pub fn annotate_commit(dirs: &Dirs, args: impl IntoIterator<Item = String>) -> io::Result<()> {
+    let mut args = args.into_iter();
+    let file = args.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Expected the commit message file",
+        )
+    })?;
+    if matches!(args.next().as_deref(), Some("merge" | "squash")) {
+        return Ok(());
+    }
+
+    let today = match load_day(dirs, Local::now().date_naive()) {
+        Ok((_, today)) => today,
+        Err(e) if e.is_not_found() => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let prefs = match load_prefs(dirs) {
+        Ok(prefs) => prefs,
+        Err(e) if e.is_not_found() => Preferences::default(),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(entry) = prefs.open_entry(&today) else {
+        return Ok(());
+    };
+
+    let elapsed = durations::elapsed(entry, Some(Local::now()));
+    let line = format!(
+        "Tracked: {} ({})",
+        entry.resolve_label(prefs.labels.as_ref()),
+        ui::utils::format_mins(elapsed.num_minutes() as u32)
+    );
+    let message = fs::read_to_string(&file)?;
+    fs::write(&file, utils::annotate_message(&message, &line))
+}
+
+/// Sends today's log off to be saved whenever it has settled after being
+/// marked dirty, and past days' logs that still need saving
+fn spawn_saver(app_state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(500));
+        loop {
+            interval.tick().await;
+            app_state.lock().unwrap().save_if_settled(Local::now());
+        }
+    })
+}
+
+/// Responds to a keypress on whichever page is open. Returns Some once the
+/// user has confirmed quitting, with whether to close the open entry on the
+/// way out.
+fn handle_key(app: &mut App, key: KeyEvent) -> error::Result<Option<bool>> {
+    let open_num = app.open_entry_number();
+    let last_log_idx = if app.today.is_empty() {
+        0
+    } else {
+        app.today.len() - 1
+    };
+    let entry_count = app.today.len();
+
+    let App {
+        ref dirs,
+        ref mut selected_page,
+        ref preferences,
+        ref today,
+        ..
+    } = *app;
+
+    match selected_page {
+        // Ctrl-s saves from anywhere
+        _ if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.save_today_log(true, Local::now());
+        }
+        ui::Page::Home(state_type) => {
+            if let ui::home::State::QuickStart { ref mut input } = state_type {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Enter => {
+                        if let Some((number, tags, timebox)) = parse_quick_start(input) {
+                            app.start_tagged_entry(number, tags, timebox);
+                            app.selected_page = ui::Page::Home(Default::default());
+                        } else {
+                            app.notify(
+                                "Start with a task number 1-8, then any #tags or a timebox like 30m",
+                            );
+                        }
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    _ => {}
+                }
+            } else if let ui::home::State::GoTo { ref mut input } = state_type {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Enter => match input.parse::<u8>() {
+                        Ok(number @ 1..=8) => {
+                            app.selected_page = ui::Page::Home(Default::default());
+                            app.start_chosen(number);
+                        }
+                        _ => app.notify("There's no such task, tasks go from 1 to 8"),
+                    },
+                    KeyCode::Char(c) if c.is_ascii_digit() && input.len() < 3 => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    _ => {}
+                }
+            } else if let ui::home::State::NamingTask {
+                number,
+                ref mut input,
+            } = state_type
+            {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Enter if !input.trim().is_empty() => {
+                        let label = input.trim().to_string();
+                        let idx = (*number - 1) as usize;
+                        app.preferences.labels.get_or_insert_with(Default::default)[idx] = label;
+                        app.save_prefs();
+                        app.notify("Saved label");
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    _ => {}
+                }
+            } else if let ui::home::State::Palette {
+                ref mut input,
+                ref mut selected,
+            } = state_type
+            {
+                let matches = ui::home::palette_matches(preferences, input);
+                match key.code {
+                    KeyCode::Esc => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Enter => {
+                        if let Some(&(number, _)) = matches.get(*selected) {
+                            if !app.stop_if_toggled(number) {
+                                app.start_entry(number);
+                            }
+                            app.selected_page = ui::Page::Home(Default::default());
+                        }
+                    }
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Down => {
+                        *selected = (*selected + 1).min(matches.len().saturating_sub(1));
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        *selected = 0;
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        *selected = 0;
+                    }
+                    _ => {}
+                }
+            } else if matches!(state_type, ui::home::State::Summary(_)) {
+                if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                    app.selected_page = ui::Page::Home(Default::default());
+                }
+            } else if matches!(state_type, ui::home::State::ConfirmQuit) {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Char('y' | 'q') => return Ok(Some(true)),
+                    KeyCode::Char('k') => {
+                        return Ok(Some(false));
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    _ => {}
+                }
+            } else if let ui::home::State::Editing {
+                ref mut state,
+                ref mut cursor_pos,
+                ref mut delete_pending,
+                ref mut visual_anchor,
+                ref mut history,
+            } = state_type
+            {
+                if state.editing {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.editing = false;
+                            state.input = Default::default();
+                            *cursor_pos = 0;
+                            // Cancelling an insert takes its entry back out
+                            if let Some(before) = history.cancel_insert() {
+                                state.options = before;
+                                state.clamp_selection();
+                                app.today = state.options.clone();
+                                app.mark_dirty();
+                            }
+                        }
+                        KeyCode::Enter => {
+                            history.record(&state.options);
+                            let (edited_idx, new_val) = state.save_edit();
+                            *cursor_pos = 0;
+                            // Update actual value in today's timelog
+                            app.today[edited_idx] = new_val;
+                            app.mark_dirty();
+                        }
+                        KeyCode::Char(c @ '0'..='9') => {
+                            match cursor_pos {
+                                0 => {
+                                    state.input.number = c.to_digit(10).unwrap() as u8;
+                                    if *cursor_pos < 12 && !state.input.is_open() || *cursor_pos < 7
+                                    {
+                                        *cursor_pos += 1;
+                                    }
+                                }
+                                1..=6 => {
+                                    if let Some(new_dt) =
+                                        adjust_datetime_digit(&state.input.start, *cursor_pos, c)
+                                    {
+                                        state.input.start = new_dt;
+                                        if *cursor_pos < 12 && !state.input.is_open()
+                                            || *cursor_pos < 7
+                                        {
+                                            *cursor_pos += 1;
+                                        }
+                                    }
+                                }
+                                7..=12 => {
+                                    let dt = state.input.end.get_or_insert_with(Local::now);
+
+                                    if let Some(new_dt) =
+                                        adjust_datetime_digit(dt, *cursor_pos - 6, c)
+                                    {
+                                        state.input.end = Some(new_dt);
+                                        if *cursor_pos < 12 && !state.input.is_open()
+                                            || *cursor_pos < 7
+                                        {
+                                            *cursor_pos += 1;
+                                        }
+                                    }
+                                }
+                                _ => panic!(),
+                            };
+                        }
+                        KeyCode::Right => {
+                            // cursor positions will go:
+                            // [foo] from 00:00:00 to 00:00:00
+                            //  0         12 34 56    78 90 12
+                            if *cursor_pos < 12 && !state.input.is_open() || *cursor_pos < 7 {
+                                *cursor_pos += 1;
+                            }
+                        }
+                        KeyCode::Char(' ') => {
+                            if *cursor_pos < 12 && !state.input.is_open() || *cursor_pos < 7 {
+                                *cursor_pos += 1;
+                            } else if *cursor_pos == 7 && state.input.is_open() {
+                                state.input.end = Some(Local::now());
+                                *cursor_pos += 1;
+                            }
+                        }
+                        KeyCode::Left => {
+                            if *cursor_pos > 0 {
+                                *cursor_pos -= 1;
+                            }
+                        }
+                        KeyCode::Backspace => {
+                            if state.selected_is_last() {
+                                state.input.end = None;
+                            } else if *cursor_pos > 0 {
+                                *cursor_pos -= 1;
+                            } else {
+                                app.notify("Only the final entry can be ongoing")
+                            }
+                        }
+                        _ => {}
+                    }
+                } else if let Some(anchor) = *visual_anchor {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q' | 'V') => {
+                            *visual_anchor = None;
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => state.select_prev(),
+                        KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+                        KeyCode::Char(c @ '1'..='8') => {
+                            *visual_anchor = None;
+                            if let Some(idx) = state.list_state.selected() {
+                                let number = c.to_digit(10).unwrap() as u8;
+                                let (from, to) = (anchor.min(idx), anchor.max(idx));
+                                history.record(&state.options);
+                                for entry in &mut state.options[from..=to] {
+                                    entry.number = number;
+                                }
+                                let count = to - from + 1;
+                                let msg = format!(
+                                    "Moved {} {} to {}",
+                                    count,
+                                    if count == 1 { "entry" } else { "entries" },
+                                    get_pref_label(number, preferences.labels.as_ref())
+                                        .unwrap_or_else(|| number.to_string())
+                                );
+                                app.today = state.options.clone();
+                                app.mark_dirty();
+                                app.notify(msg);
+                            }
+                        }
+                        _ => {}
+                    }
+                } else {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            if *delete_pending {
+                                *delete_pending = false;
+                            } else {
+                                app.selected_page = ui::Page::Home(Default::default());
+                            }
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => {
+                            state.select_prev();
+                            *delete_pending = false;
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            state.select_next();
+                            *delete_pending = false;
+                        }
+                        KeyCode::Enter => {
+                            if !*delete_pending {
+                                state.start_editing(Some(last_log_idx));
+                            }
+                        }
+                        KeyCode::Char('i') => {
+                            if !*delete_pending {
+                                history.begin_insert(&state.options);
+                                let (new_idx, new_val) =
+                                    state.insert_at_selection_with(|maybe_prev| {
+                                        let start = maybe_prev
+                                            .as_ref()
+                                            .map_or_else(Local::now, |tl| tl.start);
+                                        TimeLog {
+                                            start: start - chrono::Duration::seconds(1),
+                                            end: Some(start),
+                                            number: maybe_prev.map_or(1, |tl| tl.number),
+                                            profile: preferences.profile.clone(),
+                                            ..Default::default()
+                                        }
+                                    });
+                                state.start_editing(Some(new_idx));
+                                app.today.insert(new_idx, new_val);
+                                app.mark_dirty();
+                            }
+                        }
+                        KeyCode::Char(c @ ('+' | '=' | '-')) => {
+                            if let Some(idx) = state.list_state.selected() {
+                                history.record(&state.options);
+                                let entry = &mut state.options[idx];
+                                let mins = entry.estimate_mins.unwrap_or(0);
+                                entry.estimate_mins = if c == '-' {
+                                    Some(mins.saturating_sub(5)).filter(|&m| m > 0)
+                                } else {
+                                    Some(mins + 5)
+                                };
+                                app.today[idx] = entry.clone();
+                                app.mark_dirty();
+                            }
+                        }
+                        KeyCode::Char('c') => {
+                            if let Some(idx) = state.list_state.selected() {
+                                history.record(&state.options);
+                                let entry = &mut state.options[idx];
+                                entry.color = ui::next_entry_color(entry.color);
+                                app.today[idx] = entry.clone();
+                                app.mark_dirty();
+                            }
+                        }
+                        KeyCode::Char('d') => *delete_pending = true,
+                        KeyCode::Char('V') => {
+                            *delete_pending = false;
+                            if !state.options.is_empty() {
+                                if state.list_state.selected().is_none() {
+                                    state.list_state.select(Some(last_log_idx));
+                                }
+                                *visual_anchor = state.list_state.selected();
+                            }
+                        }
+                        KeyCode::Char('x') => {
+                            if *delete_pending {
+                                *delete_pending = false;
+                                history.record(&state.options);
+                                if let Some(deleted_idx) = state.delete_selected() {
+                                    let deleted = app.today.remove(deleted_idx);
+                                    app.mark_dirty();
+                                    app.trash.put(deleted);
+                                    app.save_trash();
+                                    app.notify("Moved to trash, R restores it");
+                                }
+                            }
+                        }
+                        KeyCode::Char('R') => {
+                            *delete_pending = false;
+                            match app.trash.take_last() {
+                                Some(entry) => {
+                                    // Undoing the delete may have
+                                    // put it back already
+                                    let restored = !app.today.iter().any(|tl| {
+                                        tl.start == entry.start
+                                            && tl.end == entry.end
+                                            && tl.number == entry.number
+                                    });
+                                    if restored {
+                                        app.today.push(entry);
+                                        app.today.sort_by_key(|tl| tl.start);
+                                        app.mark_dirty();
+                                    }
+                                    app.save_trash();
+                                    let left = app.trash.len();
+                                    app.notify(format!("Restored from trash ({} left)", left));
+                                }
+                                None => app.notify("The trash is empty"),
+                            }
+                            // The editor works on its own copy
+                            let today = app.today.clone();
+                            if let ui::Page::Home(ui::home::State::Editing { state, .. }) =
+                                &mut app.selected_page
+                            {
+                                state.options = today;
+                                state.clamp_selection();
+                            }
+                        }
+                        KeyCode::Char('u') => {
+                            *delete_pending = false;
+                            if history.undo(&mut state.options) {
+                                state.clamp_selection();
+                                app.today = state.options.clone();
+                                app.mark_dirty();
+                            } else {
+                                app.notify("Nothing to undo");
+                            }
+                        }
+                        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            *delete_pending = false;
+                            if history.redo(&mut state.options) {
+                                state.clamp_selection();
+                                app.today = state.options.clone();
+                                app.mark_dirty();
+                            } else {
+                                app.notify("Nothing to redo");
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            } else if let ui::home::State::Viewing { ref mut list_state } = state_type {
+                let selected = list_state.selected();
+                match key.code {
+                    KeyCode::Char('q') => {
+                        app.selected_page = ui::Page::Home(ui::home::State::ConfirmQuit);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        ui::home::move_selection(list_state, entry_count, true);
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        ui::home::move_selection(list_state, entry_count, false);
+                    }
+                    // Esc lets go of the selection before it stops
+                    // tracking
+                    KeyCode::Esc if selected.is_some() => {
+                        list_state.select(None);
+                    }
+                    KeyCode::Char('/') => {
+                        app.selected_page = ui::Page::Home(ui::home::State::Palette {
+                            input: String::new(),
+                            selected: 0,
+                        })
+                    }
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.selected_page = ui::Page::Home(ui::home::State::Palette {
+                            input: String::new(),
+                            selected: 0,
+                        })
+                    }
+                    // Alt+1-8 backdates too, for layouts where Shift+digit
+                    // doesn't give the US symbols
+                    KeyCode::Char(c)
+                        if ('1'..='8').contains(&c)
+                            && key.modifiers.contains(KeyModifiers::ALT) =>
+                    {
+                        app.start_backdated(c.to_digit(10).unwrap() as u8);
+                    }
+                    // Number keys 1-8 start tracking a new entry (not
+                    // 9, 9 does nothing. The tracker only has 8 sides
+                    // and I wanna be consistent)
+                    KeyCode::Char(c) if ('1'..='8').contains(&c) => {
+                        app.start_chosen(c.to_digit(10).unwrap() as u8);
+                    }
+                    // Shift+1-8 backdates the new entry
+                    KeyCode::Char(c) if ui::home::shifted_digit(c).is_some() => {
+                        app.start_backdated(ui::home::shifted_digit(c).unwrap());
+                    }
+                    // G then digits then Enter starts any task by number, for
+                    // when there's more than the number keys can reach. It's
+                    // G rather than g because g already opens Gaps.
+                    KeyCode::Char('G') => {
+                        app.selected_page = ui::Page::Home(ui::home::State::GoTo {
+                            input: String::new(),
+                        })
+                    }
+                    // 0 and Esc stop tracking
+                    KeyCode::Char('0') | KeyCode::Esc => {
+                        app.close_entry_if_open(Local::now());
+                    }
+                    KeyCode::Char('e') => {
+                        app.selected_page =
+                            ui::Page::Home(ui::home::State::editable(app.today.clone(), selected))
+                    }
+                    KeyCode::Char('t') => {
+                        app.selected_page = ui::Page::Home(ui::home::State::QuickStart {
+                            input: String::new(),
+                        })
+                    }
+                    // Enter edits the selected entry, if there is
+                    // one, rather than answering a prompt
+                    KeyCode::Enter => match selected {
+                        Some(i) => {
+                            app.selected_page = ui::Page::Home(ui::home::State::editing_entry(
+                                app.today.clone(),
+                                i,
+                            ));
+                        }
+                        None => app.accept_prompt(),
+                    },
+                    KeyCode::Char('b') => app.toggle_break_from_open(),
+                    KeyCode::Char('r') => app.resume_recent(),
+                    KeyCode::Char('c') => app.toggle_comparison(),
+                    KeyCode::Char('y') => app.duplicate_yesterday(),
+                    KeyCode::Char('[') => app.shift_comparison(-1),
+                    KeyCode::Char(']') => app.shift_comparison(1),
+                    KeyCode::Char('+') | KeyCode::Char('=') => app.zoom = app.zoom.zoomed_in(),
+                    KeyCode::Char('-') => app.zoom = app.zoom.zoomed_out(),
+                    KeyCode::Char('g') => {
+                        app.selected_page = ui::Page::Gaps(ui::gaps::State::load(app));
+                    }
+                    KeyCode::Char('w') => {
+                        app.selected_page = ui::Page::Week(ui::week::State::load_this_week(app)?);
+                    }
+                    KeyCode::Char('T') => {
+                        app.selected_page =
+                            ui::Page::Templates(ui::templates::State::load(&app.dirs)?);
+                    }
+                    KeyCode::Char('H') => {
+                        app.selected_page = ui::Page::Heatmap(ui::heatmap::State::load_this_year(
+                            &app.dirs,
+                            &app.preferences,
+                        ));
+                    }
+                    KeyCode::Char('R') => {
+                        app.selected_page = ui::Page::Report(ui::report::State::load(
+                            &app.dirs,
+                            Default::default(),
+                        )?);
+                    }
+                    KeyCode::Char('p') => {
+                        app.selected_page =
+                            ui::Page::Plan(ui::plan::State::load_tomorrow(&app.dirs, &app.today)?);
+                    }
+                    KeyCode::Char('h') => {
+                        app.selected_page = ui::Page::Stats(
+                            ui::stats::State::load_default_date_range(&app.dirs, &app.preferences),
+                        );
+                    }
+                    KeyCode::Char('s') => {
+                        // Labels are small, few, and easily cloned
+                        app.selected_page = ui::Page::Settings(ui::settings::State::new(
+                            app.preferences
+                                .labels
+                                .get_or_insert(Default::default())
+                                .to_vec(),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        ui::Page::Stats(ref mut state) => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.selected_page = ui::Page::Home(Default::default());
+            }
+            KeyCode::Right | KeyCode::Tab | KeyCode::Char('l') => {
+                state.select_next_date_range(dirs, preferences);
+            }
+            KeyCode::Left | KeyCode::BackTab | KeyCode::Char('h') => {
+                state.select_prev_date_range(dirs, preferences);
+            }
+            KeyCode::Down | KeyCode::Char('j') => state.select_next_task(),
+            KeyCode::Up | KeyCode::Char('k') => state.select_prev_task(),
+            KeyCode::Char('b') => state.toggle_billing(),
+            KeyCode::Char('o') => state.toggle_overtime(),
+            KeyCode::Char('c') => state.toggle_categories(),
+            KeyCode::Char('v') => state.toggle_compare(dirs, preferences),
+            KeyCode::Char('d') => state.toggle_expanded(),
+            KeyCode::Char('g') => state.toggle_bars(),
+            KeyCode::Char(c @ ('x' | 'm')) => {
+                let format = if c == 'x' {
+                    export::ExportFormat::Csv
+                } else {
+                    export::ExportFormat::Markdown
+                };
+                let msg = match state.export(dirs, preferences, format) {
+                    Ok(path) => format!("Exported stats to {}", path.display()),
+                    Err(e) => format!("Could not export stats: {}", e),
+                };
+                app.notify(msg);
+            }
+            _ => {}
+        },
+
+        ui::Page::Gaps(ref mut state) => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.selected_page = ui::Page::Home(Default::default());
+            }
+            KeyCode::Up | KeyCode::Char('k') => state.select_prev(),
+            KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+            KeyCode::Enter => {
+                if let Some((gap, number)) = state.take_selected(None) {
+                    if let Some(entry) = gap.to_entry(number) {
+                        app.insert_entry(entry);
+                        app.notify("Filled in gap");
+                    }
+                } else {
+                    app.notify("No suggestion for this gap, pick a task 1-8");
+                }
+            }
+            KeyCode::Char(c) if ('1'..='8').contains(&c) => {
+                let number = c.to_digit(10).map(|n| n as u8);
+                if let Some((gap, number)) = state.take_selected(number) {
+                    if let Some(entry) = gap.to_entry(number) {
+                        app.insert_entry(entry);
+                        app.notify("Filled in gap");
+                    }
+                }
+            }
+            _ => {}
+        },
+
+        ui::Page::Week(ref mut state) => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.selected_page = ui::Page::Home(Default::default());
+            }
+            KeyCode::Left | KeyCode::Char('h') => state.prev_week(dirs)?,
+            KeyCode::Right | KeyCode::Char('l') => state.next_week(dirs)?,
+            _ => {}
+        },
+
+        ui::Page::Heatmap(ref mut state) => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.selected_page = ui::Page::Home(Default::default());
+            }
+            KeyCode::Left | KeyCode::Char('h') => state.prev_year(dirs, preferences),
+            KeyCode::Right | KeyCode::Char('l') => state.next_year(dirs, preferences),
+            KeyCode::Char(c @ '1'..='8') => state.toggle_task(c as u8 - b'0'),
+            _ => {}
+        },
+
+        ui::Page::Templates(ref mut state) => {
+            if let Some(input) = state.naming.as_mut() {
+                match key.code {
+                    KeyCode::Esc => state.naming = None,
+                    KeyCode::Enter => {
+                        let msg = match state.save_named(dirs, today) {
+                            Ok(msg) => msg,
+                            Err(e) => format!("Could not save templates: {}", e),
+                        };
+                        app.notify(msg);
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+            } else if state.delete_pending {
+                match key.code {
+                    KeyCode::Char('x') => match state.delete_selected(dirs) {
+                        Ok(Some(msg)) => app.notify(msg),
+                        Ok(None) => {}
+                        Err(e) => app.notify(format!("Could not save templates: {}", e)),
+                    },
+                    _ => state.delete_pending = false,
+                }
+            } else {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => state.select_prev(),
+                    KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+                    KeyCode::Char('n') => state.naming = Some(String::new()),
+                    KeyCode::Char('d') if state.selected().is_some() => {
+                        state.delete_pending = true;
+                    }
+                    KeyCode::Enter => {
+                        if let Some(template) = state.selected().cloned() {
+                            app.apply_template(&template);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        ui::Page::Report(ref mut state) => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.selected_page = ui::Page::Home(Default::default());
+            }
+            KeyCode::Left | KeyCode::Char('h') => state.scroll_left(),
+            KeyCode::Right | KeyCode::Char('l') => state.scroll_right(),
+            KeyCode::Tab => state.toggle_period(dirs)?,
+            _ => {}
+        },
+
+        ui::Page::Plan(ref mut state) => {
+            let blocks = &mut state.blocks;
+            if blocks.editing {
+                match key.code {
+                    KeyCode::Esc => {
+                        blocks.editing = false;
+                        blocks.input = String::new();
+                    }
+                    KeyCode::Enter => match blocks.input.parse::<plan::PlannedBlock>() {
+                        Ok(block) => {
+                            blocks.save_edit();
+                            state.resort(block);
+                            let (date, planned) = (state.date, state.planned_blocks());
+                            app.save_plan(date, planned);
+                        }
+                        Err(e) => app.notify(e),
+                    },
+                    KeyCode::Char(c) => blocks.input.push(c),
+                    KeyCode::Backspace => {
+                        blocks.input.pop();
+                    }
+                    _ => {}
+                }
+            } else {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        if state.delete_pending {
+                            state.delete_pending = false;
+                        } else {
+                            app.selected_page = ui::Page::Home(Default::default());
+                        }
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => {
+                        let date = state.date - Days::new(1);
+                        app.selected_page =
+                            ui::Page::Plan(ui::plan::State::load(&app.dirs, date, &app.today)?);
+                    }
+                    KeyCode::Right | KeyCode::Char('l') => {
+                        let date = state.date + Days::new(1);
+                        app.selected_page =
+                            ui::Page::Plan(ui::plan::State::load(&app.dirs, date, &app.today)?);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        if !blocks.options.is_empty() {
+                            blocks.select_prev();
+                        }
+                        state.delete_pending = false;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        if !blocks.options.is_empty() {
+                            blocks.select_next();
+                        }
+                        state.delete_pending = false;
+                    }
+                    KeyCode::Enter if !state.delete_pending && !blocks.options.is_empty() => {
+                        blocks.start_editing(None);
+                    }
+                    KeyCode::Char('i') if !state.delete_pending => {
+                        let (new_idx, _) =
+                            blocks.insert_at_selection_with(ui::plan::State::new_block_after);
+                        blocks.list_state.select(Some(new_idx));
+                        blocks.start_editing(Some(new_idx));
+                    }
+                    KeyCode::Char('d') => state.delete_pending = true,
+                    KeyCode::Char('x') if state.delete_pending => {
+                        state.delete_pending = false;
+                        if blocks.delete_selected().is_some() {
+                            let (date, planned) = (state.date, state.planned_blocks());
+                            app.save_plan(date, planned);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        ui::Page::Settings(ref mut settings) => {
+            let state = &mut settings.labels;
+            if let Some(picker) = settings.bundle_picker.as_ref() {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        let original = picker.original.clone();
+                        settings.bundle_picker = None;
+                        original.apply_to(&mut app.preferences);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        if let Some(b) = settings.move_bundle_selection(-1) {
+                            b.apply_to(&mut app.preferences);
+                        }
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        if let Some(b) = settings.move_bundle_selection(1) {
+                            b.apply_to(&mut app.preferences);
+                        }
+                    }
+                    KeyCode::Enter => {
+                        let chosen = picker
+                            .list_state
+                            .selected()
+                            .and_then(|i| picker.bundles.get(i))
+                            .map(|b| b.name.clone());
+                        settings.bundle_picker = None;
+                        if let Some(name) = chosen {
+                            app.save_prefs();
+                            app.notify(format!("Applied {} theme bundle", name));
+                        }
+                    }
+                    _ => {}
+                }
+            } else if settings.color_picker.is_some() {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        settings.color_picker = None;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => settings.select_prev_color(),
+                    KeyCode::Down | KeyCode::Char('j') => settings.select_next_color(),
+                    KeyCode::Enter => {
+                        if let Some((slot, color)) = settings.save_color() {
+                            let colors = app
+                                .preferences
+                                .colors
+                                .get_or_insert_with(ui::default_task_colors);
+                            colors[slot] = color;
+                            app.save_prefs();
+                        }
+                    }
+                    _ => {}
+                }
+            } else if state.editing {
+                match key.code {
+                    KeyCode::Esc => {
+                        state.editing = false;
+                        state.input = String::new();
+                    }
+                    KeyCode::Enter => {
+                        let (edited_idx, new_val) = state.save_edit();
+
+                        // Update actual value in app prefs
+                        let labels = app.preferences.labels.get_or_insert(Default::default());
+                        labels[edited_idx] = new_val;
+                        app.save_prefs();
+                    }
+                    KeyCode::Char(c) => state.input.push(if state.caps_lock {
+                        c.to_ascii_uppercase()
+                    } else {
+                        c
+                    }),
+                    KeyCode::Backspace => {
+                        state.input.pop();
+                    }
+                    KeyCode::CapsLock => state.caps_lock = !state.caps_lock,
+                    _ => {}
+                }
+            } else if let Some(input) = settings.form.input.as_mut() {
+                let mut prefs = preferences.clone();
+                let msg = match key.code {
+                    KeyCode::Esc => {
+                        settings.form.cancel_input();
+                        None
+                    }
+                    KeyCode::Enter => settings.form.submit(&app.dirs, &mut prefs),
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        None
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(msg) = msg {
+                    app.save_changed_prefs(prefs);
+                    app.notify(msg);
+                }
+            } else if settings.section != ui::settings::Section::Labels {
+                let mut prefs = preferences.clone();
+                let msg = match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                        None
+                    }
+                    KeyCode::Tab => {
+                        settings.switch_section(true);
+                        None
+                    }
+                    KeyCode::BackTab => {
+                        settings.switch_section(false);
+                        None
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        settings.form.move_selection(-1);
+                        None
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        settings.form.move_selection(1);
+                        None
+                    }
+                    KeyCode::Right | KeyCode::Char('l') => {
+                        settings.form.step(&app.dirs, &mut prefs, true)
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => {
+                        settings.form.step(&app.dirs, &mut prefs, false)
+                    }
+                    KeyCode::Enter => settings.form.activate(&app.dirs, &mut prefs),
+                    _ => None,
+                };
+                if let Some(msg) = msg {
+                    app.save_changed_prefs(prefs);
+                    app.notify(msg);
+                }
+            } else {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.selected_page = ui::Page::Home(Default::default());
+                    }
+                    KeyCode::Tab => settings.switch_section(true),
+                    KeyCode::BackTab => settings.switch_section(false),
+                    KeyCode::Up | KeyCode::Char('k') => state.select_prev(),
+                    KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+                    KeyCode::Enter => state.start_editing(open_num.map(|n| (n - 1).into())),
+                    KeyCode::Char('c') => settings.open_color_picker(
+                        open_num.map(|n| (n - 1).into()),
+                        &preferences.colors.unwrap_or_else(ui::default_task_colors),
+                    ),
+                    KeyCode::Char('t') => {
+                        let theme = preferences.theme.unwrap_or_default().next();
+                        app.preferences.theme = Some(theme);
+                        app.save_prefs();
+                        app.notify(format!("Switched to {} theme", theme.name()));
+                    }
+                    KeyCode::Char('f') => {
+                        let patterns = !preferences.patterns.unwrap_or(false);
+                        app.preferences.patterns = Some(patterns);
+                        app.save_prefs();
+                        let msg = if ui::patterns_enabled(&app.preferences) {
+                            "Fill patterns on"
+                        } else {
+                            "Fill patterns off"
+                        };
+                        app.notify(msg);
+                    }
+                    KeyCode::Char('b') => settings.open_bundle_picker(
+                        bundle::load_bundles(&app.dirs),
+                        bundle::ThemeBundle::from_prefs("", preferences),
+                    ),
+                    KeyCode::Char('e') => {
+                        let msg = match bundle::export(&app.dirs, preferences) {
+                            Ok(path) => {
+                                format!("Exported theme bundle to {}", path.display())
+                            }
+                            Err(e) => {
+                                format!("Could not export theme bundle: {}", e)
+                            }
+                        };
+                        app.notify(msg);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Hands the terminal over to $VISUAL or $EDITOR, or vi failing both, to edit
+/// `path`, and takes it back once the user's done
+fn edit_externally<B: Backend>(
+    terminal: &mut Terminal<B>,
+    plain: bool,
+    path: &Path,
+) -> io::Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".into());
+    // It can come with arguments of its own, like "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    terminal::disable_raw_mode()?;
+    if !plain {
+        execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    }
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status();
+    terminal::enable_raw_mode()?;
+    if !plain {
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    }
+    terminal.clear()?;
+
+    let status = status?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            editor, status
+        )));
+    }
+    Ok(())
+}
+
+/// How long the system has been up, counting the time it spent asleep, where
+/// the OS says (Linux)
+fn uptime() -> Option<Duration> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Whether the system was asleep for a while, given how far the monotonic
+/// clock, which stops while asleep, got in the same time as the time since
+/// boot, which doesn't. Without the time since boot it goes by the wall
+/// clock, which also jumps when the clock is set, so that's only the
+/// fallback.
+fn was_asleep(monotonic: Duration, uptime: Option<Duration>, wall: Duration) -> bool {
+    let gap = Duration::from_secs(SLEEP_GAP_SECS);
+    uptime.unwrap_or(wall) > monotonic + gap
 }
 
 pub async fn run<B: Backend>(app_state: AppState, terminal: &mut Terminal<B>) -> io::Result<()> {
-    let mut i: usize = 0;
+    let mut last_autosave = std::time::Instant::now();
+    {
+        let mut app = app_state.lock().unwrap();
+        app.saver = Some(saver::Saver::start(app.dirs.clone(), app.sync.clone()));
+    }
+    let save_ticker = spawn_saver(app_state.clone());
+    // Not being able to watch isn't fatal, we just won't notice external edits
+    let dirs = app_state.lock().unwrap().dirs.clone();
+    let watcher = watch::SaveFileWatcher::start(dirs.data())
+        .map_err(|err| warn!("Could not watch save files: {}", err))
+        .ok();
+    let settings_watcher = watch::SaveFileWatcher::start_settings(dirs.config())
+        .map_err(|err| warn!("Could not watch the settings file: {}", err))
+        .ok();
+    // Whether to close the open entry on the way out, which the user can opt
+    // out of when confirming that they want to quit
+    let close_open_entry;
+    // A log the user asked to check in $EDITOR, opened once the app's
+    // released
+    let mut edit_file = None;
+    let mut last_tick = (std::time::Instant::now(), Local::now(), uptime());
     loop {
         // Lock on app state to draw the UI
         {
             let mut app = app_state.lock().unwrap();
             terminal.draw(|f| ui::draw(f, &mut app))?;
+            app.update_title(Local::now());
         }
         // Once drawn, release lock so other threads (like the bluetooth ones)
         // can read+write app state between frames
@@ -362,293 +3595,58 @@ pub async fn run<B: Backend>(app_state: AppState, terminal: &mut Terminal<B>) ->
                     // keypresses, but the processing time is quite fast.
                     let mut app = app_state.lock().unwrap();
 
-                    let open_num = app.open_entry_number();
-                    let last_log_idx = if app.today.is_empty() {
-                        0
+                    // Failing to load or save in response to a key shouldn't
+                    // take the whole app down, so it's shown until dismissed
+                    if let Some(popup) = app.error_popup.take() {
+                        match key.code {
+                            KeyCode::Enter | KeyCode::Esc => {}
+                            KeyCode::Char('e') if popup.edit_today => {
+                                app.selected_page = ui::Page::Home(ui::home::State::editable(
+                                    app.today.clone(),
+                                    None,
+                                ));
+                            }
+                            KeyCode::Char('e') if popup.edit_file.is_some() => {
+                                edit_file = popup.edit_file;
+                            }
+                            _ => app.error_popup = Some(popup),
+                        }
                     } else {
-                        app.today.len() - 1
-                    };
-
-                    let App {
-                        ref mut selected_page,
-                        ref preferences,
-                        ..
-                    } = *app;
-
-                    match selected_page {
-                        ui::Page::Home(state_type) => {
-                            if let ui::home::State::Editing {
-                                ref mut state,
-                                ref mut cursor_pos,
-                                ref mut delete_pending,
-                            } = state_type
-                            {
-                                if state.editing {
-                                    match key.code {
-                                        KeyCode::Esc => {
-                                            state.editing = false;
-                                            state.input = Default::default();
-                                            *cursor_pos = 0;
-                                        }
-                                        KeyCode::Enter => {
-                                            let (edited_idx, new_val) = state.save_edit();
-                                            *cursor_pos = 0;
-                                            // Update actual value in today's timelog
-                                            app.today[edited_idx] = new_val;
-                                            save_log(&app.today)?;
-                                        }
-                                        KeyCode::Char(c @ '0'..='9') => {
-                                            match cursor_pos {
-                                                0 => {
-                                                    state.input.number =
-                                                        c.to_digit(10).unwrap() as u8;
-                                                    if *cursor_pos < 12 && !state.input.is_open()
-                                                        || *cursor_pos < 7
-                                                    {
-                                                        *cursor_pos += 1;
-                                                    }
-                                                }
-                                                1..=6 => {
-                                                    if let Some(new_dt) = adjust_datetime_digit(
-                                                        &state.input.start,
-                                                        *cursor_pos,
-                                                        c,
-                                                    ) {
-                                                        state.input.start = new_dt;
-                                                        if *cursor_pos < 12
-                                                            && !state.input.is_open()
-                                                            || *cursor_pos < 7
-                                                        {
-                                                            *cursor_pos += 1;
-                                                        }
-                                                    }
-                                                }
-                                                7..=12 => {
-                                                    let dt = state
-                                                        .input
-                                                        .end
-                                                        .get_or_insert_with(Local::now);
-
-                                                    if let Some(new_dt) = adjust_datetime_digit(
-                                                        dt,
-                                                        *cursor_pos - 6,
-                                                        c,
-                                                    ) {
-                                                        state.input.end = Some(new_dt);
-                                                        if *cursor_pos < 12
-                                                            && !state.input.is_open()
-                                                            || *cursor_pos < 7
-                                                        {
-                                                            *cursor_pos += 1;
-                                                        }
-                                                    }
-                                                }
-                                                _ => panic!(),
-                                            };
-                                        }
-                                        KeyCode::Right => {
-                                            // cursor positions will go:
-                                            // [foo] from 00:00:00 to 00:00:00
-                                            //  0         12 34 56    78 90 12
-                                            if *cursor_pos < 12 && !state.input.is_open()
-                                                || *cursor_pos < 7
-                                            {
-                                                *cursor_pos += 1;
-                                            }
-                                        }
-                                        KeyCode::Char(' ') => {
-                                            if *cursor_pos < 12 && !state.input.is_open()
-                                                || *cursor_pos < 7
-                                            {
-                                                *cursor_pos += 1;
-                                            } else if *cursor_pos == 7 && state.input.is_open() {
-                                                state.input.end = Some(Local::now());
-                                                *cursor_pos += 1;
-                                            }
-                                        }
-                                        KeyCode::Left => {
-                                            if *cursor_pos > 0 {
-                                                *cursor_pos -= 1;
-                                            }
-                                        }
-                                        KeyCode::Backspace => {
-                                            if state.selected_is_last() {
-                                                state.input.end = None;
-                                            } else if *cursor_pos > 0 {
-                                                *cursor_pos -= 1;
-                                            } else {
-                                                app.message = Some(
-                                                    "Only the final entry can be ongoing".into(),
-                                                )
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                } else {
-                                    match key.code {
-                                        KeyCode::Esc | KeyCode::Char('q') => {
-                                            if *delete_pending {
-                                                *delete_pending = false;
-                                            } else {
-                                                app.selected_page =
-                                                    ui::Page::Home(ui::home::State::Viewing);
-                                            }
-                                        }
-                                        KeyCode::Up | KeyCode::Char('k') => {
-                                            state.select_prev();
-                                            *delete_pending = false;
-                                        }
-                                        KeyCode::Down | KeyCode::Char('j') => {
-                                            state.select_next();
-                                            *delete_pending = false;
-                                        }
-                                        KeyCode::Enter => {
-                                            if !*delete_pending {
-                                                state.start_editing(Some(last_log_idx));
-                                            }
-                                        }
-                                        KeyCode::Char('i') => {
-                                            if !*delete_pending {
-                                                let (new_idx, new_val) = state
-                                                    .insert_at_selection_with(|maybe_prev| {
-                                                        let start = maybe_prev
-                                                            .map_or_else(Local::now, |tl| tl.start);
-                                                        TimeLog {
-                                                            start: start
-                                                                - chrono::Duration::seconds(1),
-                                                            end: Some(start),
-                                                            number: maybe_prev
-                                                                .map_or(1, |tl| tl.number),
-                                                        }
-                                                    });
-                                                state.start_editing(Some(new_idx));
-                                                app.today.insert(new_idx, new_val);
-                                                save_log(&app.today)?;
-                                            }
-                                        }
-                                        KeyCode::Char('d') => *delete_pending = true,
-                                        KeyCode::Char('x') => {
-                                            if *delete_pending {
-                                                *delete_pending = false;
-                                                if let Some(deleted_idx) = state.delete_selected() {
-                                                    app.today.remove(deleted_idx);
-                                                    save_log(&app.today)?;
-                                                }
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            } else {
-                                match key.code {
-                                    KeyCode::Char('q') => {
-                                        break;
-                                    }
-                                    // Number keys 1-8 start tracking a new entry (not
-                                    // 9, 9 does nothing. The tracker only has 8 sides
-                                    // and I wanna be consistent)
-                                    KeyCode::Char(c) if ('1'..='8').contains(&c) => {
-                                        app.start_entry(c.to_digit(10).unwrap() as u8);
-                                    }
-                                    // 0 and Esc stop tracking
-                                    KeyCode::Char('0') | KeyCode::Esc => {
-                                        app.close_entry_if_open(Local::now());
-                                    }
-                                    KeyCode::Char('e') => {
-                                        app.selected_page = ui::Page::Home(
-                                            ui::home::State::editable(app.today.clone()),
-                                        )
-                                    }
-                                    KeyCode::Char('h') => {
-                                        app.selected_page = ui::Page::Stats(
-                                            ui::stats::State::load_default_date_range(
-                                                &app.preferences,
-                                            )?,
-                                        );
-                                    }
-                                    KeyCode::Char('s') => {
-                                        // Labels are small, few, and easily cloned
-                                        app.selected_page =
-                                            ui::Page::Settings(ui::settings::State::new(
-                                                app.preferences
-                                                    .labels
-                                                    .get_or_insert(Default::default())
-                                                    .to_vec(),
-                                            ));
-                                    }
-                                    _ => {}
-                                }
+                        let before = app.is_read_only().then(|| app.today.clone());
+                        match handle_key(&mut app, key) {
+                            Ok(Some(close)) => {
+                                close_open_entry = close;
+                                break;
                             }
+                            Ok(None) => {}
+                            Err(e) => app.show_error(e),
+                        }
+                        if let Some(before) = before {
+                            app.refuse_changes(before);
                         }
+                    }
+                }
 
-                        ui::Page::Stats(ref mut state) => match key.code {
-                            KeyCode::Esc | KeyCode::Char('q') => {
-                                app.selected_page = ui::Page::Home(Default::default());
-                            }
-                            KeyCode::Right
-                            | KeyCode::Down
-                            | KeyCode::Tab
-                            | KeyCode::Char('l')
-                            | KeyCode::Char('j') => {
-                                state.select_next_date_range(preferences)?;
-                            }
-                            KeyCode::Left
-                            | KeyCode::Up
-                            | KeyCode::BackTab
-                            | KeyCode::Char('h')
-                            | KeyCode::Char('k') => {
-                                state.select_prev_date_range(preferences)?;
-                            }
-                            _ => {}
-                        },
-
-                        ui::Page::Settings(ref mut state) => {
-                            if state.editing {
-                                match key.code {
-                                    KeyCode::Esc => {
-                                        state.editing = false;
-                                        state.input = String::new();
-                                    }
-                                    KeyCode::Enter => {
-                                        let (edited_idx, new_val) = state.save_edit();
-
-                                        // Update actual value in app prefs
-                                        let labels = app
-                                            .preferences
-                                            .labels
-                                            .get_or_insert(Default::default());
-                                        labels[edited_idx] = new_val;
-                                        save_prefs(&app.preferences)?;
-                                    }
-                                    KeyCode::Char(c) => state.input.push(if state.caps_lock {
-                                        c.to_ascii_uppercase()
-                                    } else {
-                                        c
-                                    }),
-                                    KeyCode::Backspace => {
-                                        state.input.pop();
-                                    }
-                                    KeyCode::CapsLock => state.caps_lock = !state.caps_lock,
-                                    _ => {}
-                                }
-                            } else {
-                                match key.code {
-                                    KeyCode::Esc | KeyCode::Char('q') => {
-                                        app.selected_page = ui::Page::Home(Default::default());
-                                    }
-                                    KeyCode::Up | KeyCode::Char('k') => state.select_prev(),
-                                    KeyCode::Down | KeyCode::Char('j') => state.select_next(),
-                                    KeyCode::Enter => {
-                                        state.start_editing(open_num.map(|n| (n - 1).into()))
-                                    }
-                                    _ => {}
-                                }
-                            }
+                Event::Mouse(mouse) => {
+                    let mut app = app_state.lock().unwrap();
+                    if app.error_popup.is_none() {
+                        let before = app.is_read_only().then(|| app.today.clone());
+                        if let Err(e) = app.handle_mouse(mouse) {
+                            app.show_error(e.into());
+                        }
+                        if let Some(before) = before {
+                            app.refuse_changes(before);
                         }
                     }
                 }
+                _ => {}
+            }
+        }
 
-                _ => {} // I've disabled mouse support in main.rs anyway
+        if let Some(path) = edit_file.take() {
+            let plain = app_state.lock().unwrap().plain;
+            if let Err(e) = edit_externally(terminal, plain, &path) {
+                app_state.lock().unwrap().show_error(e.into());
             }
         }
 
@@ -659,67 +3657,424 @@ pub async fn run<B: Backend>(app_state: AppState, terminal: &mut Terminal<B>) ->
         // refactor the loop to do more stuff after autosave/messaging then you
         // really oughta limit the scope of this lock more!
         let mut app = app_state.lock().unwrap();
-        // 300s = every 5 min do an autosave
-        if i == 300 {
-            i = 0;
-            app.message = Some("Autosaving...".into());
-
-            // Check if we have advanced into a new day
-            let its_a_new_day = app.today.first().map_or(false, |tl| {
-                tl.start.date_naive() != Local::now().date_naive()
-            });
 
-            // If so and we have an open entry:
-            let open_entry: Option<TimeLog> = if its_a_new_day && app.has_open_entry() {
-                let entry_ref = app.today.last_mut().unwrap();
-                // Copy it (pretty nice these TimeLog's impl Copy huh?)
-                let ret = Some(*entry_ref);
-                // Close it inside `app.today`, setting its end date to the end
-                // of yesterday
-                entry_ref.end = Some(
-                    // This is the latest representable DateTime on the same
-                    // calendar day
-                    datetime_with_zeroed_time(&(entry_ref.start + Days::new(1)))
-                        - chrono::Duration::nanoseconds(1),
-                );
-                ret
-            } else {
-                None
-            };
+        // The monotonic clock stops while the system sleeps but the time since
+        // boot doesn't, so this catches sleeps the suspend listener doesn't,
+        // like on systems without logind. Whatever was open is stopped as of
+        // the last time round before it.
+        let now = (std::time::Instant::now(), Local::now(), uptime());
+        let wall = (now.1 - last_tick.1).to_std().unwrap_or_default();
+        let up = now
+            .2
+            .zip(last_tick.2)
+            .map(|(now, last)| now.saturating_sub(last));
+        if was_asleep(now.0 - last_tick.0, up, wall) && !app.is_read_only() {
+            info!("Woke up from sleep, {:?} after {}", wall, last_tick.1);
+            app.on_suspend(last_tick.1);
+            app.offer_resume();
+        }
+        last_tick = now;
 
-            // Save today to file
-            save_log(&app.today)?;
+        // Checked every time round rather than at the next autosave, so that
+        // nothing after midnight gets saved into yesterday's file
+        app.roll_over_day(Local::now());
 
-            if its_a_new_day {
-                // Wipe app.today
-                app.today.clear();
+        // Take in the Stats and Heatmap pages' history once it's loaded in
+        // the background
+        let App {
+            selected_page,
+            preferences,
+            ..
+        } = &mut *app;
+        let loaded = match selected_page {
+            ui::Page::Stats(state) => state.poll_loading(preferences),
+            ui::Page::Heatmap(state) => state.poll_loading(),
+            _ => Ok(()),
+        };
+        if let Err(e) = loaded {
+            app.notify(format!("Could not load history: {}", e));
+        }
+        app.take_saved(Local::now());
+        app.take_notices();
+        for recovered in app.dirs.recoveries().take() {
+            app.show_recovered(recovered);
+        }
+        app.take_pulled();
+        app.take_fetched();
+        #[cfg(feature = "caldav")]
+        app.take_fetched_calendar();
+        #[cfg(feature = "slack")]
+        {
+            slack::show(app.slack_status());
+        }
 
-                // If we cloned a previously open entry:
-                if let Some(mut entry) = open_entry {
-                    // Set its start date to the beginning of today
-                    entry.start = datetime_with_zeroed_time(&Local::now());
-                    // Leave its `end` open and push it to the clean app.today
-                    app.today.push(entry);
+        // Pick up changes other programs made to today's file before we get a
+        // chance to overwrite them. Wait until the user is done editing
+        // entries though, since the editor has its own copy of the log, and
+        // until our own saves are done, since until then the file's mtime
+        // isn't known to be ours.
+        let editing = matches!(
+            app.selected_page,
+            ui::Page::Home(ui::home::State::Editing { .. })
+        ) || app.is_saving();
+        if let (Some(watcher), false) = (&watcher, editing) {
+            if watcher.take_external_change(&get_save_file_path(&dirs), app.known.mtime()) {
+                let base = app.known.logs().to_vec();
+                match load_log(&dirs, &mut app.known) {
+                    Ok(theirs) => {
+                        let (merged, clashing) = watch::merge(&base, &app.whole_day(), &theirs);
+                        // Anything of ours that was kept needs saving again
+                        if merged != theirs {
+                            app.mark_dirty();
+                        }
+                        app.set_whole_day(merged);
+                        if clashing.is_empty() {
+                            app.notify("Merged in changes made to today's log on disk");
+                        } else {
+                            let count = clashing.len();
+                            for entry in clashing {
+                                app.trash.put(entry);
+                            }
+                            app.save_trash();
+                            app.notify(format!(
+                                "Merged in changes made on disk, trashing {} clashing with ours",
+                                count
+                            ));
+                        }
+                    }
+                    Err(err) => {
+                        app.notify(format!("Could not reload today's log: {}", err));
+                    }
                 }
             }
-        } else {
-            i += 1;
-            if app.message.as_ref().map_or(false, |m| {
-                Local::now().signed_duration_since(m.1) > chrono::Duration::seconds(10)
-            }) {
-                app.message = None;
+        }
+
+        // Put settings edited in another program to use right away
+        if let Some(watcher) = &settings_watcher {
+            let path = get_settings_file_path(&dirs);
+            if watcher.take_external_change(&path, app.known.settings_mtime()) {
+                match load_prefs(&dirs) {
+                    Ok(prefs) => {
+                        app.known.record_settings(watch::mtime(&path));
+                        app.apply_prefs(prefs);
+                        app.notify("Reloaded settings changed on disk");
+                    }
+                    // Likely saved halfway through an edit, so keep the ones
+                    // we have until it's fixed
+                    Err(err) => app.notify(format!("Could not reload settings: {}", err)),
+                }
             }
         }
+
+        let autosave_every =
+            Duration::from_secs(u64::from(app.preferences.autosave_mins.unwrap_or(5).max(1)) * 60);
+        // Nothing below changes anything in a read-only copy of the app,
+        // which only waits to take over
+        if app.is_read_only() {
+            app.take_over_lock();
+            app.check_target_met(Local::now());
+            app.expire_messages(Local::now());
+        } else if last_autosave.elapsed() >= autosave_every {
+            last_autosave = std::time::Instant::now();
+            app.notify("Autosaving...");
+
+            // Save today to file
+            app.save_today(Local::now());
+            app.reload_calendar();
+            app.check_sources();
+            app.pull_sync();
+        } else {
+            app.check_planned_starts(Local::now());
+            app.check_calendar_starts(Local::now());
+            app.check_target_met(Local::now());
+            app.check_timebox(Local::now());
+            app.check_limits(Local::now());
+            app.check_nag(Local::now());
+            app.check_report(Local::now());
+            app.expire_messages(Local::now());
+        }
     }
 
-    // Exiting the loop means somebody pushed `q`, so let's save and quit
+    // Exiting the loop means somebody confirmed quitting, so let's save and quit
+    save_ticker.abort();
+    // Saves already sent go first, so that none of them lands on top of the
+    // last one below
+    let background = app_state.lock().unwrap().saver.take();
+    if let Some(background) = background {
+        let finished = background.finish().await;
+        let mut app = app_state.lock().unwrap();
+        for done in finished {
+            app.on_done(done, Local::now());
+        }
+    }
     let mut app = app_state.lock().unwrap();
-    app.close_entry_if_open(Local::now());
-    app.message = Some("Saving time log...".into());
+    if close_open_entry {
+        app.close_all_entries(Local::now());
+        app.end_break(Local::now());
+    }
+    if app.is_read_only() {
+        app.notify("Read-only, quitting without saving");
+    } else {
+        app.notify("Saving time log...");
+    }
     terminal.draw(|f| ui::draw(f, &mut app))?; // Draw the UI to show message
-    save_log(&app.today)?;
 
-    app.message = Some("Disconnecting Bluetooth and exiting...".into());
+    // Quitting anyway would lose everything since the last save, so give the
+    // user the chance to fix whatever's wrong (e.g. free up some space) first
+    while let Err(e) = app.save_before_quitting() {
+        warn!("Unable to save before quitting: {}", e);
+        app.error_popup = Some(ui::ErrorPopup {
+            message: format!("Could not save before quitting: {}", e),
+            keys: "Enter: try again | Esc: quit without saving",
+            edit_today: false,
+            edit_file: None,
+        });
+        terminal.draw(|f| ui::draw(f, &mut app))?;
+        // Let go of the app while waiting on the user, so that the tracker
+        // and the other inputs aren't stuck waiting on it meanwhile
+        drop(app);
+        let give_up = loop {
+            if let Event::Key(key) = event::read()? {
+                match key.code {
+                    KeyCode::Enter => break false,
+                    KeyCode::Esc => break true,
+                    _ => {}
+                }
+            }
+        };
+        app = app_state.lock().unwrap();
+        app.error_popup = None;
+        if give_up {
+            break;
+        }
+    }
+    backup::finish();
+    #[cfg(feature = "slack")]
+    {
+        slack::show(app.slack_status());
+        slack::finish();
+    }
+
+    app.notify("Disconnecting Bluetooth and exiting...");
     terminal.draw(|f| ui::draw(f, &mut app))?; // Draw the UI to show message
+
+    // Don't leave a stale timer in the title once the app is gone
+    if app.shown_title.is_some() {
+        execute!(io::stdout(), SetTitle(""))?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn start_backdated() {
+        let now = Local::now();
+        let ended = now - chrono::Duration::minutes(30);
+        let mut app = App {
+            today: vec![TimeLog::new(1, now - chrono::Duration::hours(1), Some(ended)).unwrap()],
+            ..Default::default()
+        };
+
+        // Without a backdate set, the new entry picks up where the last left off
+        app.start_backdated(2);
+        assert_eq!(app.today.len(), 2);
+        assert_eq!(app.today[1].number, 2);
+        assert_eq!(app.today[1].start, ended);
+        assert!(app.today[1].is_open());
+
+        // Starting what's already open changes nothing
+        app.start_backdated(2);
+        assert_eq!(app.today.len(), 2);
+
+        // With one, it goes back that far but never over the last entry
+        app.preferences.backdate_mins = Some(10);
+        app.start_backdated(3);
+        assert_eq!(app.today.len(), 3);
+        let start = app.today[2].start;
+        assert!(start >= now - chrono::Duration::minutes(10));
+        assert!(start <= Local::now() - chrono::Duration::minutes(10));
+        assert_eq!(app.today[1].end, Some(start));
+        assert!(app.today[2].is_open());
+    }
+
+    #[test]
+    fn roll_over_day() {
+        let dir = std::env::temp_dir().join(format!("ydnc-rollover-{}", std::process::id()));
+        let at = |d, h| Local.with_ymd_and_hms(2023, 6, d, h, 0, 0).unwrap();
+        let yesterday = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let mut app = App {
+            dirs: Dirs::in_dir(dir.clone()),
+            today: vec![
+                TimeLog::new(1, at(5, 10), Some(at(5, 11))).unwrap(),
+                TimeLog::new(2, at(5, 22), None).unwrap(),
+            ],
+            day: Some(yesterday),
+            ..Default::default()
+        };
+
+        assert!(!app.roll_over_day(at(5, 23)));
+        assert!(app.roll_over_day(at(6, 9)));
+        // What was open carries on from midnight...
+        assert_eq!(app.today.len(), 1);
+        assert_eq!(app.today[0].number, 2);
+        assert_eq!(app.today[0].start, at(6, 0));
+        assert!(app.today[0].is_open());
+        assert!(app.dirty_since.is_some());
+        // ...and ran until the end of yesterday in yesterday's log
+        let saved = load_log_for(&app.dirs, yesterday).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[1].end, Some(durations::end_of_day(&at(5, 22))));
+
+        // A day with nothing tracked still rolls over its breaks
+        app.today.clear();
+        app.today_breaks = vec![breaks::Break {
+            start: at(6, 23),
+            end: None,
+            number: 2,
+        }];
+        assert!(app.roll_over_day(at(7, 8)));
+        assert_eq!(app.day, NaiveDate::from_ymd_opt(2023, 6, 7));
+        assert!(app.today.is_empty());
+        assert_eq!(app.today_breaks.len(), 1);
+        assert_eq!(app.today_breaks[0].start, at(7, 0));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn roll_over_while_editing() {
+        let dir = std::env::temp_dir().join(format!("ydnc-rollover-edit-{}", std::process::id()));
+        let at = |d, h| Local.with_ymd_and_hms(2023, 6, d, h, 0, 0).unwrap();
+        let mut app = App {
+            dirs: Dirs::in_dir(dir.clone()),
+            today: vec![
+                TimeLog::new(1, at(5, 10), Some(at(5, 11))).unwrap(),
+                TimeLog::new(2, at(5, 22), None).unwrap(),
+            ],
+            day: NaiveDate::from_ymd_opt(2023, 6, 5),
+            ..Default::default()
+        };
+        app.selected_page = ui::Page::Home(ui::home::State::editing_entry(app.today.clone(), 1));
+
+        assert!(app.roll_over_day(at(6, 9)));
+        // The editor's indices were into yesterday's entries, so it's closed
+        // rather than left to save over today's
+        assert!(matches!(
+            app.selected_page,
+            ui::Page::Home(ui::home::State::Viewing { .. })
+        ));
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        assert!(handle_key(&mut app, enter).is_ok());
+        assert_eq!(app.today.len(), 1);
+        assert_eq!(app.today[0].start, at(6, 0));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn duplicate_yesterday() {
+        let dir = std::env::temp_dir().join(format!("ydnc-duplicate-{}", std::process::id()));
+        let today = Local::now().date_naive();
+        let yesterday = today.pred_opt().unwrap();
+        let at = |date: NaiveDate, secs| {
+            date.and_hms_opt(0, 0, secs)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let mut app = App {
+            dirs: Dirs::in_dir(dir.clone()),
+            ..Default::default()
+        };
+        save_log_for(
+            &app.dirs,
+            yesterday,
+            &[
+                TimeLog::new(3, at(yesterday, 0), Some(at(yesterday, 1))).unwrap(),
+                TimeLog {
+                    profile: Some(String::from("side")),
+                    ..TimeLog::new(4, at(yesterday, 1), Some(at(yesterday, 2))).unwrap()
+                },
+            ],
+        )
+        .unwrap();
+
+        app.duplicate_yesterday();
+        // Only the current profile's entries, at the same time of day today
+        assert_eq!(app.today.len(), 1);
+        assert_eq!(app.today[0].number, 3);
+        assert_eq!(app.today[0].start, at(today, 0));
+        assert_eq!(app.today[0].end, Some(at(today, 1)));
+        assert!(app.dirty_since.is_some());
+
+        // What's copied once isn't copied over itself again
+        app.duplicate_yesterday();
+        assert_eq!(app.today.len(), 1);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn other_profiles_kept_apart() {
+        let now = Local::now();
+        let entry = |mins, profile: Option<&str>| TimeLog {
+            start: now - chrono::Duration::minutes(mins),
+            end: Some(now - chrono::Duration::minutes(mins - 10)),
+            number: 1,
+            profile: profile.map(String::from),
+            ..Default::default()
+        };
+        let mut app = App::default();
+        let mut open = entry(20, None);
+        open.end = None;
+        app.set_whole_day(vec![entry(60, None), entry(40, Some("acme")), open]);
+        assert_eq!(app.today.len(), 2);
+        assert_eq!(app.other_profiles.len(), 1);
+        assert_eq!(app.whole_day()[1].profile(), Some("acme"));
+
+        // Switching stops what the profile left behind was tracking
+        let prefs = Preferences {
+            profile: Some(String::from("acme")),
+            ..Default::default()
+        };
+        app.apply_prefs(prefs);
+        assert_eq!(app.today.len(), 1);
+        assert_eq!(app.today[0].profile(), Some("acme"));
+        assert!(app.other_profiles.iter().all(|tl| !tl.is_open()));
+        assert_eq!(app.whole_day().len(), 3);
+    }
+
+    #[test]
+    fn lock_break_task_is_a_task() {
+        let dirs =
+            Dirs::in_dir(std::env::temp_dir().join(format!("ydnc-prefs-{}", std::process::id())));
+        for (saved, loaded) in [(Some(3), Some(3)), (Some(9), None), (Some(0), None)] {
+            let prefs = Preferences {
+                lock_break_task: saved,
+                ..Default::default()
+            };
+            save_prefs(&dirs, &prefs).unwrap();
+            assert_eq!(load_prefs(&dirs).unwrap().lock_break_task, loaded);
+        }
+        let _ = std::fs::remove_dir_all(dirs.data());
+    }
+
+    #[test]
+    fn asleep() {
+        let secs = Duration::from_secs;
+        // Setting the clock forward isn't sleeping, where there's an uptime
+        assert!(!was_asleep(secs(1), Some(secs(1)), secs(3600)));
+        assert!(was_asleep(secs(1), Some(secs(3600)), secs(3600)));
+        // Nor is a long time round the loop
+        assert!(!was_asleep(secs(600), Some(secs(600)), secs(600)));
+        assert!(!was_asleep(secs(1), Some(secs(60)), secs(60)));
+        // Without one, the wall clock is all there is to go by
+        assert!(was_asleep(secs(1), None, secs(3600)));
+        assert!(!was_asleep(secs(1), None, secs(1)));
+    }
+}
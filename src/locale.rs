@@ -0,0 +1,68 @@
+// Month and weekday names and date formats in the user's language, from the
+// locale they set in their preferences or else the one their environment
+// says. Unknown locales fall back to POSIX, i.e. English names and US dates.
+
+use chrono::{Locale, NaiveDate};
+
+use crate::Preferences;
+
+/// Turns a locale name like "fr_FR", "de_DE.UTF-8" or "sr_RS@latin" into a
+/// locale chrono knows. None if it's not one chrono has data for.
+pub fn parse_locale(name: &str) -> Option<Locale> {
+    let name = name.split('.').next()?;
+    let (name, modifier) = match name.split_once('@') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (name, None),
+    };
+    match name {
+        "" => None,
+        "C" | "POSIX" => Some(Locale::POSIX),
+        _ => modifier
+            .and_then(|m| Locale::try_from(format!("{}@{}", name, m).as_str()).ok())
+            .or_else(|| Locale::try_from(name).ok()),
+    }
+}
+
+/// The locale to format dates in
+pub fn user_locale(prefs: &Preferences) -> Locale {
+    let from_env = || {
+        // The same precedence the C library gives them
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .into_iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+    };
+    prefs
+        .locale
+        .clone()
+        .or_else(from_env)
+        .and_then(|name| parse_locale(&name))
+        .unwrap_or(Locale::POSIX)
+}
+
+/// Like `NaiveDate::format`, but with the user's month and weekday names, and
+/// with "%x" in the user's date format
+pub fn format_date(prefs: &Preferences, date: NaiveDate, fmt: &str) -> String {
+    date.format_localized(fmt, user_locale(prefs)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_names() {
+        assert_eq!(parse_locale("fr_FR"), Some(Locale::fr_FR));
+        assert_eq!(parse_locale("de_DE.UTF-8"), Some(Locale::de_DE));
+        assert_eq!(parse_locale("sr_RS@latin"), Some(Locale::sr_RS_latin));
+        assert_eq!(parse_locale("C.UTF-8"), Some(Locale::POSIX));
+        assert_eq!(parse_locale("xx_XX"), None);
+
+        let prefs = Preferences {
+            locale: Some("fr_FR".to_string()),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2023, 6, 8).unwrap();
+        assert_eq!(format_date(&prefs, date, "%A %e %B"), "jeudi  8 juin");
+        assert_eq!(format_date(&prefs, date, "%x"), "08/06/2023");
+    }
+}
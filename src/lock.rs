@@ -0,0 +1,67 @@
+use chrono::Local;
+use tokio::task::JoinHandle;
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+use zbus::{MatchRule, MessageStream, MessageType};
+
+use crate::AppState;
+
+/// Listens for the session's screen locking and unlocking over D-Bus, so that
+/// walking away without flipping the tracker doesn't keep the clock running.
+pub struct LockTask {
+    handle: JoinHandle<()>,
+}
+
+impl LockTask {
+    pub fn start(app: AppState) -> Self {
+        info!("Starting screen lock listener");
+        let handle = tokio::spawn(async move {
+            if let Err(err) = listen(&app).await {
+                warn!("Unable to listen for screen lock events: {}", err);
+            }
+        });
+        Self { handle }
+    }
+
+    pub fn stop(self) {
+        info!("Stopping screen lock listener");
+        self.handle.abort();
+    }
+}
+
+async fn listen(app: &AppState) -> zbus::Result<()> {
+    let conn = zbus::Connection::session().await?;
+    // GNOME and the freedesktop.org screensavers have different interfaces but
+    // both send an ActiveChanged signal, so listen for that from anyone
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .member("ActiveChanged")?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, &conn, None).await?;
+
+    // Some desktops send the signal from more than one interface, so only act
+    // on actual changes
+    let mut locked = false;
+    while let Some(msg) = stream.next().await {
+        let active: bool = match msg.and_then(|m| m.body()) {
+            Ok(active) => active,
+            Err(err) => {
+                warn!("Unexpected screen lock message: {}", err);
+                continue;
+            }
+        };
+        if active == locked {
+            continue;
+        }
+        locked = active;
+
+        let mut app = app.lock().unwrap();
+        if locked {
+            app.on_screen_lock(Local::now());
+        } else {
+            app.on_screen_unlock(Local::now());
+        }
+    }
+
+    Ok(())
+}
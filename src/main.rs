@@ -14,6 +14,7 @@
 // limitations under the License.
 
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -21,12 +22,75 @@ use ratatui::{backend::CrosstermBackend, Terminal};
 use std::{
     error::Error,
     io,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 use tracing::info;
 use tracing_subscriber::{filter::LevelFilter, prelude::*, EnvFilter};
 
-use ydnc_time::{bluetooth::BluetoothTask, App};
+use ydnc_time::{input::Inputs, App, Dirs};
+
+/// Reads a line from the terminal without echoing it. Esc gives up.
+#[cfg(feature = "encryption")]
+fn read_hidden_line() -> io::Result<String> {
+    use crossterm::event::{self, Event, KeyCode};
+
+    let mut line = String::new();
+    loop {
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Enter => return Ok(line),
+                KeyCode::Esc => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Interrupted,
+                        "No passphrase given",
+                    ))
+                }
+                KeyCode::Char(c) => line.push(c),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Asks for the save file passphrase until one works. Leaving it empty skips
+/// encryption for this session, unless the save files are encrypted already,
+/// in which case there's no starting without it (Esc quits).
+#[cfg(feature = "encryption")]
+fn unlock_save_files(dirs: &Dirs) -> io::Result<()> {
+    use std::io::Write;
+
+    let needed = ydnc_time::needs_passphrase(dirs);
+    loop {
+        if needed {
+            print!("Save file passphrase (Esc to quit): ");
+        } else {
+            print!("Save file passphrase (leave empty for none): ");
+        }
+        io::stdout().flush()?;
+
+        enable_raw_mode()?;
+        let passphrase = read_hidden_line();
+        disable_raw_mode()?;
+        println!();
+
+        let passphrase = passphrase?;
+        if passphrase.is_empty() {
+            if !needed {
+                return Ok(());
+            }
+            println!("The save files are encrypted, so they need the passphrase");
+            continue;
+        }
+        match ydnc_time::unlock(dirs, passphrase) {
+            Ok(()) => return Ok(()),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -50,34 +114,149 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     info!("ydnc-time starting");
 
+    // Subcommands do their thing without starting the UI:
+    // - `ydnc-time export-entries [options]` writes out entries
+    // - `ydnc-time annotate-commit <file> [source]` is for git's
+    //   prepare-commit-msg hook, adding the tracked task to commit messages
+    // - `ydnc-time auto --map <dir>=<task>,...` starts the task mapped to the
+    //   current directory in the running app, via its control socket
+    // Otherwise the UI starts, with options:
+    // - `--plain` runs it in plain mode, as lines of text without colors or
+    //   charts, on the normal screen, for screen readers and dumb terminals
+    // - `--profile NAME` switches to the named profile first, creating it if
+    //   it's new
+    // `--data-dir DIR` goes with any of them, keeping all the app's files in
+    // DIR (see the storage module)
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let data_dir = match args.iter().position(|arg| arg == "--data-dir") {
+        Some(i) if i + 1 < args.len() => {
+            let dir = args.remove(i + 1);
+            args.remove(i);
+            Some(PathBuf::from(dir))
+        }
+        Some(_) => return Err("--data-dir needs a directory".into()),
+        None => None,
+    };
+    let dirs = Dirs::locate(data_dir)
+        .ok_or("No directory to keep the logs in on this OS, pass one with --data-dir")?;
+
+    let mut plain = false;
+    let mut profile = None;
+    let mut args = args.into_iter();
+    let first = args.next();
+    match first.as_deref() {
+        Some("export-entries") => {
+            #[cfg(feature = "encryption")]
+            unlock_save_files(&dirs)?;
+
+            let path = ydnc_time::export_entries(&dirs, args)?;
+            println!("Exported entries to {}", path.display());
+            return Ok(());
+        }
+        Some("annotate-commit") => {
+            #[cfg(feature = "encryption")]
+            unlock_save_files(&dirs)?;
+
+            ydnc_time::annotate_commit(&dirs, args)?;
+            return Ok(());
+        }
+        #[cfg(all(feature = "control-socket", unix))]
+        Some("auto") => {
+            // No passphrase needed, it only talks to the app
+            if let Some(hook) = ydnc_time::auto_start(&dirs, args)? {
+                print!("{}", hook);
+            }
+            return Ok(());
+        }
+        _ => {
+            let mut options = first.into_iter().chain(args);
+            while let Some(option) = options.next() {
+                match option.as_str() {
+                    "--plain" => plain = true,
+                    "--profile" => {
+                        profile = Some(options.next().ok_or("--profile needs a name")?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
     // modeled after
     // https://github.com/fdehau/tui-rs/blob/master/examples/user_input.rs
 
+    #[cfg(feature = "encryption")]
+    unlock_save_files(&dirs)?;
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    if !plain {
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
+    if plain {
+        terminal.clear()?;
+    }
 
     // create app and wrap it so that our bluetooth and UI threads can share it
     // (bluetooth thread will only write to state; UI will both read and write
     // to it)
-    let app_state = Arc::new(Mutex::new(App::load_or_default()));
+    let mut app = App::load_or_default(dirs);
+    app.plain = plain;
+    if let Some(name) = profile {
+        app.switch_profile(&name);
+    }
+    let sources = ydnc_time::input::configured(&app.preferences);
+    let app_state = Arc::new(Mutex::new(app));
+
+    // start the tracker and other input sources in "the background" as tokio
+    // tasks
+    let inputs = Inputs::start(Arc::clone(&app_state), sources);
+
+    #[cfg(all(feature = "lock-screen", target_os = "linux"))]
+    let lock_task = ydnc_time::lock::LockTask::start(Arc::clone(&app_state));
+
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    let dbus_service = ydnc_time::dbus::DbusService::start(Arc::clone(&app_state));
+
+    #[cfg(feature = "http-api")]
+    let api_server = ydnc_time::api::ApiServer::start(Arc::clone(&app_state));
 
-    // start bluetooth handler in "the background" as a tokio task
-    let btle_task = BluetoothTask::start(Arc::clone(&app_state));
+    #[cfg(all(feature = "control-socket", unix))]
+    let control_socket = ydnc_time::control::ControlSocket::start(Arc::clone(&app_state));
 
     // Run the app -- it will return when the user exits the app
     let res = ydnc_time::run(app_state, &mut terminal).await;
 
-    btle_task.stop().await;
+    inputs.stop().await;
+    #[cfg(all(feature = "lock-screen", target_os = "linux"))]
+    lock_task.stop();
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    dbus_service.stop();
+    #[cfg(feature = "http-api")]
+    if let Some(server) = api_server {
+        server.stop();
+    }
+    #[cfg(all(feature = "control-socket", unix))]
+    if let Some(socket) = control_socket {
+        socket.stop();
+    }
 
     info!("ydnc-time stopped");
 
     // restore terminal
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if plain {
+        terminal.clear()?;
+    } else {
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+    }
     terminal.show_cursor()?;
 
     Ok(res?)
@@ -0,0 +1,156 @@
+// Save files wrap their data with the version of its format, like
+// `(version: 1, data: [...])`, so that when a format changes, files written
+// by older versions of the app can be recognized and upgraded as they're loaded
+// instead of failing to parse. Files from before formats were versioned are
+// just the bare data, and count as version 0. Upgraded files are written in the
+// current format the next time they're saved.
+//
+// To change a format: bump CURRENT_VERSION, keep a copy of the old type around
+// here, and teach the changed type's `upgrade` to parse the old type (with
+// `parse_data`) and convert it.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    error::{self, YdncError},
+    trash::Trash,
+    Preferences, TimeLog,
+};
+
+/// The format version that this version of the app writes
+pub const CURRENT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Versioned<'a, T: ?Sized> {
+    version: u32,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct VersionedData<T> {
+    data: T,
+}
+
+/// Just enough of a versioned file to tell which version it is
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// Something that's saved in a versioned format
+pub trait Migrate: Serialize + DeserializeOwned {
+    /// Parses `contents` written in format `version`, which is older than the
+    /// current one, and upgrades it to the current format
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self>;
+}
+
+impl Migrate for Vec<TimeLog> {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        // Version 1 only added the wrapper
+        parse_data(version, contents)
+    }
+}
+
+impl Migrate for Preferences {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        parse_data(version, contents)
+    }
+}
+
+/// Monthly archives of daily logs
+impl Migrate for BTreeMap<NaiveDate, Vec<TimeLog>> {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        parse_data(version, contents)
+    }
+}
+
+impl Migrate for Trash {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        parse_data(version, contents)
+    }
+}
+
+/// Parses the data out of a file in format `version` as `T`, which should be
+/// the type that held the data in that version
+pub fn parse_data<T: DeserializeOwned>(version: u32, contents: &[u8]) -> error::Result<T> {
+    if version == 0 {
+        Ok(ron::de::from_bytes(contents)?)
+    } else {
+        Ok(ron::de::from_bytes::<VersionedData<T>>(contents)?.data)
+    }
+}
+
+/// Which format version `contents` is in
+fn version_of(contents: &[u8]) -> u32 {
+    ron::de::from_bytes::<Header>(contents).map_or(0, |h| h.version)
+}
+
+/// Serializes `data` in the current format, indented if `pretty`
+pub fn to_ron<T: Serialize + ?Sized>(data: &T, pretty: bool) -> error::Result<String> {
+    let versioned = Versioned {
+        version: CURRENT_VERSION,
+        data,
+    };
+    if pretty {
+        ron::ser::to_string_pretty(&versioned, ron::ser::PrettyConfig::default())
+    } else {
+        ron::ser::to_string(&versioned)
+    }
+    .map_err(YdncError::from)
+}
+
+/// Parses data saved in any format version up to the current one, upgrading
+/// it if it's older
+pub fn from_ron<T: Migrate>(contents: &[u8]) -> error::Result<T> {
+    match version_of(contents) {
+        CURRENT_VERSION => parse_data(CURRENT_VERSION, contents),
+        version if version < CURRENT_VERSION => {
+            info!("Upgrading save data from format version {}", version);
+            T::upgrade(version, contents)
+        }
+        version => Err(YdncError::TooNew(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        let logs = vec![TimeLog {
+            number: 3,
+            ..Default::default()
+        }];
+        let saved = to_ron(&logs, true).unwrap();
+        assert!(saved.starts_with("(\n    version: 1,"));
+        assert_eq!(
+            from_ron::<Vec<TimeLog>>(saved.as_bytes()).unwrap()[0].number,
+            3
+        );
+
+        // Files from before versioning are the bare data
+        let bare = ron::ser::to_string(&logs).unwrap();
+        assert_eq!(
+            from_ron::<Vec<TimeLog>>(bare.as_bytes()).unwrap()[0].number,
+            3
+        );
+        let bare_prefs = "(autosave_mins: Some(3))";
+        assert_eq!(
+            from_ron::<Preferences>(bare_prefs.as_bytes())
+                .unwrap()
+                .autosave_mins,
+            Some(3)
+        );
+
+        let future = saved.replace("version: 1", "version: 99");
+        assert!(matches!(
+            from_ron::<Vec<TimeLog>>(future.as_bytes()),
+            Err(YdncError::TooNew(99))
+        ));
+    }
+}
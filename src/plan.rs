@@ -0,0 +1,146 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    crypto,
+    error::{self, YdncError},
+    storage::Dirs,
+    TimeLog,
+};
+
+/// One block of time the user intends to spend on a task on a given day
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct PlannedBlock {
+    pub number: u8,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl PlannedBlock {
+    pub fn duration(&self) -> chrono::Duration {
+        self.end - self.start
+    }
+}
+
+/// Formats like "3 09:00-10:30", which is also the format `from_str` accepts
+impl Display for PlannedBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}-{}",
+            self.number,
+            self.start.format("%R"),
+            self.end.format("%R")
+        )
+    }
+}
+
+impl FromStr for PlannedBlock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, times) = s
+            .trim()
+            .split_once(' ')
+            .ok_or("Expected a task number and times, like 3 09:00-10:30")?;
+
+        let number = number
+            .parse::<u8>()
+            .ok()
+            .filter(|n| (1..=8).contains(n))
+            .ok_or("Task number must be 1-8")?;
+
+        let (start, end) = times
+            .trim()
+            .split_once('-')
+            .ok_or("Expected a start and end time, like 09:00-10:30")?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%R")
+            .map_err(|_| "Start time must look like 09:00")?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%R")
+            .map_err(|_| "End time must look like 10:30")?;
+
+        if end <= start {
+            return Err("End time must be after start time".into());
+        }
+
+        Ok(Self { number, start, end })
+    }
+}
+
+/// Plans live in their own subdirectory of the save file directory so that
+/// they don't get mixed up with the daily logs.
+fn get_plan_file_path(dirs: &Dirs, date: NaiveDate) -> PathBuf {
+    dirs.data()
+        .join("plans")
+        .join(format!("{}.ron", date.format("%F")))
+}
+
+pub fn save_plan(dirs: &Dirs, date: NaiveDate, blocks: &[PlannedBlock]) -> error::Result<()> {
+    dirs.instance().check_writable()?;
+    let filename = get_plan_file_path(dirs, date);
+
+    info!("Saving plan to {}", filename.display());
+    let contents = ron::ser::to_string_pretty(blocks, ron::ser::PrettyConfig::default())?;
+    crypto::write(&filename, contents.into_bytes())?;
+
+    Ok(())
+}
+
+/// Loads the plan for the given date, sorted by start time. A day that was
+/// never planned has an empty plan rather than an error.
+pub fn load_plan(dirs: &Dirs, date: NaiveDate) -> error::Result<Vec<PlannedBlock>> {
+    let filename = get_plan_file_path(dirs, date);
+    if !filename.exists() {
+        return Ok(Vec::new());
+    }
+
+    info!("Loading plan from {}", filename.display());
+    let contents = crypto::read(&filename)?;
+    let mut blocks: Vec<PlannedBlock> =
+        ron::de::from_bytes(&contents).map_err(|e| YdncError::from(e).in_file(&filename))?;
+
+    blocks.sort_unstable_by_key(|b| b.start);
+
+    Ok(blocks)
+}
+
+/// Scores how closely a day's actual entries followed its plan: the fraction
+/// of planned time during which the planned task was actually being tracked.
+/// Returns None if nothing was planned.
+pub fn adherence(plan: &[PlannedBlock], actual: &[TimeLog], now: NaiveTime) -> Option<f64> {
+    let planned_secs: i64 = plan.iter().map(|b| b.duration().num_seconds()).sum();
+    if planned_secs <= 0 {
+        return None;
+    }
+
+    let followed_secs: i64 = plan
+        .iter()
+        .map(|block| {
+            actual
+                .iter()
+                .filter(|tl| tl.number == block.number)
+                .map(|tl| {
+                    let start = tl.start.time().max(block.start);
+                    let end = tl.end.map_or(now, |e| e.time()).min(block.end);
+                    (end - start).num_seconds().max(0)
+                })
+                .sum::<i64>()
+        })
+        .sum();
+
+    Some(followed_secs as f64 / planned_secs as f64)
+}
+
+/// Finds the next block that starts after `now`, if any. Assumes `plan` is
+/// sorted, which `load_plan` guarantees.
+pub fn next_block(plan: &[PlannedBlock], now: NaiveTime) -> Option<&PlannedBlock> {
+    plan.iter().find(|b| b.start > now)
+}
+
+/// Finds the block that is underway at `now`, if any
+pub fn current_block(plan: &[PlannedBlock], now: NaiveTime) -> Option<&PlannedBlock> {
+    plan.iter().find(|b| b.start <= now && now < b.end)
+}
@@ -0,0 +1,129 @@
+// Profiles are named sets of task labels, colors and daily targets, for
+// juggling clients or projects that each number their tasks their own way.
+// Only one is in use at a time, and its settings are the ones in the usual
+// places in the preferences; the others wait in `profiles` until they're
+// switched to, from Settings or with `--profile NAME` on the command line.
+// Entries are tagged with the profile they were tracked in, and Home, Stats and
+// exports only count the profile in use. The profile everyone starts out in
+// is called "default", and its entries aren't tagged at all.
+
+use std::collections::HashMap;
+
+use chrono::Weekday;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{Preferences, TimeLog};
+
+pub const DEFAULT: &str = "default";
+
+/// The settings of a profile that isn't in use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    labels: Option<[String; 8]>,
+    colors: Option<[Color; 8]>,
+    target_hours: Option<HashMap<Weekday, f32>>,
+}
+
+impl Profile {
+    fn from_prefs(name: &str, prefs: &Preferences) -> Self {
+        Self {
+            name: name.to_string(),
+            labels: prefs.labels.clone(),
+            colors: prefs.colors,
+            target_hours: prefs.target_hours.clone(),
+        }
+    }
+
+    fn apply_to(self, prefs: &mut Preferences) {
+        prefs.labels = self.labels;
+        prefs.colors = self.colors;
+        prefs.target_hours = self.target_hours;
+    }
+}
+
+/// The name of the profile in use
+pub fn current(prefs: &Preferences) -> &str {
+    prefs.profile.as_deref().unwrap_or(DEFAULT)
+}
+
+/// The names of all the profiles, the one in use included, sorted with the
+/// default one first
+pub fn names(prefs: &Preferences) -> Vec<String> {
+    let mut names: Vec<String> = prefs
+        .profiles
+        .iter()
+        .flatten()
+        .map(|p| p.name.clone())
+        .chain([current(prefs).to_string()])
+        .filter(|name| name != DEFAULT)
+        .collect();
+    names.sort();
+    names.dedup();
+    names.insert(0, DEFAULT.to_string());
+    names
+}
+
+/// Switches to the profile called `name`, putting the current one's settings
+/// away until it's switched back to. A profile that doesn't exist yet starts
+/// out with a copy of the current one's settings. Doesn't save the
+/// preferences.
+pub fn switch(prefs: &mut Preferences, name: &str) {
+    if name == current(prefs) {
+        return;
+    }
+    let outgoing = Profile::from_prefs(current(prefs), prefs);
+    let profiles = prefs.profiles.get_or_insert_with(Vec::new);
+    let incoming = profiles
+        .iter()
+        .position(|p| p.name == name)
+        .map(|i| profiles.remove(i));
+    profiles.retain(|p| p.name != outgoing.name);
+    profiles.push(outgoing);
+    if let Some(profile) = incoming {
+        profile.apply_to(prefs);
+    }
+    prefs.profile = (name != DEFAULT).then(|| name.to_string());
+}
+
+/// Whether `entry` was tracked in the profile in use
+pub fn in_current(entry: &TimeLog, profile: Option<&str>) -> bool {
+    entry.profile.as_deref() == profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switching_profiles() {
+        let mut prefs = Preferences {
+            labels: Some(Default::default()),
+            ..Default::default()
+        };
+        prefs.labels.as_mut().unwrap()[0] = "Email".to_string();
+
+        // A new profile starts out like the one switched away from
+        switch(&mut prefs, "clientA");
+        assert_eq!(current(&prefs), "clientA");
+        assert_eq!(prefs.labels.as_ref().unwrap()[0], "Email");
+        prefs.labels.as_mut().unwrap()[0] = "Design".to_string();
+
+        switch(&mut prefs, DEFAULT);
+        assert_eq!(prefs.profile, None);
+        assert_eq!(prefs.labels.as_ref().unwrap()[0], "Email");
+        assert_eq!(names(&prefs), vec!["default", "clientA"]);
+
+        switch(&mut prefs, "clientA");
+        assert_eq!(prefs.labels.as_ref().unwrap()[0], "Design");
+        assert_eq!(prefs.profiles.as_ref().unwrap().len(), 1);
+
+        let entry = TimeLog {
+            profile: Some("clientA".to_string()),
+            ..Default::default()
+        };
+        assert!(in_current(&entry, prefs.profile.as_deref()));
+        assert!(!in_current(&entry, None));
+    }
+}
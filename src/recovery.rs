@@ -0,0 +1,198 @@
+// Daily logs that won't parse, e.g. after a crash halfway through writing one
+// or a hand edit gone wrong, are moved aside to "<date>.ron.corrupt" instead of
+// being skipped or saved over, and whatever entries can still be read out of
+// them one by one are saved in their place. The user is then told which day
+// it was and where the original went, and can go straight into an editor to
+// check what was kept: the app's own for today, and $EDITOR for other days.
+//
+// Only the app that holds the instance lock recovers anything, once it's
+// said so with `Recoveries::enable` on its `Dirs`. Everything else, like a
+// read-only copy of the app or a subcommand run alongside it, leaves corrupt
+// logs as they are.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::NaiveDate;
+use tracing::warn;
+
+use crate::{crypto, save_log_file, storage::Dirs, TimeLog};
+
+/// A day whose log had to be recovered
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovery {
+    pub date: NaiveDate,
+    /// Where the salvaged entries were saved
+    pub path: PathBuf,
+    /// How many of its entries could be salvaged
+    pub kept: usize,
+    /// Where the original file was moved
+    pub moved_to: PathBuf,
+}
+
+/// Whether corrupt logs in a data directory get recovered, and the days that
+/// have been
+#[derive(Debug, Default)]
+pub struct Recoveries {
+    /// Whether corrupt logs get recovered, rather than just reported
+    enabled: AtomicBool,
+    /// Days recovered that the user hasn't been told about yet
+    recovered: Mutex<Vec<Recovery>>,
+}
+
+impl Recoveries {
+    /// Lets corrupt logs be recovered from now on, for the app to call once
+    /// it holds the instance lock
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// The days recovered since this was last called
+    pub fn take(&self) -> Vec<Recovery> {
+        std::mem::take(&mut *self.recovered.lock().unwrap())
+    }
+}
+
+/// Whether corrupt logs in `dirs` get recovered when they're loaded
+pub fn is_enabled(dirs: &Dirs) -> bool {
+    dirs.recoveries().enabled.load(Ordering::Relaxed) && !dirs.instance().is_read_only()
+}
+
+/// Moves the corrupt log at `path`, the log for `date`, aside and saves the
+/// entries that can be salvaged from it in its place, returning them. Leaves
+/// the file alone in a read-only copy of the app.
+pub fn recover(dirs: &Dirs, path: &Path, date: NaiveDate) -> io::Result<Vec<TimeLog>> {
+    dirs.instance().check_writable()?;
+    let contents = crypto::read(path)?;
+    let mut kept = salvage(&contents);
+    kept.sort_unstable_by_key(|tl| tl.start);
+
+    let moved_to = free_path(path);
+    fs::rename(path, &moved_to)?;
+    warn!(
+        "Moved corrupt log {} to {}, keeping {} entries",
+        path.display(),
+        moved_to.display(),
+        kept.len()
+    );
+    save_log_file(path, &kept)?;
+
+    dirs.recoveries().recovered.lock().unwrap().push(Recovery {
+        date,
+        path: path.to_path_buf(),
+        kept: kept.len(),
+        moved_to,
+    });
+    Ok(kept)
+}
+
+/// "<path>.corrupt", or with a number on the end if an earlier recovery of
+/// the same file is still there
+fn free_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".corrupt");
+    let mut moved_to = PathBuf::from(&name);
+    let mut n = 1;
+    while moved_to.exists() {
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", n));
+        moved_to = PathBuf::from(numbered);
+        n += 1;
+    }
+    moved_to
+}
+
+/// The entries in a daily log's `contents` that still parse on their own,
+/// skipping those that don't and anything cut off at the end
+pub fn salvage(contents: &[u8]) -> Vec<TimeLog> {
+    let text = String::from_utf8_lossy(contents);
+    // The entries are the first list, whether it's a versioned file's data or
+    // the bare list of one from before formats were versioned
+    let Some(list_start) = text.find('[') else {
+        return Vec::new();
+    };
+    let list = &text[list_start + 1..];
+
+    let mut entries = Vec::new();
+    let mut try_entry = |entry: &str| match ron::de::from_str::<TimeLog>(entry.trim()) {
+        Ok(tl) => entries.push(tl),
+        Err(e) => warn!("Unable to salvage an entry: {}", e),
+    };
+    let mut depth = 0usize;
+    let mut start: Option<usize> = None;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in list.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | '}' | ']' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 && c == ')' {
+                    if let Some(s) = start.take() {
+                        try_entry(&list[s..=i]);
+                    }
+                }
+            }
+            // The end of the list
+            ']' => break,
+            ',' if depth == 0 => {
+                if let Some(s) = start.take() {
+                    try_entry(&list[s..i]);
+                }
+            }
+            _ => {}
+        }
+        if start.is_none() && depth <= 1 && !c.is_whitespace() && c != ',' && c != ')' {
+            start = Some(i);
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+    use crate::migrate;
+
+    #[test]
+    fn salvaging_entries() {
+        let start = Local.with_ymd_and_hms(2023, 6, 5, 9, 0, 0).unwrap();
+        let logs: Vec<TimeLog> = (1..=3)
+            .map(|number| TimeLog {
+                start: start + chrono::Duration::hours(number.into()),
+                number,
+                tags: vec![String::from("a \"quoted\" (tag)")],
+                ..Default::default()
+            })
+            .collect();
+        let saved = migrate::to_ron(&logs, true).unwrap();
+        assert_eq!(salvage(saved.as_bytes()), logs);
+
+        // Cut off partway through the last entry, with the second one mangled
+        let cut = saved[..saved.rfind("number").unwrap()].replacen("number: 2", "number: two", 1);
+        assert_eq!(salvage(cut.as_bytes()), [logs[0].clone()]);
+
+        // Files from before formats were versioned are just the list
+        let bare = ron::ser::to_string(&logs).unwrap();
+        assert_eq!(salvage(bare.as_bytes()).len(), 3);
+        assert!(salvage(b"garbage").is_empty());
+    }
+}
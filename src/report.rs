@@ -0,0 +1,325 @@
+// Sends out a report of the day at the time of day set as `report_time`, so a
+// team can be kept up to date without any cloud service in between. The
+// report sums up the day like the daily summary does and then lists its
+// entries, as plain text or as HTML (`report_format`). It's written to the
+// `reports_dir` folder ("reports" in the data directory by default), or, if
+// `report_command` is set, piped to that command through the shell instead,
+// e.g. `(echo "Subject: $YDNC_REPORT_SUBJECT"; cat) | sendmail team@example.com`.
+// The command also gets YDNC_DATE, like the day rollover hook does.
+//
+// Each day is only reported once, even if the app is restarted after the
+// report went out. If the app isn't running at the report time, the report
+// goes out when it's next started that day, and a day it isn't started again
+// on goes unreported.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    durations, get_pref_label, locale::format_date, profiles, stats::DaySummary, storage::Dirs,
+    Notifier, Preferences, TimeLog,
+};
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Html,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Text => "txt",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// Where the last day reported is kept, so that restarting doesn't report it
+/// again. It's this device's business, so it's kept out of the data directory,
+/// which may be synced.
+fn last_sent_path(dirs: &Dirs) -> PathBuf {
+    dirs.cache().join("last-report")
+}
+
+/// The last day a report was sent for, if any. Older versions kept it in the
+/// data directory.
+pub fn last_sent(dirs: &Dirs) -> Option<NaiveDate> {
+    let contents = fs::read_to_string(last_sent_path(dirs))
+        .or_else(|_| fs::read_to_string(dirs.data().join("last-report")))
+        .ok()?;
+    NaiveDate::parse_from_str(contents.trim(), "%F").ok()
+}
+
+fn mark_sent(dirs: &Dirs, date: NaiveDate) -> io::Result<()> {
+    let path = last_sent_path(dirs);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, date.format("%F").to_string())
+}
+
+fn label(number: u8, prefs: &Preferences) -> String {
+    get_pref_label(number, prefs.labels.as_ref()).unwrap_or_else(|| number.to_string())
+}
+
+fn title(prefs: &Preferences, date: NaiveDate) -> String {
+    format!(
+        "Time report for {}",
+        format_date(prefs, date, "%A %b %-d %Y")
+    )
+}
+
+/// "09:00-10:30 Coding #review", or "09:00-now ..." for an entry still going
+fn entry_line(entry: &TimeLog, prefs: &Preferences) -> String {
+    let mut line = format!(
+        "{}-{} {}",
+        entry.start.format("%H:%M"),
+        entry.end.map_or_else(
+            || String::from("now"),
+            |end| end.format("%H:%M").to_string()
+        ),
+        label(entry.number, prefs)
+    );
+    for tag in &entry.tags {
+        let _ = write!(line, " #{}", tag);
+    }
+    line
+}
+
+/// "00:45:00 untracked during work hours, in 2 gaps"
+fn gaps_line(summary: &DaySummary) -> String {
+    match summary.gaps.len() {
+        0 => String::from("Nothing went untracked during work hours"),
+        n => format!(
+            "{} untracked during work hours, in {} gap{}",
+            durations::format_hms(summary.untracked()),
+            n,
+            if n == 1 { "" } else { "s" }
+        ),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The report on `logs`, the entries of `summary`'s day
+pub fn render(
+    summary: &DaySummary,
+    logs: &[TimeLog],
+    prefs: &Preferences,
+    format: ReportFormat,
+) -> String {
+    let title = title(prefs, summary.date);
+    let tasks = summary.tasks.iter().map(|(number, total)| {
+        format!(
+            "{}: {}",
+            label(*number, prefs),
+            durations::format_hms(*total)
+        )
+    });
+    let entries = logs.iter().map(|tl| entry_line(tl, prefs));
+
+    let mut out = String::new();
+    match format {
+        ReportFormat::Text => {
+            let _ = writeln!(out, "{}\n", title);
+            let _ = writeln!(out, "Tracked {}", durations::format_hms(summary.total));
+            for task in tasks {
+                let _ = writeln!(out, "{}", task);
+            }
+            let _ = writeln!(out, "{}\n\nEntries:", gaps_line(summary));
+            for entry in entries {
+                let _ = writeln!(out, "{}", entry);
+            }
+        }
+        ReportFormat::Html => {
+            let title = escape_html(&title);
+            let _ = writeln!(
+                out,
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>",
+                title, title
+            );
+            let _ = writeln!(
+                out,
+                "<p>Tracked <strong>{}</strong></p>\n<ul>",
+                durations::format_hms(summary.total)
+            );
+            for task in tasks {
+                let _ = writeln!(out, "<li>{}</li>", escape_html(&task));
+            }
+            let _ = writeln!(
+                out,
+                "</ul>\n<p>{}</p>\n<h2>Entries</h2>\n<ul>",
+                gaps_line(summary)
+            );
+            for entry in entries {
+                let _ = writeln!(out, "<li>{}</li>", escape_html(&entry));
+            }
+            let _ = writeln!(out, "</ul>\n</body>\n</html>");
+        }
+    }
+    out
+}
+
+/// Reports on `logs`, today's entries so far, writing the report out or
+/// handing it to the user's command, and remembers that today's been
+/// reported. A command's failure is sent to `notifier`, since it runs in the
+/// background.
+pub fn send(
+    dirs: &Dirs,
+    logs: &[TimeLog],
+    prefs: &Preferences,
+    notifier: Notifier,
+) -> io::Result<()> {
+    let now = Local::now();
+    let date = now.date_naive();
+    let logs: Vec<TimeLog> = logs
+        .iter()
+        .filter(|tl| profiles::in_current(tl, prefs.profile.as_deref()))
+        .cloned()
+        .collect();
+    let summary = DaySummary::new(date, &logs, prefs, now);
+    let format = prefs.report_format.unwrap_or_default();
+    let report = render(&summary, &logs, prefs, format);
+
+    match prefs.report_command.as_deref() {
+        Some(command) => pipe_to(command, report, title(prefs, date), date, notifier)?,
+        None => {
+            let dir = prefs
+                .reports_dir
+                .clone()
+                .unwrap_or_else(|| dirs.data().join("reports"));
+            write_to(&dir, date, format, &report)?;
+        }
+    }
+    mark_sent(dirs, date)
+}
+
+fn write_to(dir: &Path, date: NaiveDate, format: ReportFormat, report: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.{}", date.format("%F"), format.extension()));
+    info!("Writing report to {}", path.display());
+    fs::write(path, report)
+}
+
+fn pipe_to(
+    command: &str,
+    report: String,
+    subject: String,
+    date: NaiveDate,
+    notifier: Notifier,
+) -> io::Result<()> {
+    info!("Piping report to {}", command);
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .env("YDNC_REPORT_SUBJECT", subject)
+        .env("YDNC_DATE", date.format("%F").to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take();
+    let command = command.to_string();
+    // Feed and wait on it in the background, so that a slow mail server never
+    // holds up the UI
+    thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            if let Err(e) = stdin.write_all(report.as_bytes()) {
+                warn!("Unable to pipe report to {}: {}", command, e);
+            }
+        }
+        drop(stdin);
+        let failure = match child.wait_with_output() {
+            Ok(output) if output.status.success() => {
+                info!("Report command {} finished", command);
+                return;
+            }
+            Ok(output) => format!(
+                "Report command exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => format!("Could not wait for the report command: {}", e),
+        };
+        warn!("{}", failure);
+        let _ = notifier.send(failure);
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn rendering_reports() {
+        let start = Local.with_ymd_and_hms(2023, 6, 5, 9, 0, 0).unwrap();
+        let logs = vec![TimeLog {
+            start,
+            end: Some(start + Duration::minutes(90)),
+            number: 2,
+            tags: vec![String::from("review")],
+            ..Default::default()
+        }];
+        let prefs = Preferences {
+            locale: Some(String::from("en_US")),
+            ..Default::default()
+        };
+        let summary = DaySummary::new(start.date_naive(), &logs, &prefs, start);
+
+        let text = render(&summary, &logs, &prefs, ReportFormat::Text);
+        assert!(text.starts_with("Time report for Monday Jun 5 2023\n"));
+        assert!(text.contains("Tracked 01:30:00\n2: 01:30:00\n"));
+        assert!(text.contains("\nEntries:\n09:00-10:30 2 #review\n"));
+
+        let html = render(&summary, &logs, &prefs, ReportFormat::Html);
+        assert!(html.contains("<li>09:00-10:30 2 #review</li>"));
+        assert_eq!(escape_html("<a & b>"), "&lt;a &amp; b&gt;");
+    }
+
+    #[test]
+    fn last_report() {
+        let dir = std::env::temp_dir().join(format!("ydnc-report-{}", std::process::id()));
+        let dirs = Dirs::in_dir(dir.clone());
+        let date = |d| NaiveDate::from_ymd_opt(2023, 6, d).unwrap();
+        assert_eq!(last_sent(&dirs), None);
+
+        // Where older versions left it
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("last-report"), "2023-06-05").unwrap();
+        assert_eq!(last_sent(&dirs), Some(date(5)));
+
+        mark_sent(&dirs, date(6)).unwrap();
+        assert!(dirs.cache().join("last-report").exists());
+        assert_eq!(last_sent(&dirs), Some(date(6)));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
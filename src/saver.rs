@@ -0,0 +1,312 @@
+// Saves logs in a task of its own, so that a slow disk or a network drive only
+// ever holds up the save itself and never drawing or tracking. The app sends a
+// snapshot of each log to save down a channel and carries on, the snapshots
+// are saved one at a time in the order they were sent, and how each one went
+// comes back on another channel for the app to take in between frames. The
+// breaks, plans, preferences and trash are sent the same way, as are entries
+// added to past days' logs.
+
+use std::{io, time::SystemTime};
+
+use chrono::{DateTime, Local, NaiveDate};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::warn;
+
+use crate::{
+    breaks::{self, Break},
+    error, get_settings_file_path, load_log_for,
+    plan::{self, PlannedBlock},
+    save_log_for, save_prefs, sources,
+    storage::Dirs,
+    sync::SyncFolder,
+    trash::Trash,
+    watch, Preferences, TimeLog,
+};
+
+/// A log as it was when it was sent to be saved
+#[derive(Debug)]
+pub struct Snapshot {
+    pub date: NaiveDate,
+    pub logs: Vec<TimeLog>,
+    /// Whether it's a past day's log that couldn't be saved before, rather
+    /// than today's
+    pub past: bool,
+    /// When today's log had last changed as of the snapshot, to tell whether
+    /// it's changed again since
+    pub dirty_since: Option<DateTime<Local>>,
+    /// Whether the user asked for this save, and so wants to hear how it went
+    pub announce: bool,
+}
+
+/// How saving a snapshot went
+#[derive(Debug)]
+pub struct Saved {
+    pub date: NaiveDate,
+    /// The log as it was saved
+    pub logs: Vec<TimeLog>,
+    /// The file's modification time right after it was saved, for telling
+    /// our own saves apart from other programs' changes
+    pub mtime: Option<SystemTime>,
+    pub past: bool,
+    pub dirty_since: Option<DateTime<Local>>,
+    pub announce: bool,
+    pub result: error::Result<()>,
+}
+
+/// Something to save other than a day's log as the app has it
+#[derive(Debug)]
+pub enum Write {
+    /// Entries to add to a past day's log. When they're from a source, the
+    /// ones overlapping what's logged already are left out.
+    Entries {
+        date: NaiveDate,
+        entries: Vec<TimeLog>,
+        source: Option<String>,
+    },
+    Breaks {
+        date: NaiveDate,
+        breaks: Vec<Break>,
+    },
+    Plan {
+        date: NaiveDate,
+        blocks: Vec<PlannedBlock>,
+    },
+    Prefs(Box<Preferences>),
+    Trash(Trash),
+}
+
+impl Write {
+    /// What's being saved, to tell the user about if it fails
+    fn what(&self) -> String {
+        match self {
+            Self::Entries { date, .. } => format!("the entries for {}", date),
+            Self::Breaks { .. } => String::from("breaks"),
+            Self::Plan { date, .. } => format!("the plan for {}", date),
+            Self::Prefs(_) => String::from("preferences"),
+            Self::Trash(_) => String::from("the trash"),
+        }
+    }
+}
+
+/// How saving a `Write` went
+#[derive(Debug)]
+pub struct Written {
+    pub what: String,
+    /// What to tell the user once it's saved, if anything
+    pub message: Option<String>,
+    /// Like `Saved::mtime`, for the settings file once the preferences are
+    /// saved
+    pub settings_mtime: Option<SystemTime>,
+    pub result: error::Result<()>,
+}
+
+/// Something sent to be saved
+#[derive(Debug)]
+enum Job {
+    Log(Snapshot),
+    Write(Write),
+}
+
+/// How something sent to be saved went
+#[derive(Debug)]
+pub enum Done {
+    Log(Saved),
+    Write(Written),
+}
+
+/// Saves a snapshot right where it is, for when there's no saver running, and
+/// journals it for the user's other devices
+pub fn save(dirs: &Dirs, sync: &SyncFolder, snapshot: Snapshot) -> Saved {
+    let result = save_log_for(dirs, snapshot.date, &snapshot.logs);
+    let mtime = result.as_ref().ok().and_then(|path| watch::mtime(path));
+    if result.is_ok() {
+        sync.record(snapshot.date, &snapshot.logs);
+    }
+    Saved {
+        date: snapshot.date,
+        logs: snapshot.logs,
+        mtime,
+        past: snapshot.past,
+        dirty_since: snapshot.dirty_since,
+        announce: snapshot.announce,
+        result: result.map(|_| ()),
+    }
+}
+
+/// Saves a `Write` right where it is, for when there's no saver running
+pub fn write(dirs: &Dirs, sync: &SyncFolder, write: Write) -> Written {
+    let what = write.what();
+    let mut message = None;
+    let mut settings_mtime = None;
+    let result = match write {
+        Write::Entries {
+            date,
+            entries,
+            source,
+        } => add_entries(dirs, sync, date, entries, source.is_some()).map(|added| {
+            if let Some(source) = source.filter(|_| added > 0) {
+                message = Some(format!(
+                    "Logged {} blocks from {} into {}'s log",
+                    added, source, date
+                ));
+            }
+        }),
+        Write::Breaks { date, breaks } => breaks::save_breaks(dirs, date, &breaks),
+        Write::Plan { date, blocks } => plan::save_plan(dirs, date, &blocks),
+        Write::Prefs(prefs) => save_prefs(dirs, &prefs).map(|()| {
+            settings_mtime = watch::mtime(&get_settings_file_path(dirs));
+        }),
+        Write::Trash(trash) => trash.save(),
+    };
+    Written {
+        what,
+        message,
+        settings_mtime,
+        result,
+    }
+}
+
+/// Adds `entries` to `date`'s log, leaving out the ones overlapping what's
+/// there if `skip_overlapping`, and returns how many were added
+fn add_entries(
+    dirs: &Dirs,
+    sync: &SyncFolder,
+    date: NaiveDate,
+    entries: Vec<TimeLog>,
+    skip_overlapping: bool,
+) -> error::Result<usize> {
+    let mut logs = match load_log_for(dirs, date) {
+        Ok(logs) => logs,
+        Err(e) if e.is_not_found() => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let mut added = 0;
+    for entry in entries {
+        if skip_overlapping && sources::overlaps_any(&entry, &logs, Local::now()) {
+            continue;
+        }
+        logs.push(entry);
+        added += 1;
+    }
+    if added > 0 {
+        logs.sort_by_key(|tl| tl.start);
+        save_log_for(dirs, date, &logs)?;
+        sync.record(date, &logs);
+    }
+    Ok(added)
+}
+
+/// The saving task and the channels to and from it
+#[derive(Debug)]
+pub struct Saver {
+    jobs: mpsc::UnboundedSender<Job>,
+    done: mpsc::UnboundedReceiver<Done>,
+    task: JoinHandle<()>,
+    /// How many snapshots have been sent that haven't been heard back about
+    pending: usize,
+}
+
+impl Saver {
+    /// Starts saving into `dirs`, journaling what's saved through `sync`
+    pub fn start(dirs: Dirs, sync: SyncFolder) -> Self {
+        let (jobs, mut jobs_rx) = mpsc::unbounded_channel::<Job>();
+        let (done_tx, done) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            while let Some(job) = jobs_rx.recv().await {
+                // Saving is blocking file IO, which doesn't belong on the
+                // runtime's own threads
+                let (dirs, sync) = (dirs.clone(), sync.clone());
+                let finished = match job {
+                    Job::Log(snapshot) => {
+                        let (date, past, dirty_since, announce) = (
+                            snapshot.date,
+                            snapshot.past,
+                            snapshot.dirty_since,
+                            snapshot.announce,
+                        );
+                        match tokio::task::spawn_blocking(move || save(&dirs, &sync, snapshot))
+                            .await
+                        {
+                            Ok(saved) => Done::Log(saved),
+                            Err(e) => {
+                                warn!("Saving the log for {} panicked: {}", date, e);
+                                Done::Log(Saved {
+                                    date,
+                                    logs: Vec::new(),
+                                    mtime: None,
+                                    past,
+                                    dirty_since,
+                                    announce,
+                                    result: Err(io::Error::other(e).into()),
+                                })
+                            }
+                        }
+                    }
+                    Job::Write(w) => {
+                        let what = w.what();
+                        match tokio::task::spawn_blocking(move || write(&dirs, &sync, w)).await {
+                            Ok(written) => Done::Write(written),
+                            Err(e) => {
+                                warn!("Saving {} panicked: {}", what, e);
+                                Done::Write(Written {
+                                    what,
+                                    message: None,
+                                    settings_mtime: None,
+                                    result: Err(io::Error::other(e).into()),
+                                })
+                            }
+                        }
+                    }
+                };
+                if done_tx.send(finished).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            jobs,
+            done,
+            task,
+            pending: 0,
+        }
+    }
+
+    pub fn send(&mut self, snapshot: Snapshot) {
+        self.send_job(Job::Log(snapshot));
+    }
+
+    pub fn write(&mut self, write: Write) {
+        self.send_job(Job::Write(write));
+    }
+
+    fn send_job(&mut self, job: Job) {
+        if self.jobs.send(job).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    /// Whether anything sent is still being saved
+    pub fn is_saving(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// How the next finished save went, if there are any yet
+    pub fn try_recv(&mut self) -> Option<Done> {
+        let done = self.done.try_recv().ok()?;
+        self.pending = self.pending.saturating_sub(1);
+        Some(done)
+    }
+
+    /// Waits for everything already sent to be saved, and returns how it went
+    pub async fn finish(mut self) -> Vec<Done> {
+        drop(self.jobs);
+        let mut all = Vec::new();
+        while let Some(done) = self.done.recv().await {
+            all.push(done);
+        }
+        if let Err(e) = self.task.await {
+            warn!("The saver stopped unexpectedly: {}", e);
+        }
+        all
+    }
+}
@@ -0,0 +1,147 @@
+// Sets the user's Slack status to whatever they're tracking, like ":computer:
+// Coding", and clears it when they stop, when built with the "slack" feature.
+// The API token lives in its own "slack-secrets.ron" next to the settings file,
+// like `(token: "xoxp-...")`, so that settings can be shared without it; the
+// token needs the users.profile:write scope. Requests are made with curl on a
+// background thread, so that a slow network never holds up the UI.
+
+use std::{
+    fs, io,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    error::{self, YdncError},
+    storage::Dirs,
+    Notifier,
+};
+
+/// An emoji and text to show, or None to clear the status
+pub type Status = Option<(String, String)>;
+
+#[derive(Deserialize)]
+struct Secrets {
+    token: String,
+}
+
+/// The thread that updates the status, and how to send it updates, if the
+/// user has a token
+static WORKER: Mutex<Option<(mpsc::Sender<Status>, thread::JoinHandle<()>)>> = Mutex::new(None);
+/// The status last sent to the worker, so that each change is only sent once.
+/// It starts out as nothing, so that stopping, or starting with nothing being
+/// tracked, never clears a status the user set themselves, only one of ours.
+static LAST: Mutex<Status> = Mutex::new(None);
+
+fn secrets_file_path(dirs: &Dirs) -> PathBuf {
+    dirs.config().join("slack-secrets.ron")
+}
+
+fn load_secrets(dirs: &Dirs) -> error::Result<Secrets> {
+    let path = secrets_file_path(dirs);
+    let contents = fs::read(&path)?;
+    ron::de::from_bytes(&contents).map_err(|e| YdncError::from(e).in_file(&path))
+}
+
+/// Starts updating the Slack status, if the user has saved a token. Failures
+/// are sent to `notifier`.
+pub fn start(dirs: &Dirs, notifier: Notifier) {
+    let secrets = match load_secrets(dirs) {
+        Ok(secrets) => secrets,
+        Err(e) if e.is_not_found() => return,
+        Err(e) => {
+            warn!("Unable to load Slack secrets: {}", e);
+            let _ = notifier.send(format!("Could not load Slack token: {}", e));
+            return;
+        }
+    };
+    info!("Updating Slack status as tracking changes");
+    let (tx, rx) = mpsc::channel::<Status>();
+    // Dropping the sender ends the thread
+    let handle = thread::spawn(move || {
+        while let Ok(mut status) = rx.recv() {
+            // Only the latest of several queued-up changes matters
+            while let Ok(newer) = rx.try_recv() {
+                status = newer;
+            }
+            if let Err(e) = set_status(&secrets.token, &status) {
+                warn!("Unable to update Slack status: {}", e);
+                let _ = notifier.send(format!("Could not update Slack status: {}", e));
+            }
+        }
+    });
+    *WORKER.lock().unwrap() = Some((tx, handle));
+}
+
+/// Shows `status` on Slack, unless it's already showing. None clears the
+/// status, if it's one this app set.
+pub fn show(status: Status) {
+    let mut last = LAST.lock().unwrap();
+    if *last == status {
+        return;
+    }
+    if let Some((tx, _)) = WORKER.lock().unwrap().as_ref() {
+        let _ = tx.send(status.clone());
+    }
+    *last = status;
+}
+
+/// Waits for any update still underway, e.g. before exiting
+pub fn finish() {
+    if let Some((tx, handle)) = WORKER.lock().unwrap().take() {
+        drop(tx);
+        let _ = handle.join();
+    }
+}
+
+fn set_status(token: &str, status: &Status) -> io::Result<()> {
+    let (emoji, text) = status.clone().unwrap_or_default();
+    let body = serde_json::json!({
+        "profile": {
+            "status_emoji": emoji,
+            "status_text": text,
+            "status_expiration": 0,
+        }
+    });
+
+    // The token goes in through curl's config on stdin rather than its
+    // arguments, which other users can see
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .args(["--header", "Content-Type: application/json; charset=utf-8"])
+        .args(["--data-raw", &body.to_string()])
+        .arg("https://slack.com/api/users.profile.set")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = curl.stdin.take() {
+        writeln!(stdin, "header = \"Authorization: Bearer {}\"", token)?;
+    }
+    let output = curl.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    // Slack reports most errors in the body of a successful response
+    let response: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(io::Error::other)?;
+    if response["ok"].as_bool() == Some(true) {
+        Ok(())
+    } else {
+        Err(io::Error::other(
+            response["error"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string(),
+        ))
+    }
+}
@@ -0,0 +1,139 @@
+// Stops tracking when the system suspends and offers to resume on waking up,
+// by listening for logind's PrepareForSleep signal on the system bus. Systems
+// without logind are left to the run loop, which notices the time it missed
+// going by once it wakes up.
+
+use std::{future::Future, pin::Pin};
+
+use chrono::{DateTime, Local};
+use tokio::task::JoinHandle;
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+use zbus::{zvariant::OwnedFd, Connection, MatchRule, MessageStream, MessageType};
+
+use crate::{
+    input::{EventSender, InputEvent, InputSource},
+    AppState,
+};
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const LOGIN1_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+/// Listens for the system suspending and waking up through logind, so that a
+/// laptop closed for the night doesn't log the whole night to whatever was
+/// open. Holds a delay inhibitor while awake, so that the time of suspending
+/// is noted before the system actually sleeps.
+#[derive(Default)]
+pub struct SleepInput {
+    handle: Option<JoinHandle<()>>,
+}
+
+impl InputSource for SleepInput {
+    fn name(&self) -> &str {
+        "suspend listener"
+    }
+
+    fn start(&mut self, _app: &AppState, events: EventSender) {
+        self.handle = Some(tokio::spawn(async move {
+            if let Err(err) = listen(&events).await {
+                warn!("Unable to listen for suspend events: {}", err);
+            }
+        }));
+    }
+
+    fn stop(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+        Box::pin(async {})
+    }
+}
+
+/// What a PrepareForSleep signal saying whether the system is `suspending`
+/// means for the app, as of `now`
+fn event_for(suspending: bool, now: DateTime<Local>) -> InputEvent {
+    if suspending {
+        InputEvent::Suspend(now)
+    } else {
+        InputEvent::Resume
+    }
+}
+
+/// Asks logind to wait for us before sleeping, until the returned fd is closed
+async fn inhibit(conn: &Connection) -> zbus::Result<OwnedFd> {
+    let reply = conn
+        .call_method(
+            Some(LOGIN1),
+            LOGIN1_PATH,
+            Some(LOGIN1_MANAGER),
+            "Inhibit",
+            &(
+                "sleep",
+                "ydnc-time",
+                "Stopping the entry being tracked",
+                "delay",
+            ),
+        )
+        .await?;
+    reply.body()
+}
+
+async fn listen(events: &EventSender) -> zbus::Result<()> {
+    let conn = Connection::system().await?;
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .sender(LOGIN1)?
+        .interface(LOGIN1_MANAGER)?
+        .member("PrepareForSleep")?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, &conn, None).await?;
+
+    // Without the inhibitor the entry still gets stopped, just possibly at
+    // the time the system woke up again
+    let mut inhibitor = inhibit(&conn)
+        .await
+        .map_err(|err| warn!("Unable to delay suspend: {}", err))
+        .ok();
+    while let Some(msg) = stream.next().await {
+        let suspending: bool = match msg.and_then(|m| m.body()) {
+            Ok(suspending) => suspending,
+            Err(err) => {
+                warn!("Unexpected suspend message: {}", err);
+                continue;
+            }
+        };
+        if suspending {
+            info!("System is suspending");
+            if events.send(event_for(true, Local::now())).is_err() {
+                break;
+            }
+            // Closing it lets the system go ahead. The event has the time, so
+            // it doesn't matter if the app only gets to it after waking up.
+            inhibitor = None;
+        } else {
+            info!("System woke up");
+            if events.send(event_for(false, Local::now())).is_err() {
+                break;
+            }
+            if inhibitor.is_none() {
+                inhibitor = inhibit(&conn).await.ok();
+            }
+        }
+    }
+    drop(inhibitor);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events() {
+        let now = Local::now();
+        assert_eq!(event_for(true, now), InputEvent::Suspend(now));
+        assert_eq!(event_for(false, now), InputEvent::Resume);
+    }
+}
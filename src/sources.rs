@@ -0,0 +1,185 @@
+// Sources log entries that happened away from the keyboard and the tracker,
+// e.g. the commute to and from work, by asking something outside the app what
+// happened. Each source is checked at startup and again at every autosave, in
+// the background so that a slow one never holds the app up, and whatever
+// blocks of time it reports are logged into its task, leaving out any that
+// overlap entries already in the log (so reporting the same block again is
+// harmless).
+//
+// A source implements `Source`. The one built in is `HookSource`, which runs a
+// shell command and reads blocks from its output, one per line:
+//
+//     2023-06-08T08:10:00+02:00 2023-06-08T08:45:00+02:00 #train
+//
+// i.e. an RFC 3339 start and end followed by any tags. So, to log commutes as
+// task 7, a script that spots them (from a phone's location history, a GPX
+// export, transit card statements...) goes in the preferences like:
+//
+//     hook_sources: Some([(name: "commute", command: "~/bin/commutes --today", task: 7)])
+
+use std::{
+    io,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::TimeLog;
+
+/// Somewhere that finished entries can come from
+pub trait Source {
+    /// What to call the source in messages
+    fn name(&self) -> &str;
+
+    /// The entries the source knows about, which needn't all be new
+    fn fetch(&self) -> io::Result<Vec<TimeLog>>;
+}
+
+/// Each source's name and what it reported, or why it couldn't be checked
+pub type Fetched = Vec<(String, io::Result<Vec<TimeLog>>)>;
+
+/// Whether sources are being checked in the background
+static FETCHING: AtomicBool = AtomicBool::new(false);
+/// What the last check found, until the app takes it in
+static FETCHED: Mutex<Option<Fetched>> = Mutex::new(None);
+
+/// Checks `sources` in the background, for `take_fetched` to hand over once
+/// they've all been checked. Does nothing if a check is going already.
+pub fn start_fetching<S: Source + Send + 'static>(sources: Vec<S>) {
+    if sources.is_empty() || FETCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || {
+        let fetched = sources
+            .iter()
+            .map(|source| (source.name().to_string(), source.fetch()))
+            .collect();
+        *FETCHED.lock().unwrap() = Some(fetched);
+        FETCHING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// What the last check of the sources found, once it's done
+pub fn take_fetched() -> Option<Fetched> {
+    FETCHED.lock().unwrap().take()
+}
+
+/// A shell command whose output lists blocks of time to log as `task`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookSource {
+    name: String,
+    command: String,
+    task: u8,
+}
+
+impl Source for HookSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch(&self) -> io::Result<Vec<TimeLog>> {
+        if !(1..=8).contains(&self.task) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("The source {:?} needs a task from 1 to 8", self.name),
+            ));
+        }
+        let output = Command::new("sh").arg("-c").arg(&self.command).output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "{} exited with {}: {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        parse_hook_output(&String::from_utf8_lossy(&output.stdout), self.task)
+    }
+}
+
+/// Parses lines like "<start> <end> #tag" with RFC 3339 times into entries of
+/// task `number`. Blank lines are skipped.
+pub fn parse_hook_output(output: &str, number: u8) -> io::Result<Vec<TimeLog>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let parse_time = |s: Option<&str>, line: &str| {
+        let s = s.ok_or_else(|| invalid(format!("Expected a start and an end on {:?}", line)))?;
+        DateTime::parse_from_rfc3339(s)
+            .map(|dt| dt.with_timezone(&Local))
+            .map_err(|e| invalid(format!("Invalid time {:?}: {}", s, e)))
+    };
+
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut words = line.split_whitespace();
+            let start = parse_time(words.next(), line)?;
+            let end = parse_time(words.next(), line)?;
+            if end <= start {
+                return Err(invalid(format!("Block ends before it starts: {:?}", line)));
+            }
+            Ok(TimeLog {
+                start,
+                end: Some(end),
+                number,
+                tags: words
+                    .map(|w| w.trim_start_matches('#').to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Whether `entry` overlaps any of `logs`
+pub fn overlaps_any(entry: &TimeLog, logs: &[TimeLog], now: DateTime<Local>) -> bool {
+    let end = entry.end.unwrap_or(now);
+    logs.iter()
+        .any(|tl| tl.start < end && entry.start < tl.end.unwrap_or(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hook_output() {
+        let output = "2023-06-08T08:10:00+02:00 2023-06-08T08:45:00+02:00 #train\n\n\
+                      2023-06-08T17:30:00+02:00 2023-06-08T18:00:00+02:00\n";
+        let blocks = parse_hook_output(output, 7).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].number, 7);
+        assert_eq!(blocks[0].tags, vec!["train".to_string()]);
+        assert_eq!(
+            blocks[0].end.unwrap() - blocks[0].start,
+            chrono::Duration::minutes(35)
+        );
+
+        let now = Local::now();
+        assert!(overlaps_any(&blocks[0], &blocks, now));
+        assert!(!overlaps_any(&blocks[1], &blocks[..1], now));
+
+        assert!(parse_hook_output("2023-06-08T08:10:00+02:00", 7).is_err());
+        assert!(
+            parse_hook_output("2023-06-08T09:00:00+02:00 2023-06-08T08:00:00+02:00", 7).is_err()
+        );
+    }
+
+    #[test]
+    fn hook_task_out_of_range() {
+        let source = HookSource {
+            name: String::from("commute"),
+            command: String::from("true"),
+            task: 9,
+        };
+        let e = source.fetch().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+}
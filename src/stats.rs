@@ -1,16 +1,64 @@
-use std::{ffi::OsStr, fs, io};
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
-use chrono::NaiveDate;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Timelike};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use crate::{get_save_file_dir, load_log_file, TimeLog};
+use crate::{
+    archive, breaks,
+    durations::{self, BillingRounding},
+    error, gaps, load_log_file, profiles,
+    storage::Dirs,
+    Category, ClientGroup, Preferences, TimeLog,
+};
 
-#[derive(Debug, Clone, Copy)]
+mod cache;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct TimeStats {
     pub task_number: u8,
     pub count: u16,
+    #[serde(with = "cache::millis")]
     pub total: chrono::Duration,
+    #[serde(with = "cache::millis")]
     pub mean: chrono::Duration,
+    /// Sum of the estimates of the entries that had one
+    #[serde(with = "cache::millis")]
+    pub estimated: chrono::Duration,
+    /// Actual time spent on the entries that had an estimate, so that it can
+    /// be compared against `estimated`
+    #[serde(with = "cache::millis")]
+    pub estimated_actual: chrono::Duration,
+    /// The total with each entry rounded per the user's billing rule
+    #[serde(with = "cache::millis")]
+    pub billed: chrono::Duration,
+    /// The length of the middle entry, by length
+    #[serde(with = "cache::millis")]
+    pub median: chrono::Duration,
+    /// The length of the longest entry
+    #[serde(with = "cache::millis")]
+    pub longest: chrono::Duration,
+    /// How many different days the task was tracked on
+    pub days: u16,
+}
+
+impl TimeStats {
+    /// How far over (positive) or under (negative) the estimates this task ran.
+    /// None if none of its entries had an estimate.
+    pub fn estimate_variance(&self) -> Option<chrono::Duration> {
+        if self.estimated.is_zero() {
+            None
+        } else {
+            Some(self.estimated_actual - self.estimated)
+        }
+    }
 }
 
 impl Default for TimeStats {
@@ -20,37 +68,76 @@ impl Default for TimeStats {
             count: Default::default(),
             total: chrono::Duration::zero(),
             mean: chrono::Duration::zero(),
+            estimated: chrono::Duration::zero(),
+            estimated_actual: chrono::Duration::zero(),
+            billed: chrono::Duration::zero(),
+            median: chrono::Duration::zero(),
+            longest: chrono::Duration::zero(),
+            days: Default::default(),
         }
     }
 }
 
+/// The middle of `lengths`, or the mean of the two in the middle if there's an
+/// even number of them. Sorts them along the way.
+fn median(lengths: &mut [chrono::Duration]) -> chrono::Duration {
+    lengths.sort_unstable();
+    let mid = lengths.len() / 2;
+    match lengths.len() {
+        0 => chrono::Duration::zero(),
+        n if n % 2 == 1 => lengths[mid],
+        _ => (lengths[mid - 1] + lengths[mid]) / 2,
+    }
+}
+
 #[derive(Debug)]
 struct TimeStatsBuilder {
     number: u8,
+    rounding: Option<BillingRounding>,
     count: u16,
     total: chrono::Duration,
+    estimated: chrono::Duration,
+    estimated_actual: chrono::Duration,
+    billed: chrono::Duration,
+    /// Each entry's length, for the median
+    lengths: Vec<chrono::Duration>,
+    /// The days the entries started on
+    dates: HashSet<NaiveDate>,
 }
 
 impl TimeStatsBuilder {
-    fn new(number: u8) -> Self {
+    fn new(number: u8, rounding: Option<BillingRounding>) -> Self {
         Self {
             number,
+            rounding,
             count: 0,
             total: chrono::Duration::zero(),
+            estimated: chrono::Duration::zero(),
+            estimated_actual: chrono::Duration::zero(),
+            billed: chrono::Duration::zero(),
+            lengths: Vec::new(),
+            dates: HashSet::new(),
         }
     }
 
     fn add(&mut self, entry: TimeLog) -> &mut Self {
         self.count += 1;
+        let duration = durations::elapsed(&entry, None);
         // For some reason, chrono::Duration implements Add for itself, but not
         // AddAssign? Weird.
-        self.total = self.total + (entry.end.unwrap_or(entry.start) - entry.start);
+        self.total = self.total + duration;
+        self.billed = self.billed + durations::billed(&entry, None, self.rounding);
+        if let Some(mins) = entry.estimate_mins {
+            self.estimated = self.estimated + chrono::Duration::minutes(mins.into());
+            self.estimated_actual = self.estimated_actual + duration;
+        }
+        self.lengths.push(duration);
+        self.dates
+            .insert(entry.start.with_timezone(&Local).date_naive());
         self
     }
 
-    // Since all of TimeStatsBuilder's fields are Copy, it's easy to have
-    // build() only take `&self` instead of `self`
-    fn build(&self) -> TimeStats {
+    fn build(mut self) -> TimeStats {
         TimeStats {
             task_number: self.number,
             count: self.count,
@@ -60,23 +147,37 @@ impl TimeStatsBuilder {
             } else {
                 self.total / (self.count as i32)
             },
+            estimated: self.estimated,
+            estimated_actual: self.estimated_actual,
+            billed: self.billed,
+            median: median(&mut self.lengths),
+            longest: self
+                .lengths
+                .iter()
+                .max()
+                .copied()
+                .unwrap_or_else(chrono::Duration::zero),
+            days: self.dates.len() as u16,
         }
     }
 }
 
 // Normally I'd choose &Item over Item, but TimeLog is Copy woot
-pub fn compute_stats(logs: impl IntoIterator<Item = TimeLog>) -> [TimeStats; 8] {
+pub fn compute_stats(
+    logs: impl IntoIterator<Item = TimeLog>,
+    rounding: Option<BillingRounding>,
+) -> [TimeStats; 8] {
     // There's gotta be a more elegant way to do this but meh this is fine. At
     // least this is probably performant 🤷
     let mut result = [
-        TimeStatsBuilder::new(1),
-        TimeStatsBuilder::new(2),
-        TimeStatsBuilder::new(3),
-        TimeStatsBuilder::new(4),
-        TimeStatsBuilder::new(5),
-        TimeStatsBuilder::new(6),
-        TimeStatsBuilder::new(7),
-        TimeStatsBuilder::new(8),
+        TimeStatsBuilder::new(1, rounding),
+        TimeStatsBuilder::new(2, rounding),
+        TimeStatsBuilder::new(3, rounding),
+        TimeStatsBuilder::new(4, rounding),
+        TimeStatsBuilder::new(5, rounding),
+        TimeStatsBuilder::new(6, rounding),
+        TimeStatsBuilder::new(7, rounding),
+        TimeStatsBuilder::new(8, rounding),
     ];
 
     for log in logs {
@@ -86,62 +187,984 @@ pub fn compute_stats(logs: impl IntoIterator<Item = TimeLog>) -> [TimeStats; 8]
     result.map(|tsb| tsb.build())
 }
 
-/// Returns the stats from historical files available in the save directory.
-/// Includes TimeStats for each task as well as the minimum dated file located
-/// if available. Both min_date and max_date are optional and inclusive if
-/// provided.
-pub fn load_history(
+/// Totals for one tag within one task number
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagStats {
+    pub task_number: u8,
+    pub tag: String,
+    pub count: u16,
+    #[serde(with = "cache::millis")]
+    pub total: chrono::Duration,
+    #[serde(with = "cache::millis")]
+    pub billed: chrono::Duration,
+}
+
+/// Breaks each task's time down by tag. Entries with several tags count toward
+/// each of them. Sorted by task number, then tag.
+pub fn compute_tag_stats<'a>(
+    logs: impl IntoIterator<Item = &'a TimeLog>,
+    rounding: Option<BillingRounding>,
+) -> Vec<TagStats> {
+    let mut totals: BTreeMap<(u8, &str), (u16, chrono::Duration, chrono::Duration)> =
+        BTreeMap::new();
+    for log in logs {
+        let duration = durations::elapsed(log, None);
+        let billed = durations::billed(log, None, rounding);
+        for tag in &log.tags {
+            let (count, total, billed_total) = totals
+                .entry((log.number, tag.as_str()))
+                .or_insert((0, chrono::Duration::zero(), chrono::Duration::zero()));
+            *count += 1;
+            *total = *total + duration;
+            *billed_total = *billed_total + billed;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|((task_number, tag), (count, total, billed))| TagStats {
+            task_number,
+            tag: tag.to_string(),
+            count,
+            total,
+            billed,
+        })
+        .collect()
+}
+
+/// Everything computed from the save files within a date range
+#[derive(Debug, Clone)]
+pub struct History {
+    pub tasks: [TimeStats; 8],
+    pub tags: Vec<TagStats>,
+    /// The date of the earliest save file that was loaded, if any
+    pub min_date: Option<NaiveDate>,
+    /// The total time tracked on each day that has a save file, counting time
+    /// spent on parallel tasks only once
+    pub daily_totals: BTreeMap<NaiveDate, chrono::Duration>,
+    /// Each task's total on each day that has a save file
+    pub daily_task_totals: BTreeMap<NaiveDate, [chrono::Duration; 8]>,
+    /// How much more the task totals add up to than the time tracked, from
+    /// tracking parallel tasks alongside others
+    pub overlap: chrono::Duration,
+    /// When the user's workday typically started and ended, per
+    /// `mean_workday`
+    pub workday: Option<(NaiveTime, NaiveTime)>,
+    /// The earliest any day started and the latest any day ended
+    pub first_last: Option<(NaiveTime, NaiveTime)>,
+    /// When something was being tracked on each day that has a save file, per
+    /// `busy_times`
+    pub daily_busy: BTreeMap<NaiveDate, Vec<(NaiveTime, NaiveTime)>>,
+    /// Whether this came from the stats cache because the save directory
+    /// couldn't be read, so it may be missing recent changes
+    pub stale: bool,
+    /// How long was spent on breaks, which isn't part of any task's total
+    pub breaks: chrono::Duration,
+}
+
+/// Subtotals for one of the user's client groupings
+#[derive(Debug, Clone)]
+pub struct ClientStats {
+    pub name: String,
+    pub count: u16,
+    pub total: chrono::Duration,
+    pub billed: chrono::Duration,
+    /// None if none of the client's tasks have an hourly rate
+    pub earnings: Option<f64>,
+}
+
+/// What `billed` time on a task earns at the task's hourly rate. None if the
+/// task has no rate (or a rate of 0).
+pub fn earnings(
+    task_number: u8,
+    billed: chrono::Duration,
+    rates: Option<&[f64; 8]>,
+) -> Option<f64> {
+    let rate = *rates?.get((task_number as usize).checked_sub(1)?)?;
+    (rate > 0.0).then(|| rate * billed.num_seconds() as f64 / 3600.0)
+}
+
+/// Earnings are shown to the cent, without a currency since that's up to the
+/// user's rates
+pub fn format_earnings(amount: f64) -> String {
+    format!("{:.2}", amount)
+}
+
+/// Rolls per-task and per-tag stats up into one subtotal per client group: the
+/// client's whole tasks, plus the time tagged with one of the client's tags on
+/// its other tasks. A task or tag may belong to more than one client, in which
+/// case it counts toward each of them, and an entry with several of one
+/// client's tags counts once for each.
+pub fn group_by_client(
+    stats: &[TimeStats],
+    tags: &[TagStats],
+    clients: &[ClientGroup],
+    rates: Option<&[f64; 8]>,
+) -> Vec<ClientStats> {
+    clients
+        .iter()
+        .map(|client| {
+            let tasks = stats
+                .iter()
+                .filter(|ts| client.tasks.contains(&ts.task_number))
+                .map(|ts| (ts.task_number, ts.count, ts.total, ts.billed));
+            let tagged = tags
+                .iter()
+                .filter(|tag| {
+                    !client.tasks.contains(&tag.task_number) && client.tags.contains(&tag.tag)
+                })
+                .map(|tag| (tag.task_number, tag.count, tag.total, tag.billed));
+            let members: Vec<_> = tasks.chain(tagged).collect();
+            ClientStats {
+                name: client.name.clone(),
+                count: members.iter().map(|(_, count, _, _)| count).sum(),
+                total: members
+                    .iter()
+                    .fold(chrono::Duration::zero(), |acc, (_, _, total, _)| {
+                        acc + *total
+                    }),
+                billed: members
+                    .iter()
+                    .fold(chrono::Duration::zero(), |acc, (_, _, _, billed)| {
+                        acc + *billed
+                    }),
+                earnings: members
+                    .iter()
+                    .filter_map(|(number, _, _, billed)| earnings(*number, *billed, rates))
+                    .reduce(|a, b| a + b),
+            }
+        })
+        .collect()
+}
+
+/// Totals for one of the user's categories of tasks, or for the tasks in none
+/// of them
+#[derive(Debug, Clone)]
+pub struct CategoryStats {
+    pub name: String,
+    /// The tasks counted toward this category that have any time, longest first
+    pub tasks: Vec<TimeStats>,
+    pub count: u16,
+    pub total: chrono::Duration,
+}
+
+/// The name the tasks in none of the user's categories are totalled under
+pub const UNCATEGORIZED: &str = "Uncategorized";
+
+/// Rolls per-task stats up into one total per category, largest first. Unlike
+/// client groups, each task counts toward just one category, so the totals add
+/// up to the overall total. For billed time, pass stats with their billed
+/// totals in `total`. Tasks in no category go under UNCATEGORIZED,
+/// which is left out if they had no time.
+pub fn group_by_category(stats: &[TimeStats], categories: &[Category]) -> Vec<CategoryStats> {
+    let category_of = |number: u8| categories.iter().position(|c| c.tasks.contains(&number));
+    let names = categories
+        .iter()
+        .map(|c| c.name.clone())
+        .chain([UNCATEGORIZED.to_string()]);
+    let mut rollup: Vec<CategoryStats> = names
+        .enumerate()
+        .map(|(i, name)| {
+            let mut tasks: Vec<TimeStats> = stats
+                .iter()
+                .filter(|ts| category_of(ts.task_number).unwrap_or(categories.len()) == i)
+                .copied()
+                .collect();
+            tasks.sort_by_key(|ts| std::cmp::Reverse(ts.total));
+            CategoryStats {
+                name,
+                count: tasks.iter().map(|ts| ts.count).sum(),
+                total: tasks
+                    .iter()
+                    .fold(chrono::Duration::zero(), |acc, ts| acc + ts.total),
+                tasks: tasks
+                    .into_iter()
+                    .filter(|ts| ts.total > chrono::Duration::zero())
+                    .collect(),
+            }
+        })
+        .collect();
+    if rollup
+        .last()
+        .is_some_and(|c| c.total == chrono::Duration::zero())
+    {
+        rollup.pop();
+    }
+    rollup.sort_by_key(|c| std::cmp::Reverse(c.total));
+    rollup
+}
+
+/// Daily logs paired with the date of the file they were loaded from
+pub type DatedLogs = Vec<(NaiveDate, Vec<TimeLog>)>;
+
+/// Finds the daily logs in the save directory `dir` dated within the given
+/// (inclusive) range, paired with their dates. Logs are saved in year and month
+/// subdirectories like "2023/06/", but older versions of the app saved them
+/// right in `dir`, so both places are searched. Should a day be in both, the
+/// subdirectory's copy wins, since that's where it would have been saved last.
+pub(crate) fn daily_files(
+    dir: &Path,
     min_date: Option<NaiveDate>,
     max_date: Option<NaiveDate>,
-) -> io::Result<([TimeStats; 8], Option<NaiveDate>)> {
-    if let Some(dir) = get_save_file_dir() {
-        let (dates, logs): (Vec<_>, Vec<_>) = fs::read_dir(dir)?
-            .filter_map(|res| {
-                let path = res.map(|e| e.path());
-
-                // If no path, no extension, or extension != .ron, return None
-                // to skip this file. Else unwrap the successfully read path.
-                if path.as_ref().map_or(true, |p| {
-                    p.extension().map_or(true, |ext| ext != OsStr::new("ron"))
-                }) {
-                    return None;
-                }
-                let path = path.unwrap();
-
-                let file_date = path
-                    .file_name()
-                    .expect("loadable files have names")
-                    .to_string_lossy()
-                    .trim_end_matches(".ron")
-                    .parse::<NaiveDate>();
-
-                if let Err(e) = file_date {
-                    warn!("Undated file found in save directory, skipping: {}", e);
-                    return None;
-                }
-                let file_date = file_date.unwrap();
+) -> io::Result<Vec<(NaiveDate, PathBuf)>> {
+    let is_dated_dir = |path: &Path, len: usize| {
+        path.is_dir()
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.len() == len && n.chars().all(|c| c.is_ascii_digit()))
+    };
 
-                // Skip files outside our date range
-                if min_date.map_or(false, |min| file_date < min)
-                    || max_date.map_or(false, |max| file_date > max)
-                {
-                    return None;
-                }
+    let mut files: BTreeMap<NaiveDate, PathBuf> =
+        files_in(dir, min_date, max_date)?.into_iter().collect();
+    for year in fs::read_dir(dir)? {
+        let year = year?.path();
+        if !is_dated_dir(&year, 4) {
+            continue;
+        }
+        for month in fs::read_dir(&year)? {
+            let month = month?.path();
+            if is_dated_dir(&month, 2) {
+                files.extend(files_in(&month, min_date, max_date)?);
+            }
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// The daily logs directly in `dir` dated within the given (inclusive) range
+fn files_in(
+    dir: &Path,
+    min_date: Option<NaiveDate>,
+    max_date: Option<NaiveDate>,
+) -> io::Result<Vec<(NaiveDate, PathBuf)>> {
+    Ok(fs::read_dir(dir)?
+        .filter_map(|res| {
+            let path = res.map(|e| e.path());
+
+            // If no path, no extension, or extension != .ron, return None
+            // to skip this file. Else unwrap the successfully read path.
+            if path.as_ref().map_or(true, |p| {
+                p.extension().map_or(true, |ext| ext != OsStr::new("ron"))
+            }) {
+                return None;
+            }
+            let path = path.unwrap();
+
+            let file_date = path
+                .file_name()
+                .expect("loadable files have names")
+                .to_string_lossy()
+                .trim_end_matches(".ron")
+                .parse::<NaiveDate>();
+
+            if let Err(e) = file_date {
+                warn!("Undated file found in save directory, skipping: {}", e);
+                return None;
+            }
+            let file_date = file_date.unwrap();
+
+            // Skip files outside our date range
+            if min_date.map_or(false, |min| file_date < min)
+                || max_date.map_or(false, |max| file_date > max)
+            {
+                return None;
+            }
+
+            Some((file_date, path))
+        })
+        .collect())
+}
+
+/// Loads every daily log in the save directory dated within the given
+/// (inclusive) range, paired with the date of its file, including the days in
+/// monthly archives. Files that can't be read are skipped with a warning.
+pub fn load_dated_logs(
+    dirs: &Dirs,
+    min_date: Option<NaiveDate>,
+    max_date: Option<NaiveDate>,
+) -> io::Result<DatedLogs> {
+    load_dated_logs_in(dirs.data(), min_date, max_date, |path| {
+        load_log_file(dirs, path)
+    })
+}
+
+/// Like `load_dated_logs` but from the save directory `dir`, loading each
+/// daily file with `load`
+pub(crate) fn load_dated_logs_in(
+    dir: &Path,
+    min_date: Option<NaiveDate>,
+    max_date: Option<NaiveDate>,
+    load: impl Fn(&Path) -> error::Result<Vec<TimeLog>> + Sync,
+) -> io::Result<DatedLogs> {
+    // Reading and parsing years' worth of files one at a time is slow, so
+    // spread them over all the cores. They get sorted by date afterward.
+    let mut logs: DatedLogs = daily_files(dir, min_date, max_date)?
+        .into_par_iter()
+        .filter_map(|(date, path)| {
+            let r = load(&path).map(|loaded_log| (date, loaded_log));
+            if let Err(e) = r.as_ref() {
+                warn!("Unable to load history from a file in the save dir: {}", e);
+            }
+            r.ok()
+        })
+        .collect();
+
+    let daily_dates: HashSet<NaiveDate> = logs.iter().map(|(date, _)| *date).collect();
+    logs.extend(archive::load_archived(
+        dir,
+        min_date,
+        max_date,
+        &daily_dates,
+    )?);
 
-                let r = load_log_file(&path).map(|loaded_log| (file_date, loaded_log));
-                if let Err(e) = r.as_ref() {
+    logs.sort_unstable_by_key(|(date, _)| *date);
+    Ok(logs)
+}
+
+/// When one day's tracking began and ended: the start of its first entry and
+/// the end of its last finished one. None if nothing was finished that day.
+pub fn workday(logs: &[TimeLog]) -> Option<(NaiveTime, NaiveTime)> {
+    let start = logs.iter().map(|tl| tl.start).min()?;
+    let end = logs.iter().filter_map(|tl| tl.end).max()?;
+    Some((start.time(), end.time()))
+}
+
+/// The average start and end of the given workdays, like "09:12 to 17:48",
+/// for spotting a schedule that's drifting. None if there are none.
+pub fn mean_workday(
+    days: impl IntoIterator<Item = (NaiveTime, NaiveTime)>,
+) -> Option<(NaiveTime, NaiveTime)> {
+    let (mut starts, mut ends, mut count) = (0u64, 0u64, 0u64);
+    for (start, end) in days {
+        starts += u64::from(start.num_seconds_from_midnight());
+        ends += u64::from(end.num_seconds_from_midnight());
+        count += 1;
+    }
+    let mean = |secs: u64| NaiveTime::from_num_seconds_from_midnight_opt((secs / count) as u32, 0);
+    (count > 0)
+        .then(|| Some((mean(starts)?, mean(ends)?)))
+        .flatten()
+}
+
+/// The stretches of the day when something was being tracked, in order, with
+/// overlapping entries merged. Entries running past midnight count until the
+/// end of the day, and ones still going don't count yet.
+fn busy_times(logs: &[TimeLog]) -> Vec<(NaiveTime, NaiveTime)> {
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+    let mut stretches: Vec<(NaiveTime, NaiveTime)> = logs
+        .iter()
+        .filter_map(|tl| {
+            let end = tl.end?;
+            let end = if end.date_naive() > tl.start.date_naive() {
+                end_of_day
+            } else {
+                end.time()
+            };
+            (end > tl.start.time()).then(|| (tl.start.time(), end))
+        })
+        .collect();
+    stretches.sort();
+    let mut merged: Vec<(NaiveTime, NaiveTime)> = Vec::new();
+    for (start, end) in stretches {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// How much of the work hours of the work days from `from` to `to`
+/// (inclusive) went untracked, per `daily_busy`, up to `now`. Work days
+/// without a save file count as wholly untracked.
+pub fn untracked_work_time(
+    prefs: &Preferences,
+    daily_busy: &BTreeMap<NaiveDate, Vec<(NaiveTime, NaiveTime)>>,
+    from: NaiveDate,
+    to: NaiveDate,
+    now: DateTime<Local>,
+) -> chrono::Duration {
+    let (work_start, work_end) = gaps::work_hours(prefs);
+    from.iter_days()
+        .take_while(|date| *date <= to && *date <= now.date_naive())
+        .filter(|date| gaps::is_work_day(prefs, *date))
+        .fold(chrono::Duration::zero(), |acc, date| {
+            let work_end = if date == now.date_naive() {
+                work_end.min(now.time())
+            } else {
+                work_end
+            };
+            if work_end <= work_start {
+                return acc;
+            }
+            let tracked = daily_busy.get(&date).into_iter().flatten().fold(
+                chrono::Duration::zero(),
+                |acc, &(start, end)| {
+                    let (start, end) = (start.max(work_start), end.min(work_end));
+                    if end > start {
+                        acc + (end - start)
+                    } else {
+                        acc
+                    }
+                },
+            );
+            acc + (work_end - work_start) - tracked
+        })
+}
+
+/// The stats of a single day, which are added up into a `History`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayStats {
+    tasks: [TimeStats; 8],
+    tags: Vec<TagStats>,
+    workday: Option<(NaiveTime, NaiveTime)>,
+    /// How much of the tasks' total was spent tracking several at once
+    #[serde(with = "cache::millis")]
+    overlap: chrono::Duration,
+    /// How long something was being tracked, counting time spent on parallel
+    /// tasks only once
+    #[serde(with = "cache::millis")]
+    tracked: chrono::Duration,
+    /// Each task's entry lengths in milliseconds, since the median over
+    /// several days can't be had from each day's
+    lengths: [Vec<i64>; 8],
+    /// When something was being tracked, for finding the untracked time
+    /// within whatever the user's work hours are
+    busy: Vec<(NaiveTime, NaiveTime)>,
+}
+
+impl DayStats {
+    fn compute(logs: &[TimeLog], rounding: Option<BillingRounding>) -> Self {
+        let mut lengths: [Vec<i64>; 8] = Default::default();
+        for tl in logs {
+            lengths[(tl.number - 1) as usize].push(durations::elapsed(tl, None).num_milliseconds());
+        }
+        Self {
+            lengths,
+            busy: busy_times(logs),
+            tasks: compute_stats(logs.iter().cloned(), rounding),
+            tags: compute_tag_stats(logs, rounding),
+            workday: workday(logs),
+            overlap: durations::overlap(logs, None),
+            tracked: durations::covered(logs, None),
+        }
+    }
+
+    /// The time tracked, counting time spent on parallel tasks only once. It's
+    /// the time the entries covered, rather than being worked out again from
+    /// the task totals, which are rounded to the millisecond in the cache.
+    fn total(&self) -> chrono::Duration {
+        self.tracked
+    }
+}
+
+/// Adds up days' stats into the stats of the whole range
+fn add_up<'a>(days: impl IntoIterator<Item = &'a DayStats>) -> ([TimeStats; 8], Vec<TagStats>) {
+    let mut tasks: [TimeStats; 8] = std::array::from_fn(|i| TimeStats {
+        task_number: i as u8 + 1,
+        ..Default::default()
+    });
+    let mut tags: BTreeMap<(u8, String), TagStats> = BTreeMap::new();
+    let mut lengths: [Vec<chrono::Duration>; 8] = Default::default();
+
+    for day in days {
+        for (sum, ts) in tasks.iter_mut().zip(&day.tasks) {
+            sum.count += ts.count;
+            sum.total = sum.total + ts.total;
+            sum.estimated = sum.estimated + ts.estimated;
+            sum.estimated_actual = sum.estimated_actual + ts.estimated_actual;
+            sum.billed = sum.billed + ts.billed;
+            sum.longest = sum.longest.max(ts.longest);
+            sum.days += ts.days;
+        }
+        for (all, day) in lengths.iter_mut().zip(&day.lengths) {
+            all.extend(day.iter().map(|&ms| chrono::Duration::milliseconds(ms)));
+        }
+        for tag in &day.tags {
+            let sum = tags
+                .entry((tag.task_number, tag.tag.clone()))
+                .or_insert_with(|| TagStats {
+                    count: 0,
+                    total: chrono::Duration::zero(),
+                    billed: chrono::Duration::zero(),
+                    ..tag.clone()
+                });
+            sum.count += tag.count;
+            sum.total = sum.total + tag.total;
+            sum.billed = sum.billed + tag.billed;
+        }
+    }
+
+    for (ts, lengths) in tasks.iter_mut().zip(&mut lengths) {
+        if ts.count > 0 {
+            ts.mean = ts.total / (ts.count as i32);
+        }
+        ts.median = median(lengths);
+    }
+    (tasks, tags.into_values().collect())
+}
+
+/// Loads the history of `profile` (None for the default one) within the given
+/// (inclusive) dates, with billed totals rounded per `rounding`. Days whose
+/// files haven't changed since they were last loaded come from the stats cache
+/// instead of being parsed again. If the save directory can't be read at all,
+/// e.g. while it's on an unmounted drive, whatever the cache has for those
+/// dates is used instead, marked stale.
+pub fn load_history(
+    dirs: &Dirs,
+    min_date: Option<NaiveDate>,
+    max_date: Option<NaiveDate>,
+    rounding: Option<BillingRounding>,
+    profile: Option<&str>,
+) -> io::Result<History> {
+    let mut cache = cache::StatsCache::load(dirs, rounding, profile);
+    let compute = |logs: Vec<TimeLog>| {
+        let logs: Vec<TimeLog> = logs
+            .into_iter()
+            .filter(|tl| profiles::in_current(tl, profile))
+            .collect();
+        DayStats::compute(&logs, rounding)
+    };
+    let dir = dirs.data();
+    let files = match daily_files(dir, min_date, max_date) {
+        Ok(listed) => listed,
+        Err(e) => {
+            let days = cache.days_within(min_date, max_date);
+            if days.is_empty() {
+                return Err(e);
+            }
+            warn!("Unable to load history, using the stats cache: {}", e);
+            return Ok(add_up_history(&days, true, chrono::Duration::zero()));
+        }
+    };
+    let loaded: Vec<(NaiveDate, Option<SystemTime>, DayStats, bool)> = files
+        .into_par_iter()
+        .filter_map(|(date, path)| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            if let Some(stats) = modified.and_then(|m| cache.get(date, m)) {
+                return Some((date, modified, stats.clone(), false));
+            }
+            match load_log_file(dirs, &path) {
+                Ok(logs) => Some((date, modified, compute(logs), true)),
+                Err(e) => {
                     warn!("Unable to load history from a file in the save dir: {}", e);
+                    None
                 }
-                r.ok()
+            }
+        })
+        .collect();
+
+    let mut days: BTreeMap<NaiveDate, DayStats> = BTreeMap::new();
+    for (date, modified, stats, fresh) in loaded {
+        if let (Some(modified), true) = (modified, fresh) {
+            cache.insert(date, modified, stats.clone());
+        }
+        days.insert(date, stats);
+    }
+    let daily_dates: HashSet<NaiveDate> = days.keys().copied().collect();
+    cache.prune(min_date, max_date, &daily_dates);
+    if let Err(e) = cache.save() {
+        warn!("Unable to save the stats cache: {}", e);
+    }
+
+    // Archives are only a file per month, so they're not worth caching
+    for (date, logs) in archive::load_archived(dir, min_date, max_date, &daily_dates)? {
+        days.insert(date, compute(logs));
+    }
+
+    // Breaks are a file per day at most, so they're not worth caching either
+    let breaks = breaks::total_between(dirs, min_date, max_date).unwrap_or_else(|e| {
+        warn!("Unable to load breaks: {}", e);
+        chrono::Duration::zero()
+    });
+    Ok(add_up_history(&days, false, breaks))
+}
+
+fn add_up_history(
+    days: &BTreeMap<NaiveDate, DayStats>,
+    stale: bool,
+    breaks: chrono::Duration,
+) -> History {
+    let (tasks, tags) = add_up(days.values());
+    History {
+        tasks,
+        tags,
+        min_date: days.keys().next().copied(),
+        daily_totals: days
+            .iter()
+            .map(|(date, day)| (*date, day.total()))
+            .collect(),
+        daily_task_totals: days
+            .iter()
+            .map(|(date, day)| (*date, day.tasks.map(|ts| ts.total)))
+            .collect(),
+        workday: mean_workday(days.values().filter_map(|day| day.workday)),
+        first_last: days
+            .values()
+            .filter_map(|day| day.workday)
+            .reduce(|(first, last), (start, end)| (first.min(start), last.max(end))),
+        daily_busy: days
+            .iter()
+            .map(|(date, day)| (*date, day.busy.clone()))
+            .collect(),
+        overlap: days
+            .values()
+            .fold(chrono::Duration::zero(), |acc, day| acc + day.overlap),
+        stale,
+        breaks,
+    }
+}
+
+/// A task's total in minutes on each of the `days` days up to and including
+/// `to`, oldest first, for drawing how it's trending. Days without a save file
+/// count as nothing tracked.
+pub fn task_trend(
+    daily_task_totals: &BTreeMap<NaiveDate, [chrono::Duration; 8]>,
+    task_number: u8,
+    to: NaiveDate,
+    days: u64,
+) -> Vec<u64> {
+    let index = (task_number as usize).saturating_sub(1).min(7);
+    let from = to - chrono::Days::new(days.saturating_sub(1));
+    from.iter_days()
+        .take(days as usize)
+        .map(|date| {
+            daily_task_totals.get(&date).map_or(0, |tasks| {
+                u64::try_from(tasks[index].num_minutes()).unwrap_or_default()
+            })
+        })
+        .collect()
+}
+
+/// A quick look back at one day: shown when first starting the app on a new
+/// day, for the day before, and when quitting, for the day so far
+#[derive(Debug, Clone)]
+pub struct DaySummary {
+    pub date: NaiveDate,
+    /// Counting time spent on parallel tasks only once
+    pub total: chrono::Duration,
+    /// The tasks that were tracked, longest first
+    pub tasks: Vec<(u8, chrono::Duration)>,
+    /// The untracked stretches of the day's work hours
+    pub gaps: Vec<gaps::Gap>,
+}
+
+impl DaySummary {
+    /// Sums up `logs`, with any entries still open running until `now`
+    pub fn new(
+        date: NaiveDate,
+        logs: &[TimeLog],
+        prefs: &Preferences,
+        now: DateTime<Local>,
+    ) -> Self {
+        let closed: Vec<TimeLog> = logs
+            .iter()
+            .cloned()
+            .map(|tl| TimeLog {
+                end: tl.end.or(Some(now)),
+                ..tl
             })
-            .unzip();
-
-        Ok((
-            compute_stats(logs.into_iter().flatten()),
-            dates.into_iter().min(),
-        ))
-    } else {
-        warn!("Unable to load history: cannot locate and/or open save file directory");
-        Ok(([TimeStats::default(); 8], None))
+            .collect();
+        let mut tasks: Vec<(u8, chrono::Duration)> = compute_stats(closed.iter().cloned(), None)
+            .into_iter()
+            .filter(|ts| ts.total > chrono::Duration::zero())
+            .map(|ts| (ts.task_number, ts.total))
+            .collect();
+        tasks.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+
+        Self {
+            date,
+            total: durations::covered(&closed, None),
+            tasks,
+            gaps: gaps::find_gaps(
+                date,
+                &closed,
+                &[],
+                &gaps::HourHistogram::default(),
+                gaps::work_hours(prefs),
+                now,
+            ),
+        }
+    }
+
+    pub fn untracked(&self) -> chrono::Duration {
+        self.gaps
+            .iter()
+            .fold(chrono::Duration::zero(), |acc, gap| acc + gap.duration())
+    }
+}
+
+/// How a timesheet buckets days into columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Period {
+    #[default]
+    IsoWeek,
+    Month,
+}
+
+/// One column of a timesheet: the total time spent on each task number during
+/// one week or month
+#[derive(Debug, Clone)]
+pub struct TimesheetColumn {
+    pub label: String,
+    /// The first day of the week or month
+    pub start: NaiveDate,
+    pub totals: [chrono::Duration; 8],
+}
+
+/// Buckets each day's logs into ISO weeks or calendar months, in chronological
+/// order. Periods with no save files at all are left out.
+pub fn compute_timesheet(
+    dated_logs: &[(NaiveDate, Vec<TimeLog>)],
+    period: Period,
+) -> Vec<TimesheetColumn> {
+    let mut columns: BTreeMap<(i32, u32), [chrono::Duration; 8]> = BTreeMap::new();
+    for (date, logs) in dated_logs {
+        let key = match period {
+            Period::IsoWeek => (date.iso_week().year(), date.iso_week().week()),
+            Period::Month => (date.year(), date.month()),
+        };
+        let totals = columns.entry(key).or_insert([chrono::Duration::zero(); 8]);
+        for log in logs {
+            if let Some(total) = totals.get_mut((log.number as usize).wrapping_sub(1)) {
+                // Count only the part of each entry that's on the file's date
+                *total = *total + durations::elapsed_on_day(log, *date, None);
+            }
+        }
+    }
+
+    columns
+        .into_iter()
+        .map(|((year, n), totals)| TimesheetColumn {
+            label: match period {
+                Period::IsoWeek => format!("{}-W{:02}", year, n),
+                Period::Month => format!("{}-{:02}", year, n),
+            },
+            start: match period {
+                Period::IsoWeek => NaiveDate::from_isoywd_opt(year, n, chrono::Weekday::Mon),
+                Period::Month => NaiveDate::from_ymd_opt(year, n, 1),
+            }
+            .expect("periods are made from real dates"),
+            totals,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn typical_workday() {
+        let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(
+            mean_workday([(hm(9, 0), hm(17, 0)), (hm(9, 24), hm(18, 36))]),
+            Some((hm(9, 12), hm(17, 48)))
+        );
+        assert_eq!(mean_workday([]), None);
+    }
+
+    #[test]
+    fn entry_lengths() {
+        let start = Local.with_ymd_and_hms(2023, 6, 5, 9, 0, 0).unwrap();
+        let entry = |day, mins| {
+            let start = start + chrono::Duration::days(day);
+            TimeLog::new(2, start, Some(start + chrono::Duration::minutes(mins))).unwrap()
+        };
+        let days: Vec<DayStats> = [vec![entry(0, 10), entry(0, 60)], vec![entry(1, 20)]]
+            .iter()
+            .map(|logs| DayStats::compute(logs, None))
+            .collect();
+        assert_eq!(days[0].tasks[1].days, 1);
+        let (tasks, _) = add_up(&days);
+        assert_eq!(tasks[1].median, chrono::Duration::minutes(20));
+        assert_eq!(tasks[1].longest, chrono::Duration::minutes(60));
+        assert_eq!(tasks[1].days, 2);
+        assert_eq!(tasks[0].median, chrono::Duration::zero());
+        assert_eq!(
+            median(&mut [chrono::Duration::minutes(10), chrono::Duration::minutes(20)]),
+            chrono::Duration::minutes(15)
+        );
+    }
+
+    #[test]
+    fn parallel_day_total() {
+        let at = |h| Local.with_ymd_and_hms(2023, 6, 5, h, 0, 0).unwrap();
+        // On call all morning, with a meeting in the middle of it and
+        // something else after
+        let logs = [
+            TimeLog::new(1, at(9), Some(at(12))).unwrap(),
+            TimeLog::new(2, at(10), Some(at(11))).unwrap(),
+            TimeLog::new(3, at(13), Some(at(14))).unwrap(),
+        ];
+        let day = DayStats::compute(&logs, None);
+        assert_eq!(day.total(), chrono::Duration::hours(4));
+        assert_eq!(day.overlap, chrono::Duration::hours(1));
+        let cached: DayStats = ron::from_str(&ron::to_string(&day).unwrap()).unwrap();
+        assert_eq!(cached.total(), chrono::Duration::hours(4));
+    }
+
+    #[test]
+    fn untracked_work_hours() {
+        let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        // A Monday, with 9 to 5 work hours by default
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let at = |h, m| date.and_time(hm(h, m)).and_local_timezone(Local).unwrap();
+        let logs = [
+            TimeLog::new(1, at(8, 0), Some(at(10, 0))).unwrap(),
+            TimeLog::new(2, at(9, 30), Some(at(12, 0))).unwrap(),
+            TimeLog::new(3, at(13, 0), Some(at(16, 0))).unwrap(),
+            TimeLog::new(4, at(16, 30), None).unwrap(),
+        ];
+        let busy = busy_times(&logs);
+        assert_eq!(busy, [(hm(8, 0), hm(12, 0)), (hm(13, 0), hm(16, 0))]);
+
+        let prefs = Preferences::default();
+        let daily = BTreeMap::from([(date, busy)]);
+        let evening = at(20, 0);
+        assert_eq!(
+            untracked_work_time(&prefs, &daily, date, date, evening),
+            chrono::Duration::hours(2)
+        );
+        // Tuesday had no file at all, and the weekend doesn't count
+        let sunday = date + chrono::Days::new(6);
+        assert_eq!(
+            untracked_work_time(
+                &prefs,
+                &daily,
+                date,
+                sunday,
+                evening + chrono::Duration::days(6)
+            ),
+            chrono::Duration::hours(2 + 4 * 8)
+        );
+        assert_eq!(
+            untracked_work_time(&prefs, &daily, date, date, at(12, 30)),
+            chrono::Duration::minutes(30)
+        );
+    }
+
+    #[test]
+    fn trends() {
+        let date = |d| NaiveDate::from_ymd_opt(2023, 6, d).unwrap();
+        let mut totals = [chrono::Duration::zero(); 8];
+        totals[2] = chrono::Duration::minutes(90);
+        let daily = BTreeMap::from([(date(1), totals), (date(3), totals)]);
+        assert_eq!(task_trend(&daily, 3, date(4), 5), [0, 90, 0, 90, 0]);
+        assert_eq!(task_trend(&daily, 1, date(3), 3), [0, 0, 0]);
+        assert!(task_trend(&daily, 3, date(3), 0).is_empty());
+    }
+
+    #[test]
+    fn category_rollup() {
+        let stats: Vec<TimeStats> = (1..=4)
+            .map(|n| TimeStats {
+                task_number: n,
+                count: 1,
+                total: chrono::Duration::hours(n.into()),
+                ..Default::default()
+            })
+            .collect();
+        let categories = [
+            Category {
+                name: "Internal".to_string(),
+                tasks: vec![1, 2],
+            },
+            Category {
+                name: "Billable".to_string(),
+                tasks: vec![3, 2],
+            },
+        ];
+        let rollup = group_by_category(&stats, &categories);
+        let names: Vec<&str> = rollup.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Uncategorized", "Internal", "Billable"]);
+        // Task 2 only counts toward the first category it's in
+        assert_eq!(rollup[1].total, chrono::Duration::hours(3));
+        assert_eq!(rollup[1].tasks[0].task_number, 2);
+        assert_eq!(rollup[2].total, chrono::Duration::hours(3));
+
+        // With every task in a category, there's nothing uncategorized to show
+        assert_eq!(group_by_category(&stats[..3], &categories).len(), 2);
+    }
+
+    #[test]
+    fn client_rollup() {
+        let stats: Vec<TimeStats> = (1..=3)
+            .map(|n| TimeStats {
+                task_number: n,
+                count: 2,
+                total: chrono::Duration::hours(n.into()),
+                billed: chrono::Duration::hours(n.into()),
+                ..Default::default()
+            })
+            .collect();
+        let tag = |task_number, tag: &str, mins| TagStats {
+            task_number,
+            tag: tag.to_string(),
+            count: 1,
+            total: chrono::Duration::minutes(mins),
+            billed: chrono::Duration::minutes(mins),
+        };
+        let tags = [tag(1, "acme", 30), tag(2, "acme", 15), tag(3, "globex", 45)];
+        let clients = [ClientGroup {
+            name: "Acme".to_string(),
+            tasks: vec![2],
+            tags: vec!["acme".to_string()],
+        }];
+        let rates = [100.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+
+        let rollup = group_by_client(&stats, &tags, &clients, Some(&rates));
+        assert_eq!(rollup.len(), 1);
+        // All of task 2, and the tagged half hour of task 1, without counting
+        // task 2's tagged time twice
+        assert_eq!(rollup[0].total, chrono::Duration::minutes(150));
+        assert_eq!(rollup[0].count, 3);
+        assert_eq!(rollup[0].earnings, Some(50.0));
+    }
+
+    #[test]
+    fn day_summary() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let at = |h, m| {
+            date.and_hms_opt(h, m, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let logs = [
+            TimeLog {
+                start: at(9, 0),
+                end: Some(at(12, 0)),
+                number: 2,
+                ..Default::default()
+            },
+            TimeLog {
+                start: at(13, 0),
+                end: Some(at(14, 0)),
+                number: 5,
+                ..Default::default()
+            },
+            // Left open, so it runs until "now"
+            TimeLog {
+                start: at(15, 0),
+                end: None,
+                number: 5,
+                ..Default::default()
+            },
+        ];
+        let summary = DaySummary::new(date, &logs, &Preferences::default(), at(16, 0));
+        assert_eq!(summary.total, chrono::Duration::hours(5));
+        assert_eq!(
+            summary.tasks,
+            [
+                (2, chrono::Duration::hours(3)),
+                (5, chrono::Duration::hours(2))
+            ]
+        );
+        // 12-13 and 14-15, with 16-17 being after "now"
+        assert_eq!(summary.untracked(), chrono::Duration::hours(2));
     }
 }
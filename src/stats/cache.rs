@@ -0,0 +1,148 @@
+// Keeps each day's stats in "stats-cache.ron" in the OS cache directory, so
+// that visiting the Stats page again only parses the daily logs that changed
+// since last time. Days are keyed by their file's modified time; any change to
+// the file, or to the billing rounding or profile the stats were computed
+// with, makes the cached copy stale.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::DayStats;
+use crate::{
+    crypto,
+    durations::BillingRounding,
+    error::{self, YdncError},
+    storage::Dirs,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDay {
+    modified: SystemTime,
+    stats: DayStats,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StatsCache {
+    rounding: Option<BillingRounding>,
+    /// The profile whose entries were counted, None for the default one
+    #[serde(default)]
+    profile: Option<String>,
+    days: HashMap<NaiveDate, CachedDay>,
+    /// Whether anything needs saving
+    #[serde(skip)]
+    changed: bool,
+    /// Where it's kept
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl StatsCache {
+    /// Loads the cache, starting over if there isn't one, it can't be read, or
+    /// it was computed with a different billing rounding or profile
+    pub fn load(dirs: &Dirs, rounding: Option<BillingRounding>, profile: Option<&str>) -> Self {
+        let path = dirs.cache().join("stats-cache.ron");
+        let empty = Self {
+            rounding,
+            profile: profile.map(String::from),
+            path: path.clone(),
+            ..Default::default()
+        };
+        let cache = crypto::read(&path)
+            .map_err(YdncError::from)
+            .and_then(|contents| {
+                ron::de::from_bytes::<Self>(&contents)
+                    .map_err(|e| YdncError::from(e).in_file(&path))
+            });
+        match cache {
+            Ok(cache) if cache.rounding == rounding && cache.profile.as_deref() == profile => {
+                Self { path, ..cache }
+            }
+            Ok(_) => {
+                info!("Billing rounding or profile changed, starting a new stats cache");
+                empty
+            }
+            Err(e) if e.is_not_found() => empty,
+            Err(e) => {
+                warn!("Unable to load the stats cache, starting over: {}", e);
+                empty
+            }
+        }
+    }
+
+    /// The cached stats for `date`, if its file hasn't changed since
+    pub fn get(&self, date: NaiveDate, modified: SystemTime) -> Option<&DayStats> {
+        self.days
+            .get(&date)
+            .filter(|day| day.modified == modified)
+            .map(|day| &day.stats)
+    }
+
+    pub fn insert(&mut self, date: NaiveDate, modified: SystemTime, stats: DayStats) {
+        self.days.insert(date, CachedDay { modified, stats });
+        self.changed = true;
+    }
+
+    /// All the cached stats within the given (inclusive) dates, however stale,
+    /// for when the daily logs can't be read
+    pub fn days_within(
+        &self,
+        min_date: Option<NaiveDate>,
+        max_date: Option<NaiveDate>,
+    ) -> BTreeMap<NaiveDate, DayStats> {
+        self.days
+            .iter()
+            .filter(|(date, _)| {
+                min_date.is_none_or(|min| **date >= min) && max_date.is_none_or(|max| **date <= max)
+            })
+            .map(|(date, day)| (*date, day.stats.clone()))
+            .collect()
+    }
+
+    /// Forgets the days within the given (inclusive) dates that aren't in
+    /// `present`, i.e. whose files have been deleted or archived
+    pub fn prune(
+        &mut self,
+        min_date: Option<NaiveDate>,
+        max_date: Option<NaiveDate>,
+        present: &HashSet<NaiveDate>,
+    ) {
+        let before = self.days.len();
+        self.days.retain(|date, _| {
+            min_date.is_some_and(|min| *date < min)
+                || max_date.is_some_and(|max| *date > max)
+                || present.contains(date)
+        });
+        self.changed |= self.days.len() != before;
+    }
+
+    /// Saves the cache if anything changed since it was loaded
+    pub fn save(&mut self) -> error::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let contents = ron::ser::to_string(self)?;
+        crypto::write(&self.path, contents.into_bytes())?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+/// Serializes a chrono::Duration as whole milliseconds
+pub mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &chrono::Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i64(d.num_milliseconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<chrono::Duration, D::Error> {
+        i64::deserialize(d).map(chrono::Duration::milliseconds)
+    }
+}
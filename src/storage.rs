@@ -0,0 +1,142 @@
+// Where the app keeps its files. By default that's the OS's usual places for
+// app data (the daily logs, plans, breaks...), config (settings, bundles,
+// hooks, secrets) and caches. Starting the app with `--data-dir DIR`, or with
+// YDNC_TIME_DATA_DIR set, keeps all of them in that one directory instead,
+// with caches in a "cache" folder inside it: for portable installs, folders
+// synced with Dropbox or Syncthing, and keeping tests away from real data.
+//
+// The location is settled once at startup into a `Dirs`, before anything is
+// loaded, which is then handed to everything that loads or saves rather than
+// it working the location out for itself. Directories are only created when
+// something is saved in them, never just to look for a file. It also carries
+// whether this copy of the app may write there (see the instance module) and
+// the logs recovered there (see the recovery module), which its clones all
+// share and other `Dirs` don't.
+
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use directories::ProjectDirs;
+use tracing::info;
+
+use crate::{instance::Instance, recovery::Recoveries};
+
+/// The environment variable that moves everything into one directory, like
+/// `--data-dir` does
+const DATA_DIR_VAR: &str = "YDNC_TIME_DATA_DIR";
+
+/// Where the app keeps its files
+#[derive(Debug, Clone, Default)]
+pub struct Dirs {
+    data: PathBuf,
+    config: PathBuf,
+    cache: PathBuf,
+    instance: Arc<Instance>,
+    recoveries: Arc<Recoveries>,
+}
+
+/// The same directories, however this copy stands on writing to them
+impl PartialEq for Dirs {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.data, &self.config, &self.cache) == (&other.data, &other.config, &other.cache)
+    }
+}
+
+impl Eq for Dirs {}
+
+impl Dirs {
+    /// Everything in `dir`, with caches in a folder of their own
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self {
+            config: dir.clone(),
+            cache: dir.join("cache"),
+            data: dir,
+            instance: Default::default(),
+            recoveries: Default::default(),
+        }
+    }
+
+    /// The directories to use, with `dir` from `--data-dir` if it was given.
+    /// None if there's no directory given and this OS has no usual ones.
+    pub fn locate(dir: Option<PathBuf>) -> Option<Self> {
+        let dirs = resolve(dir, env::var_os(DATA_DIR_VAR))?;
+        info!("Keeping data in {}", dirs.data.display());
+        Some(dirs)
+    }
+
+    /// The directory for the daily logs and everything else the app saves
+    pub fn data(&self) -> &Path {
+        &self.data
+    }
+
+    /// The directory for the settings file and the user's own bundles, hooks
+    /// and secrets
+    pub fn config(&self) -> &Path {
+        &self.config
+    }
+
+    /// The directory for files that can always be made again
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+
+    /// Whether this copy of the app may write here
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// Corrupt logs recovered here (see the recovery module)
+    pub fn recoveries(&self) -> &Recoveries {
+        &self.recoveries
+    }
+}
+
+/// Picks the directory given on the command line, else the one in the
+/// environment variable, else the OS's usual directories. None if it's the
+/// latter and this OS doesn't have any.
+fn resolve(dir: Option<PathBuf>, var: Option<OsString>) -> Option<Dirs> {
+    let dir = dir.or_else(|| var.filter(|v| !v.is_empty()).map(PathBuf::from));
+    match dir {
+        Some(dir) => Some(Dirs::in_dir(dir)),
+        None => ProjectDirs::from_path(PathBuf::from("ydnc/time")).map(|d| Dirs {
+            data: d.data_dir().to_path_buf(),
+            config: d.preference_dir().to_path_buf(),
+            cache: d.cache_dir().to_path_buf(),
+            instance: Default::default(),
+            recoveries: Default::default(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_dir_override() {
+        let portable = Dirs::in_dir(PathBuf::from("/mnt/usb/time"));
+        assert_eq!(portable.config, PathBuf::from("/mnt/usb/time"));
+        assert_eq!(portable.cache, PathBuf::from("/mnt/usb/time/cache"));
+
+        // The command line beats the environment, which beats the default
+        assert_eq!(
+            resolve(
+                Some(PathBuf::from("/mnt/usb/time")),
+                Some(OsString::from("/sync/time"))
+            ),
+            Some(portable)
+        );
+        assert_eq!(
+            resolve(None, Some(OsString::from("/sync/time"))),
+            Some(Dirs::in_dir(PathBuf::from("/sync/time")))
+        );
+        assert_ne!(
+            resolve(None, Some(OsString::new())),
+            Some(Dirs::in_dir(PathBuf::new()))
+        );
+    }
+}
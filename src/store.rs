@@ -0,0 +1,198 @@
+// Reading and writing the app's logs from other Rust programs, like a script
+// that bills from last month's entries or a tool that logs time from
+// somewhere else, without going through the TUI or its `App`. `Storage` is a
+// handle on a data directory; the crate root re-exports it along with
+// `TimeLog`, the stats and the export formats, which make up the library's
+// stable surface.
+//
+// Days are read as they are on disk: a corrupt one is an error here rather
+// than being recovered, since the app is the one that should tell the user.
+// Writing takes the same lock the app does, so it fails while the app is
+// running rather than having the app save over what was written. Each
+// `Storage` has its own say on that, kept on its `Dirs`, so that two in one
+// process don't share one lock or read-only state.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDate};
+
+use crate::{
+    archive, crypto, day_file_in,
+    instance::{self, InstanceLock},
+    legacy_day_file_in, migrate, read_log_file, save_log_file,
+    stats::{self, DatedLogs},
+    storage::Dirs,
+    Preferences, TimeLog,
+};
+
+/// Where an install of the app keeps its logs and settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Storage {
+    dirs: Dirs,
+}
+
+impl Storage {
+    /// The directories the app itself uses when started without
+    /// `--data-dir`: the one in YDNC_TIME_DATA_DIR if that's set, else the
+    /// OS's usual ones. None if this OS doesn't have any.
+    pub fn open_default() -> Option<Self> {
+        Dirs::locate(None).map(|dirs| Self { dirs })
+    }
+
+    /// Everything in `dir`, like the app started with `--data-dir dir`
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dirs: Dirs::in_dir(dir.into()),
+        }
+    }
+
+    /// The directory the daily logs are in
+    pub fn data_dir(&self) -> &Path {
+        self.dirs.data()
+    }
+
+    /// The entries logged on `date`, sorted by start time. None logged is
+    /// no entries rather than an error.
+    pub fn load_day(&self, date: NaiveDate) -> io::Result<Vec<TimeLog>> {
+        for path in [
+            day_file_in(self.dirs.data(), date),
+            legacy_day_file_in(self.dirs.data(), date),
+        ] {
+            match read_log_file(&path) {
+                Err(e) if e.is_not_found() => continue,
+                r => return Ok(r?),
+            }
+        }
+        match archive::load_archived_day(self.dirs.data(), date) {
+            Err(e) if e.is_not_found() => Ok(Vec::new()),
+            r => Ok(r?),
+        }
+    }
+
+    /// Every day with a log within the given (inclusive) dates, oldest first.
+    /// Days that can't be read are skipped with a warning.
+    pub fn load_range(
+        &self,
+        min_date: Option<NaiveDate>,
+        max_date: Option<NaiveDate>,
+    ) -> io::Result<DatedLogs> {
+        stats::load_dated_logs_in(self.dirs.data(), min_date, max_date, read_log_file)
+    }
+
+    /// Replaces the log for `date` with `logs`. Fails while the app is
+    /// running, since it would save over them.
+    pub fn save_day(&self, date: NaiveDate, logs: &[TimeLog]) -> io::Result<()> {
+        let _lock = self.lock()?;
+        self.write_day(date, logs)
+    }
+
+    /// Adds `entry` to the log for the day it started on, holding the lock
+    /// from reading the day to saving it again
+    pub fn add_entry(&self, entry: TimeLog) -> io::Result<()> {
+        let _lock = self.lock()?;
+        let date = entry.start.with_timezone(&Local).date_naive();
+        let mut logs = self.load_day(date)?;
+        logs.push(entry);
+        self.write_day(date, &logs)
+    }
+
+    /// `save_day` for when the lock's already held
+    fn write_day(&self, date: NaiveDate, logs: &[TimeLog]) -> io::Result<()> {
+        let path = day_file_in(self.dirs.data(), date);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut logs = logs.to_vec();
+        logs.sort_by_key(|tl| tl.start);
+        save_log_file(&path, &logs)?;
+        match std::fs::remove_file(legacy_day_file_in(self.dirs.data(), date)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// The user's settings, or the defaults if they've never changed any
+    pub fn load_preferences(&self) -> io::Result<Preferences> {
+        match crypto::read(&self.dirs.config().join("settings.ron")) {
+            Ok(contents) => Ok(migrate::from_ron(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Preferences::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Takes the app's lock on the data directory for as long as it's held,
+    /// unless this already has it (see the instance module)
+    fn lock(&self) -> io::Result<Option<InstanceLock>> {
+        instance::lock_for_write(&self.dirs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn storage_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ydnc-store-{}", std::process::id()));
+        let storage = Storage::open(&dir);
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let start = Local.with_ymd_and_hms(2023, 6, 5, 9, 0, 0).unwrap();
+
+        assert!(storage.load_day(date).unwrap().is_empty());
+        assert!(TimeLog::new(9, start, None).is_err());
+        storage
+            .add_entry(
+                TimeLog::new(
+                    3,
+                    start + Duration::hours(1),
+                    Some(start + Duration::hours(2)),
+                )
+                .unwrap()
+                .with_tags(vec![String::from("review")]),
+            )
+            .unwrap();
+        storage
+            .add_entry(TimeLog::new(2, start, Some(start + Duration::minutes(30))).unwrap())
+            .unwrap();
+
+        let day = storage.load_day(date).unwrap();
+        assert_eq!(day.iter().map(TimeLog::number).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(day[1].tags(), ["review"]);
+
+        let logs = storage.load_range(Some(date), Some(date)).unwrap();
+        let stats = stats::compute_stats(logs.into_iter().flat_map(|(_, day)| day), None);
+        assert_eq!(stats[1].total, Duration::minutes(30));
+        assert_eq!(stats[2].count, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn storages_apart() {
+        let dir = std::env::temp_dir().join(format!("ydnc-stores-{}", std::process::id()));
+        let date = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let holder = Storage::open(&dir);
+        let other = Storage::open(&dir);
+
+        // One holding the lock for good keeps writing, and the other can't
+        assert!(instance::acquire(&holder.dirs));
+        holder.save_day(date, &[]).unwrap();
+        let err = other.save_day(date, &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(other.load_day(date).unwrap().is_empty());
+
+        // Read-only is only for the one made so
+        let elsewhere = Storage::open(dir.join("elsewhere"));
+        other.dirs.instance().make_read_only();
+        let err = other.save_day(date, &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        elsewhere.save_day(date, &[]).unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
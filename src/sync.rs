@@ -0,0 +1,528 @@
+// Keeps the logs of the user's devices in step through a folder they share
+// between them with Syncthing, Dropbox or the like, set as `sync_dir`. Each
+// device only ever appends to a journal of its own in there, named for the
+// device, a line at a time: every time it saves a day's log, it adds a line
+// with the entries it tracked that day. Other devices' journals are merged in
+// at startup and on every autosave, in the background. Since no two devices
+// write the same file, the sync tool never has conflicts of its own to sort
+// out, and since lines are only ever added, it never sees a file half
+// rewritten either. Journals are compacted down to each day's latest line
+// once they've grown long, by writing the shorter copy next to the journal and
+// swapping it in.
+//
+// Conflicts between devices are settled by ownership: an entry belongs to the
+// device that tracked it, and only that device's latest word on a day counts
+// for it. Merging a day swaps what's there from that device for its latest
+// entries, which carries its edits and deletions across too. Entries synced
+// from elsewhere are tagged with the device they came from, and are never
+// journaled again by the device they were synced to. Entries still going on
+// a device only come across once they're finished.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    env, fs,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    crypto, error, load_log_for, migrate, save_log_for, storage::Dirs, Notifier, Preferences,
+    TimeLog,
+};
+
+/// Journals with this many more lines than days get compacted
+const COMPACT_AFTER: usize = 500;
+
+/// One day's entries as tracked on a device, as of when it was saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Event {
+    date: NaiveDate,
+    entries: Vec<TimeLog>,
+}
+
+/// A device's journal, oldest event first
+type Journal = Vec<Event>;
+
+/// Each other device's latest entries for a day, by device name
+pub type Updates = Vec<(String, Vec<TimeLog>)>;
+
+/// Where a journal is and the entries it last gave for each day
+type Said = (PathBuf, HashMap<NaiveDate, Vec<TimeLog>>);
+
+/// Journals used to be written whole, as one list of events
+impl migrate::Migrate for Journal {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        migrate::parse_data(version, contents)
+    }
+}
+
+impl migrate::Migrate for Event {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        migrate::parse_data(version, contents)
+    }
+}
+
+/// Syncing one data directory's logs through a shared folder. The app owns
+/// one, and hands clones of it to the saver and to pulls in the background,
+/// which all share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct SyncFolder {
+    /// The shared folder and this device's name in it, if syncing is on, and
+    /// where to send failures
+    config: Arc<Mutex<Option<(PathBuf, String, Notifier)>>>,
+    /// What this device's journal last said about each day, so that saving
+    /// doesn't have to read the journal again, once it's been read
+    last_said: Arc<Mutex<Option<Said>>>,
+    /// Whether other devices' journals are being read in the background
+    pulling: Arc<AtomicBool>,
+    /// What the last pull found, until the app takes it in
+    pulled: Arc<Mutex<Option<Pulled>>>,
+}
+
+/// What this device is called in the shared folder: the name set in the
+/// preferences, else the machine's hostname, else a name made up the first
+/// time and kept in the data directory. Anything that can't go in a file name
+/// is left out.
+pub fn device_name(dirs: &Dirs, prefs: &Preferences) -> String {
+    let clean = |name: String| -> Option<String> {
+        let name: String = name
+            .trim()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        (!name.is_empty()).then_some(name)
+    };
+    prefs
+        .device_name
+        .clone()
+        .and_then(clean)
+        .or_else(|| fs::read_to_string("/etc/hostname").ok().and_then(clean))
+        .or_else(|| env::var("COMPUTERNAME").ok().and_then(clean))
+        .or_else(|| env::var("HOSTNAME").ok().and_then(clean))
+        .unwrap_or_else(|| generated_device_name(dirs))
+}
+
+/// A name for this device that no other device will have, made up the first
+/// time it's needed, e.g. on macOS where there's no hostname to go by. Two
+/// devices sharing a name would each take the other's entries for their own.
+fn generated_device_name(dirs: &Dirs) -> String {
+    let path = dirs.data().join("device-id");
+    if let Some(name) = fs::read_to_string(&path)
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+    {
+        return name;
+    }
+    let name = format!("device-{:016x}", RandomState::new().build_hasher().finish());
+    if let Err(e) = fs::create_dir_all(dirs.data()).and_then(|()| fs::write(&path, &name)) {
+        warn!(
+            "Unable to save the device name to {}: {}",
+            path.display(),
+            e
+        );
+    }
+    name
+}
+
+fn journal_path(dir: &Path, device: &str) -> PathBuf {
+    dir.join(format!("{}.journal", device))
+}
+
+/// Reads a journal, whichever way it was written. Lines that don't parse,
+/// like the last one while it's still arriving, are left out.
+fn load_journal(path: &Path) -> error::Result<Journal> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    if let Ok(journal) = crypto::read(path)
+        .map_err(error::YdncError::from)
+        .and_then(|whole| migrate::from_ron::<Journal>(&whole))
+    {
+        return Ok(journal);
+    }
+    Ok(String::from_utf8_lossy(&contents)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            crypto::decode_line(line)
+                .map_err(error::YdncError::from)
+                .and_then(|line| migrate::from_ron::<Event>(&line))
+                .map_err(|e| warn!("Skipping a line of {}: {}", path.display(), e))
+                .ok()
+        })
+        .collect())
+}
+
+/// Writes `journal` as a whole, as one line per event, next to `path` and
+/// then swaps it in
+fn write_journal(path: &Path, journal: &[Event]) -> error::Result<()> {
+    let mut contents = String::new();
+    for event in journal {
+        contents.push_str(&crypto::encode_line(migrate::to_ron(event, false)?)?);
+        contents.push('\n');
+    }
+    let tmp = path.with_extension("journal.tmp");
+    fs::write(&tmp, contents)?;
+    Ok(fs::rename(tmp, path)?)
+}
+
+/// What this device's journal last said about each day. Reading it for the
+/// first time also converts a journal written whole to lines, and compacts
+/// one that's grown long.
+fn read_own_journal(path: &Path) -> error::Result<HashMap<NaiveDate, Vec<TimeLog>>> {
+    let whole = crypto::read(path)
+        .ok()
+        .and_then(|contents| migrate::from_ron::<Journal>(&contents).ok());
+    let was_whole = whole.is_some();
+    let journal = match whole {
+        Some(journal) => journal,
+        None => load_journal(path)?,
+    };
+    let events = journal.len();
+    let latest = latest_per_day(journal);
+    if was_whole || events > latest.len() + COMPACT_AFTER {
+        info!(
+            "Rewriting {}, {} events down to {}",
+            path.display(),
+            events,
+            latest.len()
+        );
+        write_journal(path, &latest)?;
+    }
+    Ok(latest.into_iter().map(|e| (e.date, e.entries)).collect())
+}
+
+/// What a pull in the background found
+#[derive(Debug)]
+pub struct Pulled {
+    /// Other devices' latest entries for `today`, for the app to merge into
+    /// its own copy of the day
+    pub today: Option<Updates>,
+    /// How many past days were merged and saved
+    pub merged_days: usize,
+    pub error: Option<String>,
+}
+
+impl SyncFolder {
+    /// Turns syncing through `dir` on, or off if None. Failures are sent to
+    /// `notifier`.
+    pub fn set_dir(&self, dir: Option<PathBuf>, device: String, notifier: Notifier) {
+        *self.config.lock().unwrap() = dir.map(|dir| {
+            info!("Syncing through {} as {}", dir.display(), device);
+            (dir, device, notifier)
+        });
+    }
+
+    fn config(&self) -> Option<(PathBuf, String, Notifier)> {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Appends the entries this device tracked on `date` to its journal, unless
+    /// they're what it last said about that day anyway. Failures are sent on
+    /// rather than failing the save, which has already happened.
+    pub fn record(&self, date: NaiveDate, logs: &[TimeLog]) {
+        let Some((dir, device, notifier)) = self.config() else {
+            return;
+        };
+        let event = Event {
+            date,
+            entries: logs
+                .iter()
+                .filter(|tl| tl.device.is_none())
+                .cloned()
+                .collect(),
+        };
+        if let Err(e) = self.append(&journal_path(&dir, &device), event) {
+            warn!("Unable to write to the sync journal: {}", e);
+            let _ = notifier.send(format!("Could not sync through {}: {}", dir.display(), e));
+        }
+    }
+
+    fn append(&self, path: &Path, event: Event) -> error::Result<()> {
+        let mut last_said = self.last_said.lock().unwrap();
+        if last_said.as_ref().is_none_or(|(p, _)| p != path) {
+            *last_said = Some((path.to_path_buf(), read_own_journal(path)?));
+        }
+        let Some((_, said)) = last_said.as_mut() else {
+            return Ok(());
+        };
+        if said.get(&event.date) == Some(&event.entries) {
+            return Ok(());
+        }
+        // Days with nothing tracked here don't need mentioning, unless entries
+        // were mentioned before and have since been deleted
+        if event.entries.is_empty() && !said.contains_key(&event.date) {
+            return Ok(());
+        }
+
+        let mut line = crypto::encode_line(migrate::to_ron(&event, false)?)?;
+        line.push('\n');
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        // A line cut short, e.g. by a crash, mustn't swallow this one
+        if file.metadata()?.len() > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        said.insert(event.date, event.entries);
+        Ok(())
+    }
+
+    /// What every other device last said about each day it tracked anything on,
+    /// as (date, [(device, entries)]) with its entries tagged with the device
+    pub fn pull(&self) -> io::Result<Vec<(NaiveDate, Updates)>> {
+        let Some((dir, me, _)) = self.config() else {
+            return Ok(Vec::new());
+        };
+        let mut days: Vec<(NaiveDate, Updates)> = Vec::new();
+        for file in fs::read_dir(&dir)? {
+            let path = file?.path();
+            let device = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => match name.strip_suffix(".journal") {
+                    Some(device) if device != me => device.to_string(),
+                    _ => continue,
+                },
+                None => continue,
+            };
+            let journal = match load_journal(&path) {
+                Ok(journal) => journal,
+                Err(e) => {
+                    // Likely still arriving, so try again next time
+                    warn!("Unable to read {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            for event in latest_per_day(journal) {
+                // Entries still going there come across once they're finished,
+                // rather than looking like they're being tracked here
+                let entries = event
+                    .entries
+                    .into_iter()
+                    .filter(|tl| !tl.is_open())
+                    .map(|tl| TimeLog {
+                        device: Some(device.clone()),
+                        ..tl
+                    })
+                    .collect();
+                match days.iter_mut().find(|(date, _)| *date == event.date) {
+                    Some((_, updates)) => updates.push((device.clone(), entries)),
+                    None => days.push((event.date, vec![(device.clone(), entries)])),
+                }
+            }
+        }
+        days.sort_by_key(|(date, _)| *date);
+        Ok(days)
+    }
+
+    /// Reads the other devices' journals in the background, merging them into the
+    /// logs of days before `today` and saving those. Today is left for the app,
+    /// which has it open, to take from `take_pulled`. Does nothing if a pull is
+    /// going already.
+    pub fn start_pull(&self, dirs: &Dirs, today: NaiveDate) {
+        if self.config().is_none() || self.pulling.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let dirs = dirs.clone();
+        let sync = self.clone();
+        thread::spawn(move || {
+            let pulled = match sync.pull() {
+                Ok(days) => sync.merge_past_days(&dirs, today, days),
+                Err(e) => {
+                    warn!("Unable to read the sync folder: {}", e);
+                    Pulled {
+                        today: None,
+                        merged_days: 0,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            *sync.pulled.lock().unwrap() = Some(pulled);
+            sync.pulling.store(false, Ordering::SeqCst);
+        });
+    }
+
+    fn merge_past_days(
+        &self,
+        dirs: &Dirs,
+        today: NaiveDate,
+        days: Vec<(NaiveDate, Updates)>,
+    ) -> Pulled {
+        let mut pulled = Pulled {
+            today: None,
+            merged_days: 0,
+            error: None,
+        };
+        for (date, updates) in days {
+            if date == today {
+                pulled.today = Some(updates);
+                continue;
+            }
+            let result = match load_log_for(dirs, date) {
+                Err(e) if e.is_not_found() => Ok(Vec::new()),
+                r => r,
+            }
+            .and_then(|logs| {
+                let merged = merge_day(&logs, &updates);
+                if merged == logs {
+                    return Ok(false);
+                }
+                save_log_for(dirs, date, &merged)?;
+                self.record(date, &merged);
+                Ok(true)
+            });
+            match result {
+                Ok(true) => pulled.merged_days += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Unable to merge synced entries for {}: {}", date, e),
+            }
+        }
+        pulled
+    }
+
+    /// What the last pull found, once it's done
+    pub fn take_pulled(&self) -> Option<Pulled> {
+        self.pulled.lock().unwrap().take()
+    }
+}
+
+/// The last event for each day in `journal`
+fn latest_per_day(journal: Journal) -> Vec<Event> {
+    let mut latest: Vec<Event> = Vec::new();
+    for event in journal {
+        match latest.iter_mut().find(|e| e.date == event.date) {
+            Some(e) => *e = event,
+            None => latest.push(event),
+        }
+    }
+    latest
+}
+
+/// `local` with each updated device's entries swapped for its latest ones.
+/// Comes out in the same order whichever order the updates arrive in.
+pub fn merge_day(local: &[TimeLog], updates: &[(String, Vec<TimeLog>)]) -> Vec<TimeLog> {
+    let mut merged: Vec<TimeLog> = local
+        .iter()
+        .filter(|tl| {
+            !updates
+                .iter()
+                .any(|(device, _)| tl.device.as_ref() == Some(device))
+        })
+        .cloned()
+        .chain(
+            updates
+                .iter()
+                .flat_map(|(_, entries)| entries.iter().cloned()),
+        )
+        .collect();
+    merged.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.device.cmp(&b.device)));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+
+    use super::*;
+
+    #[test]
+    fn merging_devices() {
+        let start = Local::now();
+        let entry = |number: u8, mins: i64, device: Option<&str>| TimeLog {
+            start: start + Duration::minutes(mins),
+            number,
+            device: device.map(String::from),
+            ..Default::default()
+        };
+        let local = vec![
+            entry(1, 0, None),
+            entry(2, 10, Some("laptop")),
+            entry(3, 20, Some("phone")),
+        ];
+
+        // The laptop edited its entry, and the phone deleted its one
+        let updates = vec![
+            (String::from("laptop"), vec![entry(4, 10, Some("laptop"))]),
+            (String::from("phone"), Vec::new()),
+        ];
+        let merged = merge_day(&local, &updates);
+        assert_eq!(
+            merged,
+            vec![entry(1, 0, None), entry(4, 10, Some("laptop"))]
+        );
+
+        // Devices that tracked at the same time both keep their entries, in
+        // the same order either way
+        let updates = [
+            (String::from("laptop"), vec![entry(5, 0, Some("laptop"))]),
+            (String::from("phone"), vec![entry(6, 0, Some("phone"))]),
+        ];
+        let reversed = [updates[1].clone(), updates[0].clone()];
+        assert_eq!(merge_day(&local, &updates), merge_day(&local, &reversed));
+        assert_eq!(merge_day(&local, &updates).len(), 3);
+
+        let journal = vec![
+            Event {
+                date: start.date_naive(),
+                entries: vec![entry(1, 0, None)],
+            },
+            Event {
+                date: start.date_naive(),
+                entries: Vec::new(),
+            },
+        ];
+        assert_eq!(latest_per_day(journal)[0].entries, Vec::new());
+    }
+
+    #[test]
+    fn folders_apart() {
+        let dir = std::env::temp_dir().join(format!("ydnc-sync-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (notifier, _notices) = std::sync::mpsc::channel();
+        let entry = TimeLog {
+            start: Local::now() - Duration::hours(1),
+            end: Some(Local::now()),
+            number: 2,
+            ..Default::default()
+        };
+        let date = entry.start.date_naive();
+
+        let laptop = SyncFolder::default();
+        laptop.set_dir(Some(dir.clone()), String::from("laptop"), notifier.clone());
+        let phone = SyncFolder::default();
+        phone.set_dir(Some(dir.clone()), String::from("phone"), notifier);
+        // Another data directory, which doesn't sync at all
+        let elsewhere = SyncFolder::default();
+
+        laptop.record(date, std::slice::from_ref(&entry));
+        elsewhere.record(date, std::slice::from_ref(&entry));
+        let days = phone.pull().unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].1[0].0, "laptop");
+        assert_eq!(days[0].1[0].1[0].number, 2);
+        assert!(laptop.pull().unwrap().is_empty());
+        assert!(elsewhere.pull().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
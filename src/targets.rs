@@ -0,0 +1,245 @@
+// The hours the user expects to track on each day of the week, and how their
+// tracked time measures up against them, plus the most they want to spend on
+// each task in a day
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Weekday};
+
+use crate::{durations, gaps, utils, Preferences, TimeLog};
+
+/// The time the user expects to track on the given date. None if they haven't
+/// set any targets; days of the week left out of their targets expect nothing.
+pub fn target_for(prefs: &Preferences, date: NaiveDate) -> Option<chrono::Duration> {
+    let hours = prefs
+        .target_hours
+        .as_ref()?
+        .get(&date.weekday())
+        .copied()
+        .unwrap_or(0.0);
+    Some(chrono::Duration::seconds((hours * 3600.0).round() as i64))
+}
+
+/// How far ahead of (positive) or behind (negative) their targets the user is
+/// over every day from `from` through `to`, inclusive. Days missing from
+/// `tracked` count as untracked. None if they haven't set any targets.
+pub fn balance(
+    prefs: &Preferences,
+    tracked: &BTreeMap<NaiveDate, chrono::Duration>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Option<chrono::Duration> {
+    prefs.target_hours.as_ref()?;
+    Some(from.iter_days().take_while(|date| *date <= to).fold(
+        chrono::Duration::zero(),
+        |acc, date| {
+            let worked = tracked
+                .get(&date)
+                .copied()
+                .unwrap_or_else(chrono::Duration::zero);
+            acc + worked - target_for(prefs, date).unwrap_or_else(chrono::Duration::zero)
+        },
+    ))
+}
+
+/// The time expected on the given date for overtime: its target if the user
+/// has set targets, or else a full workday on work days and nothing otherwise
+pub fn expected_for(prefs: &Preferences, date: NaiveDate) -> chrono::Duration {
+    target_for(prefs, date).unwrap_or_else(|| {
+        if gaps::is_work_day(prefs, date) {
+            let (start, end) = gaps::work_hours(prefs);
+            (end - start).max(chrono::Duration::zero())
+        } else {
+            chrono::Duration::zero()
+        }
+    })
+}
+
+/// The time tracked over a day or a week next to the time expected of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overtime {
+    /// The day, or the first day of the week
+    pub start: NaiveDate,
+    pub tracked: chrono::Duration,
+    pub expected: chrono::Duration,
+}
+
+impl Overtime {
+    /// Positive for overtime, negative for undertime
+    pub fn balance(&self) -> chrono::Duration {
+        self.tracked - self.expected
+    }
+}
+
+/// The overtime of every day from `from` through `to`, inclusive. Days missing
+/// from `tracked` count as untracked.
+pub fn daily_overtime(
+    prefs: &Preferences,
+    tracked: &BTreeMap<NaiveDate, chrono::Duration>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<Overtime> {
+    from.iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| Overtime {
+            start: date,
+            tracked: tracked
+                .get(&date)
+                .copied()
+                .unwrap_or_else(chrono::Duration::zero),
+            expected: expected_for(prefs, date),
+        })
+        .collect()
+}
+
+/// Adds daily overtime up into weeks, which start on the user's week start day
+pub fn weekly_overtime(prefs: &Preferences, days: &[Overtime]) -> Vec<Overtime> {
+    let first_day = prefs.week_start_day.unwrap_or(Weekday::Sun);
+    let mut weeks: Vec<Overtime> = Vec::new();
+    for day in days {
+        let start = utils::start_of_week(day.start, first_day);
+        match weeks.last_mut() {
+            Some(week) if week.start == start => {
+                week.tracked = week.tracked + day.tracked;
+                week.expected = week.expected + day.expected;
+            }
+            _ => weeks.push(Overtime { start, ..*day }),
+        }
+    }
+    weeks
+}
+
+/// The tasks whose time on `date` in `logs` has reached the daily limit the
+/// user set for them, with those limits in minutes
+pub fn limits_reached(
+    prefs: &Preferences,
+    logs: &[TimeLog],
+    date: NaiveDate,
+    now: DateTime<Local>,
+) -> Vec<(u8, u32)> {
+    let Some(limits) = prefs.daily_limit_mins.as_ref() else {
+        return Vec::new();
+    };
+    (1..=8)
+        .zip(limits.iter().copied())
+        .filter(|&(_, mins)| mins > 0)
+        .filter(|&(number, mins)| {
+            let spent = logs
+                .iter()
+                .filter(|tl| tl.number == number)
+                .fold(chrono::Duration::zero(), |acc, tl| {
+                    acc + durations::elapsed_on_day(tl, date, Some(now))
+                });
+            spent >= chrono::Duration::minutes(mins.into())
+        })
+        .collect()
+}
+
+/// Formats like "+1h30m" or "-45m"
+pub fn format_balance(d: chrono::Duration) -> String {
+    let mins = d.num_minutes();
+    format!(
+        "{}{}",
+        if mins < 0 { "-" } else { "+" },
+        crate::ui::utils::format_mins(mins.unsigned_abs() as u32)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn balance_against_weekday_targets() {
+        let prefs = Preferences {
+            target_hours: Some(HashMap::from([(Weekday::Thu, 8.0), (Weekday::Fri, 4.0)])),
+            ..Default::default()
+        };
+        // A Thursday, Friday and Saturday
+        let thu = NaiveDate::from_ymd_opt(2023, 6, 8).unwrap();
+        let fri = thu.succ_opt().unwrap();
+        let sat = fri.succ_opt().unwrap();
+        assert_eq!(target_for(&prefs, fri), Some(chrono::Duration::hours(4)));
+        assert_eq!(target_for(&prefs, sat), Some(chrono::Duration::zero()));
+        assert_eq!(target_for(&Preferences::default(), fri), None);
+
+        let tracked = BTreeMap::from([
+            (thu, chrono::Duration::hours(9)),
+            (sat, chrono::Duration::minutes(30)),
+        ]);
+        // +1h on Thursday, -4h on Friday, +30m on Saturday
+        let total = balance(&prefs, &tracked, thu, sat).unwrap();
+        assert_eq!(total, chrono::Duration::minutes(-150));
+        assert_eq!(format_balance(total), "-2h30m");
+    }
+
+    #[test]
+    fn task_limits() {
+        let mut limits = [0; 8];
+        limits[5] = 120;
+        let prefs = Preferences {
+            daily_limit_mins: Some(limits),
+            ..Default::default()
+        };
+        let now = NaiveDate::from_ymd_opt(2023, 6, 5)
+            .unwrap()
+            .and_hms_opt(15, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap();
+        let entry = |number, hours| TimeLog {
+            start: now - chrono::Duration::hours(hours),
+            end: Some(now),
+            number,
+            ..Default::default()
+        };
+        let date = now.date_naive();
+        assert_eq!(limits_reached(&prefs, &[entry(6, 1)], date, now), []);
+        // Tasks without a limit can run as long as they like
+        assert_eq!(
+            limits_reached(&prefs, &[entry(6, 1), entry(1, 9)], date, now),
+            []
+        );
+
+        let mut open = entry(6, 1);
+        open.end = None;
+        open.start = now - chrono::Duration::minutes(61);
+        assert_eq!(
+            limits_reached(&prefs, &[entry(6, 1), open], date, now),
+            [(6, 120)]
+        );
+    }
+
+    #[test]
+    fn overtime_by_day_and_week() {
+        let prefs = Preferences {
+            week_start_day: Some(Weekday::Mon),
+            work_days: Some(vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu]),
+            ..Default::default()
+        };
+        // A Sunday through the next Tuesday
+        let sun = NaiveDate::from_ymd_opt(2023, 6, 4).unwrap();
+        let mon = sun.succ_opt().unwrap();
+        let tue = mon.succ_opt().unwrap();
+        let tracked = BTreeMap::from([
+            (sun, chrono::Duration::hours(1)),
+            (mon, chrono::Duration::hours(9)),
+        ]);
+
+        let days = daily_overtime(&prefs, &tracked, sun, tue);
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].balance(), chrono::Duration::hours(1));
+        assert_eq!(days[1].balance(), chrono::Duration::hours(1));
+        assert_eq!(days[2].balance(), chrono::Duration::hours(-8));
+
+        let weeks = weekly_overtime(&prefs, &days);
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[0].start, sun - chrono::Days::new(6));
+        assert_eq!(weeks[0].expected, chrono::Duration::zero());
+        assert_eq!(weeks[1].start, mon);
+        assert_eq!(weeks[1].tracked, chrono::Duration::hours(9));
+        assert_eq!(weeks[1].expected, chrono::Duration::hours(16));
+    }
+}
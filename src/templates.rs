@@ -0,0 +1,146 @@
+// Templates are named days of entries, like "office day" or "on call", for
+// people whose days follow a few regular shapes. They're saved from a tracked
+// day on the Templates page, keeping each entry's time of day and length but
+// not its date, and can be filled into today from there. They're kept in
+// "templates.ron" next to the settings file.
+
+use std::{io, path::PathBuf};
+
+use chrono::{Local, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{crypto, error, migrate, storage::Dirs, TimeLog};
+
+/// An entry of a template, without the day it's on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TemplateEntry {
+    start: NaiveTime,
+    mins: u32,
+    number: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    entries: Vec<TemplateEntry>,
+}
+
+impl migrate::Migrate for Vec<Template> {
+    fn upgrade(version: u32, contents: &[u8]) -> error::Result<Self> {
+        migrate::parse_data(version, contents)
+    }
+}
+
+impl Template {
+    /// A template of the finished entries in `logs`
+    pub fn from_day(name: impl Into<String>, logs: &[TimeLog]) -> Self {
+        let entries = logs
+            .iter()
+            .filter_map(|tl| {
+                let end = tl.end?;
+                Some(TemplateEntry {
+                    start: tl.start.time(),
+                    mins: (end - tl.start).num_minutes().max(0) as u32,
+                    number: tl.number,
+                    tags: tl.tags.clone(),
+                })
+            })
+            .collect();
+        Self {
+            name: name.into(),
+            entries,
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The template's entries placed on `date`. Entries at times that don't
+    /// exist on that date, e.g. skipped by a DST change, are left out.
+    pub fn entries_on(&self, date: NaiveDate) -> Vec<TimeLog> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let start = date
+                    .and_time(entry.start)
+                    .and_local_timezone(Local)
+                    .earliest()?;
+                Some(TimeLog {
+                    start,
+                    end: Some(start + chrono::Duration::minutes(entry.mins.into())),
+                    number: entry.number,
+                    tags: entry.tags.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+fn templates_path(dirs: &Dirs) -> PathBuf {
+    dirs.config().join("templates.ron")
+}
+
+/// The user's templates, none if they haven't saved any yet
+pub fn load_templates(dirs: &Dirs) -> error::Result<Vec<Template>> {
+    let path = templates_path(dirs);
+    match crypto::read(&path) {
+        Ok(contents) => migrate::from_ron(&contents).map_err(|e| e.in_file(&path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save_templates(dirs: &Dirs, templates: &[Template]) -> error::Result<()> {
+    dirs.instance().check_writable()?;
+    let path = templates_path(dirs);
+    info!("Saving templates to {}", path.display());
+    let contents = migrate::to_ron(templates, true)?;
+    Ok(crypto::write(&path, contents.into_bytes())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn templates() {
+        let start = Local.with_ymd_and_hms(2023, 6, 5, 9, 0, 0).unwrap();
+        let logs = [
+            TimeLog {
+                start,
+                end: Some(start + chrono::Duration::minutes(90)),
+                number: 2,
+                tags: vec!["standup".to_string()],
+                ..Default::default()
+            },
+            // Still going, so it doesn't have a length to keep
+            TimeLog {
+                start: start + chrono::Duration::hours(2),
+                end: None,
+                number: 3,
+                ..Default::default()
+            },
+        ];
+        let template = Template::from_day("office day", &logs);
+        assert_eq!(template.entry_count(), 1);
+
+        let date = NaiveDate::from_ymd_opt(2023, 6, 12).unwrap();
+        let entries = template.entries_on(date);
+        assert_eq!(
+            entries[0].start,
+            Local.with_ymd_and_hms(2023, 6, 12, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            entries[0].end.unwrap() - entries[0].start,
+            chrono::Duration::minutes(90)
+        );
+        assert_eq!(entries[0].tags, ["standup"]);
+    }
+}
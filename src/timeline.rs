@@ -0,0 +1,586 @@
+// Where each entry goes on the Today timeline, or any other day's, in "pixels"
+// (terminal columns), kept apart from any particular UI so that anything
+// drawing a timeline lays it out the same way. The timeline runs from 05:00 to
+// 04:59 the next day, though it can be zoomed in to show only part of that.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Timelike};
+
+use crate::{utils::datetime_with_zeroed_time, TimeLog};
+
+/// The part of the timeline day being drawn: `hours` whole hours from `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub start: NaiveTime,
+    pub hours: u32,
+}
+
+impl Window {
+    /// The whole timeline day, from 05:00 to 04:59
+    pub const DAY: Window = Window {
+        start: match NaiveTime::from_hms_opt(5, 0, 0) {
+            Some(start) => start,
+            None => unreachable!(),
+        },
+        hours: 24,
+    };
+
+    /// How far into the timeline day the window starts
+    fn offset(&self) -> chrono::Duration {
+        chrono::Duration::seconds(
+            (self.start - chrono::Duration::hours(5)).num_seconds_from_midnight() as i64,
+        )
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::DAY
+    }
+}
+
+/// How far the Home timeline is zoomed in, trading the rest of the day for
+/// more pixels per hour around now
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Zoom {
+    #[default]
+    Day,
+    HalfDay,
+    QuarterDay,
+}
+
+impl Zoom {
+    pub fn hours(self) -> u32 {
+        match self {
+            Zoom::Day => 24,
+            Zoom::HalfDay => 12,
+            Zoom::QuarterDay => 6,
+        }
+    }
+
+    pub fn zoomed_in(self) -> Self {
+        match self {
+            Zoom::Day => Zoom::HalfDay,
+            _ => Zoom::QuarterDay,
+        }
+    }
+
+    pub fn zoomed_out(self) -> Self {
+        match self {
+            Zoom::QuarterDay => Zoom::HalfDay,
+            _ => Zoom::Day,
+        }
+    }
+
+    /// The window showing `now`, on the hour and about halfway along, but
+    /// never reaching outside the timeline day
+    pub fn window(self, now: NaiveTime) -> Window {
+        let hours = self.hours();
+        let into_day = (now - chrono::Duration::hours(5)).hour();
+        let offset = into_day.saturating_sub(hours / 2).min(24 - hours);
+        Window {
+            start: Window::DAY.start + chrono::Duration::hours(offset as i64),
+            hours,
+        }
+    }
+}
+
+/// Returns a tuple of start (inclusive) and end (exclusive) x-coordinates for
+/// drawing the specified absolute duration within `window`. Times outside the
+/// window are clamped to whichever end of it is nearer.
+pub fn duration_to_x_coords(
+    start: NaiveTime,
+    end: NaiveTime,
+    window: Window,
+    max_width: u16,
+) -> (u16, u16) {
+    // - Width is in "pixels" (technically not pixels but whatever I'm gonna
+    // call them that)
+    // - The width must be divisible by the window's hours (the Home page's
+    // layout makes sure of this)
+    // - Each 1/hours of width is an hour
+    // By relying on these facts we can compute the coordinates in pixels of a
+    // given duration:
+
+    // num_secs / number_of_secs_in_window = % of the window this duration
+    // fills, multiply that % by the width then round and clamp
+    // `as` automatically clamps to the max/min value of the integer type
+
+    // Okay also I want my table scale to go from 05:00 to 04:59 (or wherever
+    // the window starts), instead of 00:00 to 23:59. Good thing NaiveTime
+    // subraction wraps around! This makes it so that values approaching (but
+    // not exceeding) the start will be at the "end" of the table, while
+    // numbers at and after it will be at the "beginning"
+    let window_secs = window.hours as f32 * 3600.0;
+    let percent_of_window = |time: NaiveTime| {
+        let secs = (time - window.offset() - chrono::Duration::hours(5)).num_seconds_from_midnight()
+            as f32;
+        if secs <= window_secs {
+            secs / window_secs
+        } else if secs - window_secs < 86400.0 - secs {
+            1.0
+        } else {
+            0.0
+        }
+    };
+
+    let start_px =
+        (((max_width as f32) * percent_of_window(start)).round() as u16).clamp(0, max_width);
+    let end_px = (((max_width as f32) * percent_of_window(end)).round() as u16).clamp(0, max_width);
+
+    (start_px, end_px)
+}
+
+/// One block of color on a timeline, covering `start_px` (inclusive) to
+/// `end_px` (exclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// Which of the laid out blocks this is for
+    pub index: usize,
+    pub task: u8,
+    pub start_px: u16,
+    pub end_px: u16,
+}
+
+/// Lays out a sequence of (task, start, end, always_show) blocks, which must be
+/// sorted and non-overlapping, across a timeline of `window` `max_width`
+/// pixels wide. Blocks that round down to nothing are left out unless
+/// `always_show`, in which case they get at least a pixel if there's room.
+/// Rounding never makes a block overlap the one before it.
+pub fn lay_out(
+    blocks: impl IntoIterator<Item = (u8, NaiveTime, NaiveTime, bool)>,
+    window: Window,
+    max_width: u16,
+) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut current_px = 0;
+
+    for (index, (task, start, end, always_show)) in blocks.into_iter().enumerate() {
+        let (start_px, end_px) = duration_to_x_coords(start, end, window, max_width);
+        current_px = current_px.max(start_px);
+
+        if end_px > current_px || (always_show && current_px < max_width) {
+            let len = end_px.saturating_sub(current_px).max(1);
+            segments.push(Segment {
+                index,
+                task,
+                start_px: current_px,
+                end_px: current_px + len,
+            });
+            current_px += len;
+        }
+    }
+
+    segments
+}
+
+/// Lays out the entries of the timeline day containing `now`. Entries that
+/// didn't happen at all during that day are left out, and entries that are
+/// still open run until `now` and always get at least a pixel. Only the part
+/// of the day in `window` is laid out. Segment indexes are into `entries`.
+pub fn lay_out_day(
+    entries: &[TimeLog],
+    now: DateTime<Local>,
+    window: Window,
+    max_width: u16,
+) -> Vec<Segment> {
+    let day_starts_at = datetime_with_zeroed_time(&now).with_hour(5).unwrap();
+    lay_out_from(entries, day_starts_at, now, window, max_width)
+}
+
+/// Like `lay_out_day`, but for the timeline day of any date, which runs from
+/// 05:00 that day to 04:59 the next. Entries reaching past either end of it
+/// are cut off there, so the logs of the next date can be passed in as well to
+/// catch what the user did after midnight.
+pub fn lay_out_date(
+    entries: &[TimeLog],
+    date: NaiveDate,
+    now: DateTime<Local>,
+    max_width: u16,
+) -> Vec<Segment> {
+    match date
+        .and_time(NaiveTime::from_hms_opt(5, 0, 0).unwrap())
+        .and_local_timezone(Local)
+        .earliest()
+    {
+        Some(day_starts_at) => lay_out_from(entries, day_starts_at, now, Window::DAY, max_width),
+        None => Vec::new(),
+    }
+}
+
+fn lay_out_from(
+    entries: &[TimeLog],
+    day_starts_at: DateTime<Local>,
+    now: DateTime<Local>,
+    window: Window,
+    max_width: u16,
+) -> Vec<Segment> {
+    let window_starts_at = day_starts_at + window.offset();
+    let window_ends_at = window_starts_at + chrono::Duration::hours(window.hours as i64)
+        - chrono::Duration::nanoseconds(1);
+
+    // Only count things that happened at least a little bit during the window
+    let today: Vec<(usize, &TimeLog)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, tl)| tl.end.is_none_or(|e| e > window_starts_at) && tl.start < window_ends_at)
+        .collect();
+
+    // Assume it's already sorted, since load() does this, and you're not
+    // manually typing in entries in the future are you ;)
+    lay_out(
+        today.iter().map(|(_, tl)| {
+            (
+                tl.number,
+                tl.start.max(window_starts_at).time(),
+                tl.end.unwrap_or(now).min(window_ends_at).time(),
+                tl.end.is_none(),
+            )
+        }),
+        window,
+        max_width,
+    )
+    .into_iter()
+    .map(|segment| Segment {
+        index: today[segment.index].0,
+        ..segment
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn duration_coords() {
+        // max_width is supposed to always be divisible by 24
+        let mw = 24;
+        assert_eq!(
+            // Remember end is exclusive, think of it like a range: 0..24 and
+            // not 0..=24
+            (0, 24),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (0, 1),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (0, 0),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(5, 29, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (0, 1),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(5, 30, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (2, 2 + 2),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (6, 6 + 9),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(19, 31, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (19, 24),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (17, 24),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            // this one is the worst-case rounding scenario, because at 1px per
+            // hour resolution, XX:29:59 rounds down to XX and YY:30:00 rounds
+            // up to YY+1, -- in this case that causes a 1h+1s duration to show
+            // up as 2 hours!
+            (18, 18 + 2),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(23, 29, 59).unwrap(),
+                NaiveTime::from_hms_opt(0, 30, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (19, 19 + 1),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(0, 30, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (18, 18 + 1),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(0, 29, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+    }
+
+    #[test]
+    fn duration_coords_wide() {
+        // max_width is supposed to always be divisible by 24
+        let mw = 48;
+        assert_eq!(
+            (0, 48),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (0, 1),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(5, 29, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (0, 0),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(5, 14, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (0, 2),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (10, 10 + 5), // Adding 5 half-hours of time from 10:00 to 12:30
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+        assert_eq!(
+            (34, 48),
+            duration_to_x_coords(
+                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
+                Window::DAY,
+                mw
+            )
+        );
+    }
+
+    #[test]
+    fn zoomed_window() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(Zoom::Day.window(time(14, 20)), Window::DAY);
+        // Never before the day starts...
+        assert_eq!(
+            Zoom::HalfDay.window(time(10, 30)),
+            Window {
+                start: time(5, 0),
+                hours: 12
+            }
+        );
+        assert_eq!(
+            Zoom::QuarterDay.window(time(14, 20)),
+            Window {
+                start: time(11, 0),
+                hours: 6
+            }
+        );
+        // ...or after it ends
+        assert_eq!(
+            Zoom::HalfDay.window(time(3, 0)),
+            Window {
+                start: time(17, 0),
+                hours: 12
+            }
+        );
+        assert_eq!(
+            Zoom::Day.zoomed_in().zoomed_in().zoomed_in(),
+            Zoom::QuarterDay
+        );
+        assert_eq!(Zoom::QuarterDay.zoomed_out().zoomed_out(), Zoom::Day);
+
+        let window = Zoom::QuarterDay.window(time(14, 20));
+        assert_eq!(
+            (2, 5),
+            duration_to_x_coords(time(12, 0), time(13, 30), window, 12)
+        );
+        assert_eq!(
+            (0, 2),
+            duration_to_x_coords(time(9, 0), time(12, 0), window, 12)
+        );
+        assert_eq!(
+            (10, 12),
+            duration_to_x_coords(time(16, 0), time(18, 0), window, 12)
+        );
+        assert_eq!(
+            (12, 12),
+            duration_to_x_coords(time(20, 0), time(21, 0), window, 12)
+        );
+
+        let at = |h, m| Local.with_ymd_and_hms(2023, 6, 8, h, m, 0).unwrap();
+        let entries = [
+            // Cut off at the start of the window
+            TimeLog {
+                start: at(9, 0),
+                end: Some(at(12, 0)),
+                number: 1,
+                ..Default::default()
+            },
+            TimeLog {
+                start: at(16, 30),
+                end: None,
+                number: 2,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            lay_out_day(&entries, at(16, 40), window, 12)
+                .iter()
+                .map(|s| (s.index, s.start_px, s.end_px))
+                .collect::<Vec<_>>(),
+            vec![(0, 0, 2), (1, 11, 12)]
+        );
+    }
+
+    #[test]
+    fn day_layout() {
+        let at = |h, m, s| Local.with_ymd_and_hms(2023, 6, 8, h, m, s).unwrap();
+        let entries = [
+            // Yesterday's, so it's left out
+            TimeLog {
+                start: at(1, 0, 0),
+                end: Some(at(2, 0, 0)),
+                number: 1,
+                ..Default::default()
+            },
+            TimeLog {
+                start: at(7, 0, 0),
+                end: Some(at(9, 30, 0)),
+                number: 2,
+                ..Default::default()
+            },
+            // Rounds to nothing
+            TimeLog {
+                start: at(9, 30, 0),
+                end: Some(at(9, 40, 0)),
+                number: 3,
+                ..Default::default()
+            },
+            TimeLog {
+                start: at(9, 40, 0),
+                end: Some(at(11, 0, 0)),
+                number: 4,
+                ..Default::default()
+            },
+            // Too short to see, but it's still going
+            TimeLog {
+                start: at(11, 0, 0),
+                end: None,
+                number: 5,
+                ..Default::default()
+            },
+        ];
+        let segment = |index, task, start_px, end_px| Segment {
+            index,
+            task,
+            start_px,
+            end_px,
+        };
+        assert_eq!(
+            lay_out_day(&entries, at(11, 10, 0), Window::DAY, 24),
+            vec![
+                segment(1, 2, 2, 5),
+                segment(3, 4, 5, 6),
+                segment(4, 5, 6, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn date_layout() {
+        let at = |d, h, m| Local.with_ymd_and_hms(2023, 6, d, h, m, 0).unwrap();
+        let entries = [
+            // Runs over from the night before, so only its last hour shows
+            TimeLog {
+                start: at(7, 23, 0),
+                end: Some(at(8, 6, 0)),
+                number: 1,
+                ..Default::default()
+            },
+            // After midnight, from the next date's log
+            TimeLog {
+                start: at(9, 1, 0),
+                end: Some(at(9, 7, 0)),
+                number: 2,
+                ..Default::default()
+            },
+        ];
+        let date = NaiveDate::from_ymd_opt(2023, 6, 8).unwrap();
+        let laid_out = lay_out_date(&entries, date, at(20, 12, 0), 24);
+        assert_eq!(
+            laid_out
+                .iter()
+                .map(|s| (s.index, s.start_px, s.end_px))
+                .collect::<Vec<_>>(),
+            vec![(0, 0, 1), (1, 20, 24)]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+// Entries deleted in the editor go to the day's trash instead of disappearing
+// for good, so that they can be restored for the rest of the day. The trash is
+// kept in "trash.ron" in the save file directory and emptied once the day is
+// over. Changing it only changes it in memory, and the app sends it to the
+// saver to be written.
+
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{crypto, error, migrate, storage::Dirs, TimeLog};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Trash {
+    date: NaiveDate,
+    /// Oldest deletion first
+    entries: Vec<TimeLog>,
+    /// Where it's kept
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Trash {
+    /// Loads the trash for `today` from the save file directory. Trash left
+    /// from an earlier day is emptied.
+    pub fn load(dirs: &Dirs, today: NaiveDate) -> Self {
+        let path = dirs.data().join("trash.ron");
+        let empty = Self {
+            date: today,
+            entries: Vec::new(),
+            path: path.clone(),
+        };
+        let trash = crypto::read(&path)
+            .map_err(error::YdncError::from)
+            .and_then(|contents| {
+                migrate::from_ron::<Self>(&contents).map_err(|e| e.in_file(&path))
+            });
+        match trash {
+            Ok(trash) if trash.date == today => Self { path, ..trash },
+            Ok(_) => {
+                if let Err(e) = empty.save() {
+                    warn!("Unable to empty the trash: {}", e);
+                }
+                empty
+            }
+            Err(e) if e.is_not_found() => empty,
+            Err(e) => {
+                warn!("Unable to load the trash: {}", e);
+                empty
+            }
+        }
+    }
+
+    pub fn save(&self) -> error::Result<()> {
+        let contents = migrate::to_ron(self, true)?;
+        Ok(crypto::write(&self.path, contents.into_bytes())?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Throws away what's in the trash and starts a new day's trash
+    pub fn empty_for(&mut self, today: NaiveDate) {
+        self.date = today;
+        self.entries.clear();
+    }
+
+    pub fn put(&mut self, entry: TimeLog) {
+        self.entries.push(entry);
+    }
+
+    /// Takes the most recently deleted entry back out of the trash
+    pub fn take_last(&mut self) -> Option<TimeLog> {
+        self.entries.pop()
+    }
+}
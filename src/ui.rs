@@ -1,24 +1,57 @@
+use std::path::PathBuf;
+
+use chrono::{Local, Timelike};
 use ratatui::{
     backend::Backend,
-    style::Color,
-    widgets::{Paragraph, Wrap},
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
+use serde::{Deserialize, Serialize};
 
-use crate::App;
+use crate::{App, Preferences, TimeLog};
 
 mod editable_list;
+mod form;
+pub mod gaps;
+pub mod heatmap;
 pub mod home;
+pub mod plan;
+pub mod report;
 pub mod settings;
 pub mod stats;
+pub mod templates;
 pub mod utils;
+pub mod week;
 pub mod widgets;
 
+/// Something the user has to see before carrying on, shown over the page
+/// until they do
+#[derive(Debug)]
+pub struct ErrorPopup {
+    pub message: String,
+    /// The keys that carry on past it
+    pub keys: &'static str,
+    /// Whether e opens today's entries in the editor, for checking what was
+    /// salvaged of them
+    pub edit_today: bool,
+    /// A past day's log that e opens in $EDITOR instead, for the same
+    pub edit_file: Option<PathBuf>,
+}
+
 #[derive(Debug)]
 pub enum Page {
     Home(home::State),
     Stats(stats::State),
     Settings(settings::State),
+    Plan(plan::State),
+    Report(report::State),
+    Gaps(gaps::State),
+    Week(week::State),
+    Templates(templates::State),
+    Heatmap(heatmap::State),
 }
 
 impl Default for Page {
@@ -27,6 +60,37 @@ impl Default for Page {
     }
 }
 
+/// Where the things that can be clicked were drawn on the last frame, so that
+/// mouse events can be matched up with them
+#[derive(Debug, Default)]
+pub struct MouseTargets {
+    /// The rows of the entry list on Home, with the index of the entry in the
+    /// top row
+    pub entries: Option<(Rect, usize)>,
+    /// Each date range option on Stats
+    pub date_ranges: Vec<(Rect, stats::DateRangeOption)>,
+}
+
+impl MouseTargets {
+    /// The index of the entry drawn at the given position, if any
+    pub fn entry_at(&self, column: u16, row: u16) -> Option<usize> {
+        let (area, offset) = self.entries?;
+        contains(area, column, row).then(|| offset + (row - area.y) as usize)
+    }
+
+    /// The date range option drawn at the given position, if any
+    pub fn date_range_at(&self, column: u16, row: u16) -> Option<stats::DateRangeOption> {
+        self.date_ranges
+            .iter()
+            .find(|(area, _)| contains(*area, column, row))
+            .map(|(_, dr)| *dr)
+    }
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    (area.x..area.x + area.width).contains(&column) && (area.y..area.y + area.height).contains(&row)
+}
+
 fn number_to_color(i: u8) -> Color {
     match i {
         1 => Color::Blue,
@@ -41,15 +105,245 @@ fn number_to_color(i: u8) -> Color {
     }
 }
 
-fn message_widget(app: &App) -> Paragraph {
-    let message = app.message.as_ref().map_or("", |m| m.0.as_str());
-    Paragraph::new(message).wrap(Wrap { trim: false })
+/// Built-in color schemes. Besides the usual colors, there are themes for
+/// color-blind users and limited terminals that also give each task its own
+/// fill pattern, so that tasks can be told apart without relying on color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Default,
+    HighContrast,
+    Monochrome,
+}
+
+impl Theme {
+    /// The theme after this one, for cycling through them in Settings
+    pub fn next(self) -> Self {
+        match self {
+            Theme::Default => Theme::HighContrast,
+            Theme::HighContrast => Theme::Monochrome,
+            Theme::Monochrome => Theme::Default,
+        }
+    }
+
+    /// The theme before this one
+    pub fn prev(self) -> Self {
+        match self {
+            Theme::Default => Theme::Monochrome,
+            Theme::HighContrast => Theme::Default,
+            Theme::Monochrome => Theme::HighContrast,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::HighContrast => "high contrast",
+            Theme::Monochrome => "monochrome",
+        }
+    }
+}
+
+/// Bright colors that stay readable with black text on them and are far apart
+/// from each other even on dark terminals
+const HIGH_CONTRAST_COLORS: [Color; 8] = [
+    Color::LightYellow,
+    Color::LightCyan,
+    Color::LightMagenta,
+    Color::LightGreen,
+    Color::White,
+    Color::LightRed,
+    Color::LightBlue,
+    Color::Yellow,
+];
+
+/// One fill character per task number, for themes that don't rely on color
+const TASK_PATTERNS: [char; 8] = ['▓', '▒', '░', '╳', '╱', '╲', '┼', '·'];
+
+/// The colors of task numbers 1-8 when the user hasn't picked any
+pub fn default_task_colors() -> [Color; 8] {
+    std::array::from_fn(|i| number_to_color(i as u8 + 1))
+}
+
+/// Resolves the color a task number is displayed with. In the default theme
+/// that's the user's choice from their preferences if they've made one, else
+/// the default palette; the other themes have fixed palettes.
+pub fn task_color(prefs: &Preferences, number: u8) -> Color {
+    let idx = (number as usize).wrapping_sub(1);
+    match prefs.theme.unwrap_or_default() {
+        Theme::Default => prefs
+            .colors
+            .as_ref()
+            .and_then(|colors| colors.get(idx))
+            .copied()
+            .unwrap_or_else(|| number_to_color(number)),
+        Theme::HighContrast => HIGH_CONTRAST_COLORS
+            .get(idx)
+            .copied()
+            .unwrap_or(Color::Reset),
+        Theme::Monochrome => Color::White,
+    }
+}
+
+/// The colors an entry's own color can be set to, to make it stand out from
+/// the rest of its task's entries, in the order the editor cycles through them
+const ENTRY_COLORS: [Color; 5] = [
+    Color::LightRed,
+    Color::LightYellow,
+    Color::LightGreen,
+    Color::LightMagenta,
+    Color::White,
+];
+
+/// The color an entry is displayed with: its own, if it has one, else its
+/// task's. Monochrome has no colors to pick from, so it ignores entry colors.
+pub fn entry_color(prefs: &Preferences, entry: &TimeLog) -> Color {
+    match entry.color {
+        Some(color) if prefs.theme.unwrap_or_default() != Theme::Monochrome => color,
+        _ => task_color(prefs, entry.number),
+    }
+}
+
+/// The entry color after `color` in the editor's cycle, ending with none
+pub fn next_entry_color(color: Option<Color>) -> Option<Color> {
+    match color.and_then(|c| ENTRY_COLORS.iter().position(|&e| e == c)) {
+        Some(i) => ENTRY_COLORS.get(i + 1).copied(),
+        None if color.is_none() => Some(ENTRY_COLORS[0]),
+        // Colors set by hand in the save file start the cycle over
+        None => None,
+    }
+}
+
+/// Whether tasks are drawn with fill patterns: always in the accessible
+/// themes, and in the default theme if the user turned them on
+pub fn patterns_enabled(prefs: &Preferences) -> bool {
+    prefs.patterns.unwrap_or(false) || prefs.theme.unwrap_or_default() != Theme::Default
+}
+
+/// The fill character a task number is drawn with, if patterns are enabled
+pub fn task_pattern(prefs: &Preferences, number: u8) -> Option<char> {
+    if patterns_enabled(prefs) {
+        TASK_PATTERNS
+            .get((number as usize).wrapping_sub(1))
+            .copied()
+    } else {
+        None
+    }
+}
+
+/// Prefixes a task's label with its pattern, if it has one, so lists and
+/// tables double as a legend for the timeline and donut
+pub fn patterned_label(prefs: &Preferences, number: u8, label: String) -> String {
+    match task_pattern(prefs, number) {
+        Some(p) => format!("{} {}", p, label),
+        None => label,
+    }
+}
+
+/// Draws lines of plain text down the screen, scrolled so that the line at
+/// `focus`, if any, is in view
+fn draw_plain_lines<B: Backend>(f: &mut Frame<B>, lines: Vec<String>, focus: Option<usize>) {
+    let area = f.size();
+    let scroll = focus.map_or(0, |i| (i + 1).saturating_sub(area.height as usize)) as u16;
+    let lines: Vec<Line> = lines.into_iter().map(Line::from).collect();
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0)),
+        area,
+    );
+}
+
+/// Draws the recent messages with the newest one in `area`, which is the line
+/// each page sets aside for messages. Older ones stack up above it, covering
+/// the page until they expire. Long messages wrap onto as many lines as they
+/// need.
+fn draw_messages<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let lines: Vec<Line> = app
+        .messages
+        .iter()
+        .map(|m| Line::from(m.0.as_str()))
+        .collect();
+    let width = area.width.max(1) as usize;
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().div_ceil(width).max(1))
+        .sum();
+    let height = (rows as u16).clamp(area.height, area.bottom());
+    let area = Rect {
+        y: area.bottom() - height,
+        height,
+        ..area
+    };
+    // Flashing alternates between reversed and normal every second
+    let now = Local::now();
+    let style =
+        if app.flash_until.is_some_and(|until| now < until) && now.second().is_multiple_of(2) {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .style(style)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
 }
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    app.mouse_targets = Default::default();
+    if app.plain {
+        match app.selected_page {
+            Page::Home(home::State::Viewing { .. }) => home::draw_plain(f, app),
+            Page::Stats(ref state) if state.is_loaded() => stats::draw_plain(f, app),
+            _ => draw_page(f, app),
+        }
+    } else {
+        draw_page(f, app);
+    }
+    draw_error_popup(f, app);
+    if app.plain {
+        f.render_widget(widgets::Unstyled, f.size());
+    }
+}
+
+/// The error the user has to see before carrying on, if there is one, over
+/// whichever page is open
+fn draw_error_popup<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let Some(ErrorPopup { message, keys, .. }) = app.error_popup.as_ref() else {
+        return;
+    };
+    let area = f.size();
+    let width = 60.min(area.width);
+    // Enough lines for the message once wrapped, a blank line, the keys and
+    // the borders
+    let per_line = usize::from(width.saturating_sub(2).max(1));
+    let height = (message.chars().count() / per_line + 1) as u16 + 4;
+    let popup = utils::centered_rect(width, height, area);
+    let text = vec![
+        Line::from(message.as_str()),
+        Line::from(""),
+        Line::from(utils::dim(*keys)),
+    ];
+    let paragraph = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL).title("Error"));
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+fn draw_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     match app.selected_page {
         Page::Home(_) => home::draw(f, app),
         Page::Stats(_) => stats::draw(f, app),
         Page::Settings(_) => settings::draw(f, app),
+        Page::Plan(_) => plan::draw(f, app),
+        Page::Report(_) => report::draw(f, app),
+        Page::Gaps(_) => gaps::draw(f, app),
+        Page::Week(_) => week::draw(f, app),
+        Page::Templates(_) => templates::draw(f, app),
+        Page::Heatmap(_) => heatmap::draw(f, app),
     }
 }
@@ -79,6 +79,16 @@ impl<StateType: TuiState, T: Clone + Default + Debug> EditableList<StateType, T>
         self.list_state.select(Some(next));
     }
 
+    /// Moves the selection back onto the last item if the items it was on
+    /// have gone away
+    pub fn clamp_selection(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected >= self.options.len() {
+                self.list_state.select(self.options.len().checked_sub(1));
+            }
+        }
+    }
+
     pub fn selected_is_last(&self) -> bool {
         let current = self.list_state.selected();
         current.map_or(true, |cur| cur == self.options.len() - 1)
@@ -181,11 +191,11 @@ impl<StateType: TuiState, T: Clone + Default + Debug> EditableList<StateType, T>
 }
 
 impl<T: Clone + Default + Debug> EditableList<ListState, T> {
-    pub fn draw_list<B: Backend>(
-        &mut self,
+    pub fn draw_list<'a, B: Backend, F: FnMut(usize, &'a T, &'a T, bool) -> Text<'a>>(
+        &'a mut self,
         f: &mut Frame<B>,
         rect: Rect,
-        render_item: for<'a> fn(usize, &'a T, &'a T, bool) -> Text<'a>,
+        mut render_item: F,
     ) {
         let widget = List::new(
             self.options
@@ -0,0 +1,513 @@
+use std::path::PathBuf;
+
+use chrono::{Local, NaiveTime, Weekday};
+use ratatui::widgets::ListState;
+
+use crate::{
+    gaps::work_hours, locale, profiles, storage::Dirs, sync, utils::start_of_week, Preferences,
+};
+
+/// How a field's value is edited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Typed in, and checked once Enter is pressed
+    Text,
+    /// Stepped through a fixed set of values
+    Choice,
+    /// Stepped up and down, or typed in
+    Number,
+    /// Flipped on and off
+    Toggle,
+}
+
+/// A preference that can be edited in Settings. Giving a new preference a
+/// place in Settings means adding it here and to one of the sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    WeekStart,
+    WorkDayStart,
+    WorkDayEnd,
+    Locale,
+    Autosave,
+    ArchiveAfterDays,
+    BackupDir,
+    SyncDir,
+    DeviceName,
+    Theme,
+    Patterns,
+    TitleTimer,
+    ToggleOpenTask,
+    PromptPlannedStarts,
+    LockBreakTask,
+    TrackerName,
+    Profile,
+    DailySummary,
+    NagMins,
+    BackdateMins,
+    NagBell,
+    NagDesktop,
+    ReportTime,
+}
+
+/// The full name of `day` in the user's locale
+fn weekday_name(prefs: &Preferences, day: Weekday) -> String {
+    locale::format_date(prefs, start_of_week(Local::now().date_naive(), day), "%A")
+}
+
+fn on_off(on: bool) -> String {
+    String::from(if on { "on" } else { "off" })
+}
+
+impl Field {
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::WeekStart => "Week starts on",
+            Field::WorkDayStart => "Work day starts at",
+            Field::WorkDayEnd => "Work day ends at",
+            Field::Locale => "Locale",
+            Field::Autosave => "Autosave every (minutes)",
+            Field::ArchiveAfterDays => "Archive logs after (days)",
+            Field::BackupDir => "Back up to",
+            Field::SyncDir => "Sync through",
+            Field::DeviceName => "This device's name",
+            Field::Theme => "Theme",
+            Field::Patterns => "Fill patterns",
+            Field::TitleTimer => "Timer in title bar",
+            Field::ToggleOpenTask => "Choosing the open task stops it",
+            Field::PromptPlannedStarts => "Offer to start planned tasks",
+            Field::LockBreakTask => "Track locked screen as task",
+            Field::TrackerName => "Tracker name",
+            Field::Profile => "Profile",
+            Field::DailySummary => "Daily summary",
+            Field::NagMins => "Remind when idle for (minutes)",
+            Field::BackdateMins => "Shift+1-8 starts (minutes ago)",
+            Field::NagBell => "Ring bell with reminders",
+            Field::NagDesktop => "Desktop notification reminders",
+            Field::ReportTime => "Send end-of-day report at",
+        }
+    }
+
+    pub fn kind(self) -> FieldKind {
+        match self {
+            Field::WorkDayStart
+            | Field::WorkDayEnd
+            | Field::Locale
+            | Field::BackupDir
+            | Field::SyncDir
+            | Field::DeviceName
+            | Field::ReportTime
+            | Field::TrackerName
+            | Field::Profile => FieldKind::Text,
+            Field::WeekStart | Field::Theme => FieldKind::Choice,
+            Field::Autosave
+            | Field::ArchiveAfterDays
+            | Field::LockBreakTask
+            | Field::NagMins
+            | Field::BackdateMins => FieldKind::Number,
+            Field::Patterns
+            | Field::TitleTimer
+            | Field::ToggleOpenTask
+            | Field::PromptPlannedStarts
+            | Field::DailySummary
+            | Field::NagBell
+            | Field::NagDesktop => FieldKind::Toggle,
+        }
+    }
+
+    /// The field's value as shown in Settings, including what an unset value
+    /// defaults to
+    pub fn display(self, dirs: &Dirs, prefs: &Preferences) -> String {
+        match self {
+            Field::WeekStart => weekday_name(prefs, prefs.week_start_day.unwrap_or(Weekday::Sun)),
+            Field::WorkDayStart => work_hours(prefs).0.format("%H:%M").to_string(),
+            Field::WorkDayEnd => work_hours(prefs).1.format("%H:%M").to_string(),
+            Field::Locale => prefs
+                .locale
+                .clone()
+                .unwrap_or_else(|| String::from("from environment")),
+            Field::BackupDir => prefs
+                .backup_dir
+                .as_ref()
+                .map_or_else(|| String::from("off"), |d| d.display().to_string()),
+            Field::SyncDir => prefs
+                .sync_dir
+                .as_ref()
+                .map_or_else(|| String::from("off"), |d| d.display().to_string()),
+            Field::DeviceName => sync::device_name(dirs, prefs),
+            Field::ReportTime => prefs
+                .report_time
+                .map_or_else(|| on_off(false), |t| t.format("%H:%M").to_string()),
+            Field::TrackerName => prefs
+                .tracker_name
+                .clone()
+                .unwrap_or_else(|| String::from("Timeular")),
+            Field::Theme => prefs.theme.unwrap_or_default().name().to_string(),
+            Field::Profile => profiles::current(prefs).to_string(),
+            Field::BackdateMins => prefs.backdate_mins.map_or_else(
+                || String::from("when the last entry ended"),
+                |n| n.to_string(),
+            ),
+            Field::Autosave | Field::ArchiveAfterDays | Field::LockBreakTask | Field::NagMins => {
+                self.number(prefs)
+                    .map_or_else(|| on_off(false), |n| n.to_string())
+            }
+            _ => on_off(self.toggle(prefs)),
+        }
+    }
+
+    /// What typing into the field starts out with: its value if it's set,
+    /// otherwise nothing
+    pub fn input(self, prefs: &Preferences) -> String {
+        match self {
+            Field::WorkDayStart => prefs.work_day_start.map(|t| t.format("%H:%M").to_string()),
+            Field::WorkDayEnd => prefs.work_day_end.map(|t| t.format("%H:%M").to_string()),
+            Field::Locale => prefs.locale.clone(),
+            Field::BackupDir => prefs.backup_dir.as_ref().map(|d| d.display().to_string()),
+            Field::SyncDir => prefs.sync_dir.as_ref().map(|d| d.display().to_string()),
+            Field::DeviceName => prefs.device_name.clone(),
+            Field::ReportTime => prefs.report_time.map(|t| t.format("%H:%M").to_string()),
+            Field::TrackerName => prefs.tracker_name.clone(),
+            Field::Profile => prefs.profile.clone(),
+            Field::Autosave => prefs.autosave_mins.map(|n| n.to_string()),
+            _ => self.number(prefs).map(|n| n.to_string()),
+        }
+        .unwrap_or_default()
+    }
+
+    /// The lowest and highest values a number field takes
+    fn range(self) -> (u32, u32) {
+        match self {
+            Field::Autosave => (1, 60),
+            Field::ArchiveAfterDays => (1, 3650),
+            Field::LockBreakTask => (1, 8),
+            Field::NagMins => (1, 480),
+            Field::BackdateMins => (1, 240),
+            _ => (0, 0),
+        }
+    }
+
+    /// Whether a number field can be left unset to turn it off
+    fn optional(self) -> bool {
+        self != Field::Autosave
+    }
+
+    fn number(self, prefs: &Preferences) -> Option<u32> {
+        match self {
+            Field::Autosave => Some(prefs.autosave_mins.unwrap_or(5)),
+            Field::ArchiveAfterDays => prefs.archive_after_days,
+            Field::LockBreakTask => prefs.lock_break_task.map(u32::from),
+            Field::NagMins => prefs.nag_mins,
+            Field::BackdateMins => prefs.backdate_mins,
+            _ => None,
+        }
+    }
+
+    fn set_number(self, prefs: &mut Preferences, n: Option<u32>) {
+        match self {
+            Field::Autosave => prefs.autosave_mins = n,
+            Field::ArchiveAfterDays => prefs.archive_after_days = n,
+            Field::LockBreakTask => prefs.lock_break_task = n.and_then(|n| u8::try_from(n).ok()),
+            Field::NagMins => prefs.nag_mins = n,
+            Field::BackdateMins => prefs.backdate_mins = n,
+            _ => {}
+        }
+    }
+
+    fn toggle(self, prefs: &Preferences) -> bool {
+        match self {
+            Field::Patterns => prefs.patterns.unwrap_or(false),
+            Field::TitleTimer => prefs.title_timer.unwrap_or(false),
+            Field::ToggleOpenTask => prefs.toggle_open_task.unwrap_or(false),
+            Field::PromptPlannedStarts => prefs.prompt_planned_starts.unwrap_or(false),
+            Field::DailySummary => prefs.daily_summary.unwrap_or(true),
+            Field::NagBell => prefs.nag_bell.unwrap_or(false),
+            Field::NagDesktop => prefs.nag_desktop.unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    fn set_toggle(self, prefs: &mut Preferences, on: bool) {
+        let pref = match self {
+            Field::Patterns => &mut prefs.patterns,
+            Field::TitleTimer => &mut prefs.title_timer,
+            Field::ToggleOpenTask => &mut prefs.toggle_open_task,
+            Field::PromptPlannedStarts => &mut prefs.prompt_planned_starts,
+            Field::DailySummary => &mut prefs.daily_summary,
+            Field::NagBell => &mut prefs.nag_bell,
+            Field::NagDesktop => &mut prefs.nag_desktop,
+            _ => return,
+        };
+        *pref = Some(on);
+    }
+
+    /// Moves a choice, number or toggle field on to its next (or previous)
+    /// value. Returns whether the value changed, which text fields never do,
+    /// except for the profile which steps through the ones there are.
+    pub fn step(self, prefs: &mut Preferences, forward: bool) -> bool {
+        if self == Field::Profile {
+            let names = profiles::names(prefs);
+            let i = names
+                .iter()
+                .position(|n| n == profiles::current(prefs))
+                .unwrap_or(0);
+            let next = if forward {
+                (i + 1) % names.len()
+            } else {
+                (i + names.len() - 1) % names.len()
+            };
+            profiles::switch(prefs, &names[next]);
+            return next != i;
+        }
+        match self.kind() {
+            FieldKind::Text => false,
+            FieldKind::Toggle => {
+                let on = self.toggle(prefs);
+                self.set_toggle(prefs, !on);
+                true
+            }
+            FieldKind::Choice => {
+                if self == Field::WeekStart {
+                    let day = prefs.week_start_day.unwrap_or(Weekday::Sun);
+                    prefs.week_start_day = Some(if forward { day.succ() } else { day.pred() });
+                } else {
+                    let theme = prefs.theme.unwrap_or_default();
+                    prefs.theme = Some(if forward { theme.next() } else { theme.prev() });
+                }
+                true
+            }
+            FieldKind::Number => {
+                let (min, max) = self.range();
+                let current = self.number(prefs);
+                let next = match (current, forward) {
+                    (None, true) => Some(min),
+                    (None, false) => None,
+                    (Some(n), true) => Some((n + 1).min(max)),
+                    // Stepping below the lowest value turns the field off
+                    (Some(n), false) if n <= min => (!self.optional()).then_some(min),
+                    (Some(n), false) => Some(n - 1),
+                };
+                self.set_number(prefs, next);
+                next != current
+            }
+        }
+    }
+
+    /// Sets the field from what was typed into it, or explains why it can't
+    /// be. Typing nothing puts the field back to its default.
+    pub fn set(self, prefs: &mut Preferences, input: &str) -> Result<(), String> {
+        let input = input.trim();
+        let text = (!input.is_empty()).then(|| input.to_string());
+        match self {
+            Field::WorkDayStart | Field::WorkDayEnd => {
+                let time = match text {
+                    Some(t) => Some(
+                        NaiveTime::parse_from_str(&t, "%H:%M")
+                            .map_err(|_| String::from("Expected a time like 09:00"))?,
+                    ),
+                    None => None,
+                };
+                let mut changed = prefs.clone();
+                if self == Field::WorkDayStart {
+                    changed.work_day_start = time;
+                } else {
+                    changed.work_day_end = time;
+                }
+                let (start, end) = work_hours(&changed);
+                if end <= start {
+                    return Err(String::from("The work day has to end after it starts"));
+                }
+                prefs.work_day_start = changed.work_day_start;
+                prefs.work_day_end = changed.work_day_end;
+            }
+            Field::Locale => {
+                if let Some(name) = text.as_ref() {
+                    if locale::parse_locale(name).is_none() {
+                        return Err(format!("Unknown locale {:?}", name));
+                    }
+                }
+                prefs.locale = text;
+            }
+            Field::BackupDir => {
+                let dir = text.map(PathBuf::from);
+                if dir.as_ref().is_some_and(|d| !d.is_dir()) {
+                    return Err(String::from("No such directory"));
+                }
+                prefs.backup_dir = dir;
+            }
+            Field::SyncDir => {
+                let dir = text.map(PathBuf::from);
+                if dir.as_ref().is_some_and(|d| !d.is_dir()) {
+                    return Err(String::from("No such directory"));
+                }
+                prefs.sync_dir = dir;
+            }
+            Field::DeviceName => {
+                let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+                if text.as_ref().is_some_and(|name| !name.chars().all(valid)) {
+                    return Err(String::from(
+                        "Only letters, digits, '-' and '_' can go in a device's name",
+                    ));
+                }
+                prefs.device_name = text;
+            }
+            Field::ReportTime => {
+                prefs.report_time = match text {
+                    Some(t) => Some(
+                        NaiveTime::parse_from_str(&t, "%H:%M")
+                            .map_err(|_| String::from("Expected a time like 17:30"))?,
+                    ),
+                    None => None,
+                };
+            }
+            Field::TrackerName => prefs.tracker_name = text,
+            Field::Profile => {
+                profiles::switch(prefs, text.as_deref().unwrap_or(profiles::DEFAULT));
+            }
+            _ if self.kind() == FieldKind::Number => {
+                let (min, max) = self.range();
+                let n = match text {
+                    Some(t) => match t.parse() {
+                        Ok(n) if (min..=max).contains(&n) => Some(n),
+                        _ => return Err(format!("Expected a number from {} to {}", min, max)),
+                    },
+                    None => None,
+                };
+                self.set_number(prefs, n);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// The fields of one Settings section, one of them selected
+#[derive(Debug, Default)]
+pub struct Form {
+    pub fields: &'static [Field],
+    pub list_state: ListState,
+    /// What's been typed so far while a text or number field is being edited
+    pub input: Option<String>,
+    /// Why the selected field's input was turned down, until it's edited again
+    pub error: Option<String>,
+}
+
+impl Form {
+    pub fn new(fields: &'static [Field]) -> Self {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+        Self {
+            fields,
+            list_state,
+            input: None,
+            error: None,
+        }
+    }
+
+    pub fn selected(&self) -> Option<Field> {
+        self.fields
+            .get(self.list_state.selected().unwrap_or(0))
+            .copied()
+    }
+
+    /// Selects the field `offset` places from the selected one, wrapping
+    /// around
+    pub fn move_selection(&mut self, offset: isize) {
+        let len = self.fields.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let current = self.list_state.selected().unwrap_or(0) as isize;
+        self.list_state
+            .select(Some((current + offset).rem_euclid(len) as usize));
+        self.error = None;
+    }
+
+    /// Steps the selected field's value, returning a message about its new
+    /// value if it changed
+    pub fn step(&mut self, dirs: &Dirs, prefs: &mut Preferences, forward: bool) -> Option<String> {
+        let field = self.selected()?;
+        self.error = None;
+        field
+            .step(prefs, forward)
+            .then(|| format!("{}: {}", field.label(), field.display(dirs, prefs)))
+    }
+
+    /// What Enter does on the selected field: starts typing into text and
+    /// number fields, and steps the rest like `step`
+    pub fn activate(&mut self, dirs: &Dirs, prefs: &mut Preferences) -> Option<String> {
+        let field = self.selected()?;
+        match field.kind() {
+            FieldKind::Text | FieldKind::Number => {
+                self.input = Some(field.input(prefs));
+                self.error = None;
+                None
+            }
+            FieldKind::Choice | FieldKind::Toggle => self.step(dirs, prefs, true),
+        }
+    }
+
+    /// Sets the selected field from the input, returning a message about its
+    /// new value, or keeps the input to fix if it's not valid
+    pub fn submit(&mut self, dirs: &Dirs, prefs: &mut Preferences) -> Option<String> {
+        let field = self.selected()?;
+        let input = self.input.take()?;
+        match field.set(prefs, &input) {
+            Ok(()) => {
+                self.error = None;
+                Some(format!("{}: {}", field.label(), field.display(dirs, prefs)))
+            }
+            Err(e) => {
+                self.error = Some(e);
+                self.input = Some(input);
+                None
+            }
+        }
+    }
+
+    pub fn cancel_input(&mut self) {
+        self.input = None;
+        self.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_validation() {
+        let mut prefs = Preferences::default();
+
+        assert!(Field::WorkDayStart.set(&mut prefs, "8:30").is_ok());
+        assert_eq!(prefs.work_day_start, NaiveTime::from_hms_opt(8, 30, 0));
+        assert!(Field::WorkDayEnd.set(&mut prefs, "08:00").is_err());
+        assert!(Field::WorkDayStart
+            .set(&mut prefs, "half past eight")
+            .is_err());
+        assert!(Field::WorkDayStart.set(&mut prefs, "").is_ok());
+        assert_eq!(prefs.work_day_start, None);
+
+        assert!(Field::LockBreakTask.set(&mut prefs, "9").is_err());
+        assert!(Field::LockBreakTask.set(&mut prefs, "3").is_ok());
+        assert_eq!(prefs.lock_break_task, Some(3));
+
+        // Stepping an optional number below its lowest value turns it off,
+        // but the autosave interval always has one
+        assert!(Field::NagMins.step(&mut prefs, true));
+        assert_eq!(prefs.nag_mins, Some(1));
+        assert!(Field::NagMins.step(&mut prefs, false));
+        assert_eq!(prefs.nag_mins, None);
+        assert!(Field::Autosave.set(&mut prefs, "1").is_ok());
+        assert!(!Field::Autosave.step(&mut prefs, false));
+        assert_eq!(prefs.autosave_mins, Some(1));
+
+        assert!(Field::DailySummary.step(&mut prefs, true));
+        assert_eq!(prefs.daily_summary, Some(false));
+        // Going forward, like → does, goes on to the later day
+        assert!(Field::WeekStart.step(&mut prefs, false));
+        assert_eq!(prefs.week_start_day, Some(Weekday::Sat));
+        assert!(Field::WeekStart.step(&mut prefs, true));
+        assert!(Field::WeekStart.step(&mut prefs, true));
+        assert_eq!(prefs.week_start_day, Some(Weekday::Mon));
+    }
+}
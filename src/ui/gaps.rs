@@ -0,0 +1,193 @@
+use chrono::{Days, Local};
+use ratatui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use tracing::warn;
+
+use crate::{
+    gaps::{find_gaps, report_days, work_hours, Gap, HourHistogram, HISTORY_WEEKS},
+    get_pref_label, load_log_for,
+    locale::format_date,
+    plan, stats, App,
+};
+
+use super::{
+    draw_messages, task_color,
+    utils::{bold, dim, format_mins},
+    Page,
+};
+
+/// Only the biggest gaps are worth going through
+const MAX_GAPS: usize = 10;
+
+#[derive(Debug)]
+pub struct State {
+    /// Largest first
+    gaps: Vec<Gap>,
+    list_state: ListState,
+}
+
+impl State {
+    /// Finds the largest untracked gaps in the work hours of the past week
+    pub fn load(app: &App) -> Self {
+        let now = Local::now();
+        let hours = work_hours(&app.preferences);
+
+        // What the user usually does at each time of the week, for suggesting
+        // a task to fill the gaps that nothing else suggests one for
+        let today = now.date_naive();
+        let history = match stats::load_dated_logs(
+            &app.dirs,
+            today.checked_sub_days(Days::new(HISTORY_WEEKS * 7)),
+            today.pred_opt(),
+        ) {
+            Ok(logs) => HourHistogram::from_logs(logs.iter().flat_map(|(_, day)| day.iter())),
+            Err(e) => {
+                warn!("Unable to load history for gap suggestions: {}", e);
+                HourHistogram::default()
+            }
+        };
+
+        let mut gaps: Vec<Gap> = report_days(&app.preferences, now.date_naive())
+            .into_iter()
+            .flat_map(|date| {
+                if date == now.date_naive() {
+                    find_gaps(date, &app.today, &app.today_plan, &history, hours, now)
+                } else {
+                    // A day without a save file was entirely untracked
+                    let logs = load_log_for(&app.dirs, date).unwrap_or_default();
+                    let plan = plan::load_plan(&app.dirs, date).unwrap_or_default();
+                    find_gaps(date, &logs, &plan, &history, hours, now)
+                }
+            })
+            .collect();
+        gaps.sort_by_key(|g| std::cmp::Reverse(g.duration()));
+        gaps.truncate(MAX_GAPS);
+
+        let mut list_state = ListState::default();
+        if !gaps.is_empty() {
+            list_state.select(Some(0));
+        }
+        Self { gaps, list_state }
+    }
+
+    pub fn select_prev(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            self.list_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            self.list_state
+                .select(Some((i + 1).min(self.gaps.len().saturating_sub(1))));
+        }
+    }
+
+    /// Removes the selected gap from the list so that it can be filled in,
+    /// along with the task to fill it with: `number` if given, else the
+    /// suggested one. Leaves the list alone if there's no task to fill with.
+    pub fn take_selected(&mut self, number: Option<u8>) -> Option<(Gap, u8)> {
+        let i = self.list_state.selected()?;
+        let gap = *self.gaps.get(i)?;
+        let number = number.or(gap.suggestion.map(|(n, _)| n))?;
+
+        self.gaps.remove(i);
+        self.list_state.select(if self.gaps.is_empty() {
+            None
+        } else {
+            Some(i.min(self.gaps.len() - 1))
+        });
+        Some((gap, number))
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Gaps(ref mut state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render gaps page when the app isn't in gaps page state!")
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .horizontal_margin(2)
+        .constraints(
+            [
+                Constraint::Length(1), // Instructions
+                Constraint::Min(2),    // Gaps
+                Constraint::Length(1), // Messages
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let help_message = Paragraph::new(Line::from(vec![
+        bold("q"),
+        Span::raw("/"),
+        bold("Esc"),
+        Span::raw(": back | "),
+        bold("k+j"),
+        Span::raw(": up+down | "),
+        bold("Enter"),
+        Span::raw(": accept suggestion | "),
+        bold("1-8 keys"),
+        Span::raw(": fill with task"),
+    ]));
+    f.render_widget(help_message, chunks[0]);
+
+    let (work_start, work_end) = work_hours(&app.preferences);
+    let title = format!(
+        "Largest untracked gaps this week ({}-{} on weekdays)",
+        work_start.format("%R"),
+        work_end.format("%R")
+    );
+
+    let list = if state.gaps.is_empty() {
+        List::new(vec![ListItem::new("No untracked gaps, nice!")])
+    } else {
+        let prefs = &app.preferences;
+        List::new(
+            state
+                .gaps
+                .iter()
+                .map(|gap| {
+                    let mut spans = vec![
+                        Span::raw(format!(
+                            "{} {}-{} ",
+                            format_date(prefs, gap.date, "%a %F"),
+                            gap.start.format("%R"),
+                            gap.end.format("%R")
+                        )),
+                        bold(format!(
+                            "({})",
+                            format_mins(gap.duration().num_minutes() as u32)
+                        )),
+                    ];
+                    if let Some((number, reason)) = gap.suggestion {
+                        spans.push(Span::raw(" → "));
+                        spans.push(Span::styled(
+                            get_pref_label(number, prefs.labels.as_ref())
+                                .unwrap_or_else(|| number.to_string()),
+                            Style::default().fg(task_color(prefs, number)),
+                        ));
+                        spans.push(dim(format!(" ({})", reason.describe())));
+                    }
+                    ListItem::new(Line::from(spans))
+                })
+                .collect::<Vec<ListItem>>(),
+        )
+    }
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+    .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[1], &mut state.list_state);
+
+    draw_messages(f, app, chunks[2]);
+}
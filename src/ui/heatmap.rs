@@ -0,0 +1,320 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::mpsc::{self, TryRecvError},
+    thread,
+};
+
+use chrono::{Datelike, Days, Local, NaiveDate, Weekday};
+use ratatui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{canvas::Canvas, Block, Borders, Paragraph},
+    Frame,
+};
+
+use crate::{
+    get_pref_label,
+    locale::format_date,
+    stats::{load_history, History},
+    storage::Dirs,
+    utils::start_of_week,
+    App, Preferences,
+};
+
+use super::{
+    draw_messages, task_color,
+    utils::{bold, spinner},
+    widgets::Heatmap,
+    Page,
+};
+
+/// A year's worth, like GitHub's
+const WEEKS: u16 = 53;
+
+/// A calendar of how much was tracked each day over the past year, to spot
+/// untracked days and patterns in the workload
+#[derive(Debug)]
+pub struct State {
+    /// The last day shown, which is today unless the user has gone back
+    end: NaiveDate,
+    /// The first day shown, at the start of a week
+    start: NaiveDate,
+    /// The time tracked on each day that has a save file, counting time spent
+    /// on parallel tasks only once
+    totals: BTreeMap<NaiveDate, chrono::Duration>,
+    /// Each task's total on each day that has a save file
+    task_totals: BTreeMap<NaiveDate, [chrono::Duration; 8]>,
+    /// Only shows this task's time, if set
+    task: Option<u8>,
+    /// The year's history while it's still loading in the background, until
+    /// `poll_loading` takes it in
+    loading: Option<mpsc::Receiver<io::Result<History>>>,
+}
+
+impl State {
+    /// Starts loading the year up to today
+    pub fn load_this_year(dirs: &Dirs, prefs: &Preferences) -> Self {
+        Self::load(dirs, prefs, Local::now().date_naive(), None)
+    }
+
+    /// Starts loading the year up to `end` in the background, since reading a
+    /// year of logs can take a while. The page shows a spinner until
+    /// `poll_loading` takes it in.
+    fn load(dirs: &Dirs, prefs: &Preferences, end: NaiveDate, task: Option<u8>) -> Self {
+        let first_day = prefs.week_start_day.unwrap_or(Weekday::Sun);
+        let start = start_of_week(end, first_day) - Days::new(7 * (WEEKS as u64 - 1));
+        let dirs = dirs.clone();
+        let rounding = prefs.billing_rounding;
+        let profile = prefs.profile.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let history = load_history(&dirs, Some(start), Some(end), rounding, profile.as_deref());
+            // If the user has already moved on, nobody's listening anymore and
+            // that's fine
+            let _ = tx.send(history);
+        });
+        Self {
+            end,
+            start,
+            totals: BTreeMap::new(),
+            task_totals: BTreeMap::new(),
+            task,
+            loading: Some(rx),
+        }
+    }
+
+    /// Takes in the year's history if it's done loading. Returns the error if
+    /// loading it failed, in which case the page is left empty.
+    pub fn poll_loading(&mut self) -> io::Result<()> {
+        let Some(rx) = self.loading.as_ref() else {
+            return Ok(());
+        };
+        let history = match rx.try_recv() {
+            Ok(history) => history,
+            Err(TryRecvError::Empty) => return Ok(()),
+            Err(TryRecvError::Disconnected) => {
+                Err(io::Error::other("Loading history was interrupted"))
+            }
+        };
+        self.loading = None;
+        let history = history?;
+        self.totals = history.daily_totals;
+        self.task_totals = history.daily_task_totals;
+        Ok(())
+    }
+
+    pub fn prev_year(&mut self, dirs: &Dirs, prefs: &Preferences) {
+        if let Some(end) = self.end.checked_sub_days(Days::new(7 * WEEKS as u64)) {
+            *self = Self::load(dirs, prefs, end, self.task);
+        }
+    }
+
+    /// Goes forward a year, as far as today
+    pub fn next_year(&mut self, dirs: &Dirs, prefs: &Preferences) {
+        let today = Local::now().date_naive();
+        if self.end < today {
+            let end = self
+                .end
+                .checked_add_days(Days::new(7 * WEEKS as u64))
+                .map_or(today, |end| end.min(today));
+            *self = Self::load(dirs, prefs, end, self.task);
+        }
+    }
+
+    /// Shows only the given task's time, or every task's again if it's the
+    /// one already shown
+    pub fn toggle_task(&mut self, number: u8) {
+        self.task = (self.task != Some(number)).then_some(number);
+    }
+
+    /// The time shown for `date`
+    fn tracked(&self, date: NaiveDate) -> chrono::Duration {
+        match self.task {
+            Some(number) => self
+                .task_totals
+                .get(&date)
+                .and_then(|tasks| tasks.get((number as usize).checked_sub(1)?))
+                .copied(),
+            None => self.totals.get(&date).copied(),
+        }
+        .unwrap_or_else(chrono::Duration::zero)
+    }
+}
+
+/// How dark to shade a day that had `tracked` when the most of any day was
+/// `most`: 0 for nothing, else in quarters of the most
+fn shade(tracked: chrono::Duration, most: chrono::Duration) -> u8 {
+    if tracked <= chrono::Duration::zero() || most <= chrono::Duration::zero() {
+        return 0;
+    }
+    let quarters = 4.0 * tracked.num_seconds() as f64 / most.num_seconds().max(1) as f64;
+    (quarters.ceil() as u8).clamp(1, 4)
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Heatmap(ref state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render heatmap page when the app isn't in heatmap page state!")
+    };
+    let prefs = &app.preferences;
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .horizontal_margin(2)
+        .constraints(
+            [
+                Constraint::Length(1),  // Instructions
+                Constraint::Length(10), // The calendar
+                Constraint::Length(2),  // Summary and legend
+                Constraint::Min(0),
+                Constraint::Length(1), // Messages
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let help_message = Paragraph::new(Line::from(vec![
+        bold("q"),
+        Span::raw("/"),
+        bold("Esc"),
+        Span::raw(": back | "),
+        bold("h+l"),
+        Span::raw("/"),
+        bold("←+→"),
+        Span::raw(": previous/next year | "),
+        bold("1-8"),
+        Span::raw(": only that task (again for all)"),
+    ]));
+    f.render_widget(help_message, chunks[0]);
+
+    // Narrow terminals get as many of the latest weeks as fit
+    let (label_width, height) = Heatmap::size(0);
+    let weeks = WEEKS.min(chunks[1].width.saturating_sub(label_width + 2) / 2);
+    let first = state.start + Days::new(7 * (WEEKS - weeks) as u64);
+    let days: Vec<NaiveDate> = first
+        .iter_days()
+        .take_while(|date| *date <= state.end)
+        .collect();
+    let most = days
+        .iter()
+        .map(|date| state.tracked(*date))
+        .max()
+        .unwrap_or_else(chrono::Duration::zero);
+    let cells = days
+        .iter()
+        .enumerate()
+        .map(|(i, date)| {
+            let shade = shade(state.tracked(*date), most);
+            ((i / 7) as u16, (i % 7) as u16, shade)
+        })
+        .collect();
+    // Each month is labeled over the first week starting in it
+    let column_labels = days
+        .iter()
+        .step_by(7)
+        .enumerate()
+        .filter(|(i, date)| *i == 0 || date.day() <= 7)
+        .map(|(i, date)| (i as u16, format_date(prefs, *date, "%b")))
+        .collect();
+    let row_labels = first
+        .iter_days()
+        .take(7)
+        .enumerate()
+        .filter(|(i, _)| i % 2 == 1)
+        .map(|(i, date)| (i as u16, format_date(prefs, date, "%a")))
+        .collect();
+
+    let (color, shown) = match state.task {
+        Some(number) => (
+            task_color(prefs, number),
+            get_pref_label(number, prefs.labels.as_ref()).unwrap_or_else(|| number.to_string()),
+        ),
+        None => (Color::Green, String::from("all tasks")),
+    };
+    let (width, _) = Heatmap::size(weeks);
+    let area = Rect {
+        width: (width + 2).min(chunks[1].width),
+        ..chunks[1]
+    };
+    let canvas = Canvas::default()
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Tracked time, {}: {} to {}",
+            shown,
+            format_date(prefs, first, "%x"),
+            format_date(prefs, state.end, "%x")
+        )))
+        .paint(
+            Heatmap::new(cells, color)
+                .row_labels(row_labels)
+                .column_labels(column_labels)
+                .painter(),
+        )
+        .x_bounds([0.0, (width - 1) as f64])
+        .y_bounds([0.0, (height - 1) as f64]);
+    // The canvas can't draw into nothing, which is all that's left inside
+    // the borders in a tiny terminal
+    if state.loading.is_some() {
+        f.render_widget(
+            Paragraph::new(format!("Loading history {}", spinner())),
+            chunks[1],
+        );
+    } else if area.width > 2 && area.height > 2 {
+        f.render_widget(canvas, area);
+    }
+
+    let untracked = days
+        .iter()
+        .filter(|date| state.tracked(**date) <= chrono::Duration::zero())
+        .count();
+    let busiest = days.iter().max_by_key(|date| state.tracked(**date));
+    let mut summary = vec![Span::raw(format!(
+        "{} of {} days with nothing tracked",
+        untracked,
+        days.len()
+    ))];
+    if let Some(busiest) = busiest.filter(|_| most > chrono::Duration::zero()) {
+        summary.push(Span::raw(format!(
+            " • the most was {} on {}",
+            humantime::format_duration(most.to_std().unwrap_or_default()),
+            format_date(prefs, *busiest, "%a %x")
+        )));
+    }
+    let mut legend = vec![Span::raw("Less ")];
+    legend.extend(Heatmap::SHADES.iter().enumerate().map(|(i, shade)| {
+        Span::styled(
+            format!("{} ", shade),
+            Style::default().fg(if i == 0 { Color::DarkGray } else { color }),
+        )
+    }));
+    legend.push(Span::raw("More"));
+    // There's nothing to sum up until the year's loaded
+    if state.loading.is_none() {
+        f.render_widget(
+            Paragraph::new(vec![Line::from(summary), Line::from(legend)]),
+            chunks[2],
+        );
+    }
+
+    draw_messages(f, app, chunks[4]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shades() {
+        let hours = chrono::Duration::hours;
+        assert_eq!(shade(hours(0), hours(8)), 0);
+        assert_eq!(shade(chrono::Duration::minutes(5), hours(8)), 1);
+        assert_eq!(shade(hours(4), hours(8)), 2);
+        assert_eq!(shade(hours(7), hours(8)), 4);
+        assert_eq!(shade(hours(8), hours(8)), 4);
+        assert_eq!(shade(hours(1), chrono::Duration::zero()), 0);
+    }
+}
@@ -1,139 +1,488 @@
-use chrono::{Local, NaiveTime, Timelike};
+use std::collections::VecDeque;
+
+use chrono::{Local, NaiveDate};
 use ratatui::{
     backend::Backend,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
     Frame,
 };
 
-use crate::{legend, utils::datetime_with_zeroed_time, App, TimeLog};
+use crate::{
+    breaks,
+    durations::{self, Rounding},
+    get_pref_label, legend,
+    locale::format_date,
+    plan::next_block,
+    stats::DaySummary,
+    targets,
+    timeline::{self, Segment, Window},
+    App, Preferences, TimeLog,
+};
 
 use super::{
+    draw_messages,
     editable_list::EditableList,
-    message_widget, number_to_color,
-    utils::{self, blinky_if_index_matches, bold},
+    entry_color, task_color, task_pattern,
+    utils::{self, blinky_if_index_matches, bold, centered_rect},
     Page,
 };
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub enum State {
-    #[default]
-    Viewing,
+    /// Looking at today's entries, with one of them selected once the user
+    /// moves up or down through the list
+    Viewing { list_state: TableState },
     Editing {
-        state: EditableList<TableState, TimeLog>,
+        /// Boxed, as entries make it much bigger than the other states
+        state: Box<EditableList<TableState, TimeLog>>,
         cursor_pos: usize,
         delete_pending: bool,
+        /// Where a block selection (started with V) was started, if one is
+        /// being made
+        visual_anchor: Option<usize>,
+        history: EditHistory,
     },
+    /// Typing in the quick-start popup, e.g. "3 #standup"
+    QuickStart { input: String },
+    /// Typing the number of a task to start, after pressing G
+    GoTo { input: String },
+    /// Typing a label for a task that was just started without one
+    NamingTask { number: u8, input: String },
+    /// Searching task labels for one to start, e.g. "cod" for Coding
+    Palette { input: String, selected: usize },
+    /// Asking whether the user really wants to quit
+    ConfirmQuit,
+    /// Looking back at the previous day, on first starting the app on a new
+    /// day
+    Summary(Box<DaySummary>),
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::Viewing {
+            list_state: Default::default(),
+        }
+    }
 }
 
 impl State {
-    pub fn editable(options: Vec<TimeLog>) -> Self {
+    /// Starts editing mode on `options` with the entry at `index` already
+    /// being edited
+    pub fn editing_entry(options: Vec<TimeLog>, index: usize) -> Self {
+        let mut editing = Self::editable(options, Some(index));
+        if let Self::Editing { ref mut state, .. } = editing {
+            state.start_editing(None);
+        }
+        editing
+    }
+
+    /// Starts editing mode on `options` with the entry at `selected`, if any,
+    /// already selected
+    pub fn editable(options: Vec<TimeLog>, selected: Option<usize>) -> Self {
+        let mut state: EditableList<TableState, TimeLog> = EditableList::new(options);
+        state.list_state.select(selected);
         Self::Editing {
-            state: EditableList::new(options),
+            state: Box::new(state),
             cursor_pos: 0,
             delete_pending: false,
+            visual_anchor: None,
+            history: Default::default(),
         }
     }
 }
 
-/// Returns a tuple of start (inclusive) and end (exclusive) x-coordinates for
-/// drawing the specified absolute duration
-fn duration_to_x_coords(start: NaiveTime, end: NaiveTime, max_width: u16) -> (u16, u16) {
-    // - Width is in "pixels" (technically not pixels but whatever I'm gonna
-    // call them that)
-    // - The width must be divisible by 24 (this is guaranteed by the layout in
-    // ui() at the moment)
-    // - Each 1/24th of width is an hour
-    // By relying on these facts we can compute the coordinates in pixels of a
-    // given duration:
-
-    // num_secs / number_of_secs_in_day = % of the day this duration fills
-    // multiply that % by the width then round and clamp
-    // `as` automatically clamps to the max/min value of the integer type
-
-    // Okay also I want my table scale to go from 05:00 to 04:59, instead of
-    // 00:00 to 23:59. Good thing NaiveTime subraction wraps around! This makes
-    // it so that values approaching (but not exceeding) 5am will be at the
-    // "end" of the table, while numbers at and after 5am will be at the
-    // "beginning"
-    let start_percent_of_day =
-        ((start - chrono::Duration::hours(5)).num_seconds_from_midnight() as f32) / 86400.0;
-    let end_percent_of_day =
-        ((end - chrono::Duration::hours(5)).num_seconds_from_midnight() as f32) / 86400.0;
-
-    let start_px = (((max_width as f32) * start_percent_of_day).round() as u16).clamp(0, max_width);
-    let end_px = (((max_width as f32) * end_percent_of_day).round() as u16).clamp(0, max_width);
-
-    (start_px, end_px)
+/// Moves the Viewing selection up or down one of `len` entries. Moving from no
+/// selection starts at the latest entry.
+pub fn move_selection(list_state: &mut TableState, len: usize, up: bool) {
+    let Some(last) = len.checked_sub(1) else {
+        return;
+    };
+    list_state.select(Some(match list_state.selected() {
+        None => last,
+        Some(i) if up => i.saturating_sub(1),
+        Some(i) => (i + 1).min(last),
+    }));
 }
 
-fn make_today_row(app: &App, max_width: u16) -> (Row, Vec<Constraint>) {
-    let table_starts_at = datetime_with_zeroed_time(&Local::now())
-        .with_hour(5)
-        .unwrap();
-    let table_ends_at =
-        table_starts_at + chrono::Duration::hours(24) - chrono::Duration::nanoseconds(1);
+/// Scores how well `query` fuzzily matches `candidate`, ignoring case. Every
+/// character of the query has to appear in the candidate in order; matches at
+/// the start of a word and runs of consecutive matches score higher. None if
+/// it doesn't match.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut prev: Option<char> = None;
+    let mut prev_matched = false;
+    for c in candidate.chars().flat_map(char::to_lowercase) {
+        let Some(&q) = query.peek() else {
+            break;
+        };
+        let matched = c == q;
+        if matched {
+            query.next();
+            score += 1;
+            if prev_matched {
+                score += 5;
+            }
+            if prev.is_none_or(|p| !p.is_alphanumeric()) {
+                score += 10;
+            }
+        }
+        prev_matched = matched;
+        prev = Some(c);
+    }
+    query.peek().is_none().then_some(score)
+}
 
-    // Only count things that happened at least a little bit during today
-    let today_iter = app
-        .today
-        .iter()
-        .filter(|tl| tl.end.map_or(true, |e| e > table_starts_at) && tl.start < table_ends_at)
-        .enumerate();
+/// The task number whose key gives `c` with Shift held on a US layout, like 3
+/// for '#'
+pub fn shifted_digit(c: char) -> Option<u8> {
+    let i = "!@#$%^&*".find(c)?;
+    Some(i as u8 + 1)
+}
+
+/// The labeled tasks matching the palette's query as (number, label), best
+/// match first
+pub fn palette_matches(prefs: &Preferences, query: &str) -> Vec<(u8, String)> {
+    let mut matches: Vec<(i32, u8, String)> = (1..=8)
+        .filter_map(|number| {
+            let label = get_pref_label(number, prefs.labels.as_ref())?;
+            let score = fuzzy_score(query, &label)?;
+            Some((score, number, label))
+        })
+        .collect();
+    matches.sort_by_key(|(score, number, _)| (-score, *number));
+    matches
+        .into_iter()
+        .map(|(_, number, label)| (number, label))
+        .collect()
+}
+
+/// How many edits back the editor can undo
+const MAX_UNDO: usize = 50;
+
+/// Earlier (and undone) versions of the day's entries, so that edits made in
+/// the editor can be undone and redone
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Vec<TimeLog>>,
+    redo: Vec<Vec<TimeLog>>,
+    /// The entries as they were before an insert that's still being edited
+    inserting: Option<Vec<TimeLog>>,
+}
+
+impl EditHistory {
+    /// Remembers the entries as they were before an edit, or before the
+    /// insert it saves. Making a new edit forgets anything that was undone.
+    pub fn record(&mut self, before: &[TimeLog]) {
+        let before = self.inserting.take().unwrap_or_else(|| before.to_vec());
+        self.undo.push_back(before);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    /// Puts `entries` back the way they were before the last edit. Returns
+    /// false if there's nothing to undo.
+    pub fn undo(&mut self, entries: &mut Vec<TimeLog>) -> bool {
+        match self.undo.pop_back() {
+            Some(before) => {
+                self.redo.push(std::mem::replace(entries, before));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Holds on to the entries as they were before an insert, which only
+    /// becomes an edit once it's saved
+    pub fn begin_insert(&mut self, before: &[TimeLog]) {
+        self.inserting = Some(before.to_vec());
+    }
+
+    /// Forgets the insert being edited, if there is one, and returns the
+    /// entries as they were before it
+    pub fn cancel_insert(&mut self) -> Option<Vec<TimeLog>> {
+        self.inserting.take()
+    }
 
-    let last_day = today_iter.clone().count().saturating_sub(1);
+    /// Makes the last undone edit to `entries` again. Returns false if there's
+    /// nothing to redo.
+    pub fn redo(&mut self, entries: &mut Vec<TimeLog>) -> bool {
+        match self.redo.pop() {
+            Some(after) => {
+                self.undo.push_back(std::mem::replace(entries, after));
+                true
+            }
+            None => false,
+        }
+    }
+}
 
+/// Fills a timeline row `max_width` pixels wide with a colored cell per
+/// segment and blank cells in between
+pub fn make_timeline_row<'a>(
+    segments: Vec<Segment>,
+    mut cell: impl FnMut(&Segment) -> Cell<'a>,
+) -> (Row<'a>, Vec<Constraint>) {
     let mut cols: Vec<Constraint> = Vec::new();
     let mut row: Vec<Cell> = Vec::new();
     let mut current_px = 0;
 
-    // Assume it's already sorted, since load() does this, and you're not
-    // manually typing in entries in the future are you ;)
-    for (i, curr_tl) in today_iter {
-        // Insert the current cell
-        let coords = if let Some(end) = curr_tl.end {
-            duration_to_x_coords(curr_tl.start.time(), end.time(), max_width)
-        } else {
-            duration_to_x_coords(curr_tl.start.time(), Local::now().time(), max_width)
-        };
-
-        if coords.0 > current_px {
-            let len = coords.0 - current_px;
-            cols.push(Constraint::Length(len));
+    for segment in segments {
+        if segment.start_px > current_px {
+            cols.push(Constraint::Length(segment.start_px - current_px));
             row.push(Cell::from(""));
-            current_px += len;
-        }
-
-        let always_show = i == last_day && curr_tl.end.is_none();
-        if coords.1 > current_px || always_show {
-            let len = (coords.1 - current_px).max(1);
-            cols.push(Constraint::Length(len));
-            row.push(
-                Cell::from(curr_tl.label(app)).style(
-                    Style::default()
-                        .fg(Color::Black)
-                        .bg(number_to_color(curr_tl.number)),
-                ),
-            );
-            current_px += len;
         }
+        cols.push(Constraint::Length(segment.end_px - segment.start_px));
+        row.push(cell(&segment));
+        current_px = segment.end_px;
     }
 
     (Row::new(row), cols)
 }
 
+/// The text of a timeline cell: the label, plus the task's fill pattern if the
+/// theme has one. The pattern fills the rest of the cell, since the table
+/// truncates cell text to the cell's width.
+pub fn timeline_text(app: &App, number: u8, label: String, max_width: u16) -> String {
+    match task_pattern(&app.preferences, number) {
+        Some(p) => format!("{}{}{}", p, label, p.to_string().repeat(max_width as usize)),
+        None => label,
+    }
+}
+
+/// Today's entries, or if `parallel`, only those of parallel tasks, which get
+/// a row of their own since they overlap everything else
+fn make_today_row(
+    app: &App,
+    parallel: bool,
+    window: Window,
+    max_width: u16,
+) -> (Row<'_>, Vec<Constraint>) {
+    let entries: Vec<TimeLog> = app
+        .today
+        .iter()
+        .filter(|tl| app.is_parallel(tl.number) == parallel)
+        .cloned()
+        .collect();
+    make_timeline_row(
+        timeline::lay_out_day(&entries, Local::now(), window, max_width),
+        |segment| {
+            let tl = &entries[segment.index];
+            Cell::from(timeline_text(app, tl.number, tl.label(app), max_width)).style(
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(entry_color(&app.preferences, tl)),
+            )
+        },
+    )
+}
+
+/// Like `make_today_row` but for the day being compared with, drawn dimly and
+/// shaded so that it can't be mistaken for today or the plan. It's laid out as
+/// of the current time on that day, so entries left open there end at "now".
+fn make_comparison_row<'a>(
+    app: &'a App,
+    date: NaiveDate,
+    entries: &'a [TimeLog],
+    window: Window,
+    max_width: u16,
+) -> (Row<'a>, Vec<Constraint>) {
+    let now = date
+        .and_time(Local::now().time())
+        .and_local_timezone(Local)
+        .earliest()
+        .unwrap_or_else(Local::now);
+    make_timeline_row(
+        timeline::lay_out_day(entries, now, window, max_width),
+        |segment| {
+            let tl = &entries[segment.index];
+            let text = match task_pattern(&app.preferences, tl.number) {
+                Some(_) => timeline_text(app, tl.number, tl.label(app), max_width),
+                None => format!("{}{}", tl.label(app), "░".repeat(max_width as usize)),
+            };
+            Cell::from(text).style(
+                Style::default()
+                    .fg(entry_color(&app.preferences, tl))
+                    .add_modifier(Modifier::DIM),
+            )
+        },
+    )
+}
+
+/// Like `make_today_row` but for what the user planned to do today
+fn make_plan_row(app: &App, window: Window, max_width: u16) -> (Row<'_>, Vec<Constraint>) {
+    let blocks = app
+        .today_plan
+        .iter()
+        .map(|block| (block.number, block.start, block.end, false));
+    make_timeline_row(timeline::lay_out(blocks, window, max_width), |segment| {
+        let label = get_pref_label(segment.task, app.preferences.labels.as_ref())
+            .unwrap_or_else(|| segment.task.to_string());
+        Cell::from(timeline_text(app, segment.task, label, max_width)).style(
+            Style::default()
+                .fg(task_color(&app.preferences, segment.task))
+                .add_modifier(Modifier::DIM),
+        )
+    })
+}
+
+/// Time spent in parallel counts once, with how much of it overlapped shown
+/// separately, like "05:00:00 (+00:45:00 in parallel)"
 fn format_total_time(today: &[TimeLog]) -> String {
-    let now = Local::now();
-    let total = today.iter().fold(chrono::Duration::zero(), |acc, tl| {
-        acc + (tl.end.as_ref().copied().unwrap_or(now) - tl.start)
+    let now = Some(Local::now());
+    let covered = durations::format_hms(durations::covered(today, now));
+    let overlap = durations::overlap(today, now);
+    if overlap > chrono::Duration::zero() {
+        format!(
+            "{} (+{} in parallel)",
+            covered,
+            durations::format_hms(overlap)
+        )
+    } else {
+        covered
+    }
+}
+
+/// The texts of the status row: the day's total, what's next (or the break or
+/// timebox going on instead), and whether the tracker is connected
+fn status_texts(app: &App) -> (String, String, String) {
+    let planned_total = app
+        .today_plan
+        .iter()
+        .fold(chrono::Duration::zero(), |acc, b| acc + b.duration());
+    // Measure today against the user's target for it, if they have one
+    let total = match targets::target_for(&app.preferences, Local::now().date_naive()) {
+        Some(target) if target > chrono::Duration::zero() => format!(
+            "{} of {}",
+            format_total_time(&app.today),
+            utils::format_mins(target.num_minutes() as u32)
+        ),
+        _ => format_total_time(&app.today),
+    };
+    let mut total_text = if app.today_plan.is_empty() {
+        format!("Total: {}", total)
+    } else {
+        format!(
+            "Total: {} (planned {})",
+            total,
+            durations::format_hms(planned_total)
+        )
+    };
+    // Breaks are counted apart from the total rather than being left as gaps
+    let breaks_total = breaks::total(&app.today_breaks, Some(Local::now()));
+    if breaks_total > chrono::Duration::zero() {
+        total_text.push_str(&format!(", breaks {}", durations::format_hms(breaks_total)));
+    }
+
+    let mut tracker_text = format!(
+        "Tracker: {}onnected",
+        if app.tracker_connected { "C" } else { "Not c" }
+    );
+    if app.is_read_only() {
+        tracker_text.push_str(" (read-only)");
+    } else if app.is_saving() {
+        tracker_text.insert_str(0, "Saving... | ");
+    } else if app.save_retry.is_some() {
+        tracker_text.insert_str(0, "Not saved | ");
+    }
+
+    // A break, or else a timeboxed entry's countdown, takes the place of
+    // what's planned next
+    let on_break = app.on_break().map(|b| {
+        format!(
+            "On a break from {}: {}",
+            get_pref_label(b.number, app.preferences.labels.as_ref())
+                .unwrap_or_else(|| b.number.to_string()),
+            durations::format_hms(b.duration(Some(Local::now())))
+        )
+    });
+    let timebox = app.open_entry().and_then(|tl| {
+        let mins = tl.estimate_mins?;
+        let left = tl.start + chrono::Duration::minutes(mins.into()) - Local::now();
+        Some(if left > chrono::Duration::zero() {
+            format!("{}: {} left", tl.label(app), durations::format_hms(left))
+        } else {
+            format!("{}: {} over", tl.label(app), durations::format_hms(-left))
+        })
+    });
+    let now = Local::now().time();
+    let next_text = on_break.or(timebox).unwrap_or_else(|| {
+        next_block(&app.today_plan, now).map_or_else(String::new, |block| {
+            // Round up so that "in 0m" never shows while it's still upcoming
+            let mins = durations::round(
+                block.start - now,
+                chrono::Duration::minutes(1),
+                Rounding::Up,
+            )
+            .num_minutes();
+            format!(
+                "Next: {} in {}",
+                get_pref_label(block.number, app.preferences.labels.as_ref())
+                    .unwrap_or_else(|| block.number.to_string()),
+                utils::format_mins(mins as u32)
+            )
+        })
     });
-    // Chrono's Duration doesn't get a format method, but NaiveTime does
-    (NaiveTime::MIN + total).format("%T").to_string()
+
+    (total_text, next_text, tracker_text)
+}
+
+/// Home as plain lines of text, for screen readers and dumb terminals: what's
+/// being tracked, the day so far and today's entries, without the timeline
+pub fn draw_plain<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let (total_text, next_text, tracker_text) = status_texts(app);
+    let mut lines = vec![
+        "Keys: q quit, 1-8 start a task, 0 stop, b break, e edit, y copy yesterday, \
+         arrows select, h history, w week, s settings"
+            .to_string(),
+        match app.open_entry_elapsed(Local::now()) {
+            Some(elapsed) => format!("Tracking {}", elapsed),
+            None => "Not tracking".to_string(),
+        },
+        total_text,
+    ];
+    if !next_text.is_empty() {
+        lines.push(next_text);
+    }
+    lines.push(tracker_text);
+    lines.extend(app.messages.iter().map(|m| m.0.clone()));
+
+    let selected = match &app.selected_page {
+        Page::Home(State::Viewing { list_state }) => list_state.selected(),
+        _ => None,
+    };
+    lines.push(format!("Today's entries: {}", app.today.len()));
+    lines.extend(app.today.iter().enumerate().map(|(i, tl)| {
+        format!(
+            "{}{}",
+            if selected == Some(i) { "> " } else { "  " },
+            tl.describe(&app.preferences)
+        )
+    }));
+    // Keep the selected entry in view
+    let focus = selected.map(|i| lines.len() - app.today.len() + i);
+    super::draw_plain_lines(f, lines, focus);
 }
 
+/// Below these heights, there isn't room for the Today bar or its legend as
+/// well as a few entries
+const MIN_TODAY_BAR_HEIGHT: u16 = 12;
+const MIN_LEGEND_HEIGHT: u16 = 16;
+
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let has_parallel = app.today.iter().any(|tl| app.is_parallel(tl.number));
+    // Small terminals give up the legend, then the Today bar, rather than the
+    // entries. The bar needs an hour's column per hour, inside its borders
+    // and the page's margins, to be readable at all.
+    let size = f.size();
+    let show_today = size.width >= 24 + 2 + 4 && size.height >= MIN_TODAY_BAR_HEIGHT;
+    let show_legend = show_today && size.height >= MIN_LEGEND_HEIGHT;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .vertical_margin(1)
@@ -141,78 +490,177 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .constraints(
             [
                 Constraint::Length(1), // Instructions
-                Constraint::Length(3), // "Today" table
-                Constraint::Length(2), // Table legend
-                Constraint::Length(1), // Status row
-                Constraint::Min(2),    // List of time entries
-                Constraint::Length(1), // Messages
+                // "Today" table, with extra rows for parallel tasks, the day
+                // it's compared with and today's plan if any
+                Constraint::Length(if show_today {
+                    3 + u16::from(has_parallel)
+                        + u16::from(app.comparison.is_some())
+                        + u16::from(!app.today_plan.is_empty())
+                } else {
+                    0
+                }),
+                Constraint::Length(if show_legend { 2 } else { 0 }), // Table legend
+                Constraint::Length(1),                               // Status row
+                Constraint::Min(2),                                  // List of time entries
+                Constraint::Length(1),                               // Messages
             ]
             .as_ref(),
         )
-        .split(f.size());
-
-    // Because integer division is truncated, we might end up with a situation
-    // where our columns would have been e.g. 142/24 = 5.9166666667 pixels wide,
-    // which would get truncated to 5px, which would make our table look all
-    // squished and only take up part of the screen. To fix this, we ensure that
-    // our table inner rectangle width is always divisible by 24.
-
-    let table_block = Block::default().borders(Borders::ALL).title("Today");
-    // Blocks with borders take up 1px on either side, so we have to increase
-    // the whole table Rect width by 2
-    let nice_table_width = ((table_block.inner(chunks[1]).width / 24) * 24) + 2;
-    let table_horiz_margin = (chunks[1].width - nice_table_width) / 2;
-    let table_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(
-            [
-                Constraint::Length(table_horiz_margin),
-                Constraint::Length(nice_table_width),
-                Constraint::Length(table_horiz_margin),
-            ]
-            .as_ref(),
-        );
+        .split(size);
 
-    let table_rect = table_layout.split(chunks[1])[1];
-    let legend_rect = table_layout.split(chunks[2])[1];
+    if show_today {
+        // Because integer division is truncated, we might end up with a situation
+        // where our columns would have been e.g. 142/24 = 5.9166666667 pixels wide,
+        // which would get truncated to 5px, which would make our table look all
+        // squished and only take up part of the screen. To fix this, we ensure that
+        // our table inner rectangle width is always divisible by the number of
+        // hours shown, which is 24 unless the user has zoomed in.
+        let window = app.zoom.window(Local::now().time());
+        let hours = window.hours as u16;
 
-    let (row, cols) = make_today_row(app, nice_table_width - 2);
-    let table = Table::new(vec![row])
-        .block(table_block)
-        .column_spacing(0)
-        .widths(&cols);
-    f.render_widget(table, table_rect);
-
-    if nice_table_width > 26 {
-        let legend: &Table<'static> = if nice_table_width < 74 {
-            &legend::TRUNC_LEGEND_TABLE
+        let title = match app.comparison {
+            Some((date, _)) => format!(
+                "Today (shaded: {})",
+                format_date(&app.preferences, date, "%a %b %-d")
+            ),
+            None => String::from("Today"),
+        };
+        let title = if window == Window::DAY {
+            title
         } else {
-            &legend::LEGEND_TABLE
+            format!(
+                "{}, {} to {}",
+                title,
+                window.start.format("%R"),
+                (window.start + chrono::Duration::hours(window.hours as i64)).format("%R")
+            )
         };
+        let table_block = Block::default().borders(Borders::ALL).title(title);
+        // Blocks with borders take up 1px on either side, so we have to increase
+        // the whole table Rect width by 2
+        let nice_table_width = ((table_block.inner(chunks[1]).width / hours) * hours) + 2;
+        let table_horiz_margin = (chunks[1].width - nice_table_width) / 2;
+        let table_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Length(table_horiz_margin),
+                    Constraint::Length(nice_table_width),
+                    Constraint::Length(table_horiz_margin),
+                ]
+                .as_ref(),
+            );
+
+        let table_rect = table_layout.split(chunks[1])[1];
+        let legend_rect = table_layout.split(chunks[2])[1];
+
+        let today_table_inner = table_block.inner(table_rect);
+        f.render_widget(table_block, table_rect);
+
+        let (row, cols) = make_today_row(app, false, window, nice_table_width - 2);
+        let table = Table::new(vec![row]).column_spacing(0).widths(&cols);
         f.render_widget(
-            legend.clone(),
-            Layout::default()
+            table,
+            Rect {
+                height: 1,
+                ..today_table_inner
+            },
+        );
+
+        // Since widths are shared by all the rows in a Table, the parallel,
+        // comparison and plan rows each have to be their own Table underneath
+        let mut next_row_y = today_table_inner.y + 1;
+        if has_parallel {
+            let (row, cols) = make_today_row(app, true, window, nice_table_width - 2);
+            let table = Table::new(vec![row]).column_spacing(0).widths(&cols);
+            f.render_widget(
+                table,
+                Rect {
+                    y: next_row_y,
+                    height: 1,
+                    ..today_table_inner
+                },
+            );
+            next_row_y += 1;
+        }
+        if let Some((date, entries)) = app.comparison.as_ref() {
+            let (row, cols) =
+                make_comparison_row(app, *date, entries, window, nice_table_width - 2);
+            let table = Table::new(vec![row]).column_spacing(0).widths(&cols);
+            f.render_widget(
+                table,
+                Rect {
+                    y: next_row_y,
+                    height: 1,
+                    ..today_table_inner
+                },
+            );
+            next_row_y += 1;
+        }
+        if !app.today_plan.is_empty() {
+            let (row, cols) = make_plan_row(app, window, nice_table_width - 2);
+            let table = Table::new(vec![row]).column_spacing(0).widths(&cols);
+            f.render_widget(
+                table,
+                Rect {
+                    y: next_row_y,
+                    height: 1,
+                    ..today_table_inner
+                },
+            );
+        }
+
+        if show_legend && nice_table_width > 26 {
+            let legend_rect = Layout::default()
                 .horizontal_margin(1)
                 .constraints([Constraint::Percentage(100)].as_ref())
-                .split(legend_rect)[0],
-        );
+                .split(legend_rect)[0];
+            if window == Window::DAY {
+                let legend: &Table<'static> = if nice_table_width < 74 {
+                    &legend::TRUNC_LEGEND_TABLE
+                } else {
+                    &legend::LEGEND_TABLE
+                };
+                f.render_widget(legend.clone(), legend_rect);
+            } else {
+                let widths = vec![Constraint::Ratio(1, window.hours); window.hours as usize];
+                let legend = Table::new([Row::new(legend::hour_labels(window))])
+                    .column_spacing(0)
+                    .widths(&widths);
+                f.render_widget(legend, legend_rect);
+            }
+        }
     }
 
-    let total_time = Paragraph::new(format!("Total: {}", format_total_time(&app.today)))
-        .alignment(Alignment::Left);
+    let (total_text, next_text, tracker_text) = status_texts(app);
+    // The open entry ticks along in front of the total
+    let mut total_spans = Vec::new();
+    if let Some(elapsed) = app.open_entry_elapsed(Local::now()) {
+        total_spans.extend([bold(elapsed), Span::raw(" | ")]);
+    }
+    total_spans.push(Span::raw(total_text));
+    let total_line = Line::from(total_spans);
+    let total_width = total_line.width() as u16;
+    let total_time = Paragraph::new(total_line).alignment(Alignment::Left);
 
-    let tracker_status = Paragraph::new(format!(
-        "Tracker: {}onnected",
-        if app.tracker_connected { "C" } else { "Not c" }
-    ))
-    .alignment(Alignment::Right);
+    let tracker_width = tracker_text.chars().count() as u16;
+    let tracker_status = Paragraph::new(tracker_text).alignment(Alignment::Right);
 
+    let next_up = Paragraph::new(next_text).alignment(Alignment::Center);
+
+    // The sides take as much room as they need, up to half of it on the left,
+    // and what's next gets whatever's left in the middle
     let status_row = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .constraints([
+            Constraint::Length(total_width.min(chunks[3].width / 2) + 1),
+            Constraint::Min(0),
+            Constraint::Length(tracker_width + 1),
+        ])
         .split(chunks[3]);
     f.render_widget(total_time, status_row[0]);
-    f.render_widget(tracker_status, status_row[1]);
+    f.render_widget(next_up, status_row[1]);
+    f.render_widget(tracker_status, status_row[2]);
 
     let label_len = app
         .preferences
@@ -225,17 +673,34 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         Constraint::Percentage(100),
     ];
 
-    f.render_widget(message_widget(app), chunks[5]);
+    draw_messages(f, app, chunks[5]);
 
-    let labels = app.preferences.labels.as_ref();
+    let prefs = &app.preferences;
+    let trash = &app.trash;
     if let Page::Home(ref mut state_type) = app.selected_page {
         if let State::Editing {
             ref mut state,
             ref cursor_pos,
             ref delete_pending,
+            ref visual_anchor,
+            ..
         } = state_type
         {
-            let help_message = Paragraph::new(Line::from(if *delete_pending {
+            let marked = visual_anchor
+                .zip(state.list_state.selected())
+                .map(|(anchor, selected)| anchor.min(selected)..=anchor.max(selected));
+            let help_message = Paragraph::new(Line::from(if visual_anchor.is_some() {
+                vec![
+                    bold("k+j"),
+                    Span::raw("/"),
+                    bold("↑+↓"),
+                    Span::raw(": extend selection | "),
+                    bold("1-8"),
+                    Span::raw(": move selected entries to task | "),
+                    bold("Esc"),
+                    Span::raw(": cancel"),
+                ]
+            } else if *delete_pending {
                 vec![
                     bold("Are you sure?"),
                     Span::raw(" Press "),
@@ -258,7 +723,7 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                     Span::raw(": (at end of log) make ongoing"),
                 ]
             } else {
-                vec![
+                let mut keys = vec![
                     bold("q"),
                     Span::raw("/"),
                     bold("Esc"),
@@ -271,13 +736,32 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                     Span::raw(": edit | "),
                     bold("i"),
                     Span::raw(": insert | "),
+                    bold("+/-"),
+                    Span::raw(": estimate | "),
+                    bold("c"),
+                    Span::raw(": color | "),
                     bold("d"),
-                    Span::raw(": delete | changes saved automatically"),
-                ]
+                    Span::raw(": delete | "),
+                ];
+                if !trash.is_empty() {
+                    keys.extend([
+                        bold("R"),
+                        Span::raw(format!(": restore deleted ({}) | ", trash.len())),
+                    ]);
+                }
+                keys.extend([
+                    bold("V"),
+                    Span::raw(": select block | "),
+                    bold("u"),
+                    Span::raw("/"),
+                    bold("Ctrl-r"),
+                    Span::raw(": undo/redo | changes saved automatically"),
+                ]);
+                keys
             }));
             f.render_widget(help_message, chunks[0]);
 
-            state.draw_table(f, chunks[4], &widths, |_i, item, input, editing| -> Row {
+            state.draw_table(f, chunks[4], &widths, |i, item, input, editing| -> Row {
                 if editing {
                     // cursor positions will go:
                     // [foo] from 00:00:00 to 00:00:00
@@ -322,213 +806,440 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                             utils::blinky_if_index_matches(
                                 *cursor_pos,
                                 0,
-                                input.resolve_label(labels),
+                                input.resolve_label(prefs.labels.as_ref()),
                             ),
                             Span::raw("]"),
                         ])),
                         Cell::from(Line::from(spans)),
                     ])
+                } else if marked.as_ref().is_some_and(|m| m.contains(&i)) {
+                    item.to_row_unstyled(prefs)
+                        .style(Style::default().add_modifier(Modifier::REVERSED))
                 } else {
-                    item.to_row_unstyled(labels)
+                    item.to_row_unstyled(prefs)
                 }
             });
+            app.mouse_targets.entries = Some((
+                Block::default().borders(Borders::ALL).inner(chunks[4]),
+                state.list_state.offset(),
+            ));
         } else {
             let help_message = Paragraph::new(Line::from(vec![
                 bold("q"),
                 Span::raw(": quit | "),
                 bold("1-8 keys"),
                 Span::raw(": start | "),
+                bold("Shift+1-8"),
+                Span::raw(": start a bit ago | "),
+                bold("G"),
+                Span::raw(": go to task | "),
                 bold("0"),
                 Span::raw("/"),
                 bold("Esc"),
                 Span::raw(": stop | "),
+                bold("b"),
+                Span::raw(": break | "),
+                bold("r"),
+                Span::raw(": resume last task (again for the one before) | "),
+                bold("t"),
+                Span::raw(": start with tags | "),
+                bold("/"),
+                Span::raw(": search tasks | "),
                 bold("e"),
                 Span::raw(": edit | "),
+                bold("y"),
+                Span::raw(": copy yesterday | "),
+                bold("k+j"),
+                Span::raw("/"),
+                bold("↑+↓"),
+                Span::raw(": select (Enter to edit it) | "),
+                bold("p"),
+                Span::raw(": plan | "),
                 bold("h"),
                 Span::raw(": history | "),
+                bold("c"),
+                Span::raw(": compare with last week ("),
+                bold("["),
+                Span::raw("/"),
+                bold("]"),
+                Span::raw(": shift day) | "),
+                bold("+"),
+                Span::raw("/"),
+                bold("-"),
+                Span::raw(": zoom timeline | "),
+                bold("R"),
+                Span::raw(": timesheet | "),
+                bold("g"),
+                Span::raw(": gaps | "),
+                bold("w"),
+                Span::raw(": week | "),
+                bold("H"),
+                Span::raw(": heatmap | "),
+                bold("T"),
+                Span::raw(": templates | "),
                 bold("s"),
                 Span::raw(": settings"),
             ]));
             f.render_widget(help_message, chunks[0]);
 
-            let today_start_at = if app.today.len() + 2 > (chunks[4].height as usize) {
-                (app.today.len() + 2) - (chunks[4].height as usize)
-            } else {
-                0
+            let selected_list = match state_type {
+                State::Viewing { list_state } if list_state.selected().is_some() => {
+                    Some(list_state)
+                }
+                _ => None,
             };
+            if let Some(list_state) = selected_list {
+                // Entries can go away underneath the selection, e.g. when the
+                // day rolls over
+                list_state.select(
+                    list_state
+                        .selected()
+                        .map(|i| i.min(app.today.len().saturating_sub(1)))
+                        .filter(|_| !app.today.is_empty()),
+                );
+                let time_entries = Table::new(
+                    app.today
+                        .iter()
+                        .map(|time_log| time_log.to_row(&app.preferences))
+                        .collect::<Vec<Row>>(),
+                )
+                .block(Block::default().borders(Borders::ALL))
+                .widths(&widths)
+                .column_spacing(1)
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                .highlight_symbol("> ");
+                f.render_stateful_widget(time_entries, chunks[4], list_state);
+                app.mouse_targets.entries = Some((
+                    Block::default().borders(Borders::ALL).inner(chunks[4]),
+                    list_state.offset(),
+                ));
+            } else {
+                // Without a selection, the latest entries are the ones to see
+                // Capped for the borders, which may be all there's room for
+                let today_start_at = (app.today.len() + 2)
+                    .saturating_sub(chunks[4].height as usize)
+                    .min(app.today.len());
 
-            let time_entries = Table::new(
-                app.today[today_start_at..]
-                    .iter()
-                    .map(|time_log| time_log.to_row(app.preferences.labels.as_ref()))
-                    .collect::<Vec<Row>>(),
-            )
-            .block(Block::default().borders(Borders::ALL))
-            .widths(&widths)
-            .column_spacing(1);
-            f.render_widget(time_entries, chunks[4]);
+                let time_entries = Table::new(
+                    app.today[today_start_at..]
+                        .iter()
+                        .map(|time_log| time_log.to_row(&app.preferences))
+                        .collect::<Vec<Row>>(),
+                )
+                .block(Block::default().borders(Borders::ALL))
+                .widths(&widths)
+                .column_spacing(1);
+                f.render_widget(time_entries, chunks[4]);
+                app.mouse_targets.entries = Some((
+                    Block::default().borders(Borders::ALL).inner(chunks[4]),
+                    today_start_at,
+                ));
+            }
+
+            if let State::QuickStart { input } = state_type {
+                let popup = centered_rect(48, 3, chunks[4]);
+                let prompt = Paragraph::new(Line::from(vec![
+                    Span::raw(input.as_str()),
+                    utils::blinky_underline(" "),
+                ]))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Start task (e.g. 3 #standup or 2 for 30m)"),
+                );
+                f.render_widget(Clear, popup);
+                f.render_widget(prompt, popup);
+            } else if let State::GoTo { input } = state_type {
+                let popup = centered_rect(32, 3, chunks[4]);
+                let prompt = Paragraph::new(Line::from(vec![
+                    Span::raw(input.as_str()),
+                    utils::blinky_underline(" "),
+                ]))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Go to task (Enter to start)"),
+                );
+                f.render_widget(Clear, popup);
+                f.render_widget(prompt, popup);
+            } else if let State::NamingTask { number, input } = state_type {
+                let popup = centered_rect(40, 3, chunks[4]);
+                let prompt = Paragraph::new(Line::from(vec![
+                    Span::raw(input.as_str()),
+                    utils::blinky_underline(" "),
+                ]))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("Name task {} (Esc to skip)", number)),
+                );
+                f.render_widget(Clear, popup);
+                f.render_widget(prompt, popup);
+            } else if let State::Palette { input, selected } = state_type {
+                draw_palette(f, prefs, input, *selected, chunks[4]);
+            } else if let State::ConfirmQuit = state_type {
+                draw_confirm_quit(f, app.today.as_slice(), prefs, chunks[4]);
+            } else if let State::Summary(summary) = state_type {
+                draw_summary(f, summary, prefs, chunks[4]);
+            }
         }
     } else {
         panic!("Can't render home page when the app isn't in home page state!")
     };
 }
 
+fn draw_palette<B: Backend>(
+    f: &mut Frame<B>,
+    prefs: &Preferences,
+    input: &str,
+    selected: usize,
+    area: Rect,
+) {
+    let mut lines = vec![Line::from(vec![
+        Span::raw(input),
+        utils::blinky_underline(" "),
+    ])];
+    let matches = palette_matches(prefs, input);
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No matching labels",
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+    }
+    for (i, (number, label)) in matches.into_iter().enumerate() {
+        let style = if i == selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::styled("■ ", Style::default().fg(task_color(prefs, number))),
+            Span::styled(format!("{} {}", number, label), style),
+        ]));
+    }
+
+    let popup = centered_rect(40, lines.len() as u16 + 2, area);
+    let palette = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Start task (type to search)"),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(palette, popup);
+}
+
+/// The lines describing a day's summary, like "Tracked 06:30:00", then each
+/// task's time, then how much of the work hours went untracked
+fn summary_lines(summary: &DaySummary, prefs: &Preferences) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(vec![
+        Span::raw("Tracked "),
+        bold(durations::format_hms(summary.total)),
+    ])];
+    for (number, total) in &summary.tasks {
+        lines.push(Line::from(format!(
+            "{}: {}",
+            get_pref_label(*number, prefs.labels.as_ref()).unwrap_or_else(|| number.to_string()),
+            durations::format_hms(*total)
+        )));
+    }
+    lines.push(Line::from(match summary.gaps.len() {
+        0 => "Nothing went untracked during work hours".to_string(),
+        n => format!(
+            "{} untracked during work hours, in {} gap{}",
+            durations::format_hms(summary.untracked()),
+            n,
+            if n == 1 { "" } else { "s" }
+        ),
+    }));
+    lines
+}
+
+/// A day's summary in a popup, which any of the keys it mentions closes
+fn draw_summary<B: Backend>(
+    f: &mut Frame<B>,
+    summary: &DaySummary,
+    prefs: &Preferences,
+    area: Rect,
+) {
+    let mut lines = summary_lines(summary, prefs);
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        bold("Enter"),
+        Span::raw("/"),
+        bold("Esc"),
+        Span::raw(": close"),
+    ]));
+
+    let popup = centered_rect(60, lines.len() as u16 + 2, area);
+    let dialog = Paragraph::new(lines).alignment(Alignment::Center).block(
+        Block::default().borders(Borders::ALL).title(format!(
+            "Looking back at {}",
+            format_date(prefs, summary.date, "%A %b %-d")
+        )),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(dialog, popup);
+}
+
+/// Sums up what quitting will do, since it can't be undone: e.g. "The open
+/// entry for Coding will be closed at 17:05"
+fn draw_confirm_quit<B: Backend>(
+    f: &mut Frame<B>,
+    today: &[TimeLog],
+    prefs: &Preferences,
+    area: Rect,
+) {
+    let open: Vec<String> = today
+        .iter()
+        .filter(|tl| tl.is_open())
+        .map(|tl| {
+            get_pref_label(tl.number, prefs.labels.as_ref())
+                .unwrap_or_else(|| tl.number.to_string())
+        })
+        .collect();
+    let mut lines = vec![];
+    if prefs.daily_summary.unwrap_or(true) {
+        let summary = DaySummary::new(Local::now().date_naive(), today, prefs, Local::now());
+        lines.extend(summary_lines(&summary, prefs));
+        lines.push(Line::from(""));
+    }
+    if !open.is_empty() {
+        lines.push(Line::from(format!(
+            "The open {} for {} will be closed at {}",
+            if open.len() == 1 { "entry" } else { "entries" },
+            open.join(" and "),
+            Local::now().format("%R")
+        )));
+    }
+    lines.push(Line::from(match today.len() {
+        1 => "1 entry will be saved".to_string(),
+        n => format!("{} entries will be saved", n),
+    }));
+    lines.push(Line::from(""));
+
+    let mut keys = vec![
+        bold("Enter"),
+        Span::raw("/"),
+        bold("y"),
+        Span::raw(": quit | "),
+    ];
+    if !open.is_empty() {
+        keys.extend([
+            bold("k"),
+            Span::raw(if open.len() == 1 {
+                ": quit, keep entry open | "
+            } else {
+                ": quit, keep entries open | "
+            }),
+        ]);
+    }
+    keys.extend([
+        bold("Esc"),
+        Span::raw("/"),
+        bold("n"),
+        Span::raw(": cancel"),
+    ]);
+    lines.push(Line::from(keys));
+
+    let popup = centered_rect(70, lines.len() as u16 + 2, area);
+    let dialog = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("Quit?"));
+    f.render_widget(Clear, popup);
+    f.render_widget(dialog, popup);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn duration_coords() {
-        // max_width is supposed to always be divisible by 24
-        let mw = 24;
-        assert_eq!(
-            // Remember end is exclusive, think of it like a range: 0..24 and
-            // not 0..=24
-            (0, 24),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (0, 1),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (0, 0),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(5, 29, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (0, 1),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(5, 30, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (2, 2 + 2),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (6, 6 + 9),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(19, 31, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (19, 24),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (17, 24),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            // this one is the worst-case rounding scenario, because at 1px per
-            // hour resolution, XX:29:59 rounds down to XX and YY:30:00 rounds
-            // up to YY+1, -- in this case that causes a 1h+1s duration to show
-            // up as 2 hours!
-            (18, 18 + 2),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(23, 29, 59).unwrap(),
-                NaiveTime::from_hms_opt(0, 30, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (19, 19 + 1),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
-                NaiveTime::from_hms_opt(0, 30, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (18, 18 + 1),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(0, 29, 0).unwrap(),
-                mw
-            )
-        );
+    fn palette_search() {
+        let mut labels: [String; 8] = Default::default();
+        labels[0] = "Errands".to_string();
+        labels[1] = "Coding".to_string();
+        labels[2] = "Meetings".to_string();
+        labels[3] = "Code review".to_string();
+        let prefs = Preferences {
+            labels: Some(labels),
+            ..Default::default()
+        };
+        let numbers = |query| {
+            palette_matches(&prefs, query)
+                .into_iter()
+                .map(|(number, _)| number)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(""), [1, 2, 3, 4]);
+        assert_eq!(numbers("COD"), [2, 4]);
+        assert_eq!(numbers("cdr"), [4]);
+        // The start of a word beats the middle of one
+        assert_eq!(numbers("r"), [4, 1]);
+        assert_eq!(numbers("xyz"), Vec::<u8>::new());
     }
 
     #[test]
-    fn duration_coords_wide() {
-        // max_width is supposed to always be divisible by 24
-        let mw = 48;
-        assert_eq!(
-            (0, 48),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (0, 1),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(5, 29, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (0, 0),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(5, 14, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (0, 2),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (10, 10 + 5), // Adding 5 half-hours of time from 10:00 to 12:30
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
-                mw
-            )
-        );
-        assert_eq!(
-            (34, 48),
-            duration_to_x_coords(
-                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
-                NaiveTime::from_hms_opt(4, 59, 59).unwrap(),
-                mw
-            )
-        );
+    fn undo_and_redo() {
+        let entry = |number| TimeLog {
+            number,
+            ..Default::default()
+        };
+        let numbers = |entries: &[TimeLog]| entries.iter().map(|tl| tl.number).collect::<Vec<_>>();
+        let mut history = EditHistory::default();
+        let mut entries = vec![entry(1)];
+        assert!(!history.undo(&mut entries));
+
+        history.record(&entries);
+        entries.push(entry(2));
+        history.record(&entries);
+        entries[0].number = 3;
+
+        assert!(history.undo(&mut entries));
+        assert_eq!(numbers(&entries), [1, 2]);
+        assert!(history.undo(&mut entries));
+        assert_eq!(numbers(&entries), [1]);
+        assert!(history.redo(&mut entries));
+        assert_eq!(numbers(&entries), [1, 2]);
+
+        // A new edit can't be redone over
+        history.record(&entries);
+        entries.pop();
+        assert!(!history.redo(&mut entries));
+
+        for _ in 0..MAX_UNDO + 10 {
+            history.record(&entries);
+        }
+        let mut undone = 0;
+        while history.undo(&mut entries) {
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_UNDO);
+    }
+
+    #[test]
+    fn cancelled_insert() {
+        let entry = |number| TimeLog {
+            number,
+            ..Default::default()
+        };
+        let numbers = |entries: &[TimeLog]| entries.iter().map(|tl| tl.number).collect::<Vec<_>>();
+        let mut history = EditHistory::default();
+        let mut entries = vec![entry(1)];
+
+        history.begin_insert(&entries);
+        entries.push(entry(2));
+        entries = history.cancel_insert().unwrap();
+        assert_eq!(numbers(&entries), [1]);
+        assert!(!history.undo(&mut entries));
+
+        // Saving the insert is one edit, back to before it was made
+        history.begin_insert(&entries);
+        entries.push(entry(3));
+        history.record(&entries);
+        assert!(history.cancel_insert().is_none());
+        assert!(history.undo(&mut entries));
+        assert_eq!(numbers(&entries), [1]);
+        assert!(!history.undo(&mut entries));
     }
 
     #[test]
@@ -539,7 +1250,8 @@ mod tests {
             format_total_time(&[TimeLog {
                 start: now - chrono::Duration::minutes(42),
                 end: Some(now),
-                number: 1
+                number: 1,
+                ..Default::default()
             }])
         );
 
@@ -553,17 +1265,20 @@ mod tests {
                 TimeLog {
                     start: hours,
                     end: Some(buff),
-                    number: 1
+                    number: 1,
+                    ..Default::default()
                 },
                 TimeLog {
                     start: secs,
                     end: Some(mins),
-                    number: 2
+                    number: 2,
+                    ..Default::default()
                 },
                 TimeLog {
                     start: mins,
                     end: Some(now),
-                    number: 3
+                    number: 3,
+                    ..Default::default()
                 }
             ])
         );
@@ -0,0 +1,211 @@
+use std::io;
+
+use chrono::{Days, Local, NaiveDate};
+use ratatui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, ListState, Paragraph},
+    Frame,
+};
+
+use crate::{
+    get_pref_label, load_log_for,
+    locale::format_date,
+    plan::{adherence, load_plan, PlannedBlock},
+    storage::Dirs,
+    App, TimeLog,
+};
+
+use super::{
+    draw_messages,
+    editable_list::EditableList,
+    utils::{bold, dim},
+    Page,
+};
+
+#[derive(Debug)]
+pub struct State {
+    pub date: NaiveDate,
+    /// Blocks are edited as text, like "3 09:00-10:30", and parsed on save
+    pub blocks: EditableList<ListState, String>,
+    pub delete_pending: bool,
+    /// What was actually tracked on `date`, for reviewing the plan. Empty for
+    /// future dates.
+    actual: Vec<TimeLog>,
+}
+
+impl State {
+    pub fn load(dirs: &Dirs, date: NaiveDate, today: &[TimeLog]) -> io::Result<Self> {
+        let blocks = load_plan(dirs, date)?;
+        let now = Local::now().date_naive();
+        let actual = if date == now {
+            today.to_vec()
+        } else if date < now {
+            load_log_for(dirs, date).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            date,
+            blocks: EditableList::new(blocks.iter().map(|b| b.to_string()).collect()),
+            delete_pending: false,
+            actual,
+        })
+    }
+
+    /// Opens the plan for tomorrow, which is what you'll usually be sketching
+    pub fn load_tomorrow(dirs: &Dirs, today: &[TimeLog]) -> io::Result<Self> {
+        Self::load(dirs, Local::now().date_naive() + Days::new(1), today)
+    }
+
+    /// Parses every block in the list. The list only ever holds blocks that
+    /// parsed successfully when they were saved, so this shouldn't fail, but
+    /// any that don't parse are skipped.
+    pub fn planned_blocks(&self) -> Vec<PlannedBlock> {
+        let mut blocks: Vec<PlannedBlock> = self
+            .blocks
+            .options
+            .iter()
+            .filter_map(|s| s.parse().ok())
+            .collect();
+        blocks.sort_unstable_by_key(|b| b.start);
+        blocks
+    }
+
+    /// Rewrites the list in start time order after an edit, keeping the
+    /// selection on `selected`
+    pub fn resort(&mut self, selected: PlannedBlock) {
+        let blocks = self.planned_blocks();
+        self.blocks
+            .list_state
+            .select(blocks.iter().position(|&b| b == selected));
+        self.blocks.options = blocks.iter().map(|b| b.to_string()).collect();
+    }
+
+    /// Makes a new block to insert after `prev`: the same task, for an hour
+    /// starting when `prev` ends
+    pub fn new_block_after(prev: Option<String>) -> String {
+        let prev: Option<PlannedBlock> = prev.and_then(|p| p.parse().ok());
+        let start = prev.map_or_else(
+            || chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            |p| p.end,
+        );
+        PlannedBlock {
+            number: prev.map_or(1, |p| p.number),
+            start,
+            end: start + chrono::Duration::hours(1),
+        }
+        .to_string()
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Plan(ref mut state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render plan page when the app isn't in plan page state!")
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .horizontal_margin(2)
+        .constraints(
+            [
+                Constraint::Length(1), // Instructions
+                Constraint::Length(2), // Date & review score
+                Constraint::Min(2),    // Plan editor
+                Constraint::Length(1), // Messages
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let help_message = Paragraph::new(Line::from(if state.delete_pending {
+        vec![
+            bold("Are you sure?"),
+            Span::raw(" Press "),
+            bold("x"),
+            Span::raw(" to confirm deletion, "),
+            bold("Esc"),
+            Span::raw(" to cancel"),
+        ]
+    } else if state.blocks.editing {
+        vec![
+            bold("Esc"),
+            Span::raw(": cancel | "),
+            bold("Enter"),
+            Span::raw(": save | format: "),
+            bold("3 09:00-10:30"),
+        ]
+    } else {
+        vec![
+            bold("q"),
+            Span::raw("/"),
+            bold("Esc"),
+            Span::raw(": back | "),
+            bold("←+→"),
+            Span::raw(": change day | "),
+            bold("k+j"),
+            Span::raw(": up+down | "),
+            bold("Enter"),
+            Span::raw(": edit | "),
+            bold("i"),
+            Span::raw(": insert | "),
+            bold("d"),
+            Span::raw(": delete | changes saved automatically"),
+        ]
+    }));
+    f.render_widget(help_message, chunks[0]);
+
+    let planned = state.planned_blocks();
+    let planned_total = planned
+        .iter()
+        .fold(chrono::Duration::zero(), |acc, b| acc + b.duration());
+    let mut header = vec![
+        Span::raw("Plan for "),
+        bold(format_date(&app.preferences, state.date, "%A %F")),
+        Span::raw(format!(
+            " ({})",
+            humantime::format_duration(planned_total.to_std().unwrap_or_default())
+        )),
+    ];
+    // Plans for today or earlier can be scored against what actually happened
+    if state.date <= Local::now().date_naive() {
+        let now = if state.date == Local::now().date_naive() {
+            Local::now().time()
+        } else {
+            chrono::NaiveTime::MIN - chrono::Duration::nanoseconds(1)
+        };
+        if let Some(score) = adherence(&planned, &state.actual, now) {
+            header.push(Span::raw(" | Adherence: "));
+            header.push(bold(format!("{:.0}%", score * 100.0)));
+        }
+    }
+    let header = Paragraph::new(Line::from(header)).block(Block::default().borders(Borders::TOP));
+    f.render_widget(header, chunks[1]);
+
+    let labels = app.preferences.labels.as_ref();
+    state
+        .blocks
+        .draw_list(f, chunks[2], |_i, item, input, editing| {
+            if editing {
+                Span::styled(input, Style::default().add_modifier(Modifier::UNDERLINED)).into()
+            } else {
+                let label = item
+                    .parse::<PlannedBlock>()
+                    .ok()
+                    .and_then(|b| get_pref_label(b.number, labels));
+                Line::from(vec![
+                    Span::raw(item),
+                    dim(label.map_or_else(String::new, |l| format!(" [{}]", l))),
+                ])
+                .into()
+            }
+        });
+
+    draw_messages(f, app, chunks[3]);
+}
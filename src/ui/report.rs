@@ -0,0 +1,202 @@
+use std::io;
+
+use ratatui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Row, Table},
+    Frame,
+};
+
+use crate::{
+    durations::format_decimal_hours,
+    get_pref_label,
+    locale::format_date,
+    stats::{compute_timesheet, load_dated_logs, Period, TimesheetColumn},
+    storage::Dirs,
+    App,
+};
+
+use super::{draw_messages, utils::bold, Page};
+
+/// Width of each period's column, enough for "2023-W26" or "123.4h". Month
+/// names can need more in some languages.
+const COLUMN_WIDTH: u16 = 8;
+
+#[derive(Debug)]
+pub struct State {
+    period: Period,
+    columns: Vec<TimesheetColumn>,
+    /// How many of the most recent columns are scrolled out of view to the
+    /// right. Starts at 0 so that the latest periods show first.
+    offset_from_end: usize,
+}
+
+impl State {
+    pub fn load(dirs: &Dirs, period: Period) -> io::Result<Self> {
+        let dated_logs = load_dated_logs(dirs, None, None)?;
+        Ok(Self {
+            period,
+            columns: compute_timesheet(&dated_logs, period),
+            offset_from_end: 0,
+        })
+    }
+
+    /// Switches between weekly and monthly columns
+    pub fn toggle_period(&mut self, dirs: &Dirs) -> io::Result<()> {
+        *self = Self::load(
+            dirs,
+            match self.period {
+                Period::IsoWeek => Period::Month,
+                Period::Month => Period::IsoWeek,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn scroll_left(&mut self) {
+        if self.offset_from_end + 1 < self.columns.len() {
+            self.offset_from_end += 1;
+        }
+    }
+
+    pub fn scroll_right(&mut self) {
+        self.offset_from_end = self.offset_from_end.saturating_sub(1);
+    }
+}
+
+/// Formats a timesheet cell as decimal hours, leaving empty cells blank so the
+/// table is easier to scan
+fn format_hours(d: chrono::Duration) -> String {
+    if d.is_zero() {
+        String::new()
+    } else {
+        format_decimal_hours(d)
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Report(ref mut state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render report page when the app isn't in report page state!")
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .horizontal_margin(2)
+        .constraints(
+            [
+                Constraint::Length(1), // Instructions
+                Constraint::Min(2),    // Timesheet
+                Constraint::Length(1), // Messages
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let help_message = Paragraph::new(Line::from(vec![
+        bold("q"),
+        Span::raw("/"),
+        bold("Esc"),
+        Span::raw(": back | "),
+        bold("h+l"),
+        Span::raw("/"),
+        bold("←+→"),
+        Span::raw(": scroll | "),
+        bold("Tab"),
+        Span::raw(": weeks/months"),
+    ]));
+    f.render_widget(help_message, chunks[0]);
+
+    let labels = app.preferences.labels.as_ref();
+    // At least wide enough for "Total"
+    let label_len = labels.map_or(5, |lbls| {
+        lbls.iter().map(|s| s.len() as u16).max().unwrap().max(5)
+    });
+
+    // Fit as many periods as we can beside the label column, ending at the
+    // scroll position
+    // Months are named in the user's language, like "juin 2023"
+    let column_label = |c: &TimesheetColumn| match state.period {
+        Period::IsoWeek => c.label.clone(),
+        Period::Month => format_date(&app.preferences, c.start, "%b %Y"),
+    };
+    let column_width = state
+        .columns
+        .iter()
+        .map(|c| column_label(c).chars().count() as u16)
+        .max()
+        .unwrap_or(0)
+        .max(COLUMN_WIDTH);
+    let available = chunks[1].width.saturating_sub(label_len + 3);
+    let num_visible = ((available / (column_width + 1)) as usize).max(1);
+    let end = state.columns.len() - state.offset_from_end.min(state.columns.len());
+    let visible = &state.columns[end.saturating_sub(num_visible)..end];
+
+    let header = Row::new(
+        std::iter::once(String::new())
+            .chain(visible.iter().map(column_label))
+            .collect::<Vec<String>>(),
+    )
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let task_rows = (1..=8u8).map(|number| {
+        Row::new(
+            std::iter::once(get_pref_label(number, labels).unwrap_or_else(|| number.to_string()))
+                .chain(
+                    visible
+                        .iter()
+                        .map(|c| format_hours(c.totals[(number - 1) as usize])),
+                )
+                .collect::<Vec<String>>(),
+        )
+    });
+
+    let total_row = Row::new(
+        std::iter::once(String::from("Total"))
+            .chain(visible.iter().map(|c| {
+                format_hours(
+                    c.totals
+                        .iter()
+                        .fold(chrono::Duration::zero(), |acc, &d| acc + d),
+                )
+            }))
+            .collect::<Vec<String>>(),
+    )
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let widths: Vec<Constraint> = std::iter::once(Constraint::Length(label_len))
+        .chain(std::iter::repeat_n(
+            Constraint::Length(column_width),
+            visible.len(),
+        ))
+        .collect();
+
+    let title = format!(
+        "Timesheet by {}{}",
+        match state.period {
+            Period::IsoWeek => "ISO week",
+            Period::Month => "month",
+        },
+        if state.offset_from_end > 0 {
+            " →"
+        } else {
+            ""
+        }
+    );
+    let table = Table::new(
+        std::iter::once(header)
+            .chain(task_rows)
+            .chain(std::iter::once(total_row))
+            .collect::<Vec<Row>>(),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .widths(&widths)
+    .column_spacing(1);
+    f.render_widget(table, chunks[1]);
+
+    draw_messages(f, app, chunks[2]);
+}
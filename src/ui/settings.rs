@@ -1,17 +1,242 @@
 use ratatui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
-    style::{Modifier, Style},
-    text::{Line, Span, Text},
-    widgets::{Block, Borders, ListState, Paragraph},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs},
     Frame,
 };
 
-use crate::App;
+use crate::{bundle::ThemeBundle, storage::Dirs, App, Preferences};
 
-use super::{editable_list::EditableList, message_widget, utils::bold, Page};
+use super::{
+    draw_messages,
+    editable_list::EditableList,
+    form::{Field, Form},
+    task_color, task_pattern,
+    utils::{bold, centered_rect},
+    Page,
+};
+
+/// The colors offered by the color picker, in the order they're listed
+pub const COLOR_CHOICES: [Color; 16] = [
+    Color::Blue,
+    Color::Cyan,
+    Color::Green,
+    Color::Magenta,
+    Color::Red,
+    Color::Yellow,
+    Color::LightBlue,
+    Color::LightCyan,
+    Color::LightGreen,
+    Color::LightMagenta,
+    Color::LightRed,
+    Color::LightYellow,
+    Color::White,
+    Color::Gray,
+    Color::DarkGray,
+    Color::Black,
+];
+
+/// The parts of Settings, one shown at a time: the task labels, and groups
+/// of the other preferences
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    #[default]
+    Labels,
+    Week,
+    Saving,
+    Look,
+    Tracking,
+}
+
+impl Section {
+    const ALL: [Section; 5] = [
+        Section::Labels,
+        Section::Week,
+        Section::Saving,
+        Section::Look,
+        Section::Tracking,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&s| s == self).unwrap_or(0)
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn prev(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Section::Labels => "Labels",
+            Section::Week => "Week",
+            Section::Saving => "Saving",
+            Section::Look => "Look",
+            Section::Tracking => "Tracking",
+        }
+    }
 
-pub type State = EditableList<ListState, String>;
+    /// The preferences edited in this section, none for labels which have
+    /// their own editor
+    pub fn fields(self) -> &'static [Field] {
+        match self {
+            Section::Labels => &[],
+            Section::Week => &[
+                Field::WeekStart,
+                Field::WorkDayStart,
+                Field::WorkDayEnd,
+                Field::Locale,
+            ],
+            Section::Saving => &[
+                Field::Autosave,
+                Field::ArchiveAfterDays,
+                Field::BackupDir,
+                Field::SyncDir,
+                Field::DeviceName,
+            ],
+            Section::Look => &[Field::Theme, Field::Patterns, Field::TitleTimer],
+            Section::Tracking => &[
+                Field::Profile,
+                Field::ToggleOpenTask,
+                Field::PromptPlannedStarts,
+                Field::LockBreakTask,
+                Field::BackdateMins,
+                Field::TrackerName,
+                Field::DailySummary,
+                Field::ReportTime,
+                Field::NagMins,
+                Field::NagBell,
+                Field::NagDesktop,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    /// The section being edited
+    pub section: Section,
+    /// The section's preferences, when it isn't the labels
+    pub form: Form,
+    pub labels: EditableList<ListState, String>,
+    /// Some while the color picker popup is open for the selected label
+    pub color_picker: Option<ListState>,
+    /// Some while the theme bundle selector is open
+    pub bundle_picker: Option<BundlePicker>,
+}
+
+/// The theme bundle selector. The highlighted bundle is previewed by applying
+/// it to the preferences without saving them, so the look the user had before
+/// is kept to go back to if they cancel.
+#[derive(Debug)]
+pub struct BundlePicker {
+    pub bundles: Vec<ThemeBundle>,
+    pub list_state: ListState,
+    pub original: ThemeBundle,
+}
+
+impl State {
+    pub fn new(labels: Vec<String>) -> Self {
+        Self {
+            section: Section::Labels,
+            form: Form::new(Section::Labels.fields()),
+            labels: EditableList::new(labels),
+            color_picker: None,
+            bundle_picker: None,
+        }
+    }
+
+    /// Starts editing `labels` afresh, e.g. after switching to another profile
+    /// with labels of its own
+    pub fn reload_labels(&mut self, labels: Vec<String>) {
+        self.labels = EditableList::new(labels);
+    }
+
+    /// Switches to the next (or previous) section, starting at its first field
+    pub fn switch_section(&mut self, forward: bool) {
+        self.section = if forward {
+            self.section.next()
+        } else {
+            self.section.prev()
+        };
+        self.form = Form::new(self.section.fields());
+    }
+
+    /// Opens the theme bundle selector with nothing highlighted yet, so that
+    /// the user's current look stays until they move through the bundles
+    pub fn open_bundle_picker(&mut self, bundles: Vec<ThemeBundle>, current: ThemeBundle) {
+        self.bundle_picker = Some(BundlePicker {
+            bundles,
+            list_state: ListState::default(),
+            original: current,
+        });
+    }
+
+    /// Highlights the bundle `offset` places away from the highlighted one,
+    /// wrapping around, and returns it for previewing
+    pub fn move_bundle_selection(&mut self, offset: isize) -> Option<ThemeBundle> {
+        let picker = self.bundle_picker.as_mut()?;
+        let len = picker.bundles.len() as isize;
+        if len == 0 {
+            return None;
+        }
+        let next = match picker.list_state.selected() {
+            Some(i) => (i as isize + offset).rem_euclid(len),
+            None if offset < 0 => len - 1,
+            None => 0,
+        } as usize;
+        picker.list_state.select(Some(next));
+        picker.bundles.get(next).cloned()
+    }
+
+    /// Opens the color picker for the selected task slot (or `default_item` if
+    /// none is selected), preselecting the slot's current color if it's one of
+    /// the choices.
+    pub fn open_color_picker(&mut self, default_item: Option<usize>, current: &[Color; 8]) {
+        if self.labels.list_state.selected().is_none() {
+            self.labels.list_state.select(default_item.or(Some(0)));
+        }
+        let slot = self.labels.list_state.selected().unwrap();
+
+        let mut picker = ListState::default();
+        picker.select(Some(
+            COLOR_CHOICES
+                .iter()
+                .position(|&c| c == current[slot])
+                .unwrap_or(0),
+        ));
+        self.color_picker = Some(picker);
+    }
+
+    pub fn select_prev_color(&mut self) {
+        if let Some(picker) = self.color_picker.as_mut() {
+            let current = picker.selected().unwrap_or(0);
+            picker.select(Some(
+                (current + COLOR_CHOICES.len() - 1) % COLOR_CHOICES.len(),
+            ));
+        }
+    }
+
+    pub fn select_next_color(&mut self) {
+        if let Some(picker) = self.color_picker.as_mut() {
+            let current = picker.selected().unwrap_or(0);
+            picker.select(Some((current + 1) % COLOR_CHOICES.len()));
+        }
+    }
+
+    /// Closes the color picker, returning the index of the edited task slot
+    /// and the color that was picked
+    pub fn save_color(&mut self) -> Option<(usize, Color)> {
+        let picker = self.color_picker.take()?;
+        let slot = self.labels.list_state.selected()?;
+        Some((slot, COLOR_CHOICES[picker.selected().unwrap_or(0)]))
+    }
+}
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let open_entry = app.open_entry_number();
@@ -30,6 +255,7 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             [
                 Constraint::Length(1), // Instructions
                 Constraint::Length(2), // Current entry #
+                Constraint::Length(2), // Sections
                 Constraint::Min(2),    // Settings editor
                 Constraint::Length(1), // Messages
             ]
@@ -37,26 +263,87 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         )
         .split(f.size());
 
-    let help_message = Paragraph::new(Line::from(if state.editing {
+    let section = state.section;
+    let help_message = Paragraph::new(Line::from(if state.bundle_picker.is_some() {
         vec![
             bold("Esc"),
             Span::raw(": cancel | "),
+            bold("k+j"),
+            Span::raw("/"),
+            bold("↑+↓"),
+            Span::raw(": preview bundle | "),
+            bold("Enter"),
+            Span::raw(": apply"),
+        ]
+    } else if state.color_picker.is_some() {
+        vec![
+            bold("Esc"),
+            Span::raw(": cancel | "),
+            bold("k+j"),
+            Span::raw("/"),
+            bold("↑+↓"),
+            Span::raw(": pick color | "),
             bold("Enter"),
             Span::raw(": save"),
         ]
-    } else {
+    } else if state.labels.editing {
+        vec![
+            bold("Esc"),
+            Span::raw(": cancel | "),
+            bold("Enter"),
+            Span::raw(": save"),
+        ]
+    } else if state.form.input.is_some() {
+        vec![
+            bold("Esc"),
+            Span::raw(": cancel | "),
+            bold("Enter"),
+            Span::raw(": save (empty for default)"),
+        ]
+    } else if section != Section::Labels {
         vec![
             bold("q"),
             Span::raw("/"),
             bold("Esc"),
             Span::raw(": back | "),
+            bold("Tab"),
+            Span::raw(": next section | "),
             bold("k+j"),
             Span::raw("/"),
             bold("↑+↓"),
             Span::raw(": up+down | "),
+            bold("h+l"),
+            Span::raw("/"),
+            bold("←+→"),
+            Span::raw(": change | "),
             bold("Enter"),
             Span::raw(": edit | changes saved automatically"),
         ]
+    } else {
+        vec![
+            bold("q"),
+            Span::raw("/"),
+            bold("Esc"),
+            Span::raw(": back | "),
+            bold("Tab"),
+            Span::raw(": next section | "),
+            bold("k+j"),
+            Span::raw("/"),
+            bold("↑+↓"),
+            Span::raw(": up+down | "),
+            bold("Enter"),
+            Span::raw(": edit | "),
+            bold("c"),
+            Span::raw(": color | "),
+            bold("t"),
+            Span::raw(": theme | "),
+            bold("f"),
+            Span::raw(": fill patterns | "),
+            bold("b"),
+            Span::raw(": theme bundles | "),
+            bold("e"),
+            Span::raw(": export bundle | changes saved automatically"),
+        ]
     }));
     f.render_widget(help_message, chunks[0]);
 
@@ -67,19 +354,118 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     .block(Block::default().borders(Borders::TOP));
     f.render_widget(active_num, chunks[1]);
 
-    state.draw_list(f, chunks[2], render_item);
+    let tabs = Tabs::new(Section::ALL.iter().map(|s| Line::from(s.title())).collect())
+        .block(Block::default().borders(Borders::TOP))
+        .select(section.index())
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+    f.render_widget(tabs, chunks[2]);
+
+    let prefs = &app.preferences;
+    if section != Section::Labels {
+        draw_form(f, &app.dirs, prefs, &mut state.form, chunks[3]);
+        draw_messages(f, app, chunks[4]);
+        return;
+    }
+    state
+        .labels
+        .draw_list(f, chunks[3], |i, item, input, editing| {
+            Line::from(vec![
+                Span::styled(
+                    format!(
+                        "{} ",
+                        task_pattern(prefs, i as u8 + 1)
+                            .map_or_else(|| "██".to_string(), |p| p.to_string().repeat(2))
+                    ),
+                    Style::default().fg(task_color(prefs, i as u8 + 1)),
+                ),
+                bold(format!("[{}]: ", i + 1)),
+                if editing {
+                    Span::styled(input, Style::default().add_modifier(Modifier::UNDERLINED))
+                } else {
+                    Span::raw(item)
+                },
+            ])
+            .into()
+        });
+
+    if let Some(picker) = state.color_picker.as_mut() {
+        let popup = centered_rect(24, COLOR_CHOICES.len() as u16 + 2, chunks[3]);
+        let list = List::new(
+            COLOR_CHOICES
+                .iter()
+                .map(|&c| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled("██ ", Style::default().fg(c)),
+                        Span::raw(format!("{:?}", c)),
+                    ]))
+                })
+                .collect::<Vec<ListItem>>(),
+        )
+        .block(Block::default().borders(Borders::ALL).title("Color"))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(list, popup, picker);
+    }
+
+    if let Some(picker) = state.bundle_picker.as_mut() {
+        let popup = centered_rect(32, picker.bundles.len() as u16 + 2, chunks[3]);
+        let list = List::new(
+            picker
+                .bundles
+                .iter()
+                .map(|b| ListItem::new(b.name.clone()))
+                .collect::<Vec<ListItem>>(),
+        )
+        .block(Block::default().borders(Borders::ALL).title("Theme bundle"))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(list, popup, &mut picker.list_state);
+    }
 
-    f.render_widget(message_widget(app), chunks[3]);
+    draw_messages(f, app, chunks[4]);
 }
 
-fn render_item<'a>(i: usize, item: &'a String, input: &'a String, editing: bool) -> Text<'a> {
-    Line::from(vec![
-        bold(format!("[{}]: ", i + 1)),
-        if editing {
-            Span::styled(input, Style::default().add_modifier(Modifier::UNDERLINED))
-        } else {
-            Span::raw(item)
-        },
-    ])
-    .into()
+/// Lists the section's fields with their values, the one being typed into
+/// showing its input and, if that was turned down, why
+fn draw_form<B: Backend>(
+    f: &mut Frame<B>,
+    dirs: &Dirs,
+    prefs: &Preferences,
+    form: &mut Form,
+    area: Rect,
+) {
+    let width = form
+        .fields
+        .iter()
+        .map(|field| field.label().len())
+        .max()
+        .unwrap_or(0);
+    let selected = form.list_state.selected();
+    let items = form
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let mut spans = vec![bold(format!("{:width$}  ", field.label(), width = width))];
+            match form.input.as_ref().filter(|_| selected == Some(i)) {
+                Some(input) => spans.push(Span::styled(
+                    input.clone(),
+                    Style::default().add_modifier(Modifier::UNDERLINED),
+                )),
+                None => spans.push(Span::raw(field.display(dirs, prefs))),
+            }
+            if let Some(error) = form.error.as_ref().filter(|_| selected == Some(i)) {
+                spans.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect::<Vec<ListItem>>();
+
+    let list = List::new(items).highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut form.list_state);
 }
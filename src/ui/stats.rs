@@ -1,24 +1,40 @@
 use std::fmt::Display;
 use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
 
-use chrono::{Datelike, Days, Local, NaiveDate, Weekday};
+use chrono::{Datelike, Days, Local, Months, NaiveDate, NaiveTime, Weekday};
 use itertools::Itertools;
 use ratatui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{canvas::Canvas, Block, Borders, Paragraph, Row, Table, Wrap},
+    widgets::{canvas::Canvas, Block, Borders, Gauge, Paragraph, Row, Sparkline, Table, Wrap},
     Frame,
 };
+use tracing::warn;
 
 use crate::{
+    export::{export_stats, ExportFormat},
     get_pref_label,
-    stats::{load_history, TimeStats},
+    locale::format_date,
+    stats::{
+        earnings, format_earnings, group_by_category, group_by_client, load_history, task_trend,
+        untracked_work_time, CategoryStats, History, TagStats, TimeStats,
+    },
+    storage::Dirs,
+    targets::{self, format_balance},
     App, Preferences,
 };
 
-use super::{message_widget, number_to_color, utils::bold, widgets::Donut, Page};
+use super::{
+    draw_messages, patterned_label, task_color, task_pattern,
+    utils::{bold, format_mins, spinner},
+    widgets::Donut,
+    Page,
+};
 
 // Inspired by ratatui::symbols::DOT but lol you can't concat strings at compile
 // time in rust without downloading a 77kb crate and that isn't worth it
@@ -141,11 +157,46 @@ impl DateRangeOption {
     pub fn to_native_dates_from_today(self, prefs: &Preferences) -> (Option<NaiveDate>, NaiveDate) {
         self.to_naive_dates(prefs, Local::now().date_naive())
     }
+
+    /// The INCLUSIVE dates of the period before `min`..=`max` that's worth
+    /// comparing them with: the same days of the week, month, quarter or year
+    /// before for those, otherwise the same number of days right before. None
+    /// for all time, which has nothing before it.
+    pub fn previous_period(self, min: NaiveDate, max: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        let back = |months| {
+            Some((
+                min.checked_sub_months(Months::new(months))?,
+                max.checked_sub_months(Months::new(months))?,
+            ))
+        };
+        match self {
+            DateRangeOption::ThisWeek | DateRangeOption::LastWeek => {
+                Some((min.checked_sub_days(Days::new(7))?, max - Days::new(7)))
+            }
+            DateRangeOption::Mtd => back(1),
+            DateRangeOption::Qtd => back(3),
+            DateRangeOption::Ytd | DateRangeOption::LastYear => back(12),
+            DateRangeOption::Today | DateRangeOption::Past7Days | DateRangeOption::Past30Days => {
+                let days = Days::new((max - min).num_days().unsigned_abs() + 1);
+                Some((min.checked_sub_days(days)?, max - days))
+            }
+            DateRangeOption::AllTime => None,
+        }
+    }
+}
+
+/// What the background task loads for a date range
+#[derive(Debug)]
+struct Loaded {
+    history: History,
+    /// The tasks' trends up to the end of the range
+    trends: [Vec<u64>; 8],
 }
 
 #[derive(Debug)]
 pub struct State {
     time_stats: [TimeStats; 8],
+    tag_stats: Vec<TagStats>,
     date_range: DateRangeOption,
     // Save dates in addition to date range selection for 2 reasons:
     //  - Don't have to recompute them on every render
@@ -154,29 +205,289 @@ pub struct State {
     //    the user alters the date selection or leaves+revisits the page
     min_date: Option<NaiveDate>,
     max_date: NaiveDate,
+    /// Shows billed totals, rounded per the user's billing rule, instead of
+    /// the exact ones
+    billing: bool,
+    /// How far over or under their daily targets the user was in this range,
+    /// not counting today since it isn't over yet
+    balance: Option<chrono::Duration>,
+    /// Shows each day's and week's tracked time against the time expected,
+    /// instead of the breakdown
+    overtime_view: bool,
+    /// The overtime of each day in this range, through today
+    overtime: Vec<targets::Overtime>,
+    /// Shows the totals of the user's categories of tasks, instead of each
+    /// task's
+    category_view: bool,
+    /// Shows each task's totals next to those of the previous period, instead
+    /// of the table of details
+    compare_view: bool,
+    /// Adds each task's days tracked, median and longest entries to the table
+    expanded: bool,
+    /// The task highlighted on the donut, with more about it under the legend
+    selected: Option<u8>,
+    /// Shows the breakdown as a bar per task instead of the donut
+    bars: bool,
+    /// The dates of the period this range is compared with, if there is one
+    previous_dates: Option<(NaiveDate, NaiveDate)>,
+    /// The tasks' stats over `previous_dates`, once loaded while comparing
+    previous: Option<[TimeStats; 8]>,
+    /// When the user's workday typically started and ended in this range
+    workday: Option<(NaiveTime, NaiveTime)>,
+    /// The earliest a day started and the latest one ended in this range
+    first_last: Option<(NaiveTime, NaiveTime)>,
+    /// How much of the work hours in this range, through now, went untracked
+    untracked: Option<chrono::Duration>,
+    /// How much of the task totals overlapped, from tracking parallel tasks
+    overlap: chrono::Duration,
+    /// Whether the stats came from the cache because the save directory
+    /// couldn't be read
+    stale: bool,
+    /// How long was spent on breaks in this range
+    breaks: chrono::Duration,
+    /// Each task's daily totals in minutes over the `TREND_DAYS` up to
+    /// `max_date`, by task number - 1
+    trends: [Vec<u64>; 8],
+    /// Receives the stats for `date_range` and the trends from the background
+    /// task loading them, until they arrive
+    loading: Option<mpsc::Receiver<io::Result<Loaded>>>,
+    /// Receives the stats over `previous_dates` from the background task
+    /// loading them once they're wanted, until they arrive
+    loading_previous: Option<mpsc::Receiver<Option<[TimeStats; 8]>>>,
 }
 
 impl State {
-    pub fn load_default_date_range(prefs: &Preferences) -> io::Result<Self> {
-        Self::load_date_range(prefs, DateRangeOption::Today)
+    pub fn load_default_date_range(dirs: &Dirs, prefs: &Preferences) -> Self {
+        Self::load_date_range(dirs, prefs, DateRangeOption::Today)
     }
 
-    pub fn load_date_range(prefs: &Preferences, date_range: DateRangeOption) -> io::Result<Self> {
+    /// Starts loading the given date range's stats in the background, since
+    /// long ranges can take a while. The page shows a spinner until
+    /// `poll_loading` takes them in.
+    pub fn load_date_range(dirs: &Dirs, prefs: &Preferences, date_range: DateRangeOption) -> Self {
         let (min_range_date, max_date) = date_range.to_native_dates_from_today(prefs);
-        let (time_stats, min_file_date) = load_history(min_range_date, Some(max_date))?;
+        let previous_dates =
+            min_range_date.and_then(|min| date_range.previous_period(min, max_date));
+        let rounding = prefs.billing_rounding;
+        let profile = prefs.profile.clone();
+        let dirs = dirs.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let load = |min, max| load_history(&dirs, min, Some(max), rounding, profile.as_deref());
+            let loaded = load(min_range_date, max_date).map(|history| {
+                let trends = load_trends(&history, min_range_date, max_date, |from| {
+                    load(Some(from), max_date)
+                });
+                Loaded { history, trends }
+            });
+            // If the user has already moved on, nobody's listening anymore and
+            // that's fine
+            let _ = tx.send(loaded);
+        });
 
-        Ok(Self {
-            time_stats,
+        Self {
+            time_stats: [TimeStats::default(); 8],
+            tag_stats: Vec::new(),
             date_range,
-            min_date: min_file_date,
+            min_date: min_range_date,
             max_date,
-        })
+            billing: false,
+            balance: None,
+            overtime_view: false,
+            overtime: Vec::new(),
+            category_view: false,
+            compare_view: false,
+            expanded: false,
+            selected: None,
+            bars: false,
+            previous_dates,
+            previous: None,
+            workday: None,
+            first_last: None,
+            untracked: None,
+            overlap: chrono::Duration::zero(),
+            stale: false,
+            breaks: chrono::Duration::zero(),
+            trends: Default::default(),
+            loading: Some(rx),
+            loading_previous: None,
+        }
+    }
+
+    /// Takes in the stats if they're done loading. Returns the error if
+    /// loading them failed, in which case the page says so.
+    pub fn poll_loading(&mut self, prefs: &Preferences) -> io::Result<()> {
+        if let Some(rx) = self.loading_previous.as_ref() {
+            match rx.try_recv() {
+                Ok(previous) => {
+                    self.previous = previous;
+                    self.loading_previous = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.loading_previous = None,
+            }
+        }
+        let Some(rx) = self.loading.as_ref() else {
+            return Ok(());
+        };
+        let history = match rx.try_recv() {
+            Ok(history) => history,
+            Err(TryRecvError::Empty) => return Ok(()),
+            Err(TryRecvError::Disconnected) => {
+                Err(io::Error::other("Loading history was interrupted"))
+            }
+        };
+        self.loading = None;
+        let Loaded { history, trends } = history?;
+
+        let max_date = self.max_date;
+        let from = self.min_date.or(history.min_date);
+        self.balance = from.and_then(|from| {
+            let yesterday = Local::now().date_naive().pred_opt()?;
+            let to = max_date.min(yesterday);
+            (from <= to)
+                .then(|| targets::balance(prefs, &history.daily_totals, from, to))
+                .flatten()
+        });
+        self.overtime = from.map_or_else(Vec::new, |from| {
+            let to = max_date.min(Local::now().date_naive());
+            targets::daily_overtime(prefs, &history.daily_totals, from, to)
+        });
+        self.time_stats = history.tasks;
+        self.tag_stats = history.tags;
+        self.workday = history.workday;
+        self.first_last = history.first_last;
+        self.untracked = from.map(|from| {
+            untracked_work_time(prefs, &history.daily_busy, from, max_date, Local::now())
+        });
+        self.overlap = history.overlap;
+        self.stale = history.stale;
+        self.breaks = history.breaks;
+        self.trends = trends;
+        self.min_date = history.min_date;
+        Ok(())
+    }
+
+    /// Whether the stats have loaded, successfully or not
+    pub fn is_loaded(&self) -> bool {
+        self.loading.is_none()
+    }
+
+    pub fn toggle_billing(&mut self) {
+        self.billing = !self.billing;
+    }
+
+    pub fn toggle_overtime(&mut self) {
+        self.overtime_view = !self.overtime_view;
+        self.category_view = false;
+        self.compare_view = false;
+    }
+
+    pub fn toggle_categories(&mut self) {
+        self.category_view = !self.category_view;
+        self.overtime_view = false;
+        self.compare_view = false;
+    }
+
+    pub fn toggle_expanded(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    pub fn toggle_bars(&mut self) {
+        self.bars = !self.bars;
     }
 
-    // Mutates self to select the previous date range. Returns an io::Result
-    // because this operation must load the newly selected date range's stats
-    // from disk
-    pub fn select_prev_date_range(&mut self, prefs: &Preferences) -> io::Result<()> {
+    /// Highlights the next tracked task down the donut's order, wrapping
+    /// around past the last
+    pub fn select_next_task(&mut self) {
+        self.step_selected(true);
+    }
+
+    /// Highlights the previous tracked task, wrapping around past the first
+    pub fn select_prev_task(&mut self) {
+        self.step_selected(false);
+    }
+
+    fn step_selected(&mut self, forward: bool) {
+        let mut time_stats = self.time_stats;
+        if self.billing {
+            for ts in time_stats.iter_mut() {
+                ts.total = ts.billed;
+            }
+        }
+        sort_for_donut(&mut time_stats);
+        let tracked: Vec<u8> = time_stats
+            .iter()
+            .filter(|ts| ts.count > 0)
+            .map(|ts| ts.task_number)
+            .collect();
+        if tracked.is_empty() {
+            return;
+        }
+        let len = tracked.len();
+        let i = match tracked.iter().position(|&n| Some(n) == self.selected) {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None if forward => 0,
+            None => len - 1,
+        };
+        self.selected = Some(tracked[i]);
+    }
+
+    /// Switches to comparing with the previous period or back. The previous
+    /// period's stats are only loaded the first time they're wanted, in the
+    /// background like the range's own.
+    pub fn toggle_compare(&mut self, dirs: &Dirs, prefs: &Preferences) {
+        self.compare_view = !self.compare_view;
+        if !self.compare_view {
+            return;
+        }
+        self.overtime_view = false;
+        self.category_view = false;
+        let Some((min, max)) = self.previous_dates else {
+            return;
+        };
+        if self.previous.is_some() || self.loading_previous.is_some() {
+            return;
+        }
+        let rounding = prefs.billing_rounding;
+        let profile = prefs.profile.clone();
+        let dirs = dirs.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            // Without the previous period the page just has nothing to
+            // compare with, which it says
+            let previous = load_history(&dirs, Some(min), Some(max), rounding, profile.as_deref())
+                .map_err(|e| warn!("Unable to load the previous period: {}", e))
+                .ok()
+                .map(|history| history.tasks);
+            let _ = tx.send(previous);
+        });
+        self.loading_previous = Some(rx);
+    }
+
+    /// Writes the breakdown currently on screen to a file in the exports
+    /// directory, returning the file's path
+    pub fn export(
+        &self,
+        dirs: &Dirs,
+        prefs: &Preferences,
+        format: ExportFormat,
+    ) -> io::Result<PathBuf> {
+        export_stats(
+            dirs,
+            format,
+            &self.time_stats,
+            &self.tag_stats,
+            prefs,
+            self.min_date,
+            self.max_date,
+        )
+    }
+
+    // Mutates self to select the previous date range, whose stats load in the
+    // background
+    pub fn select_prev_date_range(&mut self, dirs: &Dirs, prefs: &Preferences) {
         let old_dr_pos = DATE_PICKER_ORDER
             .iter()
             .position(|&dr| dr == self.date_range)
@@ -186,14 +497,12 @@ impl State {
         let len = DATE_PICKER_ORDER.len();
         let prev_dr = DATE_PICKER_ORDER[(old_dr_pos + len - 1) % len];
 
-        *self = Self::load_date_range(prefs, prev_dr)?;
-        Ok(())
+        self.select_date_range(dirs, prefs, prev_dr)
     }
 
-    // Mutates self to select the next date range. Returns an io::Result
-    // because this operation must load the newly selected date range's stats
-    // from disk
-    pub fn select_next_date_range(&mut self, prefs: &Preferences) -> io::Result<()> {
+    // Mutates self to select the next date range, whose stats load in the
+    // background
+    pub fn select_next_date_range(&mut self, dirs: &Dirs, prefs: &Preferences) {
         let old_dr_pos = DATE_PICKER_ORDER
             .iter()
             .position(|&dr| dr == self.date_range)
@@ -203,9 +512,713 @@ impl State {
         let len = DATE_PICKER_ORDER.len();
         let prev_dr = DATE_PICKER_ORDER[(old_dr_pos + 1) % len];
 
-        *self = Self::load_date_range(prefs, prev_dr)?;
-        Ok(())
+        self.select_date_range(dirs, prefs, prev_dr)
+    }
+
+    // Mutates self to select the given date range, keeping the current view
+    pub fn select_date_range(
+        &mut self,
+        dirs: &Dirs,
+        prefs: &Preferences,
+        date_range: DateRangeOption,
+    ) {
+        let compare = self.compare_view;
+        *self = Self {
+            billing: self.billing,
+            overtime_view: self.overtime_view,
+            expanded: self.expanded,
+            selected: self.selected,
+            bars: self.bars,
+            ..Self::load_date_range(dirs, prefs, date_range)
+        };
+        if compare {
+            self.toggle_compare(dirs, prefs);
+        }
+    }
+}
+
+/// Like humantime's formatting, but with a leading + or - sign. Rounds to the
+/// minute since that's all the precision estimates have.
+fn format_signed_duration(d: chrono::Duration) -> String {
+    let mins = d.num_minutes();
+    let sign = if mins < 0 { "-" } else { "+" };
+    format!(
+        "{}{}",
+        sign,
+        humantime::format_duration(std::time::Duration::from_secs(mins.unsigned_abs() * 60))
+    )
+}
+
+/// Each week's tracked time against the time expected of it, followed by each
+/// of its days', newest first
+fn overtime_table<'a>(prefs: &Preferences, days: &[targets::Overtime]) -> Table<'a> {
+    let today = Local::now().date_naive();
+    let row = |label: String, ot: &targets::Overtime| {
+        let mins = |d: chrono::Duration| format_mins(d.num_minutes().max(0) as u32);
+        let balance = ot.balance();
+        Row::new(vec![
+            Span::raw(label),
+            Span::raw(mins(ot.tracked)),
+            Span::raw(mins(ot.expected)),
+            Span::styled(
+                format_balance(balance),
+                Style::default().fg(if balance < chrono::Duration::zero() {
+                    Color::Red
+                } else {
+                    Color::Green
+                }),
+            ),
+        ])
+    };
+
+    let weeks = targets::weekly_overtime(prefs, days);
+    let rows = weeks.iter().rev().flat_map(|week| {
+        let week_row = row(
+            format!("Week of {}", format_date(prefs, week.start, "%x")),
+            week,
+        )
+        .style(Style::default().add_modifier(Modifier::BOLD));
+        let day_rows = days
+            .iter()
+            .rev()
+            .filter(|day| day.start >= week.start && day.start < week.start + Days::new(7))
+            .map(|day| {
+                let mut label = format!("  {}", format_date(prefs, day.start, "%a %x"));
+                if day.start == today {
+                    label.push_str(" (so far)");
+                }
+                row(label, day)
+            });
+        std::iter::once(week_row).chain(day_rows)
+    });
+
+    Table::new(
+        std::iter::once(
+            Row::new(vec!["", "tracked", "expected", "over/under"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .chain(rows)
+        .collect::<Vec<_>>(),
+    )
+    .widths(&[
+        Constraint::Percentage(40),
+        Constraint::Percentage(20),
+        Constraint::Percentage(20),
+        Constraint::Percentage(20),
+    ])
+    .column_spacing(1)
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Tracked vs. Expected"),
+    )
+}
+
+/// Each category's total, followed by those of the tasks counted toward it
+fn category_table<'a>(prefs: &Preferences, rollup: &[CategoryStats], billing: bool) -> Table<'a> {
+    let total_ms: i64 = rollup.iter().map(|c| c.total.num_milliseconds()).sum();
+    let percent = |d: chrono::Duration| {
+        format!(
+            "{:>3}",
+            (100.0 * d.num_milliseconds() as f64 / total_ms.max(1) as f64).round() as u8
+        )
+    };
+    let format = |d: chrono::Duration| humantime::format_duration(d.to_std().unwrap()).to_string();
+    let rows = rollup.iter().flat_map(|category| {
+        let category_row = Row::new(vec![
+            Span::raw(percent(category.total)),
+            bold(category.name.clone()),
+            Span::raw(category.count.to_string()),
+            Span::raw(format(category.total)),
+        ]);
+        let task_rows = category.tasks.iter().map(move |ts| {
+            Row::new(vec![
+                Span::styled(
+                    percent(ts.total),
+                    Style::default().bg(task_color(prefs, ts.task_number)),
+                ),
+                Span::raw(format!(
+                    "  {}",
+                    get_pref_label(ts.task_number, prefs.labels.as_ref())
+                        .unwrap_or_else(|| ts.task_number.to_string())
+                )),
+                Span::raw(ts.count.to_string()),
+                Span::raw(format(ts.total)),
+            ])
+            .style(Style::default().add_modifier(Modifier::DIM))
+        });
+        std::iter::once(category_row).chain(task_rows)
+    });
+
+    Table::new(
+        std::iter::once(
+            Row::new(vec!["%", "category", "#", "total"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .chain(rows)
+        .collect::<Vec<_>>(),
+    )
+    .widths(&[
+        Constraint::Length(3),
+        Constraint::Percentage(40),
+        Constraint::Percentage(10),
+        Constraint::Percentage(40),
+    ])
+    .column_spacing(1)
+    .block(Block::default().borders(Borders::ALL).title(if billing {
+        "Billed Time by Category"
+    } else {
+        "Time by Category"
+    }))
+}
+
+/// Each task's count, average and total, and optionally more, with its tags'
+/// rows underneath it and the user's client subtotals at the bottom, in the
+/// order of `time_stats`
+fn details_table<'a>(
+    prefs: &Preferences,
+    time_stats: &[TimeStats; 8],
+    tag_stats: &[TagStats],
+    expanded: bool,
+) -> Table<'a> {
+    let total_ms: i64 = time_stats
+        .iter()
+        .map(|ts| ts.total.num_milliseconds())
+        .sum();
+    let rows = time_stats.iter().map(|ts| {
+        (
+            (100.0 * ts.total.num_milliseconds() as f64 / total_ms as f64).round() as u8,
+            task_color(prefs, ts.task_number),
+            ts,
+        )
+    });
+    let labels = prefs.labels.as_ref();
+    let rates = prefs.hourly_rates.as_ref();
+    // Client subtotals go underneath the per-task rows
+    let client_stats = prefs.clients.as_ref().map_or_else(Vec::new, |clients| {
+        group_by_client(time_stats, tag_stats, clients, rates)
+    });
+    // -_- I wish the tui crate did the widths() fn signature better. This
+    // shouldn't have to be necessary, but it is b/c of how they typed the
+    // param.
+    let widths: &[Constraint] = match (rates.is_some(), expanded) {
+        (true, false) => &[
+            Constraint::Length(3),
+            Constraint::Percentage(20),
+            Constraint::Percentage(6),
+            Constraint::Percentage(14),
+            Constraint::Percentage(28),
+            Constraint::Percentage(16),
+            Constraint::Percentage(16),
+        ],
+        (false, false) => &[
+            Constraint::Length(3),
+            Constraint::Percentage(22),
+            Constraint::Percentage(8),
+            Constraint::Percentage(16),
+            Constraint::Percentage(34),
+            Constraint::Percentage(20),
+        ],
+        (true, true) => &[
+            Constraint::Length(3),
+            Constraint::Percentage(14),
+            Constraint::Percentage(5),
+            Constraint::Percentage(5),
+            Constraint::Percentage(11),
+            Constraint::Percentage(11),
+            Constraint::Percentage(11),
+            Constraint::Percentage(16),
+            Constraint::Percentage(11),
+            Constraint::Percentage(12),
+        ],
+        (false, true) => &[
+            Constraint::Length(3),
+            Constraint::Percentage(16),
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(18),
+            Constraint::Percentage(14),
+        ],
+    };
+    // The expanded view adds the days tracked after the count, and the
+    // median and longest entries after the average
+    let with_details = |mut cells: Vec<Span<'static>>, [days, median, longest]: [String; 3]| {
+        if expanded {
+            cells.insert(3, Span::raw(days));
+            cells.insert(5, Span::raw(median));
+            cells.insert(6, Span::raw(longest));
+        }
+        cells
+    };
+    // Earnings go in an extra column, only if the user has hourly rates
+    let with_earnings = |mut cells: Vec<Span<'static>>, earned: Option<f64>| {
+        if rates.is_some() {
+            cells.push(Span::raw(earned.map_or_else(String::new, format_earnings)));
+        }
+        cells
+    };
+    let mut header = if expanded {
+        vec![
+            "%", "task", "#", "days", "avg", "median", "longest", "total", "vs est.",
+        ]
+    } else {
+        vec!["%", "task", "#", "avg", "total", "vs est."]
+    };
+    if rates.is_some() {
+        header.push("earned");
+    }
+    Table::new(
+        [Row::new(header).style(Style::default().add_modifier(Modifier::BOLD))]
+            .into_iter()
+            .chain(rows.flat_map(|(perc, color, ts)| {
+                let task_row = Row::new(with_earnings(
+                    with_details(
+                        vec![
+                            Span::styled(format!("{:>3}", perc), Style::default().bg(color)),
+                            Span::raw(patterned_label(
+                                prefs,
+                                ts.task_number,
+                                get_pref_label(ts.task_number, labels)
+                                    .unwrap_or_else(|| ts.task_number.to_string()),
+                            )),
+                            Span::raw(ts.count.to_string()),
+                            Span::raw(
+                                humantime::format_duration(ts.mean.to_std().unwrap()).to_string(),
+                            ),
+                            Span::raw(
+                                humantime::format_duration(ts.total.to_std().unwrap()).to_string(),
+                            ),
+                            Span::raw(
+                                ts.estimate_variance()
+                                    .map_or_else(String::new, format_signed_duration),
+                            ),
+                        ],
+                        [
+                            ts.days.to_string(),
+                            humantime::format_duration(ts.median.to_std().unwrap()).to_string(),
+                            humantime::format_duration(ts.longest.to_std().unwrap()).to_string(),
+                        ],
+                    ),
+                    earnings(ts.task_number, ts.billed, rates),
+                ));
+                // Each task's tags go right underneath it
+                let tag_rows = tag_stats
+                    .iter()
+                    .filter(move |tag| tag.task_number == ts.task_number)
+                    .map(|tag| {
+                        Row::new(with_earnings(
+                            with_details(
+                                vec![
+                                    Span::raw(""),
+                                    Span::raw(format!("  #{}", tag.tag)),
+                                    Span::raw(tag.count.to_string()),
+                                    Span::raw(""),
+                                    Span::raw(
+                                        humantime::format_duration(tag.total.to_std().unwrap())
+                                            .to_string(),
+                                    ),
+                                    Span::raw(""),
+                                ],
+                                Default::default(),
+                            ),
+                            earnings(tag.task_number, tag.billed, rates),
+                        ))
+                        .style(Style::default().add_modifier(Modifier::DIM))
+                    });
+                std::iter::once(task_row).chain(tag_rows)
+            }))
+            .chain(client_stats.iter().map(|cs| -> Row {
+                Row::new(with_earnings(
+                    with_details(
+                        vec![
+                            Span::raw(format!(
+                                "{:>3}",
+                                (100.0 * cs.total.num_milliseconds() as f64 / total_ms as f64)
+                                    .round() as u8
+                            )),
+                            bold(cs.name.clone()),
+                            Span::raw(cs.count.to_string()),
+                            Span::raw(""),
+                            Span::raw(
+                                humantime::format_duration(cs.total.to_std().unwrap()).to_string(),
+                            ),
+                            Span::raw(""),
+                        ],
+                        Default::default(),
+                    ),
+                    cs.earnings,
+                ))
+            })),
+    )
+    .widths(widths)
+    .column_spacing(1)
+    .block(Block::default().borders(Borders::ALL))
+}
+
+/// Each task's total next to its total over the previous period, and how much
+/// it changed by, for the tasks tracked in either. Both are billed totals in
+/// the billing view.
+fn comparison_table<'a>(
+    prefs: &Preferences,
+    current: &[TimeStats],
+    previous: &[TimeStats; 8],
+    dates: (NaiveDate, NaiveDate),
+    billing: bool,
+) -> Table<'a> {
+    let total = |ts: &TimeStats| if billing { ts.billed } else { ts.total };
+    let format = |d: chrono::Duration| humantime::format_duration(d.to_std().unwrap()).to_string();
+    let change = |now: chrono::Duration, before: chrono::Duration| {
+        let percent = if before > chrono::Duration::zero() {
+            format!(
+                " ({:+.0}%)",
+                100.0 * (now - before).num_milliseconds() as f64 / before.num_milliseconds() as f64
+            )
+        } else {
+            String::new()
+        };
+        format!("{}{}", format_signed_duration(now - before), percent)
+    };
+    let mut rows: Vec<Row> = current
+        .iter()
+        .filter_map(|ts| {
+            let before = previous.get((ts.task_number as usize).checked_sub(1)?)?;
+            (ts.count > 0 || before.count > 0).then(|| {
+                Row::new(vec![
+                    Span::styled(
+                        "   ",
+                        Style::default().bg(task_color(prefs, ts.task_number)),
+                    ),
+                    Span::raw(patterned_label(
+                        prefs,
+                        ts.task_number,
+                        get_pref_label(ts.task_number, prefs.labels.as_ref())
+                            .unwrap_or_else(|| ts.task_number.to_string()),
+                    )),
+                    Span::raw(format(total(ts))),
+                    Span::raw(format(total(before))),
+                    Span::raw(change(total(ts), total(before))),
+                ])
+            })
+        })
+        .collect();
+    let sum = |stats: &[TimeStats]| {
+        stats
+            .iter()
+            .fold(chrono::Duration::zero(), |acc, ts| acc + total(ts))
+    };
+    let (now, before) = (sum(current), sum(previous));
+    rows.push(Row::new(vec![
+        Span::raw(""),
+        bold("all tasks"),
+        bold(format(now)),
+        bold(format(before)),
+        bold(change(now, before)),
+    ]));
+
+    Table::new(
+        std::iter::once(
+            Row::new(vec!["", "task", "this range", "previous", "change"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .chain(rows)
+        .collect::<Vec<_>>(),
+    )
+    .widths(&[
+        Constraint::Length(3),
+        Constraint::Percentage(25),
+        Constraint::Percentage(22),
+        Constraint::Percentage(22),
+        Constraint::Percentage(31),
+    ])
+    .column_spacing(1)
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "{} vs {} to {}",
+        if billing { "Billed Time" } else { "Time" },
+        format_date(prefs, dates.0, "%x"),
+        format_date(prefs, dates.1, "%x")
+    )))
+}
+
+/// Stats as plain lines of text, for screen readers and dumb terminals: each
+/// task's time and tags, or each day's overtime, without the donut
+pub fn draw_plain<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Stats(ref state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render stats page when the app isn't in stats page state!")
+    };
+    let prefs = &app.preferences;
+    let format = |d: chrono::Duration| {
+        humantime::format_duration(std::time::Duration::from_secs(d.num_seconds().max(0) as u64))
+            .to_string()
+    };
+
+    let mut lines = vec![
+        "Keys: q back, left/right change the date range, up/down select a task, \
+         b billing view, o overtime, c categories, v compare with the previous period, \
+         d more columns, g bars, x/m export CSV/Markdown"
+            .to_string(),
+        match state.min_date {
+            Some(min) => format!(
+                "{}: {} to {}",
+                state.date_range,
+                format_date(prefs, min, "%x"),
+                format_date(prefs, state.max_date, "%x")
+            ),
+            None => "All time".to_string(),
+        },
+    ];
+    if state.stale {
+        lines.push(
+            "Stale data: the save directory can't be read, so these are the last stats \
+             cached for this range"
+                .to_string(),
+        );
+    }
+
+    if state.overtime_view {
+        lines.push("Tracked vs. expected:".to_string());
+        let weeks = targets::weekly_overtime(prefs, &state.overtime);
+        for week in weeks.iter().rev() {
+            lines.push(format!(
+                "Week of {}: tracked {}, expected {}, {}",
+                format_date(prefs, week.start, "%x"),
+                format(week.tracked),
+                format(week.expected),
+                format_balance(week.balance())
+            ));
+            for day in state
+                .overtime
+                .iter()
+                .rev()
+                .filter(|day| day.start >= week.start && day.start < week.start + Days::new(7))
+            {
+                lines.push(format!(
+                    "  {}: tracked {}, expected {}, {}",
+                    format_date(prefs, day.start, "%a %x"),
+                    format(day.tracked),
+                    format(day.expected),
+                    format_balance(day.balance())
+                ));
+            }
+        }
+    } else if state.compare_view {
+        match (&state.previous, state.previous_dates) {
+            (Some(previous), Some((min, max))) => {
+                lines.push(format!(
+                    "{} vs {} to {}:",
+                    if state.billing { "Billed time" } else { "Time" },
+                    format_date(prefs, min, "%x"),
+                    format_date(prefs, max, "%x")
+                ));
+                let total = |ts: &TimeStats| if state.billing { ts.billed } else { ts.total };
+                for (ts, before) in state.time_stats.iter().zip(previous) {
+                    if ts.count > 0 || before.count > 0 {
+                        lines.push(format!(
+                            "{}: {} vs {}, {}",
+                            get_pref_label(ts.task_number, prefs.labels.as_ref())
+                                .unwrap_or_else(|| ts.task_number.to_string()),
+                            format(total(ts)),
+                            format(total(before)),
+                            format_signed_duration(total(ts) - total(before))
+                        ));
+                    }
+                }
+            }
+            _ => lines.push("There's no previous period to compare this range with".to_string()),
+        }
+    } else if state.category_view {
+        // The categories' totals come before each task's, rather than instead
+        let time_stats = state.time_stats.map(|ts| TimeStats {
+            total: if state.billing { ts.billed } else { ts.total },
+            ..ts
+        });
+        let rollup =
+            group_by_category(&time_stats, prefs.categories.as_deref().unwrap_or_default());
+        let total_ms: i64 = rollup.iter().map(|c| c.total.num_milliseconds()).sum();
+        lines.push(if state.billing {
+            "Billed time by category:".to_string()
+        } else {
+            "Time by category:".to_string()
+        });
+        for category in rollup.iter().filter(|c| c.count > 0) {
+            lines.push(format!(
+                "{}: {} ({}%), {} entries",
+                category.name,
+                format(category.total),
+                (100.0 * category.total.num_milliseconds() as f64 / total_ms.max(1) as f64).round(),
+                category.count
+            ));
+            for ts in &category.tasks {
+                lines.push(format!(
+                    "  {}: {}",
+                    get_pref_label(ts.task_number, prefs.labels.as_ref())
+                        .unwrap_or_else(|| ts.task_number.to_string()),
+                    format(ts.total)
+                ));
+            }
+        }
+    }
+    if !state.overtime_view && !state.compare_view {
+        let billed_or_total = |total, billed| if state.billing { billed } else { total };
+        let mut time_stats = state.time_stats;
+        time_stats.sort_by_key(|ts| std::cmp::Reverse(billed_or_total(ts.total, ts.billed)));
+        let total_ms: i64 = time_stats
+            .iter()
+            .map(|ts| billed_or_total(ts.total, ts.billed).num_milliseconds())
+            .sum();
+        lines.push(if state.billing {
+            "Billed time by task:".to_string()
+        } else {
+            "Time by task:".to_string()
+        });
+        for ts in time_stats.iter().filter(|ts| ts.count > 0) {
+            let total = billed_or_total(ts.total, ts.billed);
+            lines.push(format!(
+                "{}: {} ({}%), {} entries",
+                get_pref_label(ts.task_number, prefs.labels.as_ref())
+                    .unwrap_or_else(|| ts.task_number.to_string()),
+                format(total),
+                (100.0 * total.num_milliseconds() as f64 / total_ms.max(1) as f64).round(),
+                ts.count
+            ));
+            if state.expanded {
+                lines.push(format!(
+                    "  on {} days, median {}, longest {}",
+                    ts.days,
+                    format(ts.median),
+                    format(ts.longest)
+                ));
+            }
+            for tag in state
+                .tag_stats
+                .iter()
+                .filter(|tag| tag.task_number == ts.task_number)
+            {
+                lines.push(format!(
+                    "  #{}: {}, {} entries",
+                    tag.tag,
+                    format(billed_or_total(tag.total, tag.billed)),
+                    tag.count
+                ));
+            }
+        }
+        if total_ms == 0 {
+            lines.push("Nothing tracked".to_string());
+        }
     }
+
+    if let Some(b) = state.balance {
+        lines.push(format!("{} vs targets", format_balance(b)));
+    }
+    if let Some((start, end)) = state.workday {
+        lines.push(format!(
+            "Workday typically {} to {}",
+            start.format("%R"),
+            end.format("%R")
+        ));
+    }
+    if let Some((first, last)) = state.first_last {
+        lines.push(format!(
+            "Started as early as {}, ended as late as {}",
+            first.format("%R"),
+            last.format("%R")
+        ));
+    }
+    if let Some(untracked) = state.untracked {
+        lines.push(format!("{} untracked in work hours", format(untracked)));
+    }
+    if state.overlap > chrono::Duration::zero() {
+        lines.push(format!("{} of it in parallel", format(state.overlap)));
+    }
+    if state.breaks > chrono::Duration::zero() {
+        lines.push(format!("{} on breaks", format(state.breaks)));
+    }
+    lines.extend(app.messages.iter().map(|m| m.0.clone()));
+    super::draw_plain_lines(f, lines, None);
+}
+
+/// Below this width, the chart goes above the table instead of beside it
+const STACKED_WIDTH: u16 = 80;
+
+/// How wide the bars are, labels included, when they're shown instead of the
+/// donut
+const BARS_WIDTH: u16 = 34;
+const BAR_LABEL_WIDTH: u16 = 14;
+
+/// How wide the legend beside the donut is
+const LEGEND_WIDTH: u16 = 26;
+
+/// Sorts tasks the way the donut and table show them: biggest first, with
+/// ties in reverse task order
+fn sort_for_donut(time_stats: &mut [TimeStats; 8]) {
+    // I know unstable sort is faster but I think it is desirable that the
+    // chart preserve slice ordering for equal size slices.
+    time_stats.sort_by_key(|ts| ts.total);
+    time_stats.reverse();
+}
+
+/// More about the task selected on the donut, for under the legend
+fn selected_details(
+    prefs: &Preferences,
+    ts: &TimeStats,
+    tag_stats: &[TagStats],
+) -> Vec<Line<'static>> {
+    let format = |d: chrono::Duration| humantime::format_duration(d.to_std().unwrap()).to_string();
+    let mut lines = vec![
+        Line::default(),
+        Line::from(bold(
+            get_pref_label(ts.task_number, prefs.labels.as_ref())
+                .unwrap_or_else(|| ts.task_number.to_string()),
+        )),
+        Line::from(format!("{} entries on {} days", ts.count, ts.days)),
+        Line::from(format!("avg {}", format(ts.mean))),
+        Line::from(format!("median {}", format(ts.median))),
+        Line::from(format!("longest {}", format(ts.longest))),
+    ];
+    if let Some(tag) = tag_stats
+        .iter()
+        .filter(|tag| tag.task_number == ts.task_number)
+        .max_by_key(|tag| tag.total)
+    {
+        lines.push(Line::from(format!("mostly #{}", tag.tag)));
+    }
+    lines
+}
+
+/// How many days back the trend alongside each task goes
+const TREND_DAYS: u64 = 30;
+
+/// Each task's trend over the `TREND_DAYS` up to `max_date`. The range's own
+/// history covers them unless it's shorter, in which case `load` gets the
+/// days from the given date, which are mostly in the stats cache already.
+/// Without them, the trends are just left empty.
+fn load_trends(
+    history: &History,
+    min_date: Option<NaiveDate>,
+    max_date: NaiveDate,
+    load: impl FnOnce(NaiveDate) -> io::Result<History>,
+) -> [Vec<u64>; 8] {
+    let from = max_date - Days::new(TREND_DAYS - 1);
+    let loaded;
+    let daily = if min_date.is_none_or(|min| min <= from) {
+        &history.daily_task_totals
+    } else {
+        match load(from) {
+            Ok(history) => {
+                loaded = history;
+                &loaded.daily_task_totals
+            }
+            Err(e) => {
+                warn!("Unable to load the stats trends: {}", e);
+                return Default::default();
+            }
+        }
+    };
+    std::array::from_fn(|i| task_trend(daily, i as u8 + 1, max_date, TREND_DAYS))
 }
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
@@ -217,11 +1230,57 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     let State {
         mut time_stats,
+        tag_stats,
         min_date,
         max_date,
         date_range,
+        billing,
+        balance,
+        overtime_view,
+        overtime,
+        category_view,
+        compare_view,
+        expanded,
+        selected,
+        bars,
+        previous_dates,
+        previous,
+        workday,
+        first_last,
+        untracked,
+        overlap,
+        stale,
+        breaks,
+        trends,
+        loading,
+        loading_previous,
     } = state;
 
+    // The billing view is the same breakdown, just with the billed totals
+    let billing_title = if *billing {
+        for ts in time_stats.iter_mut() {
+            ts.total = ts.billed;
+            ts.mean = if ts.count == 0 {
+                chrono::Duration::zero()
+            } else {
+                ts.billed / (ts.count as i32)
+            };
+        }
+        Some(match app.preferences.billing_rounding {
+            Some(rounding) => format!("Billed Time (rounded {})", rounding.describe()),
+            None => "Billed Time (no rounding set)".to_string(),
+        })
+    } else {
+        None
+    };
+    let tag_stats: Vec<TagStats> = tag_stats
+        .iter()
+        .map(|tag| TagStats {
+            total: if *billing { tag.billed } else { tag.total },
+            ..tag.clone()
+        })
+        .collect();
+
     let topmost_vertical = Layout::default()
         .direction(Direction::Vertical)
         .vertical_margin(1)
@@ -242,22 +1301,94 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         bold("q"),
         Span::raw("/"),
         bold("Esc"),
-        Span::raw(": back home"),
+        Span::raw(": back home | "),
+        bold("x"),
+        Span::raw("/"),
+        bold("m"),
+        Span::raw(": export CSV/Markdown | "),
+        bold("b"),
+        Span::raw(if *billing {
+            ": exact view | "
+        } else {
+            ": billing view | "
+        }),
+        bold("o"),
+        Span::raw(if *overtime_view {
+            ": breakdown | "
+        } else {
+            ": overtime | "
+        }),
+        bold("c"),
+        Span::raw(if *category_view {
+            ": hide categories | "
+        } else {
+            ": by category | "
+        }),
+        bold("v"),
+        Span::raw(if *compare_view {
+            ": details | "
+        } else {
+            ": vs previous | "
+        }),
+        bold("↑+↓"),
+        Span::raw(": select task | "),
+        bold("g"),
+        Span::raw(if *bars { ": donut | " } else { ": bars | " }),
+        bold("d"),
+        Span::raw(if *expanded {
+            ": fewer columns"
+        } else {
+            ": more columns"
+        }),
     ]));
     f.render_widget(help_message, topmost_vertical[0]);
 
-    if time_stats[0].task_number == 0 {
+    let mut date_range_targets = Vec::new();
+    if loading.is_some() {
+        f.render_widget(
+            Paragraph::new(format!("Loading history {}", spinner())),
+            topmost_vertical[1],
+        );
+    } else if time_stats[0].task_number == 0 {
         f.render_widget(
             Paragraph::new("Unable to load history!"),
             topmost_vertical[1],
         );
+    } else if *overtime_view {
+        f.render_widget(
+            overtime_table(&app.preferences, overtime),
+            topmost_vertical[1],
+        );
+    } else if *category_view {
+        // The categories' totals go above the usual table of each task's
+        let rollup = group_by_category(
+            &time_stats,
+            app.preferences.categories.as_deref().unwrap_or_default(),
+        );
+        let category_rows: usize = rollup.iter().map(|c| 1 + c.tasks.len()).sum();
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(category_rows as u16 + 3),
+                    Constraint::Min(5),
+                ]
+                .as_ref(),
+            )
+            .split(topmost_vertical[1]);
+        f.render_widget(
+            category_table(&app.preferences, &rollup, *billing),
+            areas[0],
+        );
+        sort_for_donut(&mut time_stats);
+        f.render_widget(
+            details_table(&app.preferences, &time_stats, &tag_stats, *expanded),
+            areas[1],
+        );
     } else {
         // Donut chart widget
 
-        // I know unstable sort is faster but I think it is desirable that the
-        // chart preserve slice ordering for equal size slices.
-        time_stats.sort_by_key(|ts| ts.total);
-        time_stats.reverse();
+        sort_for_donut(&mut time_stats);
 
         // 1. Convert [TimeStats; 8] into list of tuples of u8 percents and
         // colors
@@ -266,80 +1397,244 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             .map(|ts| ts.total.num_milliseconds())
             .sum();
 
-        let tups = time_stats.iter().enumerate().map(|(i, ts)| {
+        let tups = time_stats.iter().map(|ts| {
             (
                 // Integer division always truncates, but I'd rather round
                 // half-away-from-0 to the nearest percent
                 (100.0 * ts.total.num_milliseconds() as f64 / total_ms as f64).round() as u8,
-                number_to_color((i % 8) as u8 + 1),
+                task_color(&app.preferences, ts.task_number),
                 ts,
             )
         });
 
         // 2. Construct a Donut with the list
-        let donut = Donut::new(2.6, 1.2, tups.clone().map(|tup| (tup.0, tup.1)).collect());
+        let donut = Donut::new(2.6, 1.2, tups.clone().map(|tup| (tup.0, tup.1)).collect())
+            .patterns(
+                tups.clone()
+                    .map(|tup| task_pattern(&app.preferences, tup.2.task_number))
+                    .collect(),
+            )
+            .labels(
+                tups.clone()
+                    .map(|tup| {
+                        get_pref_label(tup.2.task_number, app.preferences.labels.as_ref())
+                            .unwrap_or_else(|| tup.2.task_number.to_string())
+                    })
+                    .collect(),
+            )
+            .selected(
+                time_stats
+                    .iter()
+                    .position(|ts| Some(ts.task_number) == *selected),
+            );
+        let legend = donut.legend();
 
         // 3. Create & position a Canvas on which to draw the Donut, passing Donut::painter
-        let donut_horizontal = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(
-                [
-                    // Make the donut chart always be a nice circle. Subtract 1
-                    // b/c terminal cells are not square, and the adjustment
-                    // helps the donut be more circular instead of oval.
-                    Constraint::Length((topmost_vertical[1].height - 1) * 2),
-                    // Have the table take up the remaining space
-                    Constraint::Min(20),
-                ]
-                .as_ref(),
+        let area = topmost_vertical[1];
+        let (chart_area, mut rest) = if area.width < STACKED_WIDTH {
+            // Too narrow for the chart and the table side by side, so the
+            // chart goes on top, centered
+            let tracked = time_stats.iter().filter(|ts| ts.count > 0).count() as u16;
+            let height = if *bars {
+                (tracked + 2).min(area.height / 2)
+            } else {
+                area.height / 2
+            };
+            let width = if *bars {
+                area.width
+            } else {
+                (height.saturating_sub(1) * 2).min(area.width)
+            };
+            (
+                Rect {
+                    x: area.x + (area.width - width) / 2,
+                    width,
+                    height,
+                    ..area
+                },
+                Rect {
+                    y: area.y + height,
+                    height: area.height - height,
+                    ..area
+                },
             )
-            .split(topmost_vertical[1]);
+        } else {
+            let donut_horizontal = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    [
+                        if *bars {
+                            Constraint::Length(BARS_WIDTH)
+                        } else {
+                            // Make the donut chart always be a nice circle.
+                            // Subtract 1 b/c terminal cells are not square,
+                            // and the adjustment helps the donut be more
+                            // circular instead of oval.
+                            Constraint::Length(area.height.saturating_sub(1) * 2)
+                        },
+                        // Have the table take up the remaining space
+                        Constraint::Min(20),
+                    ]
+                    .as_ref(),
+                )
+                .split(area);
+            (donut_horizontal[0], donut_horizontal[1])
+        };
 
-        let canvas = Canvas::default()
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Time Breakdown"),
-            )
-            .paint(donut.painter())
-            .x_bounds([-1.0, 1.0])
-            .y_bounds([-1.0, 1.0]);
-
-        f.render_widget(canvas, donut_horizontal[0]);
-
-        // Table widget
-        let labels = app.preferences.labels.as_ref();
-        // -_- I wish the tui crate did the widths() fn signature better. This
-        // shouldn't have to be necessary, but it is b/c of how they typed the
-        // param.
-        let widths = [
-            Constraint::Length(3),
-            Constraint::Percentage(24),
-            Constraint::Percentage(10),
-            Constraint::Percentage(18),
-            Constraint::Percentage(46),
-        ];
-        let details = Table::new(
-            [Row::new(vec!["%", "task", "#", "avg", "total"])
-                .style(Style::default().add_modifier(Modifier::BOLD))]
-            .into_iter()
-            .chain(tups.map(|(perc, color, ts)| -> Row {
-                Row::new(vec![
-                    Span::styled(format!("{:>3}", perc), Style::default().bg(color)),
-                    Span::raw(
-                        get_pref_label(ts.task_number, labels)
-                            .unwrap_or_else(|| ts.task_number.to_string()),
-                    ),
-                    Span::raw(ts.count.to_string()),
-                    Span::raw(humantime::format_duration(ts.mean.to_std().unwrap()).to_string()),
-                    Span::raw(humantime::format_duration(ts.total.to_std().unwrap()).to_string()),
-                ])
-            })),
-        )
-        .widths(&widths)
-        .column_spacing(1)
-        .block(Block::default().borders(Borders::ALL));
-        f.render_widget(details, donut_horizontal[1]);
+        let chart_block = Block::default()
+            .borders(Borders::ALL)
+            .title(billing_title.unwrap_or_else(|| "Time Breakdown".to_string()));
+        if *bars {
+            // A bar per tracked task instead, which stays readable in small
+            // terminals where the canvas circle falls apart
+            let inner = chart_block.inner(chart_area);
+            f.render_widget(chart_block, chart_area);
+            let tracked = tups.clone().filter(|(_, _, ts)| ts.count > 0);
+            for (row, (perc, color, ts)) in (0..inner.height).zip(tracked) {
+                let line = Rect {
+                    y: inner.y + row,
+                    height: 1,
+                    ..inner
+                };
+                let label_width = line.width.min(BAR_LABEL_WIDTH);
+                let mut label_style = Style::default();
+                if Some(ts.task_number) == *selected {
+                    label_style = label_style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+                }
+                f.render_widget(
+                    Paragraph::new(Span::styled(
+                        patterned_label(
+                            &app.preferences,
+                            ts.task_number,
+                            get_pref_label(ts.task_number, app.preferences.labels.as_ref())
+                                .unwrap_or_else(|| ts.task_number.to_string()),
+                        ),
+                        label_style,
+                    )),
+                    Rect {
+                        width: label_width,
+                        ..line
+                    },
+                );
+                f.render_widget(
+                    Gauge::default()
+                        .ratio(f64::from(perc.min(100)) / 100.0)
+                        .label(format!("{}%", perc))
+                        .gauge_style(Style::default().fg(color).bg(Color::DarkGray))
+                        .use_unicode(true),
+                    Rect {
+                        x: line.x + label_width,
+                        width: line.width - label_width,
+                        ..line
+                    },
+                );
+            }
+        } else if chart_area.width > 2 && chart_area.height > 2 {
+            // The canvas can't draw into nothing, which is all that's left
+            // inside the borders of anything smaller
+            let canvas = Canvas::default()
+                .block(chart_block)
+                .paint(donut.painter())
+                .x_bounds([-1.0, 1.0])
+                .y_bounds([-1.0, 1.0]);
+
+            f.render_widget(canvas, chart_area);
+        }
+
+        // The legend goes between the donut and the table if there's room,
+        // with more on the selected task underneath
+        if rest.width >= 60 + LEGEND_WIDTH {
+            let areas = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(LEGEND_WIDTH), Constraint::Min(60)].as_ref())
+                .split(rest);
+            rest = areas[1];
+            let mut lines = legend;
+            if let Some(ts) = time_stats
+                .iter()
+                .find(|ts| Some(ts.task_number) == *selected)
+            {
+                lines.extend(selected_details(&app.preferences, ts, &tag_stats));
+            }
+            f.render_widget(
+                Paragraph::new(lines)
+                    .wrap(Wrap { trim: false })
+                    .block(Block::default().borders(Borders::ALL).title("Legend")),
+                areas[0],
+            );
+        }
+
+        if *compare_view {
+            let table_area = rest;
+            match (previous, previous_dates) {
+                (None, Some(_)) if loading_previous.is_some() => f.render_widget(
+                    Paragraph::new(format!("Loading the previous period {}", spinner()))
+                        .block(Block::default().borders(Borders::ALL)),
+                    table_area,
+                ),
+                (Some(previous), Some(dates)) => f.render_widget(
+                    comparison_table(&app.preferences, &time_stats, previous, *dates, *billing),
+                    table_area,
+                ),
+                _ => f.render_widget(
+                    Paragraph::new("There's no previous period to compare this range with")
+                        .block(Block::default().borders(Borders::ALL)),
+                    table_area,
+                ),
+            }
+        } else {
+            let details = details_table(&app.preferences, &time_stats, &tag_stats, *expanded);
+
+            // The trends go alongside the table if there's room, a sparkline on
+            // each task's row. Widgets can't go in table cells, hence lining them
+            // up by hand.
+            let trend_width = TREND_DAYS as u16 + 2;
+            let table_area = rest;
+            if table_area.width >= 60 + trend_width {
+                let areas = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Min(60), Constraint::Length(trend_width)].as_ref())
+                    .split(table_area);
+                f.render_widget(details, areas[0]);
+
+                let block = Block::default().borders(Borders::ALL);
+                let inner = block.inner(areas[1]);
+                f.render_widget(block, areas[1]);
+                let line = |row: u16| Rect {
+                    y: inner.y + row,
+                    height: 1,
+                    ..inner
+                };
+                if inner.height > 0 {
+                    f.render_widget(
+                        Paragraph::new(bold(format!("last {} days", TREND_DAYS))),
+                        line(0),
+                    );
+                }
+                let mut row = 1;
+                for ts in time_stats.iter() {
+                    if row >= inner.height {
+                        break;
+                    }
+                    let trend = &trends[ts.task_number as usize - 1];
+                    if trend.iter().any(|&mins| mins > 0) {
+                        f.render_widget(
+                            Sparkline::default().data(trend).style(
+                                Style::default().fg(task_color(&app.preferences, ts.task_number)),
+                            ),
+                            line(row),
+                        );
+                    }
+                    let tags = tag_stats
+                        .iter()
+                        .filter(|tag| tag.task_number == ts.task_number)
+                        .count();
+                    row = row.saturating_add(1 + tags as u16);
+                }
+            } else {
+                f.render_widget(details, table_area);
+            }
+        }
 
         // Date picker widget
         // TODO offer a UI for manual date selection
@@ -351,32 +1646,142 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             }
         });
 
-        let date_picker = Paragraph::new(vec![
-            Line::from(vec![
-                Span::styled(
-                    "Date Range:",
-                    Style::default().add_modifier(Modifier::UNDERLINED),
-                ),
-                Span::raw(if let Some(min) = min_date {
-                    format!(" {} to {}", min.format("%x"), max_date.format("%x"))
-                } else {
-                    " All time".to_string()
-                }),
-            ]),
-            Line::from(
-                // TODO once intersperse drops on stable, use that and drop the
-                // itertools dep
-                Itertools::intersperse(date_options, Span::raw(SPACED_DOT)).collect::<Vec<Span>>(),
+        let mut lines = vec![Line::from(vec![
+            Span::styled(
+                "Date Range:",
+                Style::default().add_modifier(Modifier::UNDERLINED),
             ),
-        ])
-        .wrap(Wrap { trim: false });
-
-        f.render_widget(date_picker, topmost_vertical[2])
+            Span::raw(if let Some(min) = min_date {
+                format!(
+                    " {} to {}",
+                    format_date(&app.preferences, *min, "%x"),
+                    format_date(&app.preferences, *max_date, "%x")
+                )
+            } else {
+                " All time".to_string()
+            }),
+            Span::raw(balance.map_or_else(String::new, |b| {
+                format!("{}{} vs targets", SPACED_DOT, format_balance(b))
+            })),
+            Span::raw(workday.map_or_else(String::new, |(start, end)| {
+                format!(
+                    "{}workday typically {}–{}",
+                    SPACED_DOT,
+                    start.format("%R"),
+                    end.format("%R")
+                )
+            })),
+            Span::raw(first_last.map_or_else(String::new, |(first, last)| {
+                format!(
+                    " (earliest {}, latest {})",
+                    first.format("%R"),
+                    last.format("%R")
+                )
+            })),
+            Span::raw(untracked.map_or_else(String::new, |untracked| {
+                format!(
+                    "{}{} untracked in work hours",
+                    SPACED_DOT,
+                    humantime::format_duration(untracked.to_std().unwrap_or_default())
+                )
+            })),
+            Span::raw(if *overlap > chrono::Duration::zero() {
+                format!(
+                    "{}{} of it in parallel",
+                    SPACED_DOT,
+                    humantime::format_duration(overlap.to_std().unwrap_or_default())
+                )
+            } else {
+                String::new()
+            }),
+            Span::raw(if *breaks > chrono::Duration::zero() {
+                format!(
+                    "{}{} on breaks",
+                    SPACED_DOT,
+                    humantime::format_duration(breaks.to_std().unwrap_or_default())
+                )
+            } else {
+                String::new()
+            }),
+        ])];
+        if *stale {
+            lines.insert(
+                0,
+                Line::from(Span::styled(
+                    "Stale data: the save directory can't be read, so these are the last \
+                     stats cached for this range",
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                )),
+            );
+        }
+        // The options get a row of their own, unwrapped, so that clicks can be
+        // matched up with them
+        let picker_area = topmost_vertical[2];
+        let options_area = Rect {
+            height: picker_area.height.min(1),
+            ..picker_area
+        };
+        let info_area = Rect {
+            y: picker_area.y + options_area.height,
+            height: picker_area.height - options_area.height,
+            ..picker_area
+        };
+        let mut x = options_area.x;
+        for dr in DATE_PICKER_ORDER {
+            let width = dr.to_string().chars().count() as u16;
+            date_range_targets.push((
+                Rect {
+                    x,
+                    width,
+                    ..options_area
+                },
+                dr,
+            ));
+            x += width + SPACED_DOT.chars().count() as u16;
+        }
+        let date_picker = Paragraph::new(Line::from(
+            // TODO once intersperse drops on stable, use that and drop the
+            // itertools dep
+            Itertools::intersperse(date_options, Span::raw(SPACED_DOT)).collect::<Vec<Span>>(),
+        ));
+        f.render_widget(date_picker, options_area);
+        f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), info_area);
     }
 
+    app.mouse_targets.date_ranges = date_range_targets;
+
     // Message widget
-    f.render_widget(
-        message_widget(app),
-        topmost_vertical[topmost_vertical.len() - 1],
-    );
+    draw_messages(f, app, topmost_vertical[topmost_vertical.len() - 1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previous_periods() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2023, m, d).unwrap();
+        assert_eq!(
+            DateRangeOption::ThisWeek.previous_period(date(6, 4), date(6, 7)),
+            Some((date(5, 28), date(5, 31)))
+        );
+        assert_eq!(
+            DateRangeOption::Past7Days.previous_period(date(6, 1), date(6, 8)),
+            Some((date(5, 24), date(5, 31)))
+        );
+        assert_eq!(
+            DateRangeOption::Today.previous_period(date(6, 1), date(6, 1)),
+            Some((date(5, 31), date(5, 31)))
+        );
+        assert_eq!(
+            DateRangeOption::Mtd.previous_period(date(3, 1), date(3, 31)),
+            Some((date(2, 1), date(2, 28)))
+        );
+        assert_eq!(
+            DateRangeOption::AllTime.previous_period(date(1, 1), date(6, 1)),
+            None
+        );
+    }
 }
@@ -0,0 +1,234 @@
+use chrono::Local;
+use ratatui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use crate::{
+    error, get_pref_label,
+    storage::Dirs,
+    templates::{load_templates, save_templates, Template},
+    App, TimeLog,
+};
+
+use super::{
+    draw_messages, task_color,
+    utils::{self, bold, centered_rect, dim, format_mins},
+    Page,
+};
+
+#[derive(Debug)]
+pub struct State {
+    templates: Vec<Template>,
+    list_state: ListState,
+    /// The name typed so far for a template of today, while saving one
+    pub naming: Option<String>,
+    pub delete_pending: bool,
+}
+
+impl State {
+    pub fn load(dirs: &Dirs) -> error::Result<Self> {
+        let templates = load_templates(dirs)?;
+        let mut list_state = ListState::default();
+        if !templates.is_empty() {
+            list_state.select(Some(0));
+        }
+        Ok(Self {
+            templates,
+            list_state,
+            naming: None,
+            delete_pending: false,
+        })
+    }
+
+    pub fn select_prev(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            self.list_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            self.list_state
+                .select(Some((i + 1).min(self.templates.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Template> {
+        self.templates.get(self.list_state.selected()?)
+    }
+
+    /// Saves `today`'s finished entries as a template with the name that was
+    /// typed, replacing any template already called that
+    pub fn save_named(&mut self, dirs: &Dirs, today: &[TimeLog]) -> error::Result<String> {
+        let name = self.naming.take().unwrap_or_default().trim().to_string();
+        if name.is_empty() {
+            return Ok(String::from("A template needs a name"));
+        }
+        let template = Template::from_day(name.clone(), today);
+        if template.entry_count() == 0 {
+            return Ok(String::from("Nothing finished today to save as a template"));
+        }
+        let count = template.entry_count();
+        let i = match self.templates.iter().position(|t| t.name == name) {
+            Some(i) => {
+                self.templates[i] = template;
+                i
+            }
+            None => {
+                self.templates.push(template);
+                self.templates.len() - 1
+            }
+        };
+        self.list_state.select(Some(i));
+        save_templates(dirs, &self.templates)?;
+        Ok(format!(
+            "Saved today's {} {} as {}",
+            count,
+            if count == 1 { "entry" } else { "entries" },
+            name
+        ))
+    }
+
+    pub fn delete_selected(&mut self, dirs: &Dirs) -> error::Result<Option<String>> {
+        self.delete_pending = false;
+        let Some(i) = self.list_state.selected() else {
+            return Ok(None);
+        };
+        let deleted = self.templates.remove(i);
+        self.list_state.select(if self.templates.is_empty() {
+            None
+        } else {
+            Some(i.min(self.templates.len() - 1))
+        });
+        save_templates(dirs, &self.templates)?;
+        Ok(Some(format!("Deleted the {} template", deleted.name)))
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Templates(ref mut state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render templates page when the app isn't in templates page state!")
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .horizontal_margin(2)
+        .constraints(
+            [
+                Constraint::Length(1), // Instructions
+                Constraint::Min(2),    // Templates
+                Constraint::Length(1), // Messages
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let help_message = Paragraph::new(Line::from(if state.delete_pending {
+        vec![
+            bold("x"),
+            Span::raw(": delete this template | "),
+            bold("Esc"),
+            Span::raw(": cancel"),
+        ]
+    } else {
+        vec![
+            bold("q"),
+            Span::raw("/"),
+            bold("Esc"),
+            Span::raw(": back | "),
+            bold("k+j"),
+            Span::raw(": up+down | "),
+            bold("Enter"),
+            Span::raw(": fill today from template | "),
+            bold("n"),
+            Span::raw(": save today as template | "),
+            bold("d"),
+            Span::raw(": delete"),
+        ]
+    }));
+    f.render_widget(help_message, chunks[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(chunks[1]);
+
+    let list = if state.templates.is_empty() {
+        List::new(vec![ListItem::new(
+            "No templates yet, press n to save today as one",
+        )])
+    } else {
+        List::new(
+            state
+                .templates
+                .iter()
+                .map(|t| {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(t.name.clone()),
+                        dim(format!(" ({} entries)", t.entry_count())),
+                    ]))
+                })
+                .collect::<Vec<ListItem>>(),
+        )
+    }
+    .block(Block::default().borders(Borders::ALL).title("Templates"))
+    .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+    .highlight_symbol("> ");
+    f.render_stateful_widget(list, columns[0], &mut state.list_state);
+
+    // What filling today from the selected template would add
+    let prefs = &app.preferences;
+    let entries: Vec<ListItem> = state
+        .selected()
+        .map(|t| t.entries_on(Local::now().date_naive()))
+        .unwrap_or_default()
+        .iter()
+        .map(|tl| {
+            let mins = tl
+                .end
+                .map_or(0, |end| (end - tl.start).num_minutes().max(0) as u32);
+            let mut spans = vec![
+                Span::raw(format!("{} ", tl.start.format("%R"))),
+                Span::styled(
+                    get_pref_label(tl.number, prefs.labels.as_ref())
+                        .unwrap_or_else(|| tl.number.to_string()),
+                    Style::default().fg(task_color(prefs, tl.number)),
+                ),
+                Span::raw(format!(" {}", format_mins(mins))),
+            ];
+            if !tl.tags.is_empty() {
+                spans.push(dim(format!(" #{}", tl.tags.join(" #"))));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    f.render_widget(
+        List::new(entries).block(Block::default().borders(Borders::ALL).title("Entries")),
+        columns[1],
+    );
+
+    if let Some(input) = state.naming.as_ref() {
+        let popup = centered_rect(40, 3, chunks[1]);
+        let prompt = Paragraph::new(Line::from(vec![
+            Span::raw(input.as_str()),
+            utils::blinky_underline(" "),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Save today as (Esc to cancel)"),
+        );
+        f.render_widget(Clear, popup);
+        f.render_widget(prompt, popup);
+    }
+
+    draw_messages(f, app, chunks[2]);
+}
@@ -1,6 +1,8 @@
 use std::borrow::Cow;
 
+use chrono::Local;
 use ratatui::{
+    layout::Rect,
     style::{Modifier, Style},
     text::Span,
 };
@@ -39,3 +41,33 @@ where
         Span::raw(text)
     }
 }
+
+/// Returns a Rect of (at most) the given size centered within `area`, for
+/// drawing popups over the rest of the page
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// A spinner for pages still loading in the background, turning once a second
+/// as they're redrawn
+pub fn spinner() -> char {
+    SPINNER[Local::now().timestamp() as usize % SPINNER.len()]
+}
+
+/// Formats a whole number of minutes compactly, like "45m" or "1h30m"
+pub fn format_mins(mins: u32) -> String {
+    match (mins / 60, mins % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
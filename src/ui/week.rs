@@ -0,0 +1,208 @@
+use std::{collections::BTreeMap, io};
+
+use chrono::{Days, Local, NaiveDate, Weekday};
+use ratatui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Paragraph, Table},
+    Frame,
+};
+
+use crate::{
+    legend, locale::format_date, stats::load_dated_logs, storage::Dirs, timeline,
+    utils::start_of_week, App, TimeLog,
+};
+
+use super::{
+    draw_messages, entry_color,
+    home::{make_timeline_row, timeline_text},
+    utils::bold,
+    Page,
+};
+
+/// Wide enough for the day labels, like "Wed Sep 27"
+const LABEL_WIDTH: u16 = 11;
+
+/// A week of timelines, one row per day, to compare the days side by side
+#[derive(Debug)]
+pub struct State {
+    /// The first day of the week shown
+    start: NaiveDate,
+    /// The saved logs of the week, and of the day after it, which holds
+    /// whatever the user did after midnight on its last day
+    logs: BTreeMap<NaiveDate, Vec<TimeLog>>,
+}
+
+impl State {
+    /// Loads the current week, which starts on the user's week start day
+    pub fn load_this_week(app: &App) -> io::Result<Self> {
+        let first_day = app.preferences.week_start_day.unwrap_or(Weekday::Sun);
+        Self::load(
+            &app.dirs,
+            start_of_week(Local::now().date_naive(), first_day),
+        )
+    }
+
+    fn load(dirs: &Dirs, start: NaiveDate) -> io::Result<Self> {
+        let logs = load_dated_logs(dirs, Some(start), start.checked_add_days(Days::new(7)))?
+            .into_iter()
+            .collect();
+        Ok(Self { start, logs })
+    }
+
+    pub fn prev_week(&mut self, dirs: &Dirs) -> io::Result<()> {
+        if let Some(start) = self.start.checked_sub_days(Days::new(7)) {
+            *self = Self::load(dirs, start)?;
+        }
+        Ok(())
+    }
+
+    pub fn next_week(&mut self, dirs: &Dirs) -> io::Result<()> {
+        if let Some(start) = self.start.checked_add_days(Days::new(7)) {
+            *self = Self::load(dirs, start)?;
+        }
+        Ok(())
+    }
+
+    /// The entries of the given date and the next, with today's coming from
+    /// the app rather than its save file, which may be behind
+    fn entries_around(&self, app: &App, date: NaiveDate) -> Vec<TimeLog> {
+        let today = Local::now().date_naive();
+        [Some(date), date.succ_opt()]
+            .into_iter()
+            .flatten()
+            .flat_map(|d| {
+                if d == today {
+                    app.today.clone()
+                } else {
+                    self.logs.get(&d).cloned().unwrap_or_default()
+                }
+            })
+            // Parallel tasks overlap everything else, which a single row can't
+            // show
+            .filter(|tl| !app.is_parallel(tl.number))
+            .collect()
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let state = if let Page::Week(ref state) = app.selected_page {
+        state
+    } else {
+        panic!("Can't render week page when the app isn't in week page state!")
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .horizontal_margin(2)
+        .constraints(
+            [
+                Constraint::Length(1), // Instructions
+                Constraint::Length(9), // A timeline per day
+                Constraint::Length(2), // Table legend
+                Constraint::Min(0),
+                Constraint::Length(1), // Messages
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let help_message = Paragraph::new(Line::from(vec![
+        bold("q"),
+        Span::raw("/"),
+        bold("Esc"),
+        Span::raw(": back | "),
+        bold("h+l"),
+        Span::raw("/"),
+        bold("←+→"),
+        Span::raw(": previous/next week"),
+    ]));
+    f.render_widget(help_message, chunks[0]);
+
+    // Like on Home, the timeline's inner width has to be divisible by 24 for
+    // the hours to line up
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Week of {}",
+        format_date(&app.preferences, state.start, "%a %b %-d")
+    ));
+    let available = chunks[1].width.saturating_sub(LABEL_WIDTH);
+    let nice_table_width = ((available.saturating_sub(2) / 24) * 24) + 2;
+    let table_rect = Rect {
+        x: chunks[1].x + LABEL_WIDTH,
+        width: nice_table_width.min(available),
+        ..chunks[1]
+    };
+    let inner = block.inner(table_rect);
+    f.render_widget(block, table_rect);
+
+    let now = Local::now();
+    for (i, date) in state.start.iter_days().take(7).enumerate() {
+        let y = inner.y + i as u16;
+        if y >= inner.y + inner.height {
+            break;
+        }
+
+        let mut label_style = Style::default();
+        if date == now.date_naive() {
+            label_style = label_style.add_modifier(Modifier::BOLD);
+        }
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                format_date(&app.preferences, date, "%a %b %-d"),
+                label_style,
+            )),
+            Rect {
+                x: chunks[1].x,
+                y,
+                width: LABEL_WIDTH.min(chunks[1].width),
+                height: 1,
+            },
+        );
+
+        let entries = state.entries_around(app, date);
+        let max_width = inner.width;
+        let (row, cols) = make_timeline_row(
+            timeline::lay_out_date(&entries, date, now, max_width),
+            |segment| {
+                let tl = &entries[segment.index];
+                Cell::from(timeline_text(app, tl.number, tl.label(app), max_width)).style(
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(entry_color(&app.preferences, tl)),
+                )
+            },
+        );
+        f.render_widget(
+            Table::new(vec![row]).column_spacing(0).widths(&cols),
+            Rect {
+                y,
+                height: 1,
+                ..inner
+            },
+        );
+    }
+
+    if nice_table_width > 26 {
+        let legend: &Table<'static> = if nice_table_width < 74 {
+            &legend::TRUNC_LEGEND_TABLE
+        } else {
+            &legend::LEGEND_TABLE
+        };
+        f.render_widget(
+            legend.clone(),
+            Layout::default()
+                .horizontal_margin(1)
+                .constraints([Constraint::Percentage(100)].as_ref())
+                .split(Rect {
+                    x: table_rect.x,
+                    width: table_rect.width,
+                    ..chunks[2]
+                })[0],
+        );
+    }
+
+    draw_messages(f, app, chunks[4]);
+}
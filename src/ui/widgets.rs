@@ -1,6 +1,12 @@
 use ratatui::{
-    style::Color,
-    widgets::canvas::{Context, Line},
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line as TextLine, Span},
+    widgets::{
+        canvas::{Context, Line},
+        Widget,
+    },
 };
 use tracing::warn;
 
@@ -10,6 +16,13 @@ pub struct Donut {
     /// The bigger this divisor, the bigger the margins will be. Divisor=1 for no margin.
     margin_divisor: f64,
     slices: Vec<(u8, Color)>,
+    /// Optional fill characters, one per slice, printed in the middle of each
+    /// slice so slices can be told apart without color
+    patterns: Vec<Option<char>>,
+    /// What each slice is, for the legend
+    labels: Vec<String>,
+    /// The slice drawn thicker than the rest, if any
+    selected: Option<usize>,
 }
 
 impl Donut {
@@ -23,9 +36,50 @@ impl Donut {
             hole_divisor,
             margin_divisor,
             slices,
+            patterns: Vec::new(),
+            labels: Vec::new(),
+            selected: None,
         }
     }
 
+    pub fn patterns(mut self, patterns: Vec<Option<char>>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    pub fn labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn selected(mut self, selected: Option<usize>) -> Self {
+        self.selected = selected;
+        self
+    }
+
+    /// A line per labeled slice that isn't empty, with its color and
+    /// percentage, and the selected one in bold. For drawing beside the
+    /// chart, since the canvas only fits the circle.
+    pub fn legend(&self) -> Vec<TextLine<'static>> {
+        self.slices
+            .iter()
+            .zip(&self.labels)
+            .enumerate()
+            .filter(|(_, ((perc, _), _))| *perc > 0)
+            .map(|(i, ((perc, color), label))| {
+                let style = if self.selected == Some(i) {
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                TextLine::from(vec![
+                    Span::styled("■ ", Style::default().fg(*color)),
+                    Span::styled(format!("{:>3}% {}", perc, label), style),
+                ])
+            })
+            .collect()
+    }
+
     /// Returns a function suitable for passing to canvas::Canvas::paint
     pub fn painter(self) -> impl Fn(&mut Context<'_>) {
         move |ctx: &mut Context| {
@@ -67,17 +121,136 @@ impl Donut {
                     self.slices.get(slice_idx).map_or(Color::Reset, |t| t.1)
                 };
 
+                // The selected slice sticks out a bit on both sides
+                let (hole, margin) = if self.selected == Some(slice_idx) && color != Color::Reset {
+                    (self.hole_divisor * 1.25, self.margin_divisor * 0.92)
+                } else {
+                    (self.hole_divisor, self.margin_divisor)
+                };
                 let angle = std::f64::consts::TAU * percentage;
                 let x = angle.cos();
                 let y = angle.sin();
                 ctx.draw(&Line {
-                    x1: x / self.hole_divisor, // Origin
-                    y1: y / self.hole_divisor,
-                    x2: x / self.margin_divisor, // Margin
-                    y2: y / self.margin_divisor,
+                    x1: x / hole, // Origin
+                    y1: y / hole,
+                    x2: x / margin, // Margin
+                    y2: y / margin,
                     color,
                 });
             }
+
+            // Slices are laid out counterclockwise from angle 0, so the last
+            // one ends at 100% and each one ends where the next one starts
+            let radius = (1.0 / self.hole_divisor + 1.0 / self.margin_divisor) / 2.0;
+            let mut slice_end_perc = 1.0;
+            for (i, &(perc, color)) in self.slices.iter().enumerate().rev() {
+                let slice_perc = perc as f64 / 100.0;
+                if let Some(&Some(pattern)) = self.patterns.get(i) {
+                    if perc > 0 {
+                        let angle = std::f64::consts::TAU * (slice_end_perc - slice_perc / 2.0);
+                        ctx.print(
+                            radius * angle.cos(),
+                            radius * angle.sin(),
+                            Span::styled(
+                                pattern.to_string(),
+                                Style::default().fg(Color::Black).bg(color),
+                            ),
+                        );
+                    }
+                }
+                slice_end_perc -= slice_perc;
+            }
+        }
+    }
+}
+
+/// Strips the colors and other styling from whatever was drawn underneath it,
+/// for the plain output mode
+pub struct Unstyled;
+
+impl Widget for Unstyled {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+                cell.modifier = Modifier::empty();
+            }
+        }
+    }
+}
+
+/// A GitHub-style calendar, a column per week and a row per day of the week,
+/// with each day shaded by how much was tracked. Meant for a canvas whose
+/// bounds are its `size` in cells less one, so that each day gets a cell of
+/// its own.
+pub struct Heatmap {
+    /// Each day's week, day of the week, and shade from 0 (nothing) to 4
+    days: Vec<(u16, u16, u8)>,
+    color: Color,
+    /// Labels for some of the days of the week, down the left
+    row_labels: Vec<(u16, String)>,
+    /// Labels for some of the weeks, like the months they start, along the
+    /// top
+    column_labels: Vec<(u16, String)>,
+}
+
+impl Heatmap {
+    /// From nothing to the most tracked, readable without color too
+    pub const SHADES: [&'static str; 5] = ["·", "░", "▒", "▓", "█"];
+    /// Wide enough for row labels like "Mon"
+    const LABEL_WIDTH: u16 = 4;
+
+    pub fn new(days: Vec<(u16, u16, u8)>, color: Color) -> Self {
+        Self {
+            days,
+            color,
+            row_labels: Vec::new(),
+            column_labels: Vec::new(),
+        }
+    }
+
+    pub fn row_labels(mut self, labels: Vec<(u16, String)>) -> Self {
+        self.row_labels = labels;
+        self
+    }
+
+    pub fn column_labels(mut self, labels: Vec<(u16, String)>) -> Self {
+        self.column_labels = labels;
+        self
+    }
+
+    /// How many cells wide and high it is for the given number of weeks
+    pub fn size(weeks: u16) -> (u16, u16) {
+        (Self::LABEL_WIDTH + weeks * 2, 8)
+    }
+
+    /// Returns a function suitable for passing to canvas::Canvas::paint
+    pub fn painter(self) -> impl Fn(&mut Context<'_>) {
+        // Canvas y goes up from the bottom, but the calendar reads down from
+        // the column labels at the top
+        let top = 7.0;
+        move |ctx: &mut Context| {
+            for (column, label) in &self.column_labels {
+                let x = Self::LABEL_WIDTH + column * 2;
+                ctx.print(x as f64, top, Span::raw(label.clone()));
+            }
+            for (row, label) in &self.row_labels {
+                ctx.print(0.0, top - 1.0 - *row as f64, Span::raw(label.clone()));
+            }
+            for &(column, row, shade) in &self.days {
+                let style = if shade == 0 {
+                    Style::default().fg(Color::DarkGray)
+                } else {
+                    Style::default().fg(self.color)
+                };
+                ctx.print(
+                    (Self::LABEL_WIDTH + column * 2) as f64,
+                    top - 1.0 - row as f64,
+                    Span::styled(Self::SHADES[shade.min(4) as usize], style),
+                );
+            }
         }
     }
 }
@@ -1,4 +1,7 @@
-use chrono::{DateTime, Local, TimeZone, Timelike};
+use std::{process::Command, thread};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeZone, Timelike, Weekday};
+use tracing::warn;
 
 pub fn adjust_datetime_digit(dt: &DateTime<Local>, pos: usize, c: char) -> Option<DateTime<Local>> {
     if let Some(digit) = c.to_digit(10) {
@@ -55,3 +58,160 @@ pub fn datetime_with_zeroed_time<T: TimeZone>(dt: &DateTime<T>) -> DateTime<T> {
         .with_nanosecond(0)
         .unwrap()
 }
+
+/// The same time of day as `dt` but on `date`. None if that time doesn't exist
+/// there, e.g. it's skipped by a DST change.
+pub fn on_date(dt: &DateTime<Local>, date: NaiveDate) -> Option<DateTime<Local>> {
+    date.and_time(dt.time())
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+/// The first day of the week containing `date`, for weeks that start on
+/// `first_day`
+pub fn start_of_week(date: NaiveDate, first_day: Weekday) -> NaiveDate {
+    let into_week =
+        (date.weekday().num_days_from_sunday() + 7 - first_day.num_days_from_sunday()) % 7;
+    date.checked_sub_days(Days::new(into_week.into()))
+        .unwrap_or(date)
+}
+
+/// Parses quick-start input like "3 #standup #clientA" or "2 for 30m" into a
+/// task number, its tags and how many minutes it's timeboxed to. The task
+/// number must come first; tags may be written with or without the leading
+/// '#', and "for" followed by a duration like "30m" or "1h30m" is the timebox.
+/// Anything else, "for" included, is a tag. Returns None if there's no valid
+/// task number.
+pub fn parse_quick_start(input: &str) -> Option<(u8, Vec<String>, Option<u32>)> {
+    let mut words = input.split_whitespace().peekable();
+    let number = words.next()?.parse::<u8>().ok()?;
+    if !(1..=8).contains(&number) {
+        return None;
+    }
+
+    let mut tags = Vec::new();
+    let mut timebox_mins = None;
+    while let Some(word) = words.next() {
+        if word == "for" {
+            let timebox = words
+                .peek()
+                .and_then(|next| humantime::parse_duration(next).ok())
+                .filter(|d| d.as_secs() >= 60);
+            if let Some(d) = timebox {
+                timebox_mins = Some((d.as_secs() / 60) as u32);
+                words.next();
+                continue;
+            }
+        }
+        let tag = word.trim_start_matches('#');
+        if !tag.is_empty() {
+            tags.push(tag.to_string());
+        }
+    }
+
+    Some((number, tags, timebox_mins))
+}
+
+/// Adds `line` to the end of a commit message, before the comments git puts
+/// under it, unless the message already has that line. Leaves a blank line
+/// between the message and `line` so it reads as a trailer, and when there's
+/// no message yet, leaves room above `line` for one to be written.
+pub fn annotate_message(message: &str, line: &str) -> String {
+    if message.lines().any(|l| l == line) {
+        return message.to_string();
+    }
+    let comments_at = message
+        .match_indices('\n')
+        .map(|(i, _)| i + 1)
+        .chain(std::iter::once(0))
+        .filter(|&i| message[i..].starts_with('#'))
+        .min()
+        .unwrap_or(message.len());
+    let (body, comments) = message.split_at(comments_at);
+    let comments_sep = if comments.is_empty() { "" } else { "\n" };
+    format!(
+        "{}\n\n{}\n{}{}",
+        body.trim_end(),
+        line,
+        comments_sep,
+        comments
+    )
+}
+
+/// Pops up a desktop notification with notify-send, without waiting for it.
+/// Systems without notify-send just don't get one.
+pub fn desktop_notification(summary: &str, body: &str) {
+    match Command::new("notify-send").args([summary, body]).spawn() {
+        // Reap it once it's done, so that it doesn't linger as a zombie
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("Unable to send a desktop notification: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quick_start() {
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_quick_start("3 #standup clientA"),
+            Some((3, tags(&["standup", "clientA"]), None))
+        );
+        assert_eq!(parse_quick_start("9 #standup"), None);
+        assert_eq!(parse_quick_start("standup"), None);
+
+        // Only "for" and a duration make a timebox
+        assert_eq!(
+            parse_quick_start("2 for 1h30m #meeting"),
+            Some((2, tags(&["meeting"]), Some(90)))
+        );
+        assert_eq!(parse_quick_start("2 30m"), Some((2, tags(&["30m"]), None)));
+        assert_eq!(parse_quick_start("2 #1h"), Some((2, tags(&["1h"]), None)));
+        // Less than a minute isn't one
+        assert_eq!(
+            parse_quick_start("2 for 30s"),
+            Some((2, tags(&["for", "30s"]), None))
+        );
+        // Nor is "for" on its own, which is kept
+        assert_eq!(
+            parse_quick_start("2 for review"),
+            Some((2, tags(&["for", "review"]), None))
+        );
+        assert_eq!(parse_quick_start("2 for"), Some((2, tags(&["for"]), None)));
+    }
+
+    #[test]
+    fn same_time_on_another_date() {
+        let dt = Local.with_ymd_and_hms(2023, 6, 5, 9, 30, 15).unwrap();
+        assert_eq!(
+            on_date(&dt, NaiveDate::from_ymd_opt(2023, 6, 6).unwrap()),
+            Some(Local.with_ymd_and_hms(2023, 6, 6, 9, 30, 15).unwrap())
+        );
+    }
+
+    #[test]
+    fn commit_annotation() {
+        let line = "Tracked: Coding (1h30m)";
+        assert_eq!(
+            annotate_message("Fix the thing\n", line),
+            "Fix the thing\n\nTracked: Coding (1h30m)\n"
+        );
+        // What git puts under the message stays under it
+        assert_eq!(
+            annotate_message("Fix the thing\n\n# Please enter the commit message\n", line),
+            "Fix the thing\n\nTracked: Coding (1h30m)\n\n# Please enter the commit message\n"
+        );
+        // With no message yet there's room left above for one
+        assert_eq!(
+            annotate_message("\n# Please enter the commit message\n", line),
+            "\n\nTracked: Coding (1h30m)\n\n# Please enter the commit message\n"
+        );
+        // Amending doesn't add it twice
+        let annotated = annotate_message("Fix the thing\n", line);
+        assert_eq!(annotate_message(&annotated, line), annotated);
+    }
+}
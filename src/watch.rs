@@ -0,0 +1,205 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::{info, warn};
+
+use crate::TimeLog;
+
+pub fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The save files as of the last time we read or wrote them ourselves, so that
+/// our own saves aren't mistaken for someone else's. The app keeps one for its
+/// data directory.
+#[derive(Debug, Default)]
+pub struct KnownFiles {
+    /// The modification time of today's save file as of then
+    mtime: Option<SystemTime>,
+    /// Today's entries as of then, to tell what someone else changed from what
+    /// we did
+    logs: Vec<TimeLog>,
+    /// Like `mtime` but for the settings file
+    settings_mtime: Option<SystemTime>,
+}
+
+impl KnownFiles {
+    /// Call this whenever we load or save today's log, with its modification
+    /// time right after, so that the watcher knows the file's current
+    /// contents are ours
+    pub fn record(&mut self, mtime: Option<SystemTime>, logs: &[TimeLog]) {
+        self.mtime = mtime;
+        self.logs = logs.to_vec();
+    }
+
+    /// Like `record` but for the settings file
+    pub fn record_settings(&mut self, mtime: Option<SystemTime>) {
+        self.settings_mtime = mtime;
+    }
+
+    /// Today's entries as we last loaded or saved them
+    pub fn logs(&self) -> &[TimeLog] {
+        &self.logs
+    }
+
+    pub fn mtime(&self) -> Option<SystemTime> {
+        self.mtime
+    }
+
+    pub fn settings_mtime(&self) -> Option<SystemTime> {
+        self.settings_mtime
+    }
+}
+
+/// Combines our entries with the ones on disk, given how both looked when we
+/// last loaded or saved (`base`): entries they added or changed are added to
+/// ours, and entries they removed are removed from ours unless we changed
+/// them too. Where both sides added or changed entries over the same time,
+/// ours win, and theirs are returned alongside the merged entries for the
+/// trash, so nothing is lost.
+pub fn merge(
+    base: &[TimeLog],
+    ours: &[TimeLog],
+    theirs: &[TimeLog],
+) -> (Vec<TimeLog>, Vec<TimeLog>) {
+    let ours_changed: Vec<&TimeLog> = ours.iter().filter(|tl| !base.contains(tl)).collect();
+    let mut merged: Vec<TimeLog> = ours
+        .iter()
+        .filter(|tl| !base.contains(tl) || theirs.contains(tl))
+        .cloned()
+        .collect();
+    let mut clashing = Vec::new();
+    for tl in theirs
+        .iter()
+        .filter(|tl| !base.contains(tl) && !ours.contains(tl))
+    {
+        if ours_changed.iter().any(|ours| overlap(ours, tl)) {
+            clashing.push(tl.clone());
+        } else {
+            merged.push(tl.clone());
+        }
+    }
+    merged.sort_by_key(|tl| tl.start);
+    (merged, clashing)
+}
+
+/// Whether two entries cover any of the same time, open ones going on forever
+fn overlap(a: &TimeLog, b: &TimeLog) -> bool {
+    a.end.is_none_or(|end| b.start < end) && b.end.is_none_or(|end| a.start < end)
+}
+
+/// Watches the save file directory for changes made by other programs, such as
+/// a text editor or a sync tool, so that we can reload instead of overwriting
+/// their changes at the next autosave. The settings file gets a watcher of its
+/// own, so that edits to it can be put to use right away.
+pub struct SaveFileWatcher {
+    // Watching stops when this is dropped
+    _watcher: RecommendedWatcher,
+    changed: Arc<AtomicBool>,
+}
+
+impl SaveFileWatcher {
+    /// Watches today's save file, in `dir` or its subdirectories
+    pub fn start(dir: &Path) -> notify::Result<Self> {
+        // Daily logs are saved in year and month subdirectories, so those are
+        // watched recursively
+        Self::watch(dir, RecursiveMode::Recursive)
+    }
+
+    /// Watches the settings file, in `dir`
+    pub fn start_settings(dir: &Path) -> notify::Result<Self> {
+        // The settings file sits right in `dir`, so there's no need to watch
+        // anything below it
+        Self::watch(dir, RecursiveMode::NonRecursive)
+    }
+
+    fn watch(dir: &Path, mode: RecursiveMode) -> notify::Result<Self> {
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&changed);
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => {
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                        flag.store(true, Ordering::Relaxed);
+                    }
+                }
+                Err(err) => warn!("Error watching save files: {}", err),
+            })?;
+        watcher.watch(dir, mode)?;
+        info!("Watching {} for external changes", dir.display());
+
+        Ok(Self {
+            _watcher: watcher,
+            changed,
+        })
+    }
+
+    /// Returns true if `path` has changed since we last read or wrote it, when
+    /// it was last modified at `known_mtime`. Each change is only reported
+    /// once.
+    pub fn take_external_change(&self, path: &Path, known_mtime: Option<SystemTime>) -> bool {
+        if !self.changed.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        let current = mtime(path);
+        current.is_some() && current != known_mtime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+
+    use super::*;
+
+    #[test]
+    fn merging_changes() {
+        let start = Local::now() - Duration::hours(3);
+        let entry = |hours: i64, number: u8, open: bool| TimeLog {
+            start: start + Duration::hours(hours),
+            end: (!open).then(|| start + Duration::hours(hours + 1)),
+            number,
+            ..Default::default()
+        };
+        let base = vec![entry(0, 1, false), entry(1, 2, true)];
+        // We closed the open entry and started another
+        let ours = vec![entry(0, 1, false), entry(1, 2, false), entry(2, 3, true)];
+        // They removed the first one, added one earlier on and appended one
+        // over the time our new one covers
+        let theirs = vec![entry(-2, 5, false), entry(1, 2, true), entry(2, 4, false)];
+
+        let (merged, clashing) = merge(&base, &ours, &theirs);
+        assert_eq!(
+            merged,
+            vec![entry(-2, 5, false), entry(1, 2, false), entry(2, 3, true)]
+        );
+        assert_eq!(clashing, vec![entry(2, 4, false)]);
+
+        // Nothing to merge in a read-only view, which never changes anything
+        assert_eq!(merge(&base, &base, &theirs), (theirs, Vec::new()));
+    }
+
+    #[test]
+    fn merging_the_same_entry_changed_both_ways() {
+        let start = Local::now() - Duration::hours(3);
+        let entry = |number: u8, hours: i64| TimeLog {
+            start,
+            end: Some(start + Duration::hours(hours)),
+            number,
+            ..Default::default()
+        };
+        let base = vec![entry(1, 1)];
+        // We made it longer, and they changed its task
+        let (merged, clashing) = merge(&base, &[entry(1, 2)], &[entry(2, 1)]);
+        assert_eq!(merged, vec![entry(1, 2)]);
+        assert_eq!(clashing, vec![entry(2, 1)]);
+    }
+}
@@ -0,0 +1,271 @@
+// Tracks whatever the user is working in, by checking the focused window every
+// few seconds and starting the task of the first rule that matches it. Rules
+// are regexes matched against "<app>: <title>", and are tried in order:
+//
+//     window_tracking: Some((
+//         rules: [
+//             (pattern: "^(code|Alacritty):", task: 2),
+//             (pattern: "(?i)zoom|meet\.google", task: 4),
+//         ],
+//     ))
+//
+// Entries started this way are marked as automatic, with the rule that
+// started them, and only ever take over from other automatic entries or from
+// nothing being tracked, never from something the user started themselves.
+// When no rule matches, whatever's being tracked carries on.
+//
+// The focused window is found by a backend, picked to suit the session unless
+// the preferences say otherwise: xdotool on X11, or swaymsg or hyprctl on those
+// Wayland compositors (with jq). Anything else can be a command of the user's
+// own that prints the app on one line and the title on the next.
+//
+// Tracking is only built with the "window-tracking" feature. Without it the
+// rules are still kept in the settings, they just don't start anything.
+
+#[cfg(feature = "window-tracking")]
+use std::{future::Future, pin::Pin, time::Duration};
+
+#[cfg(feature = "window-tracking")]
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "window-tracking")]
+use tokio::{process::Command, task::JoinHandle, time};
+#[cfg(feature = "window-tracking")]
+use tracing::{debug, warn};
+
+#[cfg(feature = "window-tracking")]
+use crate::{
+    input::{EventSender, InputEvent, InputSource},
+    AppState,
+};
+
+/// Used if the user hasn't set `interval_secs`
+#[cfg(feature = "window-tracking")]
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+
+/// How to find out which window is focused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    X11,
+    Sway,
+    Hyprland,
+    /// A command printing the app on one line and the title on the next
+    Command(String),
+}
+
+#[cfg(feature = "window-tracking")]
+impl Backend {
+    /// The one for the session the app is running in, if it's one we know
+    fn detect() -> Option<Self> {
+        let set = |var| std::env::var_os(var).is_some();
+        if set("SWAYSOCK") {
+            Some(Backend::Sway)
+        } else if set("HYPRLAND_INSTANCE_SIGNATURE") {
+            Some(Backend::Hyprland)
+        } else if set("DISPLAY") && !set("WAYLAND_DISPLAY") {
+            Some(Backend::X11)
+        } else {
+            None
+        }
+    }
+
+    fn command(&self) -> &str {
+        match self {
+            Backend::X11 => "xdotool getactivewindow getwindowclassname getwindowname",
+            Backend::Sway => {
+                "swaymsg -t get_tree | jq -r '.. | select(.focused? == true) | (.app_id // .window_properties.class), .name'"
+            }
+            Backend::Hyprland => "hyprctl -j activewindow | jq -r '.class, .title'",
+            Backend::Command(command) => command,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "window-tracking"), allow(dead_code))]
+pub struct WindowRule {
+    pattern: String,
+    task: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "window-tracking"), allow(dead_code))]
+pub struct WindowTracking {
+    rules: Vec<WindowRule>,
+    /// Picked to suit the session if not set
+    #[serde(default)]
+    backend: Option<Backend>,
+    /// How often to check the focused window
+    #[serde(default)]
+    interval_secs: Option<u64>,
+}
+
+/// The rules that compiled, with their patterns. Ones that didn't are left
+/// out, with the error.
+#[cfg(feature = "window-tracking")]
+fn compile(rules: &[WindowRule]) -> (Vec<(Regex, &WindowRule)>, Vec<String>) {
+    let mut compiled = Vec::new();
+    let mut errors = Vec::new();
+    for rule in rules {
+        match Regex::new(&rule.pattern) {
+            Ok(re) if (1..=8).contains(&rule.task) => compiled.push((re, rule)),
+            Ok(_) => errors.push(format!(
+                "The window rule {:?} needs a task from 1 to 8",
+                rule.pattern
+            )),
+            Err(e) => errors.push(format!("Invalid window rule {:?}: {}", rule.pattern, e)),
+        }
+    }
+    (compiled, errors)
+}
+
+/// The first rule matching `window`, as "<app>: <title>"
+#[cfg(feature = "window-tracking")]
+fn matching<'a>(rules: &'a [(Regex, &WindowRule)], window: &str) -> Option<&'a WindowRule> {
+    rules
+        .iter()
+        .find(|(re, _)| re.is_match(window))
+        .map(|(_, rule)| *rule)
+}
+
+/// A backend's output as "<app>: <title>"
+#[cfg(feature = "window-tracking")]
+fn describe_window(output: &str) -> Option<String> {
+    let mut lines = output.lines().map(str::trim).filter(|l| !l.is_empty());
+    let app = lines.next()?;
+    Some(format!("{}: {}", app, lines.next().unwrap_or_default()))
+}
+
+/// Starts tasks by the user's window rules
+#[cfg(feature = "window-tracking")]
+pub struct WindowInput {
+    tracking: WindowTracking,
+    task: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "window-tracking")]
+impl WindowInput {
+    pub fn new(tracking: WindowTracking) -> Self {
+        Self {
+            tracking,
+            task: None,
+        }
+    }
+}
+
+#[cfg(feature = "window-tracking")]
+impl InputSource for WindowInput {
+    fn name(&self) -> &str {
+        "window tracking"
+    }
+
+    fn start(&mut self, _app: &AppState, events: EventSender) {
+        let tracking = self.tracking.clone();
+        self.task = Some(tokio::spawn(async move {
+            let Some(backend) = tracking.backend.clone().or_else(Backend::detect) else {
+                let _ = events.send(InputEvent::Message(String::from(
+                    "Can't tell which window is focused here, set a window tracking backend",
+                )));
+                return;
+            };
+            let (rules, errors) = compile(&tracking.rules);
+            for error in errors {
+                let _ = events.send(InputEvent::Message(error));
+            }
+            let mut interval = time::interval(Duration::from_secs(
+                tracking
+                    .interval_secs
+                    .unwrap_or(DEFAULT_INTERVAL_SECS)
+                    .max(1),
+            ));
+            // Only send a rule on when it starts matching, not every check
+            let mut last: Option<&WindowRule> = None;
+            loop {
+                interval.tick().await;
+                let output = match Command::new("sh")
+                    .arg("-c")
+                    .arg(backend.command())
+                    .output()
+                    .await
+                {
+                    Ok(output) if output.status.success() => output,
+                    Ok(output) => {
+                        debug!("Window backend exited with {}", output.status);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Unable to run window backend: {}", e);
+                        let _ = events.send(InputEvent::Message(format!(
+                            "Window tracking stopped, couldn't run {:?}: {}",
+                            backend.command(),
+                            e
+                        )));
+                        return;
+                    }
+                };
+                let Some(window) = describe_window(&String::from_utf8_lossy(&output.stdout)) else {
+                    continue;
+                };
+                let rule = matching(&rules, &window);
+                if rule != last {
+                    if let Some(rule) = rule {
+                        debug!("{:?} matches window rule {:?}", window, rule.pattern);
+                        let event = InputEvent::AutoStart {
+                            number: rule.task,
+                            rule: rule.pattern.clone(),
+                        };
+                        if events.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    last = rule;
+                }
+            }
+        }));
+    }
+
+    fn stop(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        Box::pin(async {})
+    }
+}
+
+#[cfg(all(test, feature = "window-tracking"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_rules() {
+        let rules = [
+            WindowRule {
+                pattern: String::from("^(code|Alacritty):"),
+                task: 2,
+            },
+            WindowRule {
+                pattern: String::from("(?i)zoom"),
+                task: 4,
+            },
+            WindowRule {
+                pattern: String::from("(unclosed"),
+                task: 5,
+            },
+            WindowRule {
+                pattern: String::from("slack"),
+                task: 9,
+            },
+        ];
+        let (compiled, errors) = compile(&rules);
+        assert_eq!(compiled.len(), 2);
+        assert_eq!(errors.len(), 2);
+
+        let window = describe_window("Alacritty\nvim src/window.rs\n").unwrap();
+        assert_eq!(window, "Alacritty: vim src/window.rs");
+        assert_eq!(matching(&compiled, &window).map(|r| r.task), Some(2));
+        let window = describe_window("firefox\nStandup - Zoom").unwrap();
+        assert_eq!(matching(&compiled, &window).map(|r| r.task), Some(4));
+        assert_eq!(matching(&compiled, "firefox: News"), None);
+        assert_eq!(describe_window("\n"), None);
+    }
+}